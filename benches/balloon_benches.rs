@@ -7,7 +7,7 @@ pub fn create_balloon() -> Balloon {
 
 pub fn create_balloon_img() -> BalloonImage {
     let v: Vec<u8> = Vec::with_capacity(1000000);
-    BalloonImage { img_type: String::from("jpg"), img_data: v }
+    BalloonImage::new("jpg", v)
 }
 
 pub fn balloon_benches(c: &mut Criterion) {
@@ -28,8 +28,8 @@ pub fn balloon_benches(c: &mut Criterion) {
     let mut g = c.benchmark_group("flate2");
     g.sample_size(100_000);
 
-    g.bench_function("create_balloon", |b| b.iter(|| create_balloon()));
-    g.bench_function("create_balloon_img", |b| b.iter(|| create_balloon_img()));
+    g.bench_function("create_balloon", |b| b.iter(create_balloon));
+    g.bench_function("create_balloon_img", |b| b.iter(create_balloon_img));
     g.bench_function("b_tl_chars", |b| b.iter(|| bln.tl_chars()));
     g.bench_function("b_pr_chars", |b| b.iter(|| bln.pr_chars()));
     g.bench_function("b_cm_chars", |b| b.iter(|| bln.comments_chars()));
@@ -33,7 +33,7 @@ pub fn document_benches(c: &mut Criterion) {
 
     g.sample_size(10_000);
 
-    g.bench_function("create_doc", |b| b.iter(|| create_document()));
+    g.bench_function("create_doc", |b| b.iter(create_document));
     g.bench_function("tl_chars", |b| b.iter(|| doc.tl_chars()));
     g.bench_function("pr_chars", |b| b.iter(|| doc.pr_chars()));
     g.bench_function("cm_chars", |b| b.iter(|| doc.comment_chars()));
@@ -41,7 +41,7 @@ pub fn document_benches(c: &mut Criterion) {
     g.bench_function("len", |b| b.iter(|| doc.len()));
     g.bench_function("to_string", |b| b.iter(|| doc.to_string()));
     g.bench_function("to_xml", |b| b.iter(|| doc.to_xml()));
-    g.bench_function("from_xml", |b| b.iter(|| doc.xml_to_doc(black_box(xml_case.clone()))));
+    g.bench_function("from_xml", |b| b.iter(|| Document::from_xml(black_box(&xml_case))));
 }
 
 criterion_group!(benches, document_benches);
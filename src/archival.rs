@@ -0,0 +1,211 @@
+//! Minimal files for long-term storage.
+//!
+//! Working files keep everything around: balloon images and checkpoints.
+//! Finished chapters that are only kept for the record can be saved with an
+//! [`ArchivalPolicy`] instead, which is stored in the metadata so it is known later
+//! why the images or checkpoints are missing.
+//!
+//! Documents don't hold page images or thumbnails, only balloon images are affected.
+
+use crate::XMLConvertResult;
+
+/// What happens to balloon images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImagePolicy {
+    #[default]
+    Keep,
+    /// Written next to the file (see `Document::save_archival`) and removed from the document.
+    External,
+    Drop,
+}
+
+impl ImagePolicy {
+    fn xml_name(&self) -> &'static str {
+        match self {
+            ImagePolicy::Keep => "kept",
+            ImagePolicy::External => "external",
+            ImagePolicy::Drop => "dropped"
+        }
+    }
+}
+
+/// How a document is reduced for storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArchivalPolicy {
+    pub images: ImagePolicy,
+    /// Keep checkpoints. They hold full copies of balloons, so they are dropped by default.
+    pub checkpoints: bool,
+}
+
+/// Files older than this many days get their images dropped by [`ArchivalPolicy::recommended`].
+pub const ARCHIVE_DROP_AGE_DAYS: u32 = 365;
+
+/// Files larger than this many bytes get their images externalized by [`ArchivalPolicy::recommended`].
+pub const ARCHIVE_EXTERNAL_SIZE: u64 = 4 * 1024 * 1024;
+
+impl ArchivalPolicy {
+    /// Policy for a file last changed `age_days` ago that is `byte_size` bytes large:
+    /// old files lose their images, large ones keep them outside of the document,
+    /// small recent ones keep everything but checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::archival::{ArchivalPolicy, ImagePolicy};
+    ///
+    /// assert_eq!(ArchivalPolicy::recommended(400, 1024).images, ImagePolicy::Drop);
+    /// assert_eq!(ArchivalPolicy::recommended(30, 10 << 20).images, ImagePolicy::External);
+    /// assert_eq!(ArchivalPolicy::recommended(30, 1024).images, ImagePolicy::Keep);
+    /// ```
+    pub fn recommended(age_days: u32, byte_size: u64) -> ArchivalPolicy {
+        let images = if age_days >= ARCHIVE_DROP_AGE_DAYS {
+            ImagePolicy::Drop
+        } else if byte_size >= ARCHIVE_EXTERNAL_SIZE {
+            ImagePolicy::External
+        } else {
+            ImagePolicy::Keep
+        };
+
+        ArchivalPolicy { images, checkpoints: false }
+    }
+
+    /// Generates an xml string of the policy.
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<Archival images=\"{}\" checkpoints=\"{}\"/>",
+            self.images.xml_name(),
+            if self.checkpoints { "kept" } else { "dropped" }
+        )
+    }
+
+    // Parse an <Archival> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<ArchivalPolicy> {
+        let images = match node.attribute("images") {
+            Some("kept") => ImagePolicy::Keep,
+            Some("external") => ImagePolicy::External,
+            Some("dropped") => ImagePolicy::Drop,
+            _ => return Err("Unknown archival image policy!".into())
+        };
+
+        Ok(ArchivalPolicy { images, checkpoints: node.attribute("checkpoints") == Some("kept") })
+    }
+}
+
+#[cfg(feature = "std")]
+mod save {
+    use super::{ArchivalPolicy, ImagePolicy};
+    use crate::{Document, XMLConvertResult};
+    use crate::checkpoint::Checkpoints;
+    use crate::consts::Format;
+    use crate::export::{Dropped, ExportReport};
+    use crate::io::write_atomic;
+
+    use std::fs;
+    use std::path::Path;
+
+    impl Document {
+        /// Saves a reduced copy of the document as compressed xml (`<fp>.sffz`), following `policy`.
+        /// The policy is recorded in the metadata, see [`Document::archival`].
+        ///
+        /// With [`ImagePolicy::External`], balloon images are written to the `<fp>_images` directory
+        /// as `<balloon index>.<image type>`.
+        ///
+        /// Returns what was left out of the saved file.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use rsff::Document;
+        /// use rsff::archival::{ArchivalPolicy, ImagePolicy};
+        /// use rsff::balloon::Balloon;
+        ///
+        /// let mut d = Document::default();
+        /// let mut b = Balloon::default();
+        /// b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        /// d.balloons.push(b);
+        ///
+        /// let policy = ArchivalPolicy { images: ImagePolicy::External, checkpoints: false };
+        /// d.save_archival("archival_example", &policy).unwrap();
+        ///
+        /// let stored = Document::from_file("archival_example.sffz").unwrap();
+        /// assert!(stored.balloons[0].balloon_img.is_none());
+        /// assert_eq!(stored.archival, Some(policy));
+        /// assert_eq!(std::fs::read("archival_example_images/0.jpg").unwrap(), vec![1, 2, 3]);
+        /// # std::fs::remove_file("archival_example.sffz").unwrap();
+        /// # std::fs::remove_dir_all("archival_example_images").unwrap();
+        /// ```
+        pub fn save_archival(&self, fp: &str, policy: &ArchivalPolicy) -> XMLConvertResult<ExportReport> {
+            let mut d = self.clone();
+            let mut dropped: Vec<Dropped> = Vec::new();
+
+            if policy.images != ImagePolicy::Keep {
+                let dir = format!("{fp}_images");
+                let mut images = 0;
+
+                for (i, b) in d.balloons.iter_mut().enumerate() {
+                    b.image_history.clear();
+                    if let Some(img) = b.balloon_img.take() {
+                        if policy.images == ImagePolicy::External {
+                            fs::create_dir_all(&dir)?;
+                            fs::write(format!("{dir}/{i}.{}", img.img_type), img.img_data.as_slice())?;
+                        }
+                        images += 1;
+                    }
+                }
+
+                if images > 0 {
+                    dropped.push(Dropped::Images(images));
+                }
+            }
+
+            if !policy.checkpoints && !d.checkpoints.is_empty() {
+                dropped.push(Dropped::Checkpoints(d.checkpoints.len()));
+                d.checkpoints = Checkpoints::default();
+            }
+
+            d.archival = Some(*policy);
+
+            // Already written with the best compression level
+            write_atomic(Path::new(&format!("{fp}.{}", Format::ZLIB.extension())), &d.to_zlib_bytes())?;
+
+            Ok(ExportReport { format: Format::ZLIB, dropped })
+        }
+    }
+}
+
+#[cfg(test)]
+mod archival_tests {
+    use super::{ArchivalPolicy, ImagePolicy};
+    use crate::Document;
+
+    #[test]
+    fn archival_policy_xml() {
+        let d = Document {
+            archival: Some(ArchivalPolicy { images: ImagePolicy::Drop, checkpoints: true }),
+            ..Default::default()
+        };
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<Archival images=\"dropped\" checkpoints=\"kept\"/></Metadata>"));
+        assert_eq!(Document::from_xml(&xml).unwrap().archival, d.archival);
+        assert!(!Document::default().to_xml().contains("<Archival"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn archival_drops_checkpoints() {
+        let mut d = Document::default();
+        d.balloons.push(crate::balloon::Balloon::default());
+        d.checkpoint("tl done");
+
+        let report = d.save_archival("archival_drops_checkpoints", &ArchivalPolicy::default()).unwrap();
+        assert_eq!(report.to_string(), "1 checkpoint(s)");
+
+        let stored = Document::from_file("archival_drops_checkpoints.sffz").unwrap();
+        assert!(stored.checkpoints.is_empty());
+
+        std::fs::remove_file("archival_drops_checkpoints.sffz").unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+//! Documents inside zip/cbz archives.
+//!
+//! [`Document::from_file`] understands `archive.cbz!chapter.sffz` paths, and
+//! [`Document::save_bundle`] writes the script together with its page images
+//! as a single cbz-like archive for typesetters.
+//!
+//! Only compiled with the `archive` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::consts::Format;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::FileOptions;
+
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["zip", "cbz"];
+
+// Split `archive.zip!chapter.sffz` into the archive path and the entry name, at the
+// first `!` right after an archive extension: folders and entries may contain `!` too.
+pub(crate) fn split_archive_path(fp: &str) -> Option<(&str, &str)> {
+    fp.match_indices('!').find_map(|(i, _)| {
+        let archive = &fp[..i];
+        let ext = Path::new(archive).extension()?.to_str()?.to_lowercase();
+
+        ARCHIVE_EXTENSIONS.contains(&ext.as_str()).then(|| (archive, &fp[i + 1..]))
+    })
+}
+
+/// Names of the entries in `archive` that [`Document::from_file`] can read, in archive order.
+pub fn archive_documents(archive: &str) -> XMLConvertResult<Vec<String>> {
+    let zip = ZipArchive::new(File::open(archive)?)?;
+
+    Ok(zip.file_names()
+        .filter(|name| {
+            Path::new(name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .and_then(Format::from_extension)
+                .is_some()
+        })
+        .map(String::from)
+        .collect())
+}
+
+impl Document {
+    // Open `entry` of the zip archive at `archive`.
+    pub(crate) fn open_archive_entry(archive: &str, entry: &str) -> XMLConvertResult<Document> {
+        let format = crate::io::format_of(Path::new(entry))?;
+
+        let mut zip = ZipArchive::new(File::open(archive)?)?;
+        let mut f = zip.by_name(entry)?;
+        let mut buff: Vec<u8> = Vec::new();
+        f.read_to_end(&mut buff)?;
+
+        Document::bytes_to_doc(format, buff)
+    }
+
+    /// Saves the document (zlib compressed) and its page images as a single zip archive at `archive`.
+    ///
+    /// The script is stored as `<archive name>.sffz` next to the images, which keep their
+    /// file names, so comic readers still see a regular cbz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    ///
+    /// let d = Document::default();
+    /// d.save_bundle("bundle_example.cbz", &["testimg.jpg"]).unwrap();
+    ///
+    /// let reopened = Document::from_file("bundle_example.cbz!bundle_example.sffz").unwrap();
+    /// assert_eq!(reopened.to_xml(), d.to_xml());
+    /// # std::fs::remove_file("bundle_example.cbz").unwrap();
+    /// ```
+    pub fn save_bundle(&self, archive: &str, page_images: &[&str]) -> XMLConvertResult<()> {
+        let stem = Path::new(archive)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or("Invalid archive name!")?;
+
+        // Everything inside is compressed already
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(File::create(archive)?);
+
+        zip.start_file(format!("{stem}.{}", Format::ZLIB.extension()), options)?;
+        zip.write_all(&self.to_zlib_bytes())?;
+
+        for img in page_images {
+            let name = Path::new(img)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or("Invalid image name!")?;
+            let mut buff: Vec<u8> = Vec::new();
+            File::open(img)?.read_to_end(&mut buff)?;
+
+            zip.start_file(name, options)?;
+            zip.write_all(&buff)?;
+        }
+
+        zip.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod archive_tests {
+    use super::{archive_documents, split_archive_path};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn archive_path_split() {
+        assert_eq!(split_archive_path("a/b.CBZ!ch/1.sffz"), Some(("a/b.CBZ", "ch/1.sffz")));
+        assert_eq!(split_archive_path("wow!.sffz"), None);
+        assert_eq!(split_archive_path("Wow!/vol 1.cbz!ch 1!.sffz"), Some(("Wow!/vol 1.cbz", "ch 1!.sffz")));
+        assert_eq!(split_archive_path("a.zip!b.zip!c.sffz"), Some(("a.zip", "b.zip!c.sffz")));
+    }
+
+    #[test]
+    fn archive_bundle_round_trip() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("num".to_string());
+        d.balloons.push(b);
+
+        d.save_bundle("archive_bundle_round_trip.zip", &["testimg.jpg"]).unwrap();
+
+        assert_eq!(
+            archive_documents("archive_bundle_round_trip.zip").unwrap(),
+            vec!["archive_bundle_round_trip.sffz".to_string()]
+        );
+
+        let opened = Document::from_file("archive_bundle_round_trip.zip!archive_bundle_round_trip.sffz").unwrap();
+        assert_eq!(opened.to_xml(), d.to_xml());
+
+        let missing = Document::from_file("archive_bundle_round_trip.zip!nope.sffz");
+        assert!(matches!(missing, Err(crate::Error::Other(_))));
+
+        std::fs::remove_file("archive_bundle_round_trip.zip").unwrap();
+    }
+}
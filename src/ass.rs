@@ -0,0 +1,194 @@
+//! Aegisub (.ass) interop for credit sequences and video previews.
+//!
+//! Balloon types map to styles of the same name (`Dialogue`, `Square`, `Thinking`,
+//! `ST`, `OT`, `Narration`, `SFX`). Balloons have no timing, so every balloon gets its own slot of
+//! [`ASS_LINE_DURATION_CS`] on export, unless timing is given to [`Document::to_ass_with`];
+//! timing is ignored on import.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::subtitle::cue_times;
+
+use std::time::Duration;
+
+/// Duration of every exported line, in centiseconds.
+pub const ASS_LINE_DURATION_CS: u32 = 500;
+
+// `h:mm:ss.cc`
+fn ass_time(cs: u32) -> String {
+    format!("{}:{:02}:{:02}.{:02}", cs / 360_000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+// Remove override blocks like `{\i1}`, keep other braces.
+fn strip_overrides(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{\\") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+impl Document {
+    /// Generates an Aegisub subtitle script with one dialogue line per balloon,
+    /// using the effective text. Line breaks become `\N`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Translated by".to_string());
+    /// b.tl_content.push("Num".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let ass = d.to_ass();
+    /// assert!(ass.contains("Dialogue: 0,0:00:00.00,0:00:05.00,Dialogue,,0,0,0,,Translated by\\NNum"));
+    ///
+    /// let back = Document::from_ass(&ass).unwrap();
+    /// assert_eq!(back.balloons[0].tl_content, vec!["Translated by", "Num"]);
+    /// ```
+    pub fn to_ass(&self) -> String {
+        self.to_ass_with(&[], Duration::from_millis(ASS_LINE_DURATION_CS as u64 * 10))
+    }
+
+    /// [`Document::to_ass`] with `timing[i]` as the duration of balloon `i` and `default`
+    /// for balloons past its end, see [`crate::subtitle`].
+    pub fn to_ass_with(&self, timing: &[Duration], default: Duration) -> String {
+        let mut ass = format!(
+            "[Script Info]\n\
+            Title: {}\n\
+            ScriptType: v4.00+\n\
+            \n\
+            [V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+            Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, \
+            Alignment, MarginL, MarginR, MarginV, Encoding\n",
+            self.METADATA_INFO
+        );
+
+        for t in TYPES::all() {
+            // Thinking is italic, the rest plain
+            let italic = if *t == TYPES::THINKING { -1 } else { 0 };
+            ass.push_str(format!(
+                "Style: {},Arial,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,{},0,0,100,100,0,0,1,2,0,2,10,10,10,1\n",
+                t.xml_name(),
+                italic
+            ).as_str());
+        }
+
+        ass.push_str("\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+
+        let cs = |t: Duration| (t.as_millis() / 10) as u32;
+        for (b, (start, end)) in self.balloons.iter().zip(cue_times(self.balloons.len(), timing, default)) {
+            ass.push_str(format!(
+                "Dialogue: 0,{},{},{},,0,0,0,,{}\n",
+                ass_time(cs(start)),
+                ass_time(cs(end)),
+                b.btype.xml_name(),
+                b.effective_lines().join("\\N")
+            ).as_str());
+        }
+
+        ass
+    }
+
+    /// Generates a document from an Aegisub subtitle script.
+    /// Every dialogue line becomes a balloon with the text as tl content; override tags are removed.
+    /// Styles that aren't balloon type names become dialogues.
+    pub fn from_ass(ass: &str) -> XMLConvertResult<Document> {
+        let mut d = Document::default();
+        let mut in_events = false;
+        let mut columns: Vec<String> = Vec::new();
+
+        for line in ass.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_events = line.eq_ignore_ascii_case("[Events]");
+                continue;
+            }
+            if !in_events {
+                continue;
+            }
+
+            if let Some(format) = line.strip_prefix("Format:") {
+                columns = format.split(',').map(|c| c.trim().to_string()).collect();
+                continue;
+            }
+
+            let fields = match line.strip_prefix("Dialogue:") {
+                Some(f) => f,
+                None => continue
+            };
+            if columns.is_empty() {
+                return Err("Dialogue line before the events format line!".into());
+            }
+
+            // Text is the last column and may contain commas
+            let values: Vec<&str> = fields.splitn(columns.len(), ',').map(str::trim_start).collect();
+            let column = |name: &str| {
+                columns.iter()
+                    .position(|c| c == name)
+                    .and_then(|i| values.get(i).copied())
+            };
+            let text = column("Text").ok_or("Dialogue line without text!")?;
+
+            let mut b = Balloon {
+                btype: TYPES::from_xml_name(column("Style").unwrap_or("").trim()),
+                ..Default::default()
+            };
+            b.tl_content = strip_overrides(text)
+                .replace("\\n", "\\N")
+                .split("\\N")
+                .map(String::from)
+                .collect();
+
+            d.balloons.push(b);
+        }
+
+        Ok(d)
+    }
+}
+
+#[cfg(test)]
+mod ass_tests {
+    use super::{ass_time, strip_overrides};
+    use crate::Document;
+    use crate::consts::TYPES;
+
+    #[test]
+    fn ass_time_format() {
+        assert_eq!(ass_time(0), "0:00:00.00");
+        assert_eq!(ass_time(366_123), "1:01:01.23");
+    }
+
+    #[test]
+    fn ass_import_events() {
+        let ass = "[Script Info]\nTitle: x\n\n[Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Comment: 0,0:00:00.00,0:00:01.00,OT,,0,0,0,,skipped\n\
+            Dialogue: 0,0:00:00.00,0:00:01.00,OT,,0,0,0,,{\\i1}Hello{\\i0}, world\\Nbye {}\n\
+            Dialogue: 0,0:00:01.00,0:00:02.00,Credits,,0,0,0,,Num\n";
+
+        let d = Document::from_ass(ass).unwrap();
+
+        assert_eq!(d.len(), 2);
+        assert_eq!(d.balloons[0].btype, TYPES::OT);
+        assert_eq!(d.balloons[0].tl_content, vec!["Hello, world", "bye {}"]);
+        assert_eq!(d.balloons[1].btype, TYPES::DIALOGUE);
+        assert_eq!(strip_overrides("{\\b1"), "{\\b1");
+    }
+}
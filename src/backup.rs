@@ -0,0 +1,141 @@
+//! Automatic backups before destructive operations.
+//!
+//! [`merge`](crate::merge::merge), [`Document::finalize`], bulk transforms such as
+//! [`Document::normalize_cjk`] and [`Document::gc`] can't be undone once the result is
+//! saved over the original file. [`Document::with_backup`] first writes the current state
+//! to a backup directory and returns the path next to the operation's result, so undoing
+//! what a script did is opening that file.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::consts::Format;
+use crate::io::write_atomic;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where backups are written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupPolicy {
+    /// Created if missing.
+    pub dir: PathBuf,
+}
+
+impl BackupPolicy {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+/// Result of an operation run through [`Document::with_backup`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backed<R> {
+    pub result: R,
+    /// The backup written before the operation, `None` without a policy.
+    pub backup: Option<PathBuf>,
+}
+
+// Keep operation names usable in file names.
+fn file_safe(op: &str) -> String {
+    op.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+impl Document {
+    /// Writes the current state as compressed xml to `policy.dir`, named
+    /// `<unix seconds>-<nanoseconds>-<op>.sffz`, and returns its path.
+    pub fn backup(&self, policy: &BackupPolicy, op: &str) -> XMLConvertResult<PathBuf> {
+        fs::create_dir_all(&policy.dir)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let stem = format!("{}-{:09}-{}", now.as_secs(), now.subsec_nanos(), file_safe(op));
+        let ext = Format::ZLIB.extension();
+
+        // Two backups within the same clock tick don't overwrite each other
+        let mut path = policy.dir.join(format!("{stem}.{ext}"));
+        let mut n = 1;
+        while path.exists() {
+            path = policy.dir.join(format!("{stem}-{n}.{ext}"));
+            n += 1;
+        }
+
+        write_atomic(&path, &self.to_zlib_bytes())?;
+
+        Ok(path)
+    }
+
+    /// Runs `f` on the document, after writing a backup if `policy` is set.
+    /// `f` doesn't run if the backup fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::backup::BackupPolicy;
+    /// use rsff::balloon::{Balloon, BalloonImage};
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.add_image("jpg".to_string(), vec![0; 100]);
+    /// b.replace_image(BalloonImage::new("jpg", vec![1; 100]));
+    /// d.balloons.push(b);
+    ///
+    /// let policy = BackupPolicy::new("backup_example");
+    /// let gc = d.with_backup(Some(&policy), "gc", |d| d.gc()).unwrap();
+    /// assert_eq!(gc.result.history_removed, 1);
+    ///
+    /// // Changed my mind
+    /// let d = Document::from_file(gc.backup.unwrap().to_str().unwrap()).unwrap();
+    /// assert_eq!(d.balloons[0].image_history.len(), 1);
+    /// # std::fs::remove_dir_all("backup_example").unwrap();
+    /// ```
+    pub fn with_backup<R>(
+        &mut self,
+        policy: Option<&BackupPolicy>,
+        op: &str,
+        f: impl FnOnce(&mut Document) -> R
+    ) -> XMLConvertResult<Backed<R>> {
+        let backup = match policy {
+            Some(p) => Some(self.backup(p, op)?),
+            None => None
+        };
+
+        Ok(Backed { result: f(self), backup })
+    }
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use super::BackupPolicy;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::merge::{OnConflict, merge};
+
+    #[test]
+    fn backup_before_merge_and_finalize() {
+        let policy = BackupPolicy::new("backup_before_merge_and_finalize");
+        let base = Document::default();
+        let mut ours = base.clone();
+        ours.balloons.push(Balloon::default().with_line(crate::consts::Track::TL, "num"));
+        let theirs = base.clone();
+
+        // The merged document replaces ours
+        let merged = ours.with_backup(Some(&policy), "merge 3-way", |d| merge(&base, d, &theirs, OnConflict::Abort)).unwrap();
+        let first = merged.backup.unwrap();
+        assert!(first.file_name().unwrap().to_str().unwrap().ends_with("-merge_3-way.sffz"));
+
+        let mut d = merged.result.unwrap().document;
+        let second = d.with_backup(Some(&policy), "merge 3-way", |d| d.finalize()).unwrap().backup.unwrap();
+        assert_ne!(first, second);
+        assert!(d.lock.is_some());
+        assert!(Document::from_file(second.to_str().unwrap()).unwrap().lock.is_none());
+
+        // No policy, no backup
+        assert_eq!(d.with_backup(None, "gc", |d| d.gc().history_removed).unwrap().backup, None);
+
+        std::fs::remove_dir_all("backup_before_merge_and_finalize").unwrap();
+    }
+}
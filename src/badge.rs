@@ -0,0 +1,148 @@
+//! Small status badges (e.g. "Ch. 45 | PR 80%") for team websites.
+
+use crate::Document;
+use crate::balloon::{escape_attr, escape_text};
+use crate::progress::Progress;
+use crate::project::Project;
+
+/// A two-part badge: a grey label and a colored message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Badge {
+    pub label: String,
+    pub message: String,
+    /// Named color, as understood by shields.io: "red", "yellow" or "green".
+    pub color: &'static str,
+}
+
+// Approximate width of text rendered in 11px Verdana.
+fn text_width(s: &str) -> usize {
+    s.chars().count() * 7 + 10
+}
+
+fn hex(color: &str) -> &'static str {
+    match color {
+        "red" => "#e05d44",
+        "yellow" => "#dfb317",
+        _ => "#4c1"
+    }
+}
+
+impl Badge {
+    /// Badge for a progress: the current stage (TL, then PR) and its completion.
+    pub fn from_progress(label: &str, p: &Progress) -> Self {
+        let (message, color) = if p.conflicts > 0 {
+            (format!("{} conflict(s)", p.conflicts), "red")
+        } else if p.translated < p.balloons {
+            (format!("TL {}%", p.translated_percent()), "red")
+        } else if p.proofread < p.balloons {
+            (format!("PR {}%", p.proofread_percent()), "yellow")
+        } else {
+            (String::from("done"), "green")
+        };
+
+        Self { label: label.to_string(), message, color }
+    }
+
+    /// Flat badge as a standalone SVG image.
+    pub fn to_svg(&self) -> String {
+        let lw = text_width(&self.label);
+        let mw = text_width(&self.message);
+        let label = escape_text(&self.label);
+        let message = escape_text(&self.message);
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"20\" role=\"img\" aria-label=\"{aria}\">\
+            <title>{label}: {message}</title>\
+            <rect width=\"{lw}\" height=\"20\" fill=\"#555\"/>\
+            <rect x=\"{lw}\" width=\"{mw}\" height=\"20\" fill=\"{color}\"/>\
+            <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,sans-serif\" font-size=\"11\">\
+            <text x=\"{lx}\" y=\"14\">{label}</text>\
+            <text x=\"{mx}\" y=\"14\">{message}</text>\
+            </g></svg>",
+            aria = escape_attr(&format!("{}: {}", self.label, self.message)),
+            w = lw + mw,
+            color = hex(self.color),
+            lx = lw / 2,
+            mx = lw + mw / 2,
+        )
+    }
+
+    /// Badge in the shields.io endpoint JSON schema.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "schemaVersion": 1,
+            "label": self.label,
+            "message": self.message,
+            "color": self.color
+        }).to_string()
+    }
+}
+
+impl Document {
+    /// Status badge of the document, labeled `label`. See [`Badge::from_progress`].
+    pub fn status_badge(&self, label: &str) -> Badge {
+        Badge::from_progress(label, &self.progress())
+    }
+}
+
+impl Project {
+    /// Status badge of every chapter, in order, labeled with the chapter label.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::project::Project;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("num".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let mut p = Project::new("My Series");
+    /// p.add_chapter("Ch. 45", d);
+    ///
+    /// let badge = &p.status_badge()[0];
+    /// assert_eq!(badge.message, "PR 0%");
+    /// std::fs::write("ch45.svg", badge.to_svg()).unwrap();
+    /// # std::fs::remove_file("ch45.svg").unwrap();
+    /// ```
+    pub fn status_badge(&self) -> Vec<Badge> {
+        self.chapters
+            .iter()
+            .map(|c| c.document.status_badge(&c.label))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod badge_tests {
+    use super::Badge;
+    use crate::progress::Progress;
+
+    #[test]
+    fn badge_stages() {
+        let p = Progress { balloons: 5, translated: 5, proofread: 4, conflicts: 0 };
+        let b = Badge::from_progress("Ch. 45", &p);
+        assert_eq!((b.message.as_str(), b.color), ("PR 80%", "yellow"));
+
+        let p = Progress { balloons: 5, translated: 5, proofread: 5, conflicts: 0 };
+        assert_eq!(Badge::from_progress("Ch. 45", &p).message, "done");
+
+        let p = Progress { balloons: 5, translated: 1, proofread: 0, conflicts: 0 };
+        assert_eq!(Badge::from_progress("Ch. 45", &p).message, "TL 20%");
+    }
+
+    #[test]
+    fn badge_svg_escapes_text() {
+        let b = Badge { label: "A&B".to_string(), message: "\"done\"".to_string(), color: "green" };
+        let svg = b.to_svg();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<text x=\"15\" y=\"14\">A&amp;B</text>"));
+        assert!(svg.contains("aria-label=\"A&amp;B: &quot;done&quot;\""));
+        assert!(roxmltree::Document::parse(&svg).is_ok());
+    }
+}
@@ -1,19 +1,105 @@
-use crate::consts::TYPES;
-use base64::{engine, Engine as _, alphabet};
-
-const B64: engine::GeneralPurpose = engine::GeneralPurpose::new(&alphabet::URL_SAFE, engine::general_purpose::NO_PAD);
+use crate::{B64, XMLConvertResult};
+use crate::confidence::Confidence;
+use crate::consts::{DEFAULT_PRECEDENCE, TYPES, Track};
+use crate::hash::Fnv64;
+use crate::layout::{BBox, bbox_attr, num_attr};
+use crate::provenance::Provenance;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::Hasher;
+use std::io::Write;
+use std::sync::Arc;
+use base64::Engine as _;
+use base64::write::EncoderWriter;
 
 /// A simple image container
+/// 
+/// Image data is reference counted, so cloning a balloon (or a document, see
+/// [`Document::to_shared`](crate::Document::to_shared)) does not copy the bytes. Use [`BalloonImage::data_mut`] to edit them copy-on-write.
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BalloonImage {
     pub img_type: String,
-    pub img_data: Vec<u8>
+    #[cfg_attr(feature = "serde", serde(with = "img_data_base64"))]
+    pub img_data: Arc<Vec<u8>>,
+    /// How this image is compressed when saving, overriding
+    /// [`ExportOptions::image_compression`](crate::export::ExportOptions::image_compression).
+    pub compression: Option<ImageCompression>
+}
+
+/// How a balloon image is (re)compressed when the document is saved.
+///
+/// Recompression needs the `image-sanitize` feature; without it images are always saved as they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageCompression {
+    /// Keep the bytes as they are, e.g. for a cover crop.
+    Original,
+    /// Re-encode as png, lossless.
+    Png,
+    /// Re-encode as jpeg with this quality (1-100).
+    Jpeg(u8),
+}
+
+impl ImageCompression {
+    fn xml_value(&self) -> String {
+        match self {
+            ImageCompression::Original => String::from("original"),
+            ImageCompression::Png => String::from("png"),
+            ImageCompression::Jpeg(q) => format!("jpeg:{q}")
+        }
+    }
+
+    fn from_xml_value(v: &str) -> Option<ImageCompression> {
+        match v {
+            "original" => Some(ImageCompression::Original),
+            "png" => Some(ImageCompression::Png),
+            _ => v.strip_prefix("jpeg:")?.parse().ok().map(ImageCompression::Jpeg)
+        }
+    }
+}
+
+// Image bytes as base64 text, like in xml.
+#[cfg(feature = "serde")]
+mod img_data_base64 {
+    use crate::B64;
+    use base64::Engine as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::sync::Arc;
+
+    pub(super) fn serialize<S: Serializer>(data: &Arc<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&B64.encode(data.as_slice()))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Arc<Vec<u8>>, D::Error> {
+        let encoded = String::deserialize(d)?;
+        B64.decode(encoded).map(Arc::new).map_err(serde::de::Error::custom)
+    }
+}
+
+impl BalloonImage {
+    /// An image of type `img_type` (its file extension, e.g. `"png"`) saved as it is.
+    pub fn new(img_type: impl Into<String>, img_data: Vec<u8>) -> Self {
+        Self { img_type: img_type.into(), img_data: Arc::new(img_data), compression: None }
+    }
+
+    /// The image bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.img_data
+    }
+
+    /// Mutable access to the image bytes.
+    /// Clones the data first if it is shared with another balloon.
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        Arc::make_mut(&mut self.img_data)
+    }
 }
 
 
 /// A struct represents a balloon.
 /// 
-/// Contains translation and proofred contents, comments, balloon image (if has any). Must have a distinct type.
+/// Contains translation, translation check and proofred contents, comments, balloon image (if has any). Must have a distinct type.
 /// # Examples
 /// 
 /// ```
@@ -23,15 +109,220 @@ pub struct BalloonImage {
 /// b.tl_content.push("This is a tl line.".to_string());
 /// ```
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Balloon {
     pub tl_content: Vec<String>,
+    /// Translation check, between tl and pr.
+    pub tlc_content: Vec<String>,
     pub pr_content: Vec<String>,
     pub comments: Vec<String>,
     pub btype: TYPES,
     pub balloon_img: Option<BalloonImage>,
+    /// Number of the page the balloon is on, see [`crate::layout`].
+    pub page: Option<u32>,
+    /// Id of the panel the balloon is in, see [`crate::layout`].
+    pub panel: Option<u32>,
+    /// Stable number of the balloon on its page, see [`Document::assign_indices`](crate::Document::assign_indices).
+    pub order: Option<u32>,
+    /// Area of the balloon on its page, see [`crate::fit`].
+    pub bbox: Option<BBox>,
+    /// Character saying the text, for script exports. See [`crate::export::TextLayout`].
+    pub speaker: Option<String>,
+    /// Scores of the OCR/MT pipeline that produced the balloon, see [`crate::confidence`].
+    pub confidence: Confidence,
+    /// Where each line came from, by track and line index. See [`crate::provenance`].
+    #[cfg_attr(feature = "serde", serde(with = "crate::provenance::serde_entries"))]
+    pub provenance: BTreeMap<(Track, usize), Provenance>,
+    /// Images replaced by [`Balloon::replace_image`], oldest first.
+    pub image_history: Vec<BalloonImage>,
+}
+
+/// Number of replaced images [`Balloon::replace_image`] keeps per balloon.
+pub const IMAGE_HISTORY_LEN: usize = 3;
+
+// Parsers turn `\r\n` and `\r` into `\n`, and tabs and line breaks in attributes into
+// spaces. Character references are kept as they are.
+const ATTR_ESCAPES: [(char, &str); 6] = [('&', "&amp;"), ('"', "&quot;"), ('<', "&lt;"), ('\t', "&#9;"), ('\n', "&#10;"), ('\r', "&#13;")];
+const TEXT_ESCAPES: [(char, &str); 4] = [('&', "&amp;"), ('<', "&lt;"), ('>', "&gt;"), ('\r', "&#13;")];
+
+// `s` with the characters of `escapes` replaced in one pass. Most text has nothing to
+// escape and is borrowed as it is.
+pub(crate) fn escape<'a>(s: &'a str, escapes: &[(char, &str)]) -> Cow<'a, str> {
+    let replacement = |c: char| escapes.iter().find(|(e, _)| *e == c).map(|(_, r)| *r);
+
+    let Some(first) = s.find(|c| replacement(c).is_some()) else {
+        return Cow::Borrowed(s);
+    };
+
+    let mut escaped = String::with_capacity(s.len() + 8);
+    escaped.push_str(&s[..first]);
+    for c in s[first..].chars() {
+        match replacement(c) {
+            Some(r) => escaped.push_str(r),
+            None => escaped.push(c)
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+// Escape free text for an xml attribute value.
+pub(crate) fn escape_attr(s: &str) -> Cow<'_, str> {
+    escape(s, &ATTR_ESCAPES)
+}
+
+// Escape free text for an element's content. The parser unescapes it again.
+pub(crate) fn escape_text(s: &str) -> Cow<'_, str> {
+    escape(s, &TEXT_ESCAPES)
+}
+
+// `xml:space="preserve"` for text starting or ending with spaces, so xml tools that
+// reformat files (pretty printers, editors) keep them. The parser always keeps them.
+fn space_attr(s: &str) -> &'static str {
+    let is_space = |c: char| matches!(c, ' ' | '\t');
+    if s.starts_with(is_space) || s.ends_with(is_space) {
+        " xml:space=\"preserve\""
+    } else {
+        ""
+    }
+}
+
+// Write `data` as b64 to `w`, a few kilobytes at a time.
+fn write_b64<W: Write>(w: W, data: &[u8]) -> XMLConvertResult<()> {
+    let mut enc = EncoderWriter::new(w, &B64);
+    enc.write_all(data)?;
+    enc.finish()?;
+
+    Ok(())
 }
 
 impl Balloon {
+    /// Creates an empty balloon of type `btype`.
+    pub fn new(btype: TYPES) -> Self {
+        Self { btype, ..Default::default() }
+    }
+
+    /// Builder style: appends `text` to `track`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::prelude::*;
+    ///
+    /// let b = Balloon::new(TYPES::OT)
+    ///     .with_line(Track::TL, "Boom")
+    ///     .with_line(Track::PR, "BOOM");
+    ///
+    /// assert_eq!(b.effective_lines(), ["BOOM"]);
+    /// ```
+    pub fn with_line(mut self, track: Track, text: impl Into<String>) -> Self {
+        match track {
+            Track::TL => self.tl_content.push(text.into()),
+            Track::TLC => self.tlc_content.push(text.into()),
+            Track::PR => self.pr_content.push(text.into())
+        }
+
+        self
+    }
+
+    /// Builder style: appends a comment.
+    pub fn with_comment(mut self, text: impl Into<String>) -> Self {
+        self.comments.push(text.into());
+
+        self
+    }
+
+    // Generate a balloon from a <Balloon> xml node.
+    pub(crate) fn from_xml_node(c: roxmltree::Node) -> XMLConvertResult<Balloon> {
+        let mut b = Balloon {
+            btype: TYPES::from_xml_attrs(c.attribute("type").unwrap_or(""), c.attribute("subtype")),
+            page: num_attr(c, "page")?,
+            panel: num_attr(c, "panel")?,
+            order: num_attr(c, "order")?,
+            bbox: bbox_attr(c)?,
+            speaker: c.attribute("speaker").map(String::from),
+            confidence: Confidence::from_xml_node(c)?,
+            ..Default::default()
+        };
+
+        let tls = c.children().filter(|c| {c.tag_name().name() == "TL"});
+        let tlcs = c.children().filter(|c| {c.tag_name().name() == "TLC"});
+        let prs = c.children().filter(|c| {c.tag_name().name() == "PR"});
+        let comments = c.children().filter(|c| {c.tag_name().name() == "Comment"});
+        let img = c.children().find(|c| {c.tag_name().name() == "img"});
+        let old_imgs = c.children().filter(|c| {c.tag_name().name() == "OldImg"});
+
+        for (i, tl) in tls.enumerate() {
+            let content = match tl.text() {
+                Some(t) => t.to_string(),
+                None => String::new()
+            };
+            b.tl_content.push(content);
+
+            if let Some(p) = Provenance::from_xml_node(tl)? {
+                b.provenance.insert((Track::TL, i), p);
+            }
+        }
+
+        for (i, tlc) in tlcs.enumerate() {
+            let content = match tlc.text() {
+                Some(t) => t.to_string(),
+                None => String::new()
+            };
+            b.tlc_content.push(content);
+
+            if let Some(p) = Provenance::from_xml_node(tlc)? {
+                b.provenance.insert((Track::TLC, i), p);
+            }
+        }
+
+        for (i, pr) in prs.enumerate() {
+            let content = match pr.text() {
+                Some(t) => t.to_string(),
+                None => String::new()
+            };
+            b.pr_content.push(content);
+
+            if let Some(p) = Provenance::from_xml_node(pr)? {
+                b.provenance.insert((Track::PR, i), p);
+            }
+        }
+
+        for comment in comments {
+            let content = match comment.text() {
+                Some(t) => t.to_string(),
+                None => String::new()
+            };
+            b.comments.push(content);
+        }
+
+        if let Some(img) = img {
+            let compression = match img.attribute("compression") {
+                Some(c) => Some(ImageCompression::from_xml_value(c).ok_or("Unknown image compression!")?),
+                None => None
+            };
+            let i = BalloonImage {
+                img_type: img.attribute("type").unwrap_or("").to_string(),
+                // Images of packages are stored next to the xml, the package fills them in
+                img_data: Arc::new(B64.decode(img.text().unwrap_or(""))?),
+                compression
+            };
+            b.balloon_img = Some(i);
+        } else {
+            b.balloon_img = None;
+        }
+
+        for img in old_imgs {
+            b.image_history.push(BalloonImage {
+                img_type: img.attribute("type").unwrap_or("").to_string(),
+                img_data: Arc::new(B64.decode(img.text().unwrap_or(""))?),
+                compression: None
+            });
+        }
+
+        Ok(b)
+    }
+
     /// Add image to balloon. Creates a `BalloonImage` struct and adds to the balloon.
     /// `img_type` is a string defines image's extention. '.jpg' etc.
     /// `img_data` is raw image as bytes.
@@ -50,7 +341,49 @@ impl Balloon {
     /// );
     /// ```
     pub fn add_image(&mut self, img_type: String, img_data: Vec<u8>) {
-        self.balloon_img = Some(BalloonImage {img_type, img_data});
+        self.balloon_img = Some(BalloonImage::new(img_type, img_data));
+    }
+
+    /// Replaces the image of the balloon. The previous one is kept in [`Balloon::image_history`]
+    /// (up to [`IMAGE_HISTORY_LEN`] images) and saved with the document, so a wrong crop can
+    /// still be undone with [`Balloon::undo_image`] after reopening the file.
+    /// Use [`Document::gc`](crate::Document::gc) to drop the history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::{Balloon, BalloonImage};
+    ///
+    /// let mut b = Balloon::default();
+    /// b.add_image("png".to_string(), vec![1]);
+    ///
+    /// b.replace_image(BalloonImage { img_type: "png".to_string(), img_data: vec![2].into(), compression: None });
+    /// assert_eq!(b.image_history.len(), 1);
+    ///
+    /// assert!(b.undo_image());
+    /// assert_eq!(*b.balloon_img.unwrap().img_data, vec![1]);
+    /// ```
+    pub fn replace_image(&mut self, new: BalloonImage) {
+        if let Some(old) = self.balloon_img.replace(new) {
+            self.image_history.push(old);
+        }
+
+        if self.image_history.len() > IMAGE_HISTORY_LEN {
+            let excess = self.image_history.len() - IMAGE_HISTORY_LEN;
+            self.image_history.drain(..excess);
+        }
+    }
+
+    /// Puts the last replaced image back. `false` if the history is empty.
+    /// The current image is discarded.
+    pub fn undo_image(&mut self) -> bool {
+        match self.image_history.pop() {
+            Some(img) => {
+                self.balloon_img = Some(img);
+                true
+            },
+            None => false
+        }
     }
 
     /// Removes the image from balloon.
@@ -58,6 +391,14 @@ impl Balloon {
         self.balloon_img = None;
     }
 
+    /// Returns `true` if the balloon has any tl, tlc, pr or comment line.
+    pub fn has_text(&self) -> bool {
+        !(self.tl_content.is_empty()
+            && self.tlc_content.is_empty()
+            && self.pr_content.is_empty()
+            && self.comments.is_empty())
+    }
+
     /// Total character count of all translation content.
     /// *(Spaces included.)*
     pub fn tl_chars(&self) -> usize {
@@ -67,6 +408,15 @@ impl Balloon {
             .sum()
     }
 
+    /// Total character count of all translation check content.
+    /// *(Spaces included.)*
+    pub fn tlc_chars(&self) -> usize {
+        self.tlc_content
+            .iter()
+            .map(|text| {text.len()})
+            .sum()
+    }
+
     /// Total character count of all proofread content.
     /// *(Spaces included.)*
     pub fn pr_chars(&self) -> usize {
@@ -85,102 +435,255 @@ impl Balloon {
             .sum()
     }
 
-    /// Total line count of the balloon.
-    /// Counts pr content lines if balloon has pr content, otherwise counts tl content lines.
+    /// Total line count of the balloon, see [`Balloon::effective_lines`].
     pub fn line_count(&self) -> usize {
-        if self.pr_content.len() > 0 {
-            return self.pr_content.len();
-        } else {
-            return self.tl_content.len();
+        self.effective_lines().len()
+    }
+
+    /// Lines of one text track.
+    pub fn lines(&self, track: Track) -> &[String] {
+        match track {
+            Track::TL => &self.tl_content,
+            Track::TLC => &self.tlc_content,
+            Track::PR => &self.pr_content
         }
     }
 
-    /// Generates stringified version of the balloon.
-    /// Use this with caution because of data loss.
+    /// The lines that end up on the page: the first non-empty track of [`DEFAULT_PRECEDENCE`]
+    /// (pr, then tlc, then tl).
+    pub fn effective_lines(&self) -> &[String] {
+        self.effective_lines_with(&DEFAULT_PRECEDENCE)
+    }
+
+    /// Like [`Balloon::effective_lines`], with a custom track precedence,
+    /// e.g. `[Track::PR, Track::TL]` for groups that don't trust their tlc yet.
+    pub fn effective_lines_with(&self, precedence: &[Track]) -> &[String] {
+        precedence
+            .iter()
+            .map(|t| self.lines(*t))
+            .find(|l| !l.is_empty())
+            .unwrap_or(&[])
+    }
+
+    /// Stable 64-bit hash of the balloon's content (type, layout, tl, tlc, pr, comments and image).
     /// 
-    /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
-    pub fn to_string(&self) -> String {
-        // Decide balloon type header text
-        let type_str = match self.btype {
-            TYPES::DIALOGUE => "(): ",
-            TYPES::OT => "OT: ",
-            TYPES::SQUARE => "[]: ",
-            TYPES::ST => "ST: ",
-            TYPES::THINKING => "{}: "
-        };
+    /// Equal balloons always have equal hashes, across runs and platforms,
+    /// so it can be stored and compared later to skip unchanged balloons when syncing.
+    /// The [`order`](Balloon::order) number is left out: numbering balloons is not an edit.
+    pub fn hash(&self) -> u64 {
+        self.content_hasher().finish()
+    }
 
-        // If balloon has pr content, generate balloon text from pr content
-        // else, generate balloon text from tl content
-        if self.pr_content.len() > 0 {
-            return self.pr_content
-                .iter()
-                .map(|pr| {
-                    format!("{}{}", type_str, pr)
-                })
-                .collect::<Vec<String>>()
-                .join("\n//\n");
-        } else {
-            return self.tl_content
-                .iter()
-                .map(|tl| {
-                    format!("{}{}", type_str, tl)
-                })
-                .collect::<Vec<String>>()
-                .join("\n//\n");
+    // Hash of everything the balloon stores, `order` included. Identifies stored copies,
+    // e.g. checkpoint pool entries and journal records. Same as `hash` without an order.
+    pub(crate) fn full_hash(&self) -> u64 {
+        let mut h = self.content_hasher();
+        if let Some(order) = self.order {
+            h.write_u8(b'r');
+            h.write_u64(order as u64);
+        }
+
+        h.finish()
+    }
+
+    // Hasher fed with the content hashed by `hash`.
+    fn content_hasher(&self) -> Fnv64 {
+        let mut h = Fnv64::default();
+
+        h.write(self.btype.xml_name().as_bytes());
+
+        for (tag, value) in [(b'p', self.page), (b'n', self.panel)] {
+            if let Some(v) = value {
+                h.write_u8(tag);
+                h.write_u64(v as u64);
+            }
+        }
+
+        if let Some(bbox) = self.bbox {
+            h.write_u8(b'b');
+            for v in [bbox.x, bbox.y, bbox.w, bbox.h] {
+                h.write_u64(v as u64);
+            }
+        }
+
+        if let Some(speaker) = &self.speaker {
+            h.write_u8(b's');
+            h.write_u64(speaker.len() as u64);
+            h.write(speaker.as_bytes());
+        }
+
+        for (tag, value) in [(b'o', self.confidence.ocr), (b'm', self.confidence.mt)] {
+            if let Some(v) = value {
+                h.write_u8(tag);
+                h.write_u32(v.to_bits());
+            }
+        }
+
+        // Only lines that exist, like to_xml
+        for ((track, line), p) in self.provenance.iter().filter(|((t, l), _)| *l < self.lines(*t).len()) {
+            let attrs = p.xml_attrs();
+            h.write_u8(b'v');
+            h.write_u8(*track as u8);
+            h.write_u64(*line as u64);
+            h.write_u64(attrs.len() as u64);
+            h.write(attrs.as_bytes());
+        }
+
+        // Prefix every field with a tag of its own and its length so moving text
+        // from one line/field to another changes the hash.
+        for (tag, lines) in [(b'T', &self.tl_content), (b'L', &self.tlc_content), (b'P', &self.pr_content), (b'C', &self.comments)] {
+            for line in lines {
+                h.write_u8(tag);
+                h.write_u64(line.len() as u64);
+                h.write(line.as_bytes());
+            }
+        }
+
+        if let Some(img) = &self.balloon_img {
+            // Only hashed when set, so hashes of older balloons don't change
+            if let Some(c) = img.compression {
+                let v = c.xml_value();
+                h.write_u8(b'c');
+                h.write_u64(v.len() as u64);
+                h.write(v.as_bytes());
+            }
+
+            h.write_u8(b'I');
+            h.write_u64(img.img_type.len() as u64);
+            h.write(img.img_type.as_bytes());
+            h.write(&img.img_data);
+        }
+
+        for img in &self.image_history {
+            h.write_u8(b'H');
+            h.write_u64(img.img_type.len() as u64);
+            h.write(img.img_type.as_bytes());
+            h.write_u64(img.img_data.len() as u64);
+            h.write(&img.img_data);
         }
+
+        h
     }
 
     /// Generates an xml string of the balloon. No data loss so you can use this whenever you want.
+    /// Whitespace in lines is kept as is, lines starting or ending with spaces are marked
+    /// `xml:space="preserve"` for other xml tools.
     /// 
     /// **Note:** Raw image data will be converted to a b64 encoded string.
     pub fn to_xml(&self) -> String {
-        // Decide balloon type attribute text for xml
-        let b_type_text = match self.btype {
-            TYPES::DIALOGUE => "Dialogue",
-            TYPES::SQUARE => "Square",
-            TYPES::ST => "ST",
-            TYPES::OT => "OT",
-            TYPES::THINKING => "Thinking"
-        };
+        let mut xml: Vec<u8> = Vec::new();
+        // Writing to a vec can't fail and everything written is utf-8
+        self.write_xml(&mut xml).unwrap();
+        String::from_utf8(xml).unwrap()
+    }
 
-        let mut xml = format!(
-            "<Balloon type=\"{}\">",
-            b_type_text
-        );
+    /// Writes the xml of [`Balloon::to_xml`] to `w`, encoding images in chunks instead of
+    /// building their b64 text in memory.
+    pub fn write_xml<W: Write>(&self, w: W) -> XMLConvertResult<()> {
+        self.write_xml_with(w, None)
+    }
+
+    // Like `write_xml`, with the image written as a reference to `img_src` instead of its
+    // data if set, see `crate::package`.
+    pub(crate) fn write_xml_with<W: Write>(&self, mut w: W, img_src: Option<&str>) -> XMLConvertResult<()> {
+        // Tags are written straight to `w`, this runs for every balloon of every save
+        let compat = self.btype.compat();
+        write!(w, "<Balloon type=\"{}\"", compat.xml_name())?;
 
-        // Iterate over tl, pr, comments and create tags and their inner contents
-        for tl in &self.tl_content {
-            xml.push_str(
-                format!("<TL>{}</TL>", tl).as_str()
-            );
+        // Older versions only read `type`
+        if compat != self.btype {
+            write!(w, " subtype=\"{}\"", self.btype.xml_name())?;
+        }
+
+        // Layout attributes are only written if set
+        if let Some(page) = self.page {
+            write!(w, " page=\"{}\"", page)?;
+        }
+        if let Some(panel) = self.panel {
+            write!(w, " panel=\"{}\"", panel)?;
         }
+        if let Some(order) = self.order {
+            write!(w, " order=\"{}\"", order)?;
+        }
+        if let Some(bbox) = self.bbox {
+            write!(w, " x=\"{}\" y=\"{}\" w=\"{}\" h=\"{}\"", bbox.x, bbox.y, bbox.w, bbox.h)?;
+        }
+        if let Some(speaker) = &self.speaker {
+            // Names are free text, quotes would end the attribute
+            write!(w, " speaker=\"{}\"", escape_attr(speaker))?;
+        }
+        w.write_all(self.confidence.xml_attrs().as_bytes())?;
 
-        for pr in &self.pr_content {
-            xml.push_str(
-                format!("<PR>{}</PR>", pr).as_str()
-            );
+        w.write_all(b">")?;
+
+        // Iterate over tl, tlc, pr, comments and create tags and their inner contents.
+        // Text is escaped, so lines like "<3" or "Q&A" don't break the file
+        for (tag, track) in [("TL", Track::TL), ("TLC", Track::TLC), ("PR", Track::PR)] {
+            for (i, line) in self.lines(track).iter().enumerate() {
+                write!(w, "<{tag}{}{}>{}</{tag}>", self.provenance_attrs(track, i), space_attr(line), escape_text(line))?;
+            }
         }
 
         for comment in &self.comments {
-            xml.push_str(
-                format!("<Comment>{}</Comment>", comment).as_str()
-            );
+            write!(w, "<Comment{}>{}</Comment>", space_attr(comment), escape_text(comment))?;
         }
 
         // If balloon has an image:
         // Encode raw image data with b64 and save it's file extention to type attribute
-        if self.balloon_img.is_some() {
-            let img = self.balloon_img.as_ref().unwrap();
-            let encoded_img = B64.encode(&img.img_data);
+        if let Some(img) = &self.balloon_img {
+            let compression = img.compression
+                .map(|c| format!(" compression=\"{}\"", c.xml_value()))
+                .unwrap_or_default();
+
+            match img_src {
+                Some(src) => write!(w, "<img type=\"{}\"{} src=\"{}\"/>", escape_attr(&img.img_type), compression, escape_attr(src))?,
+                None => {
+                    write!(w, "<img type=\"{}\"{}>", escape_attr(&img.img_type), compression)?;
+                    write_b64(&mut w, &img.img_data)?;
+                    w.write_all(b"</img>")?;
+                }
+            }
+        }
 
-            xml.push_str(
-                format!("<img type=\"{}\">{}</img>", img.img_type, encoded_img).as_str()
-            );
+        for img in &self.image_history {
+            write!(w, "<OldImg type=\"{}\">", escape_attr(&img.img_type))?;
+            write_b64(&mut w, &img.img_data)?;
+            w.write_all(b"</OldImg>")?;
         }
 
-        xml.push_str("</Balloon>");
+        w.write_all(b"</Balloon>")?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Balloon {
+    /// Generates stringified version of the balloon.
+    /// Use this with caution because of data loss.
+    /// 
+    /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Decide balloon type header text
+        let type_str = match self.btype {
+            TYPES::DIALOGUE => "(): ",
+            TYPES::OT => "OT: ",
+            TYPES::SQUARE => "[]: ",
+            TYPES::ST => "ST: ",
+            TYPES::THINKING => "{}: ",
+            TYPES::NARRATION => "NR: ",
+            TYPES::SFX => "FX: "
+        };
+
+        // Generate balloon text from the track that ends up on the page
+        let text = self.effective_lines()
+            .iter()
+            .map(|line| {
+                format!("{}{}", type_str, line)
+            })
+            .collect::<Vec<String>>()
+            .join("\n//\n");
 
-        return xml;
+        write!(f, "{}", text)
     }
 }
 
@@ -197,7 +700,7 @@ mod ballon_tests {
             "jpg".to_string(),
             test_img.into_bytes()
         );
-        assert!(true);
+        assert!(b.balloon_img.is_some());
     }
 
     #[test]
@@ -209,7 +712,93 @@ mod ballon_tests {
             test_img.into_bytes()
         );
         b.remove_img();
-        assert!(true);
+        assert!(b.balloon_img.is_none());
+    }
+
+    #[test]
+    fn balloon_xml_escapes_text() {
+        let mut d = crate::Document { METADATA_INFO: "Q&A <draft>".to_string(), ..Default::default() };
+        let mut b = Balloon::default();
+        b.tl_content.push("I <3 you & \"him\"".to_string());
+        b.pr_content.push("</PR><TL>injected".to_string());
+        b.comments.push("a > b && c < d".to_string());
+        d.balloons.push(b);
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<TL>I &lt;3 you &amp; \"him\"</TL>"));
+
+        let back = crate::Document::from_xml(&xml).unwrap();
+        assert_eq!(back.METADATA_INFO, d.METADATA_INFO);
+        assert_eq!(back.balloons[0].hash(), d.balloons[0].hash());
+        assert_eq!(back.balloons[0].pr_content, vec!["</PR><TL>injected"]);
+
+        // Plain text isn't copied
+        assert!(matches!(super::escape_text("no markup"), std::borrow::Cow::Borrowed(_)));
+        assert_eq!(super::escape_attr("a\"b<c&d>"), "a&quot;b&lt;c&amp;d>");
+    }
+
+    #[test]
+    fn balloon_xml_keeps_line_breaks_and_tabs() {
+        let mut d = crate::Document::default();
+        d.balloons.push(
+            Balloon::default()
+                .with_line(crate::consts::Track::TL, "windows\r\nline")
+                .with_line(crate::consts::Track::TL, "old mac\rline")
+                .with_comment("tab\tand\nbreak")
+        );
+        d.balloons[0].speaker = Some("Ann\tMarie\nthe\r2nd".to_string());
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<TL>windows&#13;\nline</TL>"));
+        assert!(xml.contains("speaker=\"Ann&#9;Marie&#10;the&#13;2nd\""));
+
+        let back = crate::Document::from_xml(&xml).unwrap();
+        assert_eq!(back.balloons[0].tl_content, d.balloons[0].tl_content);
+        assert_eq!(back.balloons[0].comments, d.balloons[0].comments);
+        assert_eq!(back.balloons[0].speaker, d.balloons[0].speaker);
+        assert_eq!(back.balloons[0].hash(), d.balloons[0].hash());
+    }
+
+    #[test]
+    fn balloon_xml_without_types() {
+        let xml = "<Balloon><TL>hi</TL><img>AQID</img></Balloon>";
+        let b = Balloon::from_xml_node(roxmltree::Document::parse(xml).unwrap().root_element()).unwrap();
+
+        assert_eq!(b.btype, crate::consts::TYPES::DIALOGUE);
+        assert_eq!(b.balloon_img.unwrap().img_type, "");
+    }
+
+    #[test]
+    fn balloon_image_compression_xml() {
+        let mut d = crate::Document::default();
+        let mut b = Balloon::default();
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        b.balloon_img.as_mut().unwrap().compression = Some(super::ImageCompression::Jpeg(80));
+        d.balloons.push(b);
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<img type=\"jpg\" compression=\"jpeg:80\">"));
+        let back = crate::Document::from_xml(&xml).unwrap();
+        assert_eq!(back.balloons[0].balloon_img.as_ref().unwrap().compression, Some(super::ImageCompression::Jpeg(80)));
+        assert!(crate::Document::from_xml(&xml.replace("jpeg:80", "webp")).is_err());
+    }
+
+    #[test]
+    fn balloon_xml_streams_images() {
+        use base64::Engine as _;
+
+        let mut b = Balloon::default();
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7) as u8).collect();
+        b.add_image("png".to_string(), data.clone());
+        b.replace_image(super::BalloonImage { img_type: "png".to_string(), img_data: vec![9; 5].into(), compression: None });
+
+        // Images are encoded in chunks, the text must match a one-shot encoding
+        let xml = b.to_xml();
+        let expected = format!("<img type=\"png\">{}</img><OldImg type=\"png\">{}</OldImg></Balloon>", super::B64.encode([9; 5]), super::B64.encode(&data));
+        assert!(xml.ends_with(expected.as_str()));
+
+        let back = Balloon::from_xml_node(roxmltree::Document::parse(&xml).unwrap().root_element()).unwrap();
+        assert_eq!(back.hash(), b.hash());
     }
 
     #[test]
@@ -251,6 +840,23 @@ mod ballon_tests {
         );
     }
 
+    #[test]
+    fn balloon_effective_lines_precedence() {
+        use crate::consts::Track;
+
+        let mut b = Balloon::default();
+        b.tl_content.push("tl".to_string());
+        assert_eq!(b.effective_lines(), ["tl"]);
+
+        b.tlc_content.push("tlc".to_string());
+        assert_eq!(b.effective_lines(), ["tlc"]);
+        assert_eq!(b.effective_lines_with(&[Track::PR, Track::TL]), ["tl"]);
+
+        b.pr_content.push("pr".to_string());
+        assert_eq!(b.effective_lines(), ["pr"]);
+        assert!(b.to_xml().contains("<TL>tl</TL><TLC>tlc</TLC><PR>pr</PR>"));
+    }
+
     #[test]
     fn balloon_get_pr_len() {
         let mut b = Balloon::default();
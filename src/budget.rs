@@ -0,0 +1,180 @@
+//! Publisher constraints stored in the document metadata.
+//!
+//! Budgets travel with the file, so everybody who opens it checks the text
+//! against the same limits.
+
+use crate::{Document, XMLConvertResult};
+use crate::layout::num_attr;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Limits the text has to stay within. `None` means no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Budgets {
+    /// Characters of a balloon's effective text (see `Balloon::effective_lines`), spaces included.
+    pub chars_per_balloon: Option<usize>,
+    /// Effective lines of a balloon.
+    pub lines_per_balloon: Option<usize>,
+    /// Balloons on a page.
+    pub balloons_per_page: Option<usize>,
+}
+
+impl Budgets {
+    /// `true` if no limit is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Budgets::default()
+    }
+
+    /// Generates an xml string of the budgets.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Budgets");
+
+        for (name, value) in [
+            ("chars", self.chars_per_balloon),
+            ("lines", self.lines_per_balloon),
+            ("balloons_per_page", self.balloons_per_page)
+        ] {
+            if let Some(v) = value {
+                xml.push_str(format!(" {}=\"{}\"", name, v).as_str());
+            }
+        }
+
+        xml.push_str("/>");
+
+        xml
+    }
+
+    // Parse a <Budgets> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<Budgets> {
+        let attr = |name: &str| -> XMLConvertResult<Option<usize>> {
+            Ok(num_attr(node, name)?.map(|v| v as usize))
+        };
+
+        Ok(Budgets {
+            chars_per_balloon: attr("chars")?,
+            lines_per_balloon: attr("lines")?,
+            balloons_per_page: attr("balloons_per_page")?,
+        })
+    }
+}
+
+/// A budget that is exceeded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OverBudget {
+    /// Balloon `index` has `chars` characters.
+    Chars { index: usize, chars: usize, max: usize },
+    /// Balloon `index` has `lines` lines.
+    Lines { index: usize, lines: usize, max: usize },
+    /// Page `page` has `balloons` balloons.
+    Balloons { page: u32, balloons: usize, max: usize },
+}
+
+impl fmt::Display for OverBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverBudget::Chars { index, chars, max } => write!(f, "balloon {index}: {chars}/{max} chars"),
+            OverBudget::Lines { index, lines, max } => write!(f, "balloon {index}: {lines}/{max} lines"),
+            OverBudget::Balloons { page, balloons, max } => write!(f, "page {page}: {balloons}/{max} balloons")
+        }
+    }
+}
+
+impl Document {
+    /// Every exceeded budget: balloons in document order, then pages in ascending number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// d.budgets.chars_per_balloon = Some(5);
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Num nam".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// assert_eq!(d.over_budget()[0].to_string(), "balloon 0: 7/5 chars");
+    /// ```
+    pub fn over_budget(&self) -> Vec<OverBudget> {
+        let mut over: Vec<OverBudget> = Vec::new();
+
+        for (index, b) in self.balloons.iter().enumerate() {
+            if let Some(max) = self.budgets.chars_per_balloon {
+                let chars: usize = b.effective_lines().iter().map(|l| l.chars().count()).sum();
+                if chars > max {
+                    over.push(OverBudget::Chars { index, chars, max });
+                }
+            }
+
+            if let Some(max) = self.budgets.lines_per_balloon {
+                let lines = b.line_count();
+                if lines > max {
+                    over.push(OverBudget::Lines { index, lines, max });
+                }
+            }
+        }
+
+        if let Some(max) = self.budgets.balloons_per_page {
+            let mut per_page: BTreeMap<u32, usize> = BTreeMap::new();
+            for page in self.balloons.iter().filter_map(|b| b.page) {
+                *per_page.entry(page).or_default() += 1;
+            }
+
+            for (page, balloons) in per_page {
+                if balloons > max {
+                    over.push(OverBudget::Balloons { page, balloons, max });
+                }
+            }
+        }
+
+        over
+    }
+}
+
+#[cfg(test)]
+mod budget_tests {
+    use super::{Budgets, OverBudget};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn budget_violations() {
+        let mut d = Document {
+            budgets: Budgets { chars_per_balloon: Some(3), lines_per_balloon: Some(1), balloons_per_page: Some(1) },
+            ..Default::default()
+        };
+
+        for (page, text) in [(1, "num"), (1, "namnam"), (2, "nü")] {
+            let mut b = Balloon { page: Some(page), ..Default::default() };
+            b.tl_content.push(text.to_string());
+            d.balloons.push(b);
+        }
+        d.balloons[2].tl_content.push("a".to_string());
+
+        assert_eq!(
+            d.over_budget(),
+            vec![
+                OverBudget::Chars { index: 1, chars: 6, max: 3 },
+                OverBudget::Lines { index: 2, lines: 2, max: 1 },
+                OverBudget::Balloons { page: 1, balloons: 2, max: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn budget_xml_round_trip() {
+        let mut d = Document::default();
+        assert!(!d.to_xml().contains("Budgets"));
+
+        d.budgets.lines_per_balloon = Some(4);
+        let xml = d.to_xml();
+        assert!(xml.contains("<Budgets lines=\"4\"/></Metadata>"));
+
+        let parsed = Document::from_xml(&xml).unwrap();
+        assert_eq!(parsed.budgets, d.budgets);
+    }
+}
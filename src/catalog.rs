@@ -0,0 +1,498 @@
+//! A library index of sff files.
+//!
+//! Scans a directory tree (`<root>/<series>/.../<chapter>.sffz`) and keeps series, chapter,
+//! stats and hash of every document in a single file, so library views don't have to
+//! open hundreds of documents. [`Catalog::update`] only reopens files whose size or
+//! modification time changed.
+//!
+//! Entries also keep a fingerprint of their balloons, so copies of a chapter scattered
+//! over the years (`final_v2_REAL.sffz`) can be found with [`Catalog::duplicates`].
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_attr;
+use crate::consts::Format;
+use crate::hash::Fnv64;
+use crate::io::write_atomic;
+use crate::stats::DocumentStats;
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// A document of the catalog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogEntry {
+    /// Relative to the catalog root, `/` separated.
+    pub path: String,
+    /// First directory below the root. Empty for files directly in the root.
+    pub series: String,
+    /// File name without extension.
+    pub chapter: String,
+    pub stats: DocumentStats,
+    /// FNV-1a hash of the file content.
+    pub hash: u64,
+    /// Hash of the balloons, equal for the same script in another container or with
+    /// other metadata.
+    pub content_hash: u64,
+    /// MinHash of the balloon hashes, see [`Catalog::duplicates`]. Empty for entries of
+    /// catalogs saved before it existed.
+    pub signature: Vec<u64>,
+    /// Size in bytes.
+    pub byte_size: u64,
+    /// Modification time in seconds since the unix epoch.
+    pub modified: u64,
+}
+
+impl CatalogEntry {
+    fn to_xml(&self) -> String {
+        format!(
+            "<Entry path=\"{}\" series=\"{}\" chapter=\"{}\" hash=\"{:016x}\" content=\"{:016x}\" signature=\"{}\" size=\"{}\" modified=\"{}\"{}/>",
+            escape_attr(&self.path),
+            escape_attr(&self.series),
+            escape_attr(&self.chapter),
+            self.hash,
+            self.content_hash,
+            self.signature.iter().map(|h| format!("{h:016x}")).collect::<Vec<_>>().join(","),
+            self.byte_size,
+            self.modified,
+            self.stats.xml_attrs()
+        )
+    }
+
+    fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<CatalogEntry> {
+        let text = |name: &str| node.attribute(name).unwrap_or("").to_string();
+        let num = |name: &str| -> XMLConvertResult<u64> {
+            Ok(node.attribute(name).ok_or("Incomplete catalog entry!")?.parse()?)
+        };
+
+        Ok(CatalogEntry {
+            path: text("path"),
+            series: text("series"),
+            chapter: text("chapter"),
+            stats: DocumentStats::from_xml_node(node)?,
+            hash: u64::from_str_radix(node.attribute("hash").unwrap_or(""), 16)?,
+            content_hash: u64::from_str_radix(node.attribute("content").unwrap_or("0"), 16)?,
+            signature: node.attribute("signature")
+                .unwrap_or("")
+                .split(',')
+                .filter(|h| !h.is_empty())
+                .map(|h| u64::from_str_radix(h, 16))
+                .collect::<Result<_, _>>()?,
+            byte_size: num("size")?,
+            modified: num("modified")?,
+        })
+    }
+}
+
+/// What [`Catalog::update`] changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CatalogUpdate {
+    pub added: usize,
+    /// Files whose size or modification time changed.
+    pub updated: usize,
+    /// Files that don't exist anymore.
+    pub removed: usize,
+    /// Files that could not be read, with the error. Their previous entry, if any, is
+    /// kept and they are retried on the next update.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Entries of a [`Catalog`] that are copies of the same chapter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateGroup {
+    /// Paths of the entries, sorted.
+    pub paths: Vec<String>,
+    /// `true` if every entry has the same balloons.
+    pub exact: bool,
+    /// Lowest estimated similarity between two entries of the group, 0 to 1.
+    pub similarity: f64,
+}
+
+/// Number of hashes in [`CatalogEntry::signature`].
+pub const SIGNATURE_LEN: usize = 16;
+
+// SplitMix64 finalizer, a different hash function for every signature slot.
+fn mix(h: u64, slot: usize) -> u64 {
+    let mut z = h.wrapping_add((slot as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// MinHash of a set of balloon hashes.
+fn signature(hashes: &[u64]) -> Vec<u64> {
+    (0..SIGNATURE_LEN)
+        .map(|slot| hashes.iter().map(|h| mix(*h, slot)).min().unwrap_or(u64::MAX))
+        .collect()
+}
+
+// Estimated share of balloons two signatures have in common.
+fn similarity(a: &[u64], b: &[u64]) -> f64 {
+    let same = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    same as f64 / SIGNATURE_LEN as f64
+}
+
+/// Index of all sffx/sffz files below `root`, sorted by path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Catalog {
+    pub root: PathBuf,
+    pub entries: Vec<CatalogEntry>,
+}
+
+// Every sffx/sffz file below `dir`. Symlinked directories are not followed, they can
+// point back up the tree.
+fn find_documents(dir: &Path, out: &mut Vec<PathBuf>) -> XMLConvertResult<()> {
+    for e in fs::read_dir(dir)? {
+        let p = e?.path();
+
+        if fs::symlink_metadata(&p)?.is_dir() {
+            find_documents(&p, out)?;
+        } else if p.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Format::from_extension)
+            .is_some_and(|f| f.is_lossless())
+        {
+            out.push(p);
+        }
+    }
+
+    Ok(())
+}
+
+// Size and modification time (seconds since the unix epoch) of a file.
+fn file_stamp(p: &Path) -> XMLConvertResult<(u64, u64)> {
+    let md = fs::metadata(p)?;
+
+    Ok((md.len(), md.modified()?.duration_since(UNIX_EPOCH)?.as_secs()))
+}
+
+impl Catalog {
+    /// Creates an empty catalog of `root`. Call [`Catalog::update`] to fill it.
+    pub fn new(root: &str) -> Self {
+        Self { root: PathBuf::from(root), entries: Vec::new() }
+    }
+
+    /// Entries of a series, in path order.
+    pub fn series(&self, series: &str) -> Vec<&CatalogEntry> {
+        self.entries.iter().filter(|e| e.series == series).collect()
+    }
+
+    // Open the file at `rel` and read everything the entry needs.
+    fn read_entry(&self, rel: String) -> XMLConvertResult<CatalogEntry> {
+        let p = self.root.join(&rel);
+        let bytes = fs::read(&p)?;
+        let (byte_size, modified) = file_stamp(&p)?;
+
+        let mut h = Fnv64::default();
+        h.write(&bytes);
+
+        let format = crate::io::format_of(&p)?;
+        let d = Document::bytes_to_doc(format, bytes)?;
+
+        let balloons = d.balloon_hashes();
+        let mut content = Fnv64::default();
+        for b in &balloons {
+            content.write_u64(*b);
+        }
+
+        let mut components = rel.split('/');
+        let series = match (components.next(), components.next()) {
+            (Some(first), Some(_)) => first.to_string(),
+            _ => String::new()
+        };
+
+        Ok(CatalogEntry {
+            series,
+            chapter: p.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string(),
+            stats: d.stats(),
+            hash: h.finish(),
+            content_hash: content.finish(),
+            signature: signature(&balloons),
+            byte_size,
+            modified,
+            path: rel,
+        })
+    }
+
+    /// Rescans the root: adds new files, reopens changed ones and drops deleted ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::catalog::Catalog;
+    /// use rsff::consts::Format;
+    ///
+    /// std::fs::create_dir_all("catalog_example/My Series").unwrap();
+    /// Document::default().save(Format::ZLIB, "catalog_example/My Series/ch1").unwrap();
+    ///
+    /// let mut catalog = Catalog::new("catalog_example");
+    /// assert_eq!(catalog.update().unwrap().added, 1);
+    /// assert_eq!(catalog.series("My Series")[0].chapter, "ch1");
+    ///
+    /// catalog.save("catalog_example.xml").unwrap();
+    /// let mut loaded = Catalog::load("catalog_example.xml").unwrap();
+    /// assert_eq!(loaded, catalog);
+    ///
+    /// // Nothing changed, nothing is reopened
+    /// assert_eq!(loaded.update().unwrap().updated, 0);
+    /// # std::fs::remove_dir_all("catalog_example").unwrap();
+    /// # std::fs::remove_file("catalog_example.xml").unwrap();
+    /// ```
+    pub fn update(&mut self) -> XMLConvertResult<CatalogUpdate> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        find_documents(&self.root, &mut files)?;
+
+        let mut old: HashMap<String, CatalogEntry> = self.entries
+            .drain(..)
+            .map(|e| (e.path.clone(), e))
+            .collect();
+        let mut update = CatalogUpdate::default();
+
+        for p in files {
+            let rel = p.strip_prefix(&self.root)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let previous = old.remove(&rel);
+            let unchanged = |e: &CatalogEntry| {
+                file_stamp(&p).is_ok_and(|stamp| stamp == (e.byte_size, e.modified)) && !e.signature.is_empty()
+            };
+
+            match previous {
+                Some(e) if unchanged(&e) => self.entries.push(e),
+                previous => match self.read_entry(rel.clone()) {
+                    Ok(entry) => {
+                        match previous {
+                            Some(_) => update.updated += 1,
+                            None => update.added += 1
+                        }
+                        self.entries.push(entry);
+                    },
+                    Err(e) => {
+                        update.failed.push((rel, e.to_string()));
+                        self.entries.extend(previous);
+                    }
+                }
+            }
+        }
+
+        update.removed = old.len();
+        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(update)
+    }
+
+    /// Groups of entries that are copies of the same chapter: the same balloons, or an
+    /// estimated share of common balloons of at least `min_similarity` (0 to 1). A copy
+    /// with a few lines proofread still shares most balloons, `0.8` finds those.
+    /// Entries without balloons are left out.
+    ///
+    /// The share is estimated from the entries' signatures, so it is off by a few
+    /// percent; no file is reopened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::catalog::Catalog;
+    /// use rsff::consts::{Format, Track};
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "num"));
+    ///
+    /// std::fs::create_dir_all("duplicates_example/old").unwrap();
+    /// d.save(Format::ZLIB, "duplicates_example/ch1").unwrap();
+    /// d.save(Format::RAW, "duplicates_example/old/final_v2_REAL").unwrap();
+    ///
+    /// let mut catalog = Catalog::new("duplicates_example");
+    /// catalog.update().unwrap();
+    ///
+    /// let groups = catalog.duplicates(1.0);
+    /// assert_eq!(groups[0].paths, ["ch1.sffz", "old/final_v2_REAL.sffx"]);
+    /// assert!(groups[0].exact);
+    /// # std::fs::remove_dir_all("duplicates_example").unwrap();
+    /// ```
+    pub fn duplicates(&self, min_similarity: f64) -> Vec<DuplicateGroup> {
+        let entries: Vec<&CatalogEntry> = self.entries
+            .iter()
+            .filter(|e| e.stats.balloons > 0 && !e.signature.is_empty())
+            .collect();
+
+        // Union-find over the pairs that are similar enough
+        let mut parent: Vec<usize> = (0..entries.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        for i in 0..entries.len() {
+            for j in i + 1..entries.len() {
+                let (a, b) = (entries[i], entries[j]);
+                if a.content_hash == b.content_hash || similarity(&a.signature, &b.signature) >= min_similarity {
+                    let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[rj] = ri;
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<&CatalogEntry>> = HashMap::new();
+        for (i, e) in entries.iter().enumerate() {
+            groups.entry(root(&mut parent, i)).or_default().push(e);
+        }
+
+        let mut out: Vec<DuplicateGroup> = groups
+            .into_values()
+            .filter(|g| g.len() > 1)
+            .map(|g| {
+                let mut lowest: f64 = 1.0;
+                for (i, a) in g.iter().enumerate() {
+                    for b in &g[i + 1..] {
+                        if a.content_hash != b.content_hash {
+                            lowest = lowest.min(similarity(&a.signature, &b.signature));
+                        }
+                    }
+                }
+
+                DuplicateGroup {
+                    paths: g.iter().map(|e| e.path.clone()).collect(),
+                    exact: g.iter().all(|e| e.content_hash == g[0].content_hash),
+                    similarity: lowest,
+                }
+            })
+            .collect();
+        out.sort_by(|a, b| a.paths.cmp(&b.paths));
+
+        out
+    }
+
+    /// Generates an xml string of the catalog.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!("<Catalog root=\"{}\">", escape_attr(&self.root.to_string_lossy()));
+
+        for e in &self.entries {
+            xml.push_str(e.to_xml().as_str());
+        }
+
+        xml.push_str("</Catalog>");
+
+        xml
+    }
+
+    /// Writes the catalog to `fp`. Like [`Document::save`], the file is replaced atomically.
+    pub fn save(&self, fp: &str) -> XMLConvertResult<()> {
+        write_atomic(Path::new(fp), self.to_xml().as_bytes())
+    }
+
+    /// Reads a catalog written by [`Catalog::save`].
+    pub fn load(fp: &str) -> XMLConvertResult<Catalog> {
+        let xml = fs::read_to_string(fp)?;
+        let tree = roxmltree::Document::parse(&xml)?;
+        let root = tree.root_element();
+
+        let mut catalog = Catalog::new(root.attribute("root").unwrap_or("."));
+        for e in root.children().filter(|c| c.tag_name().name() == "Entry") {
+            catalog.entries.push(CatalogEntry::from_xml_node(e)?);
+        }
+
+        Ok(catalog)
+    }
+}
+
+#[cfg(test)]
+mod catalog_tests {
+    use super::Catalog;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    #[test]
+    fn catalog_incremental_update() {
+        let root = "catalog_incremental_update";
+        std::fs::create_dir_all(format!("{root}/S/vol1")).unwrap();
+
+        let mut d = Document::default();
+        d.save(Format::RAW, &format!("{root}/S/vol1/ch1")).unwrap();
+        d.save(Format::TXT, &format!("{root}/S/notes")).unwrap();
+        d.save(Format::ZLIB, &format!("{root}/loose")).unwrap();
+
+        let mut c = Catalog::new(root);
+        assert_eq!(c.update().unwrap().added, 2);
+        assert_eq!(c.entries[0].path, "S/vol1/ch1.sffx");
+        assert_eq!(c.entries[0].series, "S");
+        assert_eq!(c.entries[1].series, "");
+
+        d.balloons.push(Balloon::default());
+        d.save(Format::RAW, &format!("{root}/S/vol1/ch1")).unwrap();
+        std::fs::remove_file(format!("{root}/loose.sffz")).unwrap();
+
+        let u = c.update().unwrap();
+        assert_eq!((u.added, u.updated, u.removed), (0, 1, 1));
+        assert_eq!(c.entries[0].stats.balloons, 1);
+
+        // A broken file doesn't stop the scan, a link back up the tree isn't followed
+        std::fs::write(format!("{root}/S/broken.sffx"), "<Document>").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("..", format!("{root}/S/vol1/up")).unwrap();
+        let u = c.update().unwrap();
+        assert_eq!(u.failed.len(), 1);
+        assert_eq!(u.failed[0].0, "S/broken.sffx");
+        assert_eq!(c.entries.len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn catalog_near_duplicates() {
+        let root = "catalog_near_duplicates";
+        std::fs::create_dir_all(root).unwrap();
+
+        let mut d = Document::default();
+        for i in 0..20 {
+            d.balloons.push(Balloon::default().with_line(Track::TL, format!("line {i}")));
+        }
+        d.save(Format::ZLIB, &format!("{root}/ch1")).unwrap();
+
+        d.balloons[3].pr_content.push("proofread".to_string());
+        d.save(Format::ZLIB, &format!("{root}/ch1_final")).unwrap();
+
+        let other: Document = Document { balloons: vec![Balloon::default().with_line(Track::TL, "other")], ..Default::default() };
+        other.save(Format::ZLIB, &format!("{root}/ch2")).unwrap();
+        Document::default().save(Format::ZLIB, &format!("{root}/empty1")).unwrap();
+        Document::default().save(Format::ZLIB, &format!("{root}/empty2")).unwrap();
+
+        let mut c = Catalog::new(root);
+        c.update().unwrap();
+
+        // Empty documents and the other chapter are not copies
+        let groups = c.duplicates(0.7);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, ["ch1.sffz", "ch1_final.sffz"]);
+        assert!(!groups[0].exact);
+        assert!(groups[0].similarity >= 0.7);
+
+        // Catalogs saved without signatures are refreshed
+        let mut xml = c.to_xml();
+        while let Some(start) = xml.find(" signature=") {
+            let end = start + xml[start..].find(" size=").unwrap();
+            xml.replace_range(start..end, "");
+        }
+        std::fs::write(format!("{root}.xml"), xml).unwrap();
+        let mut old = Catalog::load(&format!("{root}.xml")).unwrap();
+        assert!(old.entries[0].signature.is_empty());
+        assert_eq!(old.update().unwrap().updated, 5);
+        assert_eq!(old, c);
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_file(format!("{root}.xml")).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+//! Which chapter of which series a document is, stored in the metadata.
+//!
+//! Apps used to put this in `METADATA_INFO` in their own format and parse it back with
+//! their own regex. [`ChapterInfo`] has typed fields that are checked when a file is
+//! opened, so every app reads the same values. `METADATA_INFO` stays free text.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_attr;
+use crate::layout::num_attr;
+
+/// Series, chapter and languages of a document. Everything is optional.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChapterInfo {
+    pub series: Option<String>,
+    /// Chapter number as written, e.g. `"12"` or `"12.5"` for extras.
+    pub number: Option<String>,
+    pub volume: Option<u32>,
+    /// Language tag of the raws, e.g. `"ja"`, `"ko"` or `"zh-Hans"`.
+    pub source_language: Option<String>,
+    /// Language tag of the translation, e.g. `"en"` or `"pt-BR"`.
+    pub target_language: Option<String>,
+}
+
+// A chapter number: digits with at most one decimal point, "12" or "12.5".
+fn is_chapter_number(s: &str) -> bool {
+    let digits = |p: &str| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit());
+
+    match s.split_once('.') {
+        Some((int, fraction)) => digits(int) && digits(fraction),
+        None => digits(s)
+    }
+}
+
+// A language tag: a 2-3 letter language, then subtags of 1-8 letters or digits.
+fn is_language_tag(s: &str) -> bool {
+    let mut parts = s.split('-');
+    let language = parts.next().unwrap_or("");
+
+    (2..=3).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_alphabetic())
+        && parts.all(|p| (1..=8).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+impl ChapterInfo {
+    /// `true` if nothing is set.
+    pub fn is_empty(&self) -> bool {
+        *self == ChapterInfo::default()
+    }
+
+    /// Checks the values: the series is not blank, the number looks like `12` or `12.5`
+    /// and the languages are language tags like `ja` or `pt-BR`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::chapter::ChapterInfo;
+    ///
+    /// let mut info = ChapterInfo { number: Some("12.5".to_string()), ..Default::default() };
+    /// assert!(info.validate().is_ok());
+    ///
+    /// info.source_language = Some("Japanese".to_string());
+    /// assert!(info.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> XMLConvertResult<()> {
+        if self.series.as_deref().is_some_and(|s| s.trim().is_empty()) {
+            return Err("Blank series title!".into());
+        }
+        if let Some(n) = self.number.as_deref().filter(|n| !is_chapter_number(n)) {
+            return Err(format!("Invalid chapter number \"{n}\"!").into());
+        }
+        for lang in [&self.source_language, &self.target_language].into_iter().flatten() {
+            if !is_language_tag(lang) {
+                return Err(format!("Invalid language tag \"{lang}\"!").into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates an xml string of the chapter info.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Chapter");
+
+        let attrs = [
+            ("series", self.series.clone()),
+            ("number", self.number.clone()),
+            ("volume", self.volume.map(|v| v.to_string())),
+            ("source", self.source_language.clone()),
+            ("target", self.target_language.clone()),
+        ];
+        for (name, value) in attrs {
+            if let Some(v) = value {
+                xml.push_str(format!(" {name}=\"{}\"", escape_attr(&v)).as_str());
+            }
+        }
+
+        xml.push_str("/>");
+
+        xml
+    }
+
+    // Parse a <Chapter> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<ChapterInfo> {
+        let attr = |name: &str| node.attribute(name).map(str::to_string);
+
+        let info = ChapterInfo {
+            series: attr("series"),
+            number: attr("number"),
+            volume: num_attr(node, "volume")?,
+            source_language: attr("source"),
+            target_language: attr("target"),
+        };
+        info.validate()?;
+
+        Ok(info)
+    }
+}
+
+impl Document {
+    /// Replaces the chapter info after checking it with [`ChapterInfo::validate`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::chapter::ChapterInfo;
+    ///
+    /// let mut d = Document::default();
+    /// let info = ChapterInfo {
+    ///     series: Some("My Series".to_string()),
+    ///     number: Some("45".to_string()),
+    ///     source_language: Some("ko".to_string()),
+    ///     target_language: Some("en".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// d.set_chapter_info(info).unwrap();
+    ///
+    /// let back = Document::from_xml(&d.to_xml()).unwrap();
+    /// assert_eq!(back.chapter.number.as_deref(), Some("45"));
+    /// ```
+    pub fn set_chapter_info(&mut self, info: ChapterInfo) -> XMLConvertResult<()> {
+        info.validate()?;
+        self.chapter = info;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod chapter_tests {
+    use super::{ChapterInfo, is_chapter_number, is_language_tag};
+    use crate::Document;
+
+    #[test]
+    fn chapter_info_validation() {
+        assert!(["1", "12.5", "007"].iter().all(|n| is_chapter_number(n)));
+        assert!(!["", ".5", "1.", "1.2.3", "12a", "-1"].iter().any(|n| is_chapter_number(n)));
+        assert!(["ja", "pt-BR", "zh-Hans", "yue"].iter().all(|l| is_language_tag(l)));
+        assert!(!["", "j", "english", "pt_BR", "en-", "ja-123456789"].iter().any(|l| is_language_tag(l)));
+
+        let mut d = Document::default();
+        assert!(d.set_chapter_info(ChapterInfo { series: Some(" ".to_string()), ..Default::default() }).is_err());
+        assert!(d.chapter.is_empty());
+
+        d.chapter = ChapterInfo { series: Some("A \"B\"".to_string()), volume: Some(3), ..Default::default() };
+        let xml = d.to_xml();
+        assert!(xml.contains("<Chapter series=\"A &quot;B&quot;\" volume=\"3\"/>"));
+        assert_eq!(Document::from_xml(&xml).unwrap().chapter, d.chapter);
+
+        // Invalid values don't open
+        let bad = xml.replace("volume=\"3\"", "number=\"ch. 3\"");
+        assert!(Document::from_xml(&bad).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+//! Named versions stored inside the document.
+//!
+//! Checkpoints let translators roll back to e.g. "before the editor's rewrite"
+//! without an external VCS. Balloons are stored once in a pool keyed by their
+//! content hash, so a checkpoint only costs the balloons that changed since the
+//! previous ones.
+//!
+//! The hashes are saved with the pooled balloons. Xml parsers normalize some whitespace,
+//! so a reopened balloon doesn't always hash like the one that was saved.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::{Balloon, escape_attr};
+
+use std::collections::{HashMap, HashSet};
+
+/// A named state of the document's balloons.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    pub name: String,
+    // Balloon hashes, in order.
+    balloons: Vec<u64>,
+}
+
+impl Checkpoint {
+    /// Balloon count at the time of the checkpoint.
+    pub fn len(&self) -> usize {
+        self.balloons.len()
+    }
+
+    /// Returns `true` if the document had no balloons at the time of the checkpoint.
+    pub fn is_empty(&self) -> bool {
+        self.balloons.is_empty()
+    }
+}
+
+/// All checkpoints of a document, oldest first.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoints {
+    list: Vec<Checkpoint>,
+    pool: HashMap<u64, Balloon>,
+}
+
+impl Checkpoints {
+    /// Checkpoints, oldest first.
+    pub fn list(&self) -> &[Checkpoint] {
+        &self.list
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// The newest checkpoint with this name.
+    pub fn get(&self, name: &str) -> Option<&Checkpoint> {
+        self.list.iter().rev().find(|c| c.name == name)
+    }
+
+    /// Balloons of a checkpoint.
+    pub fn balloons(&self, checkpoint: &Checkpoint) -> Vec<Balloon> {
+        checkpoint.balloons
+            .iter()
+            .map(|h| self.pool[h].clone())
+            .collect()
+    }
+
+    // Drop pooled balloons no checkpoint refers to anymore.
+    fn prune(&mut self) {
+        let used: HashSet<u64> = self.list
+            .iter()
+            .flat_map(|c| c.balloons.iter().copied())
+            .collect();
+        self.pool.retain(|h, _| used.contains(h));
+    }
+
+    /// Generates an xml string of the checkpoints.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Checkpoints><Pool>");
+
+        // Sorted so the same checkpoints always give the same xml
+        let mut hashes: Vec<&u64> = self.pool.keys().collect();
+        hashes.sort();
+        for h in hashes {
+            xml.push_str(format!("<Entry id=\"{:016x}\">{}</Entry>", h, self.pool[h].to_xml()).as_str());
+        }
+
+        xml.push_str("</Pool>");
+
+        for c in &self.list {
+            let balloons: Vec<String> = c.balloons
+                .iter()
+                .map(|h| format!("{:016x}", h))
+                .collect();
+            xml.push_str(format!(
+                "<Checkpoint name=\"{}\" balloons=\"{}\"/>",
+                escape_attr(&c.name),
+                balloons.join(",")
+            ).as_str());
+        }
+
+        xml.push_str("</Checkpoints>");
+
+        xml
+    }
+
+    // Parse a <Checkpoints> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<Checkpoints> {
+        let mut cps = Checkpoints::default();
+
+        if let Some(pool) = node.children().find(|c| c.tag_name().name() == "Pool") {
+            for entry in pool.children().filter(|c| c.is_element()) {
+                match entry.tag_name().name() {
+                    "Entry" => {
+                        let id = u64::from_str_radix(entry.attribute("id").unwrap_or(""), 16)?;
+                        let b = entry.children()
+                            .find(|c| c.tag_name().name() == "Balloon")
+                            .ok_or("checkpoint entry without balloon")?;
+                        cps.pool.insert(id, Balloon::from_xml_node(b)?);
+                    },
+                    // Files saved before ids were written
+                    "Balloon" => {
+                        let b = Balloon::from_xml_node(entry)?;
+                        cps.pool.insert(b.full_hash(), b);
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        for c in node.children().filter(|c| c.tag_name().name() == "Checkpoint") {
+            let mut balloons: Vec<u64> = Vec::new();
+
+            for h in c.attribute("balloons").unwrap_or("").split(',').filter(|h| !h.is_empty()) {
+                let h = u64::from_str_radix(h, 16)?;
+                if !cps.pool.contains_key(&h) {
+                    return Err(format!("checkpoint refers to unknown balloon {h:016x}").into());
+                }
+                balloons.push(h);
+            }
+
+            cps.list.push(Checkpoint { name: c.attribute("name").unwrap_or("").to_string(), balloons });
+        }
+
+        Ok(cps)
+    }
+}
+
+impl Document {
+    /// Stores the current balloons as a checkpoint named `name`.
+    /// Metadata and pages are not part of checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Original".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// d.checkpoint("after TL pass");
+    /// d.balloons[0].tl_content[0] = "Rewritten".to_string();
+    ///
+    /// assert!(d.restore_checkpoint("after TL pass"));
+    /// assert_eq!(d.balloons[0].tl_content[0], "Original");
+    /// ```
+    pub fn checkpoint(&mut self, name: &str) {
+        let mut hashes: Vec<u64> = Vec::with_capacity(self.balloons.len());
+
+        for b in &self.balloons {
+            // Numbering balloons is no edit for `hash`, but the copy must keep the numbers
+            let h = b.full_hash();
+            self.checkpoints.pool.entry(h).or_insert_with(|| b.clone());
+            hashes.push(h);
+        }
+
+        self.checkpoints.list.push(Checkpoint { name: name.to_string(), balloons: hashes });
+    }
+
+    /// Replaces the balloons with the ones of the newest checkpoint named `name`.
+    /// Returns `false` if there is no such checkpoint. The checkpoint itself is kept.
+    pub fn restore_checkpoint(&mut self, name: &str) -> bool {
+        match self.checkpoints.get(name) {
+            None => false,
+            Some(c) => {
+                self.balloons = self.checkpoints.balloons(c);
+                true
+            }
+        }
+    }
+
+    /// Removes every checkpoint named `name`. Returns how many were removed.
+    pub fn remove_checkpoint(&mut self, name: &str) -> usize {
+        let before = self.checkpoints.list.len();
+        self.checkpoints.list.retain(|c| c.name != name);
+        self.checkpoints.prune();
+
+        before - self.checkpoints.list.len()
+    }
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn balloon(text: &str) -> Balloon {
+        let mut b = Balloon::default();
+        b.tl_content.push(text.to_string());
+        b
+    }
+
+    #[test]
+    fn checkpoint_pool_deduplicates() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+        d.checkpoint("one");
+
+        d.balloons[1] = balloon("B");
+        d.checkpoint("two");
+
+        assert_eq!(d.checkpoints.len(), 2);
+        assert_eq!(d.checkpoints.pool.len(), 3);
+
+        assert_eq!(d.remove_checkpoint("one"), 1);
+        assert_eq!(d.checkpoints.pool.len(), 2);
+    }
+
+    #[test]
+    fn checkpoint_xml_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.checkpoint("after TL pass");
+        d.balloons.clear();
+
+        let xml = d.to_xml();
+        let mut parsed = Document::from_xml(&xml).unwrap();
+
+        assert!(parsed.is_empty());
+        assert_eq!(parsed.checkpoints.list()[0].name, "after TL pass");
+        assert_eq!(parsed.to_xml(), xml);
+
+        assert!(parsed.restore_checkpoint("after TL pass"));
+        assert_eq!(parsed.balloons[0].tl_content[0], "a");
+        assert!(!parsed.restore_checkpoint("nope"));
+    }
+
+    #[test]
+    fn checkpoint_ids_survive_whitespace_normalization() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("line\r\nbreak"));
+        d.balloons[0].speaker = Some("Ann\tMarie".to_string());
+        d.checkpoint("v1");
+
+        let mut parsed = Document::from_xml(&d.to_xml()).unwrap();
+        assert!(parsed.restore_checkpoint("v1"));
+
+        // Files without ids are still read
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.checkpoint("v1");
+        let entry = format!("<Entry id=\"{:016x}\">", d.balloons[0].hash());
+        let old = d.to_xml().replace(&entry, "").replace("</Entry>", "");
+        assert!(Document::from_xml(&old).unwrap().restore_checkpoint("v1"));
+    }
+
+    #[test]
+    fn checkpoint_keeps_balloon_numbers() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+
+        d.checkpoint("before");
+        d.assign_indices();
+        d.checkpoint("after");
+        let numbered: Vec<Option<u32>> = d.balloons.iter().map(|b| b.order).collect();
+        assert!(numbered.iter().all(Option::is_some));
+
+        assert!(d.restore_checkpoint("before"));
+        assert!(d.balloons.iter().all(|b| b.order.is_none()));
+
+        let mut parsed = Document::from_xml(&d.to_xml()).unwrap();
+        for doc in [&mut d, &mut parsed] {
+            assert!(doc.restore_checkpoint("after"));
+            assert_eq!(doc.balloons.iter().map(|b| b.order).collect::<Vec<_>>(), numbered);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Cleanup of CJK text pasted from other sources.
+//!
+//! Converts between full-width and half-width forms and normalizes punctuation
+//! variants (wave dashes, half-width CJK brackets, marks and katakana), field by field,
+//! with a report of every changed line.
+
+use crate::Document;
+use crate::balloon::Balloon;
+
+/// Target form for ASCII letters, digits, symbols and spaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    /// `Ａ１！` → `A1!`, ideographic space → space.
+    Half,
+    /// `A1!` → `Ａ１！`, space → ideographic space.
+    Full,
+}
+
+/// What [`Document::normalize_cjk`] does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CjkOptions {
+    /// Convert ASCII-range characters to this width. `None` leaves widths alone.
+    pub width: Option<Width>,
+    /// Normalize punctuation variants and half-width katakana, see [`normalize_punctuation`].
+    pub punctuation: bool,
+}
+
+/// A text field of a balloon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    TL,
+    TLC,
+    PR,
+    Comments,
+}
+
+impl Field {
+    /// All fields.
+    pub fn all() -> &'static [Field] {
+        &[Field::TL, Field::TLC, Field::PR, Field::Comments]
+    }
+
+    pub(crate) fn lines_mut(self, b: &mut Balloon) -> &mut Vec<String> {
+        match self {
+            Field::TL => &mut b.tl_content,
+            Field::TLC => &mut b.tlc_content,
+            Field::PR => &mut b.pr_content,
+            Field::Comments => &mut b.comments
+        }
+    }
+}
+
+/// A line changed by [`Document::normalize_cjk`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cleaned {
+    pub balloon: usize,
+    pub field: Field,
+    pub line: usize,
+    /// The line before the change.
+    pub old: String,
+}
+
+/// Full-width ASCII variants (U+FF01..U+FF5E) and the ideographic space to ASCII.
+pub fn to_half_width(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            c => c
+        })
+        .collect()
+}
+
+/// Printable ASCII to full-width variants, space to the ideographic space.
+pub fn to_full_width(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            ' ' => '\u{3000}',
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            c => c
+        })
+        .collect()
+}
+
+// Full-width katakana of U+FF66..=U+FF9D, in order.
+const KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+// `c` with a dakuten (`voiced`) or handakuten, if that kana exists.
+fn with_mark(c: char, voiced: bool) -> Option<char> {
+    let code = c as u32;
+    let ha_row = (0x30CF..=0x30DB).contains(&code) && (code - 0x30CF).is_multiple_of(3);
+
+    let marked = match c {
+        'ウ' if voiced => 'ヴ',
+        'ワ' if voiced => 'ヷ',
+        'ヲ' if voiced => 'ヺ',
+        _ if ha_row => char::from_u32(code + if voiced { 1 } else { 2 })?,
+        // Voiced kana follow their plain kana: カガキギ...チヂッツヅテデトド
+        'カ'..='チ' if voiced && code % 2 == 1 => char::from_u32(code + 1)?,
+        'ツ' | 'テ' | 'ト' if voiced => char::from_u32(code + 1)?,
+        _ => return None
+    };
+
+    Some(marked)
+}
+
+/// Normalizes CJK punctuation variants and half-width katakana:
+/// full-width tilde to wave dash (`～` → `〜`), half-width
+/// ideographic marks and brackets to their regular forms (`｡｢｣､･` → `。「」、・`),
+/// and half-width katakana (U+FF66..U+FF9F) to full width, joining voiced and semi-voiced
+/// marks with their kana (`ｶﾞﾊﾟｰ` → `ガパー`).
+pub fn normalize_punctuation(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\u{FF5E}' => '\u{301C}',
+            '\u{FF61}' => '\u{3002}',
+            '\u{FF62}' => '\u{300C}',
+            '\u{FF63}' => '\u{300D}',
+            '\u{FF64}' => '\u{3001}',
+            '\u{FF65}' => '\u{30FB}',
+            '\u{FF66}'..='\u{FF9D}' => KATAKANA.chars().nth(c as usize - 0xFF66).unwrap_or(c),
+            // Marks without a kana before them
+            '\u{FF9E}' => '\u{309B}',
+            '\u{FF9F}' => '\u{309C}',
+            c => c
+        };
+
+        let mark = match chars.peek() {
+            Some('\u{FF9E}') => with_mark(c, true),
+            Some('\u{FF9F}') => with_mark(c, false),
+            _ => None
+        };
+
+        match mark {
+            Some(marked) => {
+                out.push(marked);
+                chars.next();
+            }
+            None => out.push(c)
+        }
+    }
+
+    out
+}
+
+impl CjkOptions {
+    /// Applies the options to a single string.
+    pub fn apply(&self, s: &str) -> String {
+        // Punctuation first, so the full-width tilde becomes a wave dash instead of `~`
+        let mut s = if self.punctuation { normalize_punctuation(s) } else { s.to_string() };
+
+        match self.width {
+            Some(Width::Half) => s = to_half_width(&s),
+            Some(Width::Full) => s = to_full_width(&s),
+            None => ()
+        }
+
+        s
+    }
+}
+
+impl Document {
+    /// Applies `opts` to the given fields of every balloon and returns the changed lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::cjk::{CjkOptions, Field, Width};
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.comments.push("ｳｿ｢ＯＫ｣～".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let opts = CjkOptions { width: Some(Width::Half), punctuation: true };
+    /// let report = d.normalize_cjk(&[Field::Comments], &opts);
+    ///
+    /// assert_eq!(report.len(), 1);
+    /// assert_eq!(d.balloons[0].comments[0], "ウソ「OK」〜");
+    /// ```
+    pub fn normalize_cjk(&mut self, fields: &[Field], opts: &CjkOptions) -> Vec<Cleaned> {
+        let mut report: Vec<Cleaned> = Vec::new();
+
+        for (i, b) in self.balloons.iter_mut().enumerate() {
+            for field in fields {
+                for (n, line) in field.lines_mut(b).iter_mut().enumerate() {
+                    let new = opts.apply(line);
+                    if new != *line {
+                        let old = std::mem::replace(line, new);
+                        report.push(Cleaned { balloon: i, field: *field, line: n, old });
+                    }
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod cjk_tests {
+    use super::{CjkOptions, Field, Width, normalize_punctuation, to_full_width, to_half_width};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn cjk_width_round_trip() {
+        assert_eq!(to_half_width("Ｎｕｍ！\u{3000}１２"), "Num! 12");
+        assert_eq!(to_full_width("Num! 12"), "Ｎｕｍ！\u{3000}１２");
+        assert_eq!(to_half_width("日本語"), "日本語");
+    }
+
+    #[test]
+    fn cjk_half_width_katakana() {
+        assert_eq!(normalize_punctuation("ｦｧｰｱﾝ"), "ヲァーアン");
+        assert_eq!(normalize_punctuation("ｶﾞｷﾞﾂﾞﾄﾞﾊﾞﾋﾟﾎﾟｳﾞﾜﾞｦﾞ"), "ガギヅドバピポヴヷヺ");
+        assert_eq!(normalize_punctuation("ﾁﾞｬﾝｽ"), "ヂャンス");
+        // Kana without a voiced form keep the mark apart
+        assert_eq!(normalize_punctuation("ｱﾞﾅﾟﾟ"), "ア゛ナ゜゜");
+        assert_eq!(normalize_punctuation("｢ﾃﾞｰﾀ｣"), "「データ」");
+    }
+
+    #[test]
+    fn cjk_only_selected_fields() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("ＯＫ".to_string());
+        b.comments.push("ＯＫ".to_string());
+        b.comments.push("fine".to_string());
+        d.balloons.push(b);
+
+        let opts = CjkOptions { width: Some(Width::Half), ..Default::default() };
+        let report = d.normalize_cjk(&[Field::TL], &opts);
+
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].field, report[0].old.as_str()), (Field::TL, "ＯＫ"));
+        assert_eq!(d.balloons[0].tl_content[0], "OK");
+        assert_eq!(d.balloons[0].comments[0], "ＯＫ");
+        assert!(d.normalize_cjk(Field::all(), &CjkOptions::default()).is_empty());
+    }
+}
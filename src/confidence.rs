@@ -0,0 +1,115 @@
+//! Confidence scores of automated pipelines.
+//!
+//! OCR and machine translation providers can attach how sure they are of a balloon,
+//! from `0.0` (guess) to `1.0` (certain), so reviewers can start with the most doubtful ones.
+
+use crate::{Document, XMLConvertResult};
+
+/// Confidence of the automated steps that produced a balloon. `None` if the step
+/// didn't run or didn't report a score.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Confidence {
+    /// Text recognition of the source text.
+    pub ocr: Option<f32>,
+    /// Machine translation of the tl content.
+    pub mt: Option<f32>,
+}
+
+impl Confidence {
+    /// `true` if no score is set.
+    pub fn is_empty(&self) -> bool {
+        self.ocr.is_none() && self.mt.is_none()
+    }
+
+    /// The lowest score, if any.
+    pub fn lowest(&self) -> Option<f32> {
+        match (self.ocr, self.mt) {
+            (Some(o), Some(m)) => Some(o.min(m)),
+            (o, m) => o.or(m)
+        }
+    }
+
+    // Attributes of a <Balloon> tag, starting with a space.
+    pub(crate) fn xml_attrs(&self) -> String {
+        let mut xml = String::new();
+
+        for (name, value) in [("ocr", self.ocr), ("mt", self.mt)] {
+            if let Some(v) = value {
+                xml.push_str(format!(" {}=\"{}\"", name, v).as_str());
+            }
+        }
+
+        xml
+    }
+
+    // Read the attributes of a <Balloon> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<Confidence> {
+        let attr = |name: &str| -> XMLConvertResult<Option<f32>> {
+            Ok(node.attribute(name).map(str::parse).transpose()?)
+        };
+
+        Ok(Confidence {
+            ocr: attr("ocr")?,
+            mt: attr("mt")?,
+        })
+    }
+}
+
+impl Document {
+    /// Indices of the balloons with a score below `threshold`, most doubtful first.
+    /// Balloons without scores are never listed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// for (ocr, mt) in [(Some(0.9), Some(0.4)), (None, None), (Some(0.2), None), (Some(0.95), Some(0.9))] {
+    ///     let mut b = Balloon::default();
+    ///     b.confidence.ocr = ocr;
+    ///     b.confidence.mt = mt;
+    ///     d.balloons.push(b);
+    /// }
+    ///
+    /// assert_eq!(d.low_confidence(0.5), vec![2, 0]);
+    /// ```
+    pub fn low_confidence(&self, threshold: f32) -> Vec<usize> {
+        let mut low: Vec<(usize, f32)> = self.balloons
+            .iter()
+            .map(|b| b.confidence.lowest())
+            .enumerate()
+            .filter_map(|(i, c)| c.filter(|c| *c < threshold).map(|c| (i, c)))
+            .collect();
+
+        // Stable, so equal scores stay in document order
+        low.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        low.into_iter().map(|(i, _)| i).collect()
+    }
+}
+
+#[cfg(test)]
+mod confidence_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn confidence_roundtrip() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.confidence.ocr = Some(0.875);
+        d.balloons.push(b);
+        d.balloons.push(Balloon::default());
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<Balloon type=\"Dialogue\" ocr=\"0.875\">"));
+
+        let back = Document::from_xml(&xml).unwrap();
+        assert_eq!(back.balloons[0].confidence, d.balloons[0].confidence);
+        assert!(back.balloons[1].confidence.is_empty());
+        assert_eq!(back.low_confidence(1.0), vec![0]);
+    }
+}
@@ -1,30 +1,283 @@
-/// Supported output file types.
+/// Supported file formats.
 /// 
-/// `RAW`: Raw XML string
-/// `ZLIB`: Compressed XML
+/// `RAW`: Raw XML string\
+/// `ZLIB`: Compressed XML\
 /// `TXT`: Raw, lossy .txt file
-#[derive(Clone)]
-pub enum OUT {
+/// 
+/// # Examples
+/// 
+/// ```
+/// use rsff::consts::Format;
+/// 
+/// // Build a format picker without hard-coding a table.
+/// for f in Format::all() {
+///     println!("{} (*.{}), lossless: {}", f.description(), f.extension(), f.is_lossless());
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Format {
     RAW,
     ZLIB,
     TXT,
+    /// Review spreadsheet, see [`Document::to_csv`](crate::Document::to_csv).
+    CSV,
+    /// Readable Markdown, export only. See [`Document::to_markdown`](crate::Document::to_markdown).
+    MARKDOWN,
+    /// Html preview with embedded images, export only. See [`Document::to_html`](crate::Document::to_html).
+    HTML,
+    /// Aegisub subtitles, see [`Document::to_ass`](crate::Document::to_ass).
+    ASS,
+    /// Lossless JSON, images as base64 strings. Only with the `json` feature.
+    #[cfg(feature = "json")]
+    JSON,
+    /// JSON Lines, one balloon per line after the document record. Lossless, only with
+    /// the `json` feature. See [`Document::to_jsonl`](crate::Document::to_jsonl).
+    #[cfg(feature = "json")]
+    JSONL,
+    /// Excel workbook, export only. Only with the `xlsx` feature, see [`crate::xlsx`].
+    #[cfg(feature = "xlsx")]
+    XLSX,
+    /// OpenDocument text with the txt layout, export only. Only with the `odt` feature,
+    /// see [`crate::odt`].
+    #[cfg(feature = "odt")]
+    ODT,
+    /// Zstandard compressed xml, lossless like `ZLIB` and faster to open.
+    /// Only with the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    ZSTD,
+}
+
+/// Old name of [`Format`].
+#[deprecated(note = "renamed to `Format`")]
+pub type OUT = Format;
+
+impl Format {
+    /// Every supported format, in the order they should be offered to users.
+    pub const ALL: &'static [Format] = &[
+        Format::ZLIB,
+        Format::RAW,
+        #[cfg(feature = "zstd")]
+        Format::ZSTD,
+        #[cfg(feature = "json")]
+        Format::JSON,
+        #[cfg(feature = "json")]
+        Format::JSONL,
+        Format::TXT,
+        Format::CSV,
+        Format::MARKDOWN,
+        Format::HTML,
+        Format::ASS,
+        #[cfg(feature = "xlsx")]
+        Format::XLSX,
+        #[cfg(feature = "odt")]
+        Format::ODT,
+    ];
+
+    /// Every supported format, see [`Format::ALL`].
+    pub fn all() -> &'static [Format] {
+        Format::ALL
+    }
+
+    /// File extension without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::RAW => "sffx",
+            Format::ZLIB => "sffz",
+            Format::TXT => "txt",
+            Format::CSV => "csv",
+            Format::MARKDOWN => "md",
+            Format::HTML => "html",
+            Format::ASS => "ass",
+            #[cfg(feature = "json")]
+            Format::JSON => "json",
+            #[cfg(feature = "json")]
+            Format::JSONL => "jsonl",
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => "xlsx",
+            #[cfg(feature = "odt")]
+            Format::ODT => "odt",
+            #[cfg(feature = "zstd")]
+            Format::ZSTD => "sffzst"
+        }
+    }
+
+    /// Format for a file extension (without the leading dot), if supported.
+    pub fn from_extension(ext: &str) -> Option<Format> {
+        Format::all()
+            .iter()
+            .find(|f| f.extension() == ext)
+            .copied()
+    }
+
+    /// `true` if saving and reopening gives back the exact same document.
+    pub fn is_lossless(&self) -> bool {
+        match self {
+            Format::RAW | Format::ZLIB => true,
+            #[cfg(feature = "json")]
+            Format::JSON | Format::JSONL => true,
+            Format::TXT | Format::CSV | Format::MARKDOWN | Format::HTML | Format::ASS => false,
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => false,
+            #[cfg(feature = "odt")]
+            Format::ODT => false,
+            #[cfg(feature = "zstd")]
+            Format::ZSTD => true
+        }
+    }
+
+    /// `true` if balloon images are kept.
+    pub fn supports_images(&self) -> bool {
+        match self {
+            Format::RAW | Format::ZLIB | Format::HTML => true,
+            #[cfg(feature = "json")]
+            Format::JSON | Format::JSONL => true,
+            Format::TXT | Format::CSV | Format::MARKDOWN | Format::ASS => false,
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => false,
+            #[cfg(feature = "odt")]
+            Format::ODT => false,
+            #[cfg(feature = "zstd")]
+            Format::ZSTD => true
+        }
+    }
+
+    /// Short human readable description, e.g. for file dialogs.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Format::RAW => "Scanlation File Format (raw XML)",
+            Format::ZLIB => "Scanlation File Format (compressed XML)",
+            Format::TXT => "Plain text (lossy)",
+            Format::CSV => "Spreadsheet (lossy)",
+            Format::MARKDOWN => "Markdown (lossy, export only)",
+            Format::HTML => "Html preview (lossy, export only)",
+            Format::ASS => "Aegisub subtitles (lossy)",
+            #[cfg(feature = "json")]
+            Format::JSON => "JSON",
+            #[cfg(feature = "json")]
+            Format::JSONL => "JSON Lines",
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => "Excel workbook (lossy, export only)",
+            #[cfg(feature = "odt")]
+            Format::ODT => "OpenDocument text (lossy, export only)",
+            #[cfg(feature = "zstd")]
+            Format::ZSTD => "Scanlation File Format (zstd compressed XML)"
+        }
+    }
 }
 
 /// Balloon types. Default value is `DIALOGUE`.
 /// 
 /// ST: Sub-text\
-/// OT: Over-text
-#[derive(PartialEq, Debug, Clone)]
+/// OT: Over-text\
+/// NARRATION: Narration boxes\
+/// SFX: Sound effects
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum TYPES {
+    #[default]
     DIALOGUE,
     SQUARE,
     THINKING,
     ST,
-    OT
+    OT,
+    NARRATION,
+    SFX
 }
 
-impl Default for TYPES {
-    fn default() -> Self {
-        Self::DIALOGUE
+impl TYPES {
+    /// Every balloon type.
+    pub fn all() -> &'static [TYPES] {
+        &[TYPES::DIALOGUE, TYPES::SQUARE, TYPES::THINKING, TYPES::ST, TYPES::OT, TYPES::NARRATION, TYPES::SFX]
+    }
+
+    /// The closest type of version 1.0, for tools that don't know the newer types:
+    /// narration boxes used to be squares and sound effects over-texts.
+    pub fn compat(&self) -> TYPES {
+        match self {
+            TYPES::NARRATION => TYPES::SQUARE,
+            TYPES::SFX => TYPES::OT,
+            t => t.clone()
+        }
     }
-}
\ No newline at end of file
+
+    // Value of the `type` attribute in xml.
+    pub(crate) fn xml_name(&self) -> &'static str {
+        match self {
+            TYPES::DIALOGUE => "Dialogue",
+            TYPES::SQUARE => "Square",
+            TYPES::ST => "ST",
+            TYPES::OT => "OT",
+            TYPES::THINKING => "Thinking",
+            TYPES::NARRATION => "Narration",
+            TYPES::SFX => "SFX"
+        }
+    }
+
+    // Type of a `type` attribute value, unknown values are dialogues.
+    pub(crate) fn from_xml_name(name: &str) -> TYPES {
+        TYPES::all()
+            .iter()
+            .find(|t| t.xml_name() == name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Type of a balloon from its `type` and `subtype` attributes.
+    // Newer types are written as their `compat` type with the real one in `subtype`, so
+    // older versions still read a sensible type. Unknown subtypes of even newer versions
+    // fall back to `type` the same way.
+    pub(crate) fn from_xml_attrs(btype: &str, subtype: Option<&str>) -> TYPES {
+        subtype
+            .and_then(|s| TYPES::all().iter().find(|t| t.xml_name() == s))
+            .cloned()
+            .unwrap_or_else(|| TYPES::from_xml_name(btype))
+    }
+}
+
+/// Text tracks of a balloon, in pipeline order: translation, translation check, proofread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Track {
+    TL,
+    TLC,
+    PR
+}
+
+/// Track precedence used by `Balloon::effective_lines`: the first non-empty track wins.
+pub const DEFAULT_PRECEDENCE: [Track; 3] = [Track::PR, Track::TLC, Track::TL];
+
+#[cfg(test)]
+mod consts_tests {
+    use super::{Format, TYPES};
+
+    #[test]
+    fn format_extension_round_trip() {
+        for f in Format::all() {
+            assert_eq!(Format::from_extension(f.extension()), Some(*f));
+        }
+        assert_eq!(Format::from_extension("docx"), None);
+    }
+
+    #[test]
+    fn format_lossy_txt() {
+        assert!(Format::ZLIB.is_lossless());
+        assert!(!Format::TXT.is_lossless());
+        assert!(!Format::TXT.supports_images());
+    }
+
+    #[test]
+    fn types_compat_mapping() {
+        assert_eq!(TYPES::from_xml_attrs("Square", Some("Narration")), TYPES::NARRATION);
+        // A type from a newer version falls back to its compat type
+        assert_eq!(TYPES::from_xml_attrs("OT", Some("Scream")), TYPES::OT);
+        assert_eq!(TYPES::from_xml_attrs("Scream", None), TYPES::DIALOGUE);
+
+        let d = crate::Document::from_txt("FX: Boom\n\nNR: Meanwhile").unwrap();
+        assert_eq!(d.balloons[0].btype, TYPES::SFX);
+        assert_eq!(d.balloons[1].to_xml(), "<Balloon type=\"Square\" subtype=\"Narration\"><TL>Meanwhile</TL></Balloon>");
+        assert_eq!(d.to_string(), "FX: Boom\n\nNR: Meanwhile");
+    }
+}
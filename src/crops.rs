@@ -0,0 +1,157 @@
+//! Attaching a folder of balloon crops in one pass.
+//!
+//! Cleaners hand over a folder with one image per balloon, named after the balloon it
+//! belongs to. [`Document::attach_crops`] reads the folder, finds the balloon of every
+//! file with a [`NamingScheme`] and attaches the image. Balloons that already have an
+//! image get the crop through [`Balloon::replace_image`](crate::balloon::Balloon::replace_image),
+//! so the old one can still be undone.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::BalloonImage;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How crop file names point at balloons. Names are matched without extension and
+/// case insensitive; balloons are counted from 1 in [reading order](Document::reading_order).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamingScheme {
+    /// `p03_b07.png` (or `p3-b7.png`): balloon 7 of page 3.
+    #[default]
+    PageBalloon,
+    /// `007.png`: balloon 7 of the chapter.
+    Sequential,
+}
+
+impl NamingScheme {
+    // Page (for `PageBalloon`) and 1-based balloon number of a file stem.
+    fn parse(&self, stem: &str) -> Option<(Option<u32>, usize)> {
+        let stem = stem.to_lowercase();
+
+        match self {
+            NamingScheme::PageBalloon => {
+                let (page, balloon) = stem.split_once(['_', '-'])?;
+                let page = page.strip_prefix('p')?.parse().ok()?;
+                let balloon = balloon.strip_prefix('b')?.parse().ok()?;
+                Some((Some(page), balloon))
+            },
+            NamingScheme::Sequential => Some((None, stem.parse().ok()?))
+        }
+    }
+}
+
+/// What [`Document::attach_crops`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CropReport {
+    /// Attached files and the index of their balloon.
+    pub attached: Vec<(PathBuf, usize)>,
+    /// Files whose name doesn't follow the scheme or points at a balloon that doesn't exist.
+    pub unmatched: Vec<PathBuf>,
+}
+
+impl Document {
+    // Index of the balloon a crop name points at.
+    fn crop_target(&self, page: Option<u32>, number: usize) -> Option<usize> {
+        let order = self.reading_order();
+        let mut candidates = order
+            .into_iter()
+            .filter(|i| page.is_none() || self.balloons[*i].page == page);
+
+        candidates.nth(number.checked_sub(1)?)
+    }
+
+    /// Attaches every image of `dir` to the balloon its name points at, see [`crate::crops`].
+    /// The image type is the file extension. Files are handled in name order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::crops::NamingScheme;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon { page: Some(3), ..Default::default() });
+    ///
+    /// std::fs::create_dir_all("crops_example").unwrap();
+    /// std::fs::write("crops_example/p03_b01.png", [1, 2, 3]).unwrap();
+    /// std::fs::write("crops_example/p03_b02.png", [4, 5, 6]).unwrap();
+    ///
+    /// let report = d.attach_crops("crops_example", NamingScheme::PageBalloon).unwrap();
+    /// assert_eq!(report.attached.len(), 1);
+    /// assert_eq!(report.unmatched.len(), 1);
+    /// assert_eq!(d.balloons[0].balloon_img.as_ref().unwrap().img_type, "png");
+    /// # std::fs::remove_dir_all("crops_example").unwrap();
+    /// ```
+    pub fn attach_crops<P: AsRef<Path>>(&mut self, dir: P, scheme: NamingScheme) -> XMLConvertResult<CropReport> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        files.retain(|p| p.is_file());
+        files.sort();
+
+        let mut report = CropReport::default();
+
+        for path in files {
+            let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+            let target = scheme.parse(stem).and_then(|(page, number)| self.crop_target(page, number));
+
+            let Some(i) = target else {
+                report.unmatched.push(path);
+                continue;
+            };
+
+            let img_type = path.extension()
+                .and_then(OsStr::to_str)
+                .unwrap_or("")
+                .to_lowercase();
+            let img = BalloonImage { img_type, img_data: fs::read(&path)?.into(), compression: None };
+            self.balloons[i].replace_image(img);
+
+            report.attached.push((path, i));
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod crops_tests {
+    use super::NamingScheme;
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    use std::fs;
+
+    #[test]
+    fn crops_follow_reading_order() {
+        assert_eq!(NamingScheme::PageBalloon.parse("P3-B07"), Some((Some(3), 7)));
+        assert_eq!(NamingScheme::PageBalloon.parse("page3"), None);
+        assert_eq!(NamingScheme::Sequential.parse("012"), Some((None, 12)));
+
+        // Page 2 comes first in reading order even though its balloon is last
+        let mut d = Document::default();
+        for page in [Some(5), None, Some(2)] {
+            d.balloons.push(Balloon { page, ..Default::default() });
+        }
+        d.balloons[0].add_image("jpg".to_string(), vec![0]);
+
+        let dir = "crops_test";
+        fs::create_dir_all(format!("{dir}/sub")).unwrap();
+        for name in ["1.PNG", "2.png", "4.png", "notes.txt"] {
+            fs::write(format!("{dir}/{name}"), name).unwrap();
+        }
+
+        let report = d.attach_crops(dir, NamingScheme::Sequential).unwrap();
+        assert_eq!(report.attached.iter().map(|(_, i)| *i).collect::<Vec<_>>(), vec![2, 0]);
+        assert_eq!(report.unmatched.len(), 2);
+        assert_eq!(*d.balloons[2].balloon_img.as_ref().unwrap().img_data, b"1.PNG");
+        assert_eq!(d.balloons[2].balloon_img.as_ref().unwrap().img_type, "png");
+        assert!(d.balloons[0].undo_image());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
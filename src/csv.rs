@@ -0,0 +1,431 @@
+//! Importing spreadsheets with any column layout, and review spreadsheets.
+//!
+//! Every team keeps its script in a different spreadsheet, so a [`CsvMapping`] says which
+//! column holds what. Each non-empty row becomes a balloon; line breaks inside a cell
+//! become separate lines.
+//!
+//! For reviews in Google Sheets and the like, [`Document::to_csv`] writes one row per line
+//! and [`Document::from_csv_file`] reads the edited sheet back.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+
+/// A column of the spreadsheet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    /// Zero-based position.
+    Index(usize),
+    /// Header text, compared case-insensitively. Needs a header row.
+    Name(String),
+}
+
+/// What a column holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvField {
+    /// Balloon index. Rows with the same index as the previous row, or an empty one,
+    /// add lines to the same balloon. The numbers themselves don't matter.
+    Index,
+    TL,
+    TLC,
+    PR,
+    Comment,
+    /// Balloon type token, see [`CsvMapping::types`].
+    Type,
+    Page,
+    Panel,
+    Speaker,
+}
+
+impl CsvField {
+    fn name(&self) -> &'static str {
+        match self {
+            CsvField::Index => "index",
+            CsvField::TL => "tl",
+            CsvField::TLC => "tlc",
+            CsvField::PR => "pr",
+            CsvField::Comment => "comment",
+            CsvField::Type => "type",
+            CsvField::Page => "page",
+            CsvField::Panel => "panel",
+            CsvField::Speaker => "speaker"
+        }
+    }
+
+    // Field of a header cell, by field name.
+    fn from_name(name: &str) -> Option<CsvField> {
+        [
+            CsvField::Index, CsvField::TL, CsvField::TLC, CsvField::PR, CsvField::Comment,
+            CsvField::Type, CsvField::Page, CsvField::Panel, CsvField::Speaker
+        ]
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Whether the first row holds column names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Header {
+    /// A header if one of its cells is a mapped column name or a field name (`tl`, `type`...).
+    #[default]
+    Detect,
+    Yes,
+    No,
+}
+
+/// How to read a spreadsheet, see [`Document::from_csv_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvMapping {
+    /// Column → field. Several columns can fill the same field, their lines are appended in order.
+    /// Unmapped columns are ignored.
+    pub columns: Vec<(Column, CsvField)>,
+    pub header: Header,
+    /// Type token → balloon type, e.g. `("narration", TYPES::SQUARE)`. Compared case-insensitively.
+    /// Tokens not listed here are read as xml type names (`OT`, `Thinking`...), anything else is a dialogue.
+    pub types: Vec<(String, TYPES)>,
+    pub delimiter: char,
+}
+
+impl Default for CsvMapping {
+    /// One tl column, header detection, no type tokens, comma delimiter.
+    fn default() -> Self {
+        Self {
+            columns: vec![(Column::Index(0), CsvField::TL)],
+            header: Header::Detect,
+            types: Vec::new(),
+            delimiter: ',',
+        }
+    }
+}
+
+impl CsvMapping {
+    fn btype(&self, token: &str) -> TYPES {
+        let token = token.trim();
+
+        self.types
+            .iter()
+            .find(|(t, _)| t.eq_ignore_ascii_case(token))
+            .map(|(_, btype)| btype.clone())
+            .unwrap_or_else(|| TYPES::from_xml_name(token))
+    }
+
+    fn is_header(&self, row: &[String]) -> bool {
+        match self.header {
+            Header::Yes => true,
+            Header::No => false,
+            Header::Detect => row.iter().any(|cell| {
+                let cell = cell.trim();
+                self.columns.iter().any(|(c, f)| {
+                    f.name().eq_ignore_ascii_case(cell)
+                        || matches!(c, Column::Name(n) if n.eq_ignore_ascii_case(cell))
+                })
+            })
+        }
+    }
+}
+
+// Split csv text into rows of cells. Quoted cells can hold delimiters, line breaks and `""`.
+pub(crate) fn parse_rows(csv: &str, delimiter: char) -> XMLConvertResult<Vec<Vec<String>>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                },
+                '"' => quoted = false,
+                c => cell.push(c)
+            }
+            continue;
+        }
+
+        match c {
+            '"' if cell.is_empty() => quoted = true,
+            '\r' => {},
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            },
+            c if c == delimiter => row.push(std::mem::take(&mut cell)),
+            c => cell.push(c)
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quoted cell!".into());
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+// Quote a cell if it can't be written as it is. Every csv writer of the crate uses it.
+pub(crate) fn quote_cell(cell: &str, delimiter: char) -> String {
+    if cell.contains(['"', '\n', '\r', delimiter]) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+impl Document {
+    /// Generates a document from csv text laid out as described by `mapping`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::TYPES;
+    /// use rsff::csv::{Column, CsvField, CsvMapping};
+    ///
+    /// let csv = "Page;Kind;Text;Notes\n3;sfx;BOOM;\n3;bubble;\"Hi;\nthere\";check this\n";
+    ///
+    /// let mapping = CsvMapping {
+    ///     columns: vec![
+    ///         (Column::Name("Page".to_string()), CsvField::Page),
+    ///         (Column::Index(1), CsvField::Type),
+    ///         (Column::Name("text".to_string()), CsvField::TL),
+    ///         (Column::Name("Notes".to_string()), CsvField::Comment),
+    ///     ],
+    ///     types: vec![("sfx".to_string(), TYPES::OT)],
+    ///     delimiter: ';',
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let d = Document::from_csv_with(csv, &mapping).unwrap();
+    /// assert_eq!(d.balloons[0].btype, TYPES::OT);
+    /// assert_eq!(d.balloons[1].tl_content, vec!["Hi;", "there"]);
+    /// assert_eq!(d.balloons[1].page, Some(3));
+    /// ```
+    pub fn from_csv_with(csv: &str, mapping: &CsvMapping) -> XMLConvertResult<Document> {
+        let mut d = Document::default();
+        let mut rows = parse_rows(csv, mapping.delimiter)?.into_iter().peekable();
+
+        let header = match rows.peek() {
+            Some(first) if mapping.is_header(first) => rows.next(),
+            _ => None
+        };
+
+        // Resolve names to positions once
+        let mut columns: Vec<(usize, CsvField)> = Vec::with_capacity(mapping.columns.len());
+        for (column, field) in &mapping.columns {
+            let i = match column {
+                Column::Index(i) => *i,
+                Column::Name(name) => header
+                    .as_ref()
+                    .and_then(|h| h.iter().position(|cell| cell.trim().eq_ignore_ascii_case(name)))
+                    .ok_or(format!("No column named \"{name}\"!"))?
+            };
+            columns.push((i, *field));
+        }
+
+        let index = columns.iter().find(|(_, f)| *f == CsvField::Index).map(|(i, _)| *i);
+        let mut last_index: Option<String> = None;
+
+        for row in rows {
+            if row.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+
+            // More lines of the previous balloon
+            let mut b = match index.map(|i| row.get(i).map(|c| c.trim()).unwrap_or("")) {
+                Some(i) if (i.is_empty() || last_index.as_deref() == Some(i)) && !d.balloons.is_empty() => {
+                    d.balloons.pop().unwrap()
+                },
+                Some(i) => {
+                    last_index = Some(i.to_string());
+                    Balloon::default()
+                },
+                None => Balloon::default()
+            };
+
+            for (i, field) in &columns {
+                let cell = row.get(*i).map(|c| c.trim()).unwrap_or("");
+                if cell.is_empty() {
+                    continue;
+                }
+                let lines = cell.lines().map(|l| l.trim().to_string());
+
+                match field {
+                    CsvField::Index => (),
+                    CsvField::TL => b.tl_content.extend(lines),
+                    CsvField::TLC => b.tlc_content.extend(lines),
+                    CsvField::PR => b.pr_content.extend(lines),
+                    CsvField::Comment => b.comments.extend(lines),
+                    CsvField::Type => b.btype = mapping.btype(cell),
+                    CsvField::Page => b.page = Some(cell.parse()?),
+                    CsvField::Panel => b.panel = Some(cell.parse()?),
+                    CsvField::Speaker => b.speaker = Some(cell.to_string())
+                }
+            }
+
+            d.balloons.push(b);
+        }
+
+        d.sanitize();
+
+        Ok(d)
+    }
+
+    /// Opens a csv file and generates a document, see [`Document::from_csv_with`].
+    #[cfg(feature = "std")]
+    pub fn from_csv_file_with(fp: &str, mapping: &CsvMapping) -> XMLConvertResult<Document> {
+        Document::from_csv_with(&std::fs::read_to_string(fp)?, mapping)
+    }
+
+    /// Generates a review spreadsheet: a header row, then one row per line with the balloon's
+    /// index and type, its tl, pr and comment lines side by side. The tlc column is only
+    /// there if some balloon has tlc content. Balloons without lines get a row too.
+    ///
+    /// Use `'\t'` as `delimiter` for tsv.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::{TYPES, Track};
+    ///
+    /// let mut d = Document::default();
+    /// let b = Balloon::new(TYPES::SFX)
+    ///     .with_line(Track::TL, "Boom")
+    ///     .with_line(Track::PR, "BOOM")
+    ///     .with_line(Track::PR, "BOOM!");
+    /// d.balloons.push(b);
+    ///
+    /// assert_eq!(d.to_csv(','), "index,type,tl,pr,comment\n0,SFX,Boom,BOOM,\n0,SFX,,BOOM!,\n");
+    /// ```
+    pub fn to_csv(&self, delimiter: char) -> String {
+        let tlc = self.balloons.iter().any(|b| !b.tlc_content.is_empty());
+        let fields: Vec<CsvField> = [CsvField::TL, CsvField::TLC, CsvField::PR, CsvField::Comment]
+            .into_iter()
+            .filter(|f| tlc || *f != CsvField::TLC)
+            .collect();
+
+        let mut header = vec![CsvField::Index.name(), CsvField::Type.name()];
+        header.extend(fields.iter().map(|f| f.name()));
+        let mut csv = header.join(&delimiter.to_string());
+        csv.push('\n');
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            let columns: Vec<&[String]> = fields
+                .iter()
+                .map(|f| match f {
+                    CsvField::TL => b.tl_content.as_slice(),
+                    CsvField::TLC => b.tlc_content.as_slice(),
+                    CsvField::PR => b.pr_content.as_slice(),
+                    _ => b.comments.as_slice()
+                })
+                .collect();
+            let rows = columns.iter().map(|c| c.len()).max().unwrap_or(0).max(1);
+
+            for n in 0..rows {
+                let mut row = vec![i.to_string(), b.btype.xml_name().to_string()];
+                row.extend(columns.iter().map(|c| quote_cell(c.get(n).map(String::as_str).unwrap_or(""), delimiter)));
+                csv.push_str(&row.join(&delimiter.to_string()));
+                csv.push('\n');
+            }
+        }
+
+        csv
+    }
+
+    /// Generates a document from a review spreadsheet made by [`Document::to_csv`].
+    ///
+    /// Columns are found by their header, so they can be moved around, and rows with a
+    /// new index can be added for new balloons.
+    pub fn from_csv(csv: &str, delimiter: char) -> XMLConvertResult<Document> {
+        let header = parse_rows(csv.lines().next().unwrap_or(""), delimiter)?;
+        let columns: Vec<(Column, CsvField)> = header
+            .first()
+            .map(|h| {
+                h.iter()
+                    .enumerate()
+                    .filter_map(|(i, cell)| CsvField::from_name(cell).map(|f| (Column::Index(i), f)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if !columns.iter().any(|(_, f)| *f == CsvField::Index) {
+            return Err("Missing index column!".into());
+        }
+
+        let mapping = CsvMapping { columns, header: Header::Yes, types: Vec::new(), delimiter };
+        Document::from_csv_with(csv, &mapping)
+    }
+
+    /// Opens a review spreadsheet, see [`Document::from_csv`].
+    /// Files with the `tsv` extension are tab-separated.
+    #[cfg(feature = "std")]
+    pub fn from_csv_file(fp: &str) -> XMLConvertResult<Document> {
+        let delimiter = if fp.ends_with(".tsv") { '\t' } else { ',' };
+        Document::from_csv(&std::fs::read_to_string(fp)?, delimiter)
+    }
+}
+
+#[cfg(test)]
+mod csv_tests {
+    use super::{CsvField, CsvMapping, Column, Header, parse_rows};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    #[test]
+    fn csv_quoted_cells() {
+        let rows = parse_rows("a,\"b,\"\"c\"\"\"\r\n\"x\ny\",\n", ',').unwrap();
+        assert_eq!(rows, vec![vec!["a", "b,\"c\""], vec!["x\ny", ""]]);
+
+        assert!(parse_rows("\"open", ',').is_err());
+    }
+
+    #[test]
+    fn csv_header_detection() {
+        let mapping = CsvMapping {
+            columns: vec![(Column::Index(1), CsvField::TL), (Column::Index(0), CsvField::Type)],
+            ..Default::default()
+        };
+
+        // "TL" is a field name, so the first row is skipped
+        let d = Document::from_csv_with("Type,TL\nOT,num\n,,\nThinking,nam", &mapping).unwrap();
+        assert_eq!(d.len(), 2);
+        assert_eq!(d.balloons[1].btype, TYPES::THINKING);
+
+        let no_header = CsvMapping { header: Header::No, ..mapping };
+        assert_eq!(Document::from_csv_with("Type,TL\nOT,num", &no_header).unwrap().len(), 2);
+
+        let missing = CsvMapping { columns: vec![(Column::Name("Text".to_string()), CsvField::TL)], ..Default::default() };
+        assert!(Document::from_csv_with("a,b", &missing).is_err());
+    }
+
+    #[test]
+    fn csv_review_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::new(TYPES::OT).with_line(Track::TL, "a, \"b\"").with_line(Track::TLC, "c"));
+        d.balloons.push(Balloon::default());
+        let mut b = Balloon::default().with_line(Track::TL, "x").with_line(Track::TL, "y");
+        b.comments.push("z".to_string());
+        d.balloons.push(b);
+
+        let tsv = d.to_csv('\t');
+        assert!(tsv.starts_with("index\ttype\ttl\ttlc\tpr\tcomment\n"));
+        let back = Document::from_csv(&tsv, '\t').unwrap();
+        assert_eq!(back.balloon_hashes(), d.balloon_hashes());
+
+        // Reordered columns, an edited line and a new balloon
+        let edited = "pr,index,tl\nOK,0,a\n,0,b\nNew,5,\n";
+        let back = Document::from_csv(edited, ',').unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back.balloons[0].tl_content, vec!["a", "b"]);
+        assert_eq!(back.balloons[1].pr_content, vec!["New"]);
+        assert!(Document::from_csv("tl\na", ',').is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Anonymized (source, target) text pairs for training translation models.
+//!
+//! Documents don't store the raw text of the scans, so the pairs are post-editing pairs:
+//! the first translation of a balloon (its tl lines, often machine output) and the text
+//! that ended up on the page after translation check and proofreading. Balloons that were
+//! never checked have no target and are left out.
+//!
+//! Only the text goes out: no metadata, comments, speakers or images. The text itself is
+//! scrubbed following a [`ScrubPolicy`], so groups can contribute their archives to open
+//! models without leaking who they are.
+
+use crate::Document;
+use crate::consts::{TYPES, Track};
+use crate::emphasis::strip_emphasis;
+use crate::project::Project;
+
+/// What is scrubbed from the text of [`Document::dataset_pairs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScrubPolicy {
+    /// Names replaced by `placeholder` as whole words in any case, e.g. group members or staff
+    /// mentioned in credits. The speakers and the series of the document are always added.
+    pub names: Vec<String>,
+    pub placeholder: String,
+    /// Replace `@handles` and links with `placeholder`, even inside brackets or before punctuation.
+    pub handles: bool,
+    /// Balloons of these types are left out, e.g. sound effects.
+    pub skip_types: Vec<TYPES>,
+    /// Keep the language tags of the chapter in the pairs.
+    pub languages: bool,
+}
+
+impl Default for ScrubPolicy {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            placeholder: String::from("<name>"),
+            handles: true,
+            skip_types: Vec::new(),
+            languages: true,
+        }
+    }
+}
+
+/// One training example: a balloon's first translation and its final text, one line each.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetPair {
+    pub source: String,
+    pub target: String,
+    /// Language tag of the translation, if known and kept by the policy.
+    pub language: Option<String>,
+}
+
+// `true` if `text` has no letter or digit right before `start` and right after `end`.
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    !text[..start].chars().next_back().is_some_and(char::is_alphanumeric)
+        && !text[end..].chars().next().is_some_and(char::is_alphanumeric)
+}
+
+// Byte length of `name` at the start of `text`, ignoring case.
+fn match_len(text: &str, name: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+
+    for n in name.chars() {
+        let (_, c) = chars.next()?;
+        if !c.to_lowercase().eq(n.to_lowercase()) {
+            return None;
+        }
+    }
+
+    Some(chars.next().map_or(text.len(), |(i, _)| i))
+}
+
+// Replace every whole-word occurrence of `name` in `text`, ignoring case.
+fn replace_name(text: &str, name: &str, placeholder: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, _) in text.char_indices() {
+        // Inside an occurrence that was already replaced
+        if start < last {
+            continue;
+        }
+
+        if let Some(len) = match_len(&text[start..], name) {
+            let end = start + len;
+            if is_whole_word(text, start, end) {
+                out.push_str(&text[last..start]);
+                out.push_str(placeholder);
+                last = end;
+            }
+        }
+    }
+    out.push_str(&text[last..]);
+
+    out
+}
+
+fn is_handle(word: &str) -> bool {
+    let at = word.strip_prefix('@').is_some_and(|w| w.starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+
+    at || ["http://", "https://", "www."].iter().any(|p| word.to_lowercase().starts_with(p))
+}
+
+// `word` with a handle or link replaced by `placeholder`, keeping the punctuation around it,
+// e.g. `(@zed_tl),` becomes `(<name>),`.
+fn scrub_handle(word: &str, placeholder: &str) -> Option<String> {
+    let core = word.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '@');
+    let core = core.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_' && c != '/');
+    if !is_handle(core) {
+        return None;
+    }
+
+    let start = word.len() - word.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '@').len();
+    let end = start + core.len();
+
+    Some(format!("{}{placeholder}{}", &word[..start], &word[end..]))
+}
+
+impl ScrubPolicy {
+    // The balloon lines as one scrubbed line.
+    fn scrub(&self, lines: &[String], names: &[String]) -> String {
+        let mut text = lines
+            .iter()
+            .map(|l| strip_emphasis(l.trim()))
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if self.handles {
+            text = text
+                .split(' ')
+                .map(|w| scrub_handle(w, &self.placeholder).unwrap_or_else(|| w.to_string()))
+                .collect::<Vec<String>>()
+                .join(" ");
+        }
+
+        // After the handles, so a name doesn't cut `@zed_tl` in pieces
+        for name in names {
+            text = replace_name(&text, name, &self.placeholder);
+        }
+
+        text
+    }
+}
+
+impl Document {
+    // Names scrubbed from the text: the policy's, the speakers and the series, longest first
+    // so "Mary Jane" goes before "Mary".
+    fn scrubbed_names(&self, policy: &ScrubPolicy) -> Vec<String> {
+        let mut names: Vec<String> = policy.names
+            .iter()
+            .chain(self.balloons.iter().filter_map(|b| b.speaker.as_ref()))
+            .chain(self.chapter.series.as_ref())
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect();
+        names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        names.dedup();
+
+        names
+    }
+
+    /// Anonymized training pairs of the balloons that were checked or proofread, see
+    /// [`crate::dataset`]. Pairs that end up empty after scrubbing are left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::dataset::ScrubPolicy;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(
+    ///     Balloon::default()
+    ///         .with_line(Track::TL, "Ken, you has come")
+    ///         .with_line(Track::PR, "Ken, you came!")
+    /// );
+    /// d.balloons[0].speaker = Some("Ken".to_string());
+    ///
+    /// let pairs = d.dataset_pairs(&ScrubPolicy::default());
+    /// assert_eq!(pairs[0].source, "<name>, you has come");
+    /// assert_eq!(pairs[0].target, "<name>, you came!");
+    /// ```
+    pub fn dataset_pairs(&self, policy: &ScrubPolicy) -> Vec<DatasetPair> {
+        let names = self.scrubbed_names(policy);
+        let language = self.chapter.target_language.clone().filter(|_| policy.languages);
+
+        self.balloons
+            .iter()
+            .filter(|b| !policy.skip_types.contains(&b.btype))
+            .filter_map(|b| {
+                let target = b.effective_lines_with(&[Track::PR, Track::TLC]);
+                if b.tl_content.is_empty() || target.is_empty() {
+                    return None;
+                }
+
+                let pair = DatasetPair {
+                    source: policy.scrub(&b.tl_content, &names),
+                    target: policy.scrub(target, &names),
+                    language: language.clone(),
+                };
+                (!pair.source.is_empty() && !pair.target.is_empty()).then_some(pair)
+            })
+            .collect()
+    }
+}
+
+impl Project {
+    /// [`Document::dataset_pairs`] of every chapter, in release order.
+    pub fn dataset_pairs(&self, policy: &ScrubPolicy) -> Vec<DatasetPair> {
+        self.chapters
+            .iter()
+            .flat_map(|c| c.document.dataset_pairs(policy))
+            .collect()
+    }
+}
+
+/// Tab separated `source`, `target` lines, the layout most training tools read.
+pub fn pairs_to_tsv(pairs: &[DatasetPair]) -> String {
+    let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+
+    pairs
+        .iter()
+        .map(|p| format!("{}\t{}\n", clean(&p.source), clean(&p.target)))
+        .collect()
+}
+
+#[cfg(test)]
+mod dataset_tests {
+    use super::{ScrubPolicy, pairs_to_tsv, replace_name, scrub_handle};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    #[test]
+    fn dataset_is_scrubbed() {
+        assert_eq!(replace_name("Anna, Annabel and anna", "Anna", "X"), "X, Annabel and X");
+        assert_eq!(replace_name("MARY JANE! mary-jane", "Mary Jane", "X"), "X! mary-jane");
+        assert_eq!(replace_name("ÉLODIE élodie", "Élodie", "X"), "X X");
+        assert_eq!(scrub_handle("(@zed_tl),", "X").as_deref(), Some("(X),"));
+        assert_eq!(scrub_handle("<https://x.example/>.", "X").as_deref(), Some("<X>."));
+        assert_eq!(scrub_handle("@", "X"), None);
+
+        let mut d = Document::default();
+        d.chapter.series = Some("Moon Knight".to_string());
+        d.chapter.target_language = Some("en".to_string());
+        d.METADATA_INFO = "Scanned by Secret Group".to_string();
+
+        d.balloons.push(Balloon::default().with_line(Track::TL, "Only a draft"));
+        d.balloons.push(
+            Balloon::default()
+                .with_line(Track::TL, "Mary Jane,")
+                .with_line(Track::TL, "*look*  ")
+                .with_line(Track::TLC, "Mary Jane, *look*!")
+        );
+        d.balloons.push(Balloon::new(TYPES::SFX).with_line(Track::TL, "Bam").with_line(Track::PR, "BAM"));
+        d.balloons.push(
+            Balloon::default()
+                .with_line(Track::TL, "Thanks Zed! follow @zed_tl https://x.example")
+                .with_line(Track::PR, "Moon Knight by Zed")
+        );
+        d.balloons[1].speaker = Some("Mary".to_string());
+
+        let policy = ScrubPolicy {
+            names: vec!["Mary Jane".to_string(), "Zed".to_string(), " ".to_string()],
+            skip_types: vec![TYPES::SFX],
+            ..Default::default()
+        };
+        let pairs = d.dataset_pairs(&policy);
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!((pairs[0].source.as_str(), pairs[0].target.as_str()), ("<name>, look", "<name>, look!"));
+        assert_eq!(pairs[1].source, "Thanks <name>! follow <name> <name>");
+        assert_eq!(pairs[1].target, "<name> by <name>");
+        assert_eq!(pairs[0].language.as_deref(), Some("en"));
+        assert!(!pairs_to_tsv(&pairs).contains("Secret"));
+        assert_eq!(pairs_to_tsv(&pairs).lines().next(), Some("<name>, look\t<name>, look!"));
+
+        let no_languages = ScrubPolicy { languages: false, handles: false, ..Default::default() };
+        let pairs = d.dataset_pairs(&no_languages);
+        assert!(pairs.iter().all(|p| p.language.is_none()));
+        assert!(pairs[2].source.contains("@zed_tl"));
+    }
+}
@@ -0,0 +1,247 @@
+//! Balloons changed since a checkpoint.
+//!
+//! A typesetter who already placed the text of a checkpoint only needs the
+//! balloons that changed since then, see [`Document::delta`].
+//!
+//! Balloons are matched to the checkpoint by content, so inserting or deleting a balloon
+//! doesn't mark every balloon after it as changed.
+
+use crate::{Document, Error, XMLConvertResult};
+#[cfg(feature = "std")]
+use crate::consts::Format;
+#[cfg(feature = "std")]
+use crate::export::ExportOptions;
+#[cfg(feature = "std")]
+use crate::io::write_atomic;
+
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+/// What changed since a checkpoint, see [`Document::changes_since`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// Indices of balloons edited or added since the checkpoint.
+    pub changed: Vec<usize>,
+    /// Indices, in the checkpoint, of balloons that were removed.
+    pub removed: Vec<usize>,
+}
+
+/// The balloons changed since a checkpoint, see [`Document::delta`].
+#[derive(Clone, Debug)]
+pub struct Delta {
+    /// Same metadata and pages as the document, with only the changed balloons.
+    pub document: Document,
+    /// Index in the full document of every balloon of `document`.
+    pub source: Vec<usize>,
+    /// Indices, in the checkpoint, of balloons that were removed.
+    pub removed: Vec<usize>,
+}
+
+// Match `new` to `old` by their longest common subsequence. Unmatched balloons between
+// two matches are edits as far as both sides have some, additions or removals after that.
+fn changes(old: &[u64], new: &[u64]) -> Changes {
+    // lcs[i][j]: longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut c = Changes::default();
+    let (mut i, mut j) = (0, 0);
+    // Unmatched old balloons and count of unmatched new ones of the current gap
+    let mut gap_old: Vec<usize> = Vec::new();
+    let mut gap_new = 0;
+
+    // The first old balloons of a gap were edited into the new ones, the rest removed
+    fn close_gap(gap_old: &mut Vec<usize>, gap_new: &mut usize, c: &mut Changes) {
+        c.removed.extend(gap_old.drain(..).skip(*gap_new));
+        *gap_new = 0;
+    }
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            close_gap(&mut gap_old, &mut gap_new, &mut c);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            gap_old.push(i);
+            i += 1;
+        } else {
+            c.changed.push(j);
+            gap_new += 1;
+            j += 1;
+        }
+    }
+    close_gap(&mut gap_old, &mut gap_new, &mut c);
+
+    c
+}
+
+impl Document {
+    /// Balloons edited, added and removed since the checkpoint `since`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// for text in ["a", "b", "c"] {
+    ///     d.balloons.push(Balloon::default().with_line(Track::TL, text));
+    /// }
+    /// d.checkpoint("v1");
+    ///
+    /// d.balloons.remove(0);
+    /// d.balloons[1].pr_content.push("C".to_string());
+    ///
+    /// let changes = d.changes_since("v1").unwrap();
+    /// assert_eq!(changes.changed, [1]);
+    /// assert_eq!(changes.removed, [0]);
+    /// ```
+    pub fn changes_since(&self, since: &str) -> XMLConvertResult<Changes> {
+        let checkpoint = self.checkpoints
+            .get(since)
+            .ok_or_else(|| Error::UnknownCheckpoint(since.to_string()))?;
+        let old: Vec<u64> = self.checkpoints.balloons(checkpoint).iter().map(|b| b.hash()).collect();
+
+        Ok(changes(&old, &self.balloon_hashes()))
+    }
+
+    /// Indices of balloons edited or added since the checkpoint `since`, see
+    /// [`Document::changes_since`] for the removed ones.
+    pub fn changed_since(&self, since: &str) -> XMLConvertResult<Vec<usize>> {
+        Ok(self.changes_since(since)?.changed)
+    }
+
+    /// A document with the same metadata and pages, holding only the balloons
+    /// changed since the checkpoint `since` (see [`Document::changes_since`]), with
+    /// their indices in this document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default());
+    /// d.balloons.push(Balloon::default());
+    /// d.checkpoint("v1");
+    ///
+    /// d.balloons[1].pr_content.push("Fixed typo".to_string());
+    ///
+    /// let delta = d.delta("v1").unwrap();
+    /// assert_eq!(delta.document.len(), 1);
+    /// assert_eq!(delta.document.balloons[0].pr_content[0], "Fixed typo");
+    /// assert_eq!(delta.source, [1]);
+    /// ```
+    pub fn delta(&self, since: &str) -> XMLConvertResult<Delta> {
+        let changes = self.changes_since(since)?;
+
+        let document = Document {
+            METADATA_SCRIPT_VERSION: self.METADATA_SCRIPT_VERSION.clone(),
+            METADATA_APP_VERSION: self.METADATA_APP_VERSION.clone(),
+            METADATA_INFO: self.METADATA_INFO.clone(),
+            chapter: self.chapter.clone(),
+            budgets: self.budgets,
+            settings: self.settings.clone(),
+            handoff: self.handoff.clone(),
+            pages: self.pages.clone(),
+            balloons: changes.changed.iter().map(|i| self.balloons[*i].clone()).collect(),
+            ..Default::default()
+        };
+
+        Ok(Delta { document, source: changes.changed, removed: changes.removed })
+    }
+
+    /// Saves [`Document::delta`] like [`Document::save`] does. Txt files mark every
+    /// balloon with its index in this document (`#[id:N]`), so the edited file can be
+    /// applied back with [`Document::rematch_txt`].
+    #[cfg(feature = "std")]
+    pub fn export_delta(&self, since: &str, out_type: Format, fp: &str) -> XMLConvertResult<PathBuf> {
+        let delta = self.delta(since)?;
+
+        if out_type != Format::TXT {
+            return delta.document.save_with(out_type, fp, &ExportOptions::default());
+        }
+
+        let path = PathBuf::from(format!("{fp}.{}", out_type.extension()));
+        write_atomic(&path, delta.document.to_string_with_ids_of(delta.source).as_bytes())?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod delta_tests {
+    use super::changes;
+    use crate::{Document, Error};
+    use crate::balloon::Balloon;
+
+    fn balloon(text: &str) -> Balloon {
+        let mut b = Balloon::default();
+        b.tl_content.push(text.to_string());
+        b
+    }
+
+    #[test]
+    fn delta_changed_balloons() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+        d.balloons.push(balloon("c"));
+        d.checkpoint("v1");
+
+        assert_eq!(d.changed_since("v1").unwrap(), Vec::<usize>::new());
+        assert!(matches!(d.changed_since("v2"), Err(Error::UnknownCheckpoint(name)) if name == "v2"));
+
+        d.balloons[0].pr_content.push("A".to_string());
+        d.balloons.push(balloon("d"));
+        assert_eq!(d.changed_since("v1").unwrap(), vec![0, 3]);
+
+        let delta = d.delta("v1").unwrap();
+        assert_eq!(delta.document.len(), 2);
+        assert_eq!(delta.source, [0, 3]);
+        assert!(delta.document.checkpoints.is_empty());
+
+        // A removed balloon doesn't shift the ones after it
+        d.balloons.remove(1);
+        let c = d.changes_since("v1").unwrap();
+        assert_eq!((c.changed, c.removed), (vec![0, 2], vec![1]));
+
+        // Edits pair up inside a gap, the rest are additions or removals
+        let c = changes(&[1, 2, 3, 4, 5], &[1, 7, 5, 8]);
+        assert_eq!((c.changed, c.removed), (vec![1, 3], vec![2, 3]));
+        assert!(d.delta("v1").unwrap().removed == [1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn delta_txt_keeps_source_ids() {
+        use crate::consts::Format;
+
+        let mut d = Document::default();
+        for text in ["a", "b", "c"] {
+            d.balloons.push(balloon(text));
+        }
+        d.checkpoint("v1");
+        d.balloons[2].tl_content[0] = "C".to_string();
+
+        let path = d.export_delta("v1", Format::TXT, "delta_txt_test").unwrap();
+        let txt = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(txt, "#[id:2]\n(): C");
+
+        let back = d.rematch_txt(&txt.replace("(): C", "(): C!")).unwrap();
+        assert_eq!(back.balloons[0].tl_content, ["C!"]);
+        assert!(d.export_delta("v2", Format::TXT, "delta_txt_test").is_err());
+    }
+}
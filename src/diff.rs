@@ -0,0 +1,149 @@
+//! Word-level diffs of balloon text.
+//!
+//! Review UIs show what the proofreader changed inside a line with
+//! [`Balloon::tl_pr_diff`]. Text is split into words and whitespace, so the spans
+//! always join back to the exact original and new text.
+
+use crate::balloon::Balloon;
+
+/// What happened to the text of a [`DiffSpan`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    Unchanged,
+    /// Only in the new text.
+    Inserted,
+    /// Only in the old text.
+    Deleted,
+}
+
+/// A run of words with the same [`DiffKind`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffSpan {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+// Split into alternating runs of whitespace and other characters.
+fn words(s: &str) -> Vec<&str> {
+    let mut out: Vec<&str> = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in s.char_indices().skip(1) {
+        let prev = s[..i].chars().next_back().unwrap();
+        if prev.is_whitespace() != c.is_whitespace() {
+            out.push(&s[start..i]);
+            start = i;
+        }
+    }
+
+    if start < s.len() {
+        out.push(&s[start..]);
+    }
+
+    out
+}
+
+/// Word-level diff of `old` and `new`. Deleted words come before the words inserted in their place.
+///
+/// Joining the [`DiffKind::Unchanged`] and [`DiffKind::Deleted`] spans gives `old`,
+/// joining the unchanged and [`DiffKind::Inserted`] ones gives `new`.
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffSpan> {
+    let a = words(old);
+    let b = words(new);
+
+    // lcs[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    let mut push = |kind: DiffKind, text: &str| {
+        match spans.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => spans.push(DiffSpan { kind, text: text.to_string() })
+        }
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(DiffKind::Unchanged, a[i]);
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(DiffKind::Deleted, a[i]);
+            i += 1;
+        } else {
+            push(DiffKind::Inserted, b[j]);
+            j += 1;
+        }
+    }
+
+    spans
+}
+
+impl Balloon {
+    /// Word-level diff of the tl lines against the pr lines, lines joined with `\n`.
+    /// Without pr lines nothing was proofread yet, and the tl is one unchanged span.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::Balloon;
+    /// use rsff::diff::{DiffKind, DiffSpan};
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Where you going?".to_string());
+    /// b.pr_content.push("Where are you going?".to_string());
+    ///
+    /// assert_eq!(b.tl_pr_diff(), vec![
+    ///     DiffSpan { kind: DiffKind::Unchanged, text: "Where ".to_string() },
+    ///     DiffSpan { kind: DiffKind::Inserted, text: "are ".to_string() },
+    ///     DiffSpan { kind: DiffKind::Unchanged, text: "you going?".to_string() },
+    /// ]);
+    /// ```
+    pub fn tl_pr_diff(&self) -> Vec<DiffSpan> {
+        let tl = self.tl_content.join("\n");
+
+        if self.pr_content.is_empty() {
+            if tl.is_empty() {
+                return Vec::new();
+            }
+            return vec![DiffSpan { kind: DiffKind::Unchanged, text: tl }];
+        }
+
+        diff_words(&tl, &self.pr_content.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use super::{DiffKind, diff_words};
+
+    #[test]
+    fn diff_joins_back_to_both_sides() {
+        let old = "I  told you\nnot to go!";
+        let new = "I told you not to go there!";
+        let spans = diff_words(old, new);
+
+        let side = |skip: DiffKind| spans
+            .iter()
+            .filter(|s| s.kind != skip)
+            .map(|s| s.text.as_str())
+            .collect::<String>();
+        assert_eq!(side(DiffKind::Inserted), old);
+        assert_eq!(side(DiffKind::Deleted), new);
+
+        // Replaced words: deletion first
+        let kinds: Vec<DiffKind> = diff_words("a b", "a c").iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, vec![DiffKind::Unchanged, DiffKind::Deleted, DiffKind::Inserted]);
+        assert!(diff_words("", "").is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! Inline emphasis markers in balloon text.
+//!
+//! Lines are plain text, so emphasis is written with lightweight markers: `*bold*` and
+//! `_italic_`. The markers are part of the line, so they survive every text round trip
+//! (txt, csv, spreadsheets) as typed. [`parse_emphasis`] turns a line into styled spans
+//! for editors and typesetting scripts.
+//!
+//! A marker only counts at a word boundary and with a matching closing marker on the
+//! same line, so `snake_case`, `2*3` or a lone `*` stay plain text.
+
+use crate::balloon::Balloon;
+use crate::consts::Track;
+
+/// A run of text with the same emphasis.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StyledSpan {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+fn is_marker(c: char) -> bool {
+    c == '*' || c == '_'
+}
+
+// Positions of the markers that open or close emphasis, in pairs.
+fn marker_positions(chars: &[char]) -> Vec<bool> {
+    let mut is_pair_end = vec![false; chars.len()];
+
+    let opens = |i: usize| {
+        let before = i.checked_sub(1).map(|j| chars[j]);
+        !before.is_some_and(char::is_alphanumeric) && chars.get(i + 1).is_some_and(|c| !c.is_whitespace())
+    };
+    let closes = |i: usize| {
+        !chars[i - 1].is_whitespace() && !chars.get(i + 1).is_some_and(|c| c.is_alphanumeric())
+    };
+
+    for i in 0..chars.len() {
+        if !is_marker(chars[i]) || is_pair_end[i] || !opens(i) {
+            continue;
+        }
+
+        // Nearest closing marker of the same kind, with some text in between
+        let close = (i + 2..chars.len()).find(|j| chars[*j] == chars[i] && !is_pair_end[*j] && closes(*j));
+        if let Some(j) = close {
+            is_pair_end[i] = true;
+            is_pair_end[j] = true;
+        }
+    }
+
+    is_pair_end
+}
+
+/// Splits `line` in spans of the same emphasis, without the markers.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::emphasis::{StyledSpan, parse_emphasis};
+///
+/// let spans = parse_emphasis("I *told* you");
+/// assert_eq!(spans[1], StyledSpan { text: "told".to_string(), bold: true, italic: false });
+///
+/// // Not markers
+/// assert_eq!(parse_emphasis("file_name_here, 2 * 3")[0].text, "file_name_here, 2 * 3");
+/// ```
+pub fn parse_emphasis(line: &str) -> Vec<StyledSpan> {
+    let chars: Vec<char> = line.chars().collect();
+    let markers = marker_positions(&chars);
+
+    let mut spans: Vec<StyledSpan> = Vec::new();
+    let (mut bold, mut italic) = (false, false);
+
+    for (c, is_marker) in chars.into_iter().zip(markers) {
+        if is_marker {
+            if c == '*' {
+                bold = !bold;
+            } else {
+                italic = !italic;
+            }
+            continue;
+        }
+
+        match spans.last_mut() {
+            Some(last) if last.bold == bold && last.italic == italic => last.text.push(c),
+            _ => spans.push(StyledSpan { text: c.to_string(), bold, italic })
+        }
+    }
+
+    spans
+}
+
+/// `line` without its emphasis markers, e.g. for counting the characters that end up on the page.
+pub fn strip_emphasis(line: &str) -> String {
+    parse_emphasis(line)
+        .into_iter()
+        .map(|s| s.text)
+        .collect()
+}
+
+impl Balloon {
+    /// The lines of `track` as styled spans, see [`parse_emphasis`].
+    pub fn styled_lines(&self, track: Track) -> Vec<Vec<StyledSpan>> {
+        self.lines(track)
+            .iter()
+            .map(|l| parse_emphasis(l))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod emphasis_tests {
+    use super::{StyledSpan, parse_emphasis, strip_emphasis};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::Track;
+
+    fn span(text: &str, bold: bool, italic: bool) -> StyledSpan {
+        StyledSpan { text: text.to_string(), bold, italic }
+    }
+
+    #[test]
+    fn emphasis_markers() {
+        assert_eq!(
+            parse_emphasis("*_Both_* and _it *al* ic_!"),
+            vec![span("Both", true, true), span(" and ", false, false), span("it ", false, true), span("al", true, true), span(" ic", false, true), span("!", false, false)]
+        );
+        assert_eq!(strip_emphasis("* not* *bold *, a_b_c, _open"), "* not* *bold *, a_b_c, _open");
+        assert_eq!(strip_emphasis("**"), "**");
+
+        // Markers are kept through a txt round trip
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "You *idiot*!").with_line(Track::TL, "_Sigh_"));
+        let back = Document::from_txt(&d.to_string()).unwrap();
+        assert_eq!(back.balloons[0].styled_lines(Track::TL), d.balloons[0].styled_lines(Track::TL));
+        assert_eq!(back.balloons[0].styled_lines(Track::TL)[1], vec![span("Sigh", false, true)]);
+    }
+}
@@ -0,0 +1,166 @@
+//! The error type of every fallible operation.
+
+use std::fmt;
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::string::FromUtf8Error;
+
+/// Why an operation failed.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::{Document, Error};
+///
+/// match Document::from_xml("<Document><Balloons>") {
+///     Err(Error::Parse(e)) => println!("not a document: {e}"),
+///     _ => unreachable!()
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// The content is not well-formed xml.
+    Parse(roxmltree::Error),
+    /// Text that should be utf-8 isn't.
+    Utf8(FromUtf8Error),
+    /// A balloon image is not valid base64.
+    Base64(base64::DecodeError),
+    /// A compressed (sffz) file could not be decompressed.
+    Decompression(io::Error),
+    /// The file has no extension.
+    MissingExtension,
+    /// The file extension is not a supported format.
+    UnsupportedExtension(String),
+    /// An attribute that should be a number isn't, with the parser's message.
+    Number(String),
+    /// Well-formed but invalid content, e.g. a missing tag or an unknown attribute value.
+    Invalid(String),
+    /// The document has no checkpoint with this name.
+    UnknownCheckpoint(String),
+    /// Errors of optional dependencies (zip archives, plugins...).
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Parse(e) => write!(f, "malformed xml: {e}"),
+            Error::Utf8(e) => write!(f, "invalid utf-8: {e}"),
+            Error::Base64(e) => write!(f, "invalid base64: {e}"),
+            Error::Decompression(e) => write!(f, "decompression failed: {e}"),
+            Error::MissingExtension => write!(f, "no file extension"),
+            Error::UnsupportedExtension(ext) => write!(f, "unsupported file extension \"{ext}\""),
+            Error::Number(e) => write!(f, "invalid number: {e}"),
+            Error::Invalid(message) => write!(f, "{message}"),
+            Error::UnknownCheckpoint(name) => write!(f, "no checkpoint named \"{name}\""),
+            Error::Other(e) => write!(f, "{e}")
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::Decompression(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            Error::Base64(e) => Some(e),
+            Error::Other(e) => Some(e.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(e: roxmltree::Error) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Self {
+        Error::Utf8(e)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Self {
+        Error::Base64(e)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Self {
+        Error::Number(e.to_string())
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(e: ParseFloatError) -> Self {
+        Error::Number(e.to_string())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Invalid(message.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Invalid(message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::path::StripPrefixError> for Error {
+    fn from(e: std::path::StripPrefixError) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::time::SystemTimeError> for Error {
+    fn from(e: std::time::SystemTimeError) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<xmlparser::Error> for Error {
+    fn from(e: xmlparser::Error) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
+
+#[cfg(any(feature = "archive", feature = "xlsx", feature = "odt"))]
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
+
+#[cfg(any(feature = "image-sanitize", feature = "preview"))]
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Other(Box::new(e))
+    }
+}
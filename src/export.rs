@@ -0,0 +1,645 @@
+//! What an export keeps and what it drops, and how text exports are laid out.
+
+use crate::Document;
+use crate::balloon::ImageCompression;
+use crate::consts::{Format, TYPES, Track};
+use crate::handoff::Handoff;
+use crate::reflow::wrap_preview;
+use crate::rematch::ID_MARKER;
+
+use std::fmt;
+
+/// A kind of data a lossy format drops, with how much of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dropped {
+    /// Script/app version and info.
+    Metadata,
+    /// Number of balloon images.
+    Images(usize),
+    /// Number of comment lines.
+    Comments(usize),
+    /// Number of tl lines not written because their balloon has tlc or pr content.
+    TlLines(usize),
+    /// Number of tlc lines not written because their balloon has pr content.
+    TlcLines(usize),
+    /// Number of page and panel notes.
+    Notes(usize),
+    /// Number of checkpoints.
+    Checkpoints(usize),
+    /// Number of balloons with a speaker.
+    Speakers(usize),
+    /// Number of comments on page areas, see [`crate::region`].
+    RegionComments(usize),
+}
+
+impl fmt::Display for Dropped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dropped::Metadata => write!(f, "metadata"),
+            Dropped::Images(n) => write!(f, "{n} image(s)"),
+            Dropped::Comments(n) => write!(f, "{n} comment(s)"),
+            Dropped::TlLines(n) => write!(f, "{n} tl line(s) replaced by tlc/pr"),
+            Dropped::TlcLines(n) => write!(f, "{n} tlc line(s) replaced by pr"),
+            Dropped::Notes(n) => write!(f, "{n} page/panel note(s)"),
+            Dropped::Checkpoints(n) => write!(f, "{n} checkpoint(s)"),
+            Dropped::Speakers(n) => write!(f, "{n} speaker(s)"),
+            Dropped::RegionComments(n) => write!(f, "{n} region comment(s)")
+        }
+    }
+}
+
+/// Speaker name of balloons without one in [`TextLayout::Script`] exports.
+pub const UNKNOWN_SPEAKER: &str = "???";
+
+/// How balloons are laid out in txt exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TextLayout {
+    /// One block per balloon with its type header. Can be opened again.
+    #[default]
+    Balloons,
+    /// Stage-play script for dubbing: consecutive lines of the same speaker grouped
+    /// under the speaker's name, in document order. Export only.
+    Script,
+    /// Balloons grouped by type, e.g. every SFX together for a typesetting pass. Groups
+    /// come in the order of [`TYPES::all`] under a `// SFX (3)` header, balloons in
+    /// document order, each after its `#[id:N]` marker. Opened with [`Document::rematch_txt`].
+    ByType,
+}
+
+/// Which parts of a document an export writes. Everything by default.
+///
+/// Set it once in [`ExportOptions`] and every export made with those options leaves
+/// out the same data, e.g. a review export with comments and a public transcript without.
+/// Exporters without options (like [`Document::to_ass`]) can be fed [`Document::filtered`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExportFilter {
+    /// tl and tlc lines.
+    pub tl: bool,
+    /// pr lines.
+    pub pr: bool,
+    pub comments: bool,
+    /// Balloon images, with their image history.
+    pub images: bool,
+    pub speakers: bool,
+    /// Page and panel notes.
+    pub notes: bool,
+}
+
+impl Default for ExportFilter {
+    fn default() -> Self {
+        Self { tl: true, pr: true, comments: true, images: true, speakers: true, notes: true }
+    }
+}
+
+impl ExportFilter {
+    /// Only the text readers see: no comments, images or notes.
+    pub fn transcript() -> Self {
+        Self { comments: false, images: false, notes: false, ..Default::default() }
+    }
+
+    /// Sets [`ExportFilter::tl`].
+    pub fn with_tl(mut self, tl: bool) -> Self {
+        self.tl = tl;
+        self
+    }
+
+    /// Sets [`ExportFilter::pr`].
+    pub fn with_pr(mut self, pr: bool) -> Self {
+        self.pr = pr;
+        self
+    }
+
+    /// Sets [`ExportFilter::comments`].
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Sets [`ExportFilter::images`].
+    pub fn with_images(mut self, images: bool) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Sets [`ExportFilter::speakers`].
+    pub fn with_speakers(mut self, speakers: bool) -> Self {
+        self.speakers = speakers;
+        self
+    }
+
+    /// Sets [`ExportFilter::notes`].
+    pub fn with_notes(mut self, notes: bool) -> Self {
+        self.notes = notes;
+        self
+    }
+}
+
+/// How hard sffz and sffzst files are compressed. The algorithm is the format: zlib for
+/// [`Format::ZLIB`], zstd for [`Format::ZSTD`](crate::consts::Format) (`zstd` feature).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressionLevel {
+    /// Fast saves and bigger files, e.g. for autosaves.
+    Fast,
+    /// The best level for sffz, the zstd default (3) for sffzst.
+    #[default]
+    Default,
+    /// Smallest files and slow saves, e.g. for archives.
+    Best,
+    /// A level of the algorithm: 0 to 9 for zlib, 1 to 22 for zstd.
+    Level(i32),
+}
+
+impl CompressionLevel {
+    // Zlib level, 0 to 9.
+    #[cfg(feature = "std")]
+    pub(crate) fn zlib(&self) -> u32 {
+        match self {
+            CompressionLevel::Fast => 1,
+            CompressionLevel::Default | CompressionLevel::Best => 9,
+            CompressionLevel::Level(l) => (*l).clamp(0, 9) as u32
+        }
+    }
+
+    // Zstd level, 1 to 22.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn zstd(&self) -> i32 {
+        match self {
+            CompressionLevel::Fast => 1,
+            CompressionLevel::Default => 3,
+            CompressionLevel::Best => 19,
+            CompressionLevel::Level(l) => (*l).clamp(1, 22)
+        }
+    }
+}
+
+/// Options for [`Document::save_with`] and [`Document::to_text`].
+///
+/// New options may be added in minor versions, so build them with the `with_*` methods:
+///
+/// ```
+/// use rsff::export::{ExportFilter, ExportOptions, TextLayout};
+///
+/// let opts = ExportOptions::default()
+///     .with_layout(TextLayout::Script)
+///     .with_filter(ExportFilter::transcript().with_speakers(false));
+/// assert!(!opts.filter.speakers);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExportOptions {
+    /// Only used by txt exports.
+    pub layout: TextLayout,
+    pub filter: ExportFilter,
+    /// Txt exports start with the note of this handoff, as `//` lines that are
+    /// skipped when the file is opened again.
+    pub handoff: Option<Handoff>,
+    /// Compression of balloon images without their own
+    /// [`BalloonImage::compression`](crate::balloon::BalloonImage::compression).
+    /// `None` saves them as they are.
+    pub image_compression: Option<ImageCompression>,
+    /// Txt exports with the [`TextLayout::Balloons`] layout put an `#[id:N]` line before
+    /// every balloon, see [`Document::rematch_txt`].
+    pub ids: bool,
+    /// Txt exports wrap the lines of every balloon at this many characters, as
+    /// [`Balloon::wrap_preview`](crate::balloon::Balloon::wrap_preview) shows them.
+    pub wrap: Option<usize>,
+    /// Compression of sffz and sffzst files.
+    pub compression: CompressionLevel,
+}
+
+impl ExportOptions {
+    /// Options for autosaves: fast compression.
+    #[cfg_attr(feature = "std", doc = r#"
+# Examples
+
+```
+use rsff::Document;
+use rsff::consts::Format;
+use rsff::export::ExportOptions;
+
+let d = Document::from_file("test.sffx").unwrap();
+let path = d.save_with(Format::ZLIB, "autosave_example", &ExportOptions::autosave()).unwrap();
+assert_eq!(Document::from_file(path.to_str().unwrap()).unwrap().to_xml(), d.to_xml());
+# std::fs::remove_file(path).unwrap();
+```"#)]
+    pub fn autosave() -> Self {
+        Self { compression: CompressionLevel::Fast, ..Default::default() }
+    }
+
+    /// Options for archives: the smallest files.
+    pub fn archive() -> Self {
+        Self { compression: CompressionLevel::Best, ..Default::default() }
+    }
+
+    /// Sets [`ExportOptions::layout`].
+    pub fn with_layout(mut self, layout: TextLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets [`ExportOptions::filter`].
+    pub fn with_filter(mut self, filter: ExportFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets [`ExportOptions::handoff`].
+    pub fn with_handoff(mut self, handoff: Handoff) -> Self {
+        self.handoff = Some(handoff);
+        self
+    }
+
+    /// Sets [`ExportOptions::image_compression`].
+    pub fn with_image_compression(mut self, compression: ImageCompression) -> Self {
+        self.image_compression = Some(compression);
+        self
+    }
+
+    /// Sets [`ExportOptions::ids`].
+    pub fn with_ids(mut self, ids: bool) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Sets [`ExportOptions::wrap`].
+    pub fn with_wrap(mut self, width: usize) -> Self {
+        self.wrap = Some(width);
+        self
+    }
+
+    /// Sets [`ExportOptions::compression`].
+    pub fn with_compression(mut self, compression: CompressionLevel) -> Self {
+        self.compression = compression;
+        self
+    }
+}
+
+/// Exactly what data is lost when the document is exported to `format`.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::balloon::Balloon;
+/// use rsff::consts::Format;
+///
+/// let mut d = Document::default();
+/// let mut b = Balloon::default();
+/// b.tl_content.push("num".to_string());
+/// b.comments.push("nam".to_string());
+/// d.balloons.push(b);
+///
+/// let report = d.export_report(Format::TXT);
+/// if !report.is_lossless() {
+///     // "You will lose: metadata, 1 comment(s)"
+///     println!("You will lose: {report}");
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportReport {
+    pub format: Format,
+    /// Empty for lossless exports.
+    pub dropped: Vec<Dropped>,
+}
+
+impl ExportReport {
+    /// `true` if nothing was dropped.
+    pub fn is_lossless(&self) -> bool {
+        self.dropped.is_empty()
+    }
+}
+
+impl fmt::Display for ExportReport {
+    /// Comma separated list of the dropped data.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self.dropped
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+
+        write!(f, "{}", items.join(", "))
+    }
+}
+
+impl Document {
+    /// Computes what exporting to `format` would drop, without exporting.
+    /// Use it to show an accurate "you will lose X" dialog before saving.
+    pub fn export_report(&self, format: Format) -> ExportReport {
+        let mut dropped: Vec<Dropped> = Vec::new();
+
+        if !format.is_lossless() {
+            dropped.push(Dropped::Metadata);
+
+            // Csv keeps every line of every track
+            if format == Format::TXT {
+                let comments: usize = self.balloons
+                    .iter()
+                    .map(|b| b.comments.len())
+                    .sum();
+                if comments > 0 {
+                    dropped.push(Dropped::Comments(comments));
+                }
+
+                // Lossy text output only writes the effective track (pr, then tlc, then tl).
+                let tl_lines: usize = self.balloons
+                    .iter()
+                    .filter(|b| !b.pr_content.is_empty() || !b.tlc_content.is_empty())
+                    .map(|b| b.tl_content.len())
+                    .sum();
+                if tl_lines > 0 {
+                    dropped.push(Dropped::TlLines(tl_lines));
+                }
+
+                let tlc_lines: usize = self.balloons
+                    .iter()
+                    .filter(|b| !b.pr_content.is_empty())
+                    .map(|b| b.tlc_content.len())
+                    .sum();
+                if tlc_lines > 0 {
+                    dropped.push(Dropped::TlcLines(tlc_lines));
+                }
+            }
+
+            let notes = self.note_count();
+            if notes > 0 {
+                dropped.push(Dropped::Notes(notes));
+            }
+
+            let regions = self.region_comment_count();
+            if regions > 0 {
+                dropped.push(Dropped::RegionComments(regions));
+            }
+
+            if !self.checkpoints.is_empty() {
+                dropped.push(Dropped::Checkpoints(self.checkpoints.len()));
+            }
+
+            // Scripts show speakers, but txt and csv files can't be read back with them
+            let speakers = self.balloons
+                .iter()
+                .filter(|b| b.speaker.is_some())
+                .count();
+            if speakers > 0 {
+                dropped.push(Dropped::Speakers(speakers));
+            }
+        }
+
+        if !format.supports_images() {
+            let images = self.balloons
+                .iter()
+                .filter(|b| b.balloon_img.is_some())
+                .count();
+            if images > 0 {
+                dropped.push(Dropped::Images(images));
+            }
+        }
+
+        ExportReport { format, dropped }
+    }
+
+    /// Copy of the document without the parts `filter` leaves out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::export::ExportFilter;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Hi.".to_string());
+    /// b.comments.push("too casual?".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let public = d.filtered(&ExportFilter::transcript());
+    /// assert!(public.balloons[0].comments.is_empty());
+    /// assert_eq!(public.balloons[0].tl_content, d.balloons[0].tl_content);
+    /// ```
+    pub fn filtered(&self, filter: &ExportFilter) -> Document {
+        let mut d = self.clone();
+
+        for b in d.balloons.iter_mut() {
+            if !filter.tl {
+                b.tl_content.clear();
+                b.tlc_content.clear();
+                b.provenance.retain(|(t, _), _| *t == Track::PR);
+            }
+            if !filter.pr {
+                b.pr_content.clear();
+                b.provenance.retain(|(t, _), _| *t != Track::PR);
+            }
+            if !filter.comments {
+                b.comments.clear();
+            }
+            if !filter.images {
+                b.balloon_img = None;
+                b.image_history.clear();
+            }
+            if !filter.speakers {
+                b.speaker = None;
+            }
+        }
+
+        if !filter.comments {
+            for p in d.pages.iter_mut() {
+                p.regions.clear();
+            }
+        }
+
+        if !filter.notes {
+            for p in d.pages.iter_mut() {
+                p.notes.clear();
+                for panel in p.panels.iter_mut() {
+                    panel.notes.clear();
+                }
+            }
+        }
+
+        d
+    }
+
+    /// Generates the text of a txt export, leaving out what `opts.filter` excludes
+    /// and starting with the handoff note selected by `opts.handoff`.
+    /// `TextLayout::Balloons` with the default filter gives the same text as `to_string`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::export::{ExportOptions, TextLayout};
+    ///
+    /// let mut d = Document::default();
+    /// for (speaker, line) in [("Num", "Hi."), ("Num", "Anyone?"), ("Nam", "Here.")] {
+    ///     let mut b = Balloon::default();
+    ///     b.speaker = Some(speaker.to_string());
+    ///     b.tl_content.push(line.to_string());
+    ///     d.balloons.push(b);
+    /// }
+    ///
+    /// let opts = ExportOptions::default().with_layout(TextLayout::Script);
+    /// assert_eq!(d.to_text(&opts), "Num:\nHi.\nAnyone?\n\nNam:\nHere.");
+    /// ```
+    pub fn to_text(&self, opts: &ExportOptions) -> String {
+        let mut d = self.filtered(&opts.filter);
+        if let Some(width) = opts.wrap {
+            for b in d.balloons.iter_mut() {
+                for lines in [&mut b.tl_content, &mut b.tlc_content, &mut b.pr_content] {
+                    if !lines.is_empty() {
+                        *lines = wrap_preview(&lines.join("\n"), width);
+                    }
+                }
+            }
+        }
+        let text = match opts.layout {
+            TextLayout::Balloons if opts.ids => d.to_string_with_ids(),
+            TextLayout::Balloons => d.to_string(),
+            TextLayout::Script => d.to_script(),
+            TextLayout::ByType => d.to_text_by_type()
+        };
+
+        match opts.handoff.and_then(|h| self.handoff_note(h)) {
+            Some(note) => {
+                let header: Vec<String> = note.lines().map(|l| format!("// {l}")).collect();
+                format!("{}\n\n{}", header.join("\n"), text)
+            },
+            None => text
+        }
+    }
+
+    // Balloons grouped by type, with id markers.
+    fn to_text_by_type(&self) -> String {
+        let mut groups: Vec<String> = Vec::new();
+
+        for t in TYPES::all() {
+            let balloons: Vec<String> = self.balloons
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.btype == *t)
+                .map(|(i, b)| format!("{ID_MARKER}{i}]\n{b}"))
+                .collect();
+
+            if !balloons.is_empty() {
+                groups.push(format!("// {} ({})\n{}", t.xml_name(), balloons.len(), balloons.join("\n\n")));
+            }
+        }
+
+        groups.join("\n\n")
+    }
+
+    // Consecutive balloons of the same speaker form one block.
+    fn to_script(&self) -> String {
+        let mut blocks: Vec<(&str, Vec<&str>)> = Vec::new();
+
+        for b in &self.balloons {
+            let speaker = b.speaker.as_deref().unwrap_or(UNKNOWN_SPEAKER);
+            let lines = b.effective_lines().iter().map(String::as_str);
+
+            match blocks.last_mut() {
+                Some((s, block)) if *s == speaker => block.extend(lines),
+                _ => blocks.push((speaker, lines.collect()))
+            }
+        }
+
+        blocks
+            .iter()
+            .map(|(speaker, lines)| format!("{}:\n{}", speaker, lines.join("\n")))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::{Dropped, ExportFilter, ExportOptions, TextLayout};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, TYPES, Track};
+
+    fn doc() -> Document {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("num".to_string());
+        b.tl_content.push("nam".to_string());
+        b.pr_content.push("numnam".to_string());
+        b.comments.push("num".to_string());
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        d.balloons.push(b);
+        d
+    }
+
+    #[test]
+    fn export_report_lossless() {
+        let r = doc().export_report(Format::ZLIB);
+        assert!(r.is_lossless());
+    }
+
+    #[test]
+    fn export_report_txt() {
+        let r = doc().export_report(Format::TXT);
+        assert_eq!(
+            r.dropped,
+            vec![Dropped::Metadata, Dropped::Comments(1), Dropped::TlLines(2), Dropped::Images(1)]
+        );
+        assert_eq!(
+            r.to_string(),
+            "metadata, 1 comment(s), 2 tl line(s) replaced by tlc/pr, 1 image(s)"
+        );
+    }
+
+    #[test]
+    fn export_report_csv() {
+        let r = doc().export_report(Format::CSV);
+        assert_eq!(r.dropped, vec![Dropped::Metadata, Dropped::Images(1)]);
+    }
+
+    #[test]
+    fn export_script_groups_speakers() {
+        let mut d = doc();
+        let mut b = crate::balloon::Balloon::default();
+        b.tl_content.push("nam".to_string());
+        d.balloons.push(b);
+        d.balloons[0].speaker = Some("Num".to_string());
+
+        let opts = ExportOptions { layout: TextLayout::Script, ..Default::default() };
+        assert_eq!(d.to_text(&opts), "Num:\nnumnam\n\n???:\nnam");
+        assert_eq!(d.to_text(&ExportOptions::default()), d.to_string());
+        assert!(d.export_report(Format::TXT).dropped.contains(&Dropped::Speakers(1)));
+    }
+
+    #[test]
+    fn export_groups_by_type() {
+        let mut d = Document::default();
+        for (t, text) in [(TYPES::SFX, "a"), (TYPES::DIALOGUE, "b"), (TYPES::SFX, "c")] {
+            d.balloons.push(Balloon::new(t).with_line(Track::TL, text));
+        }
+
+        let opts = ExportOptions { layout: TextLayout::ByType, ..Default::default() };
+        let txt = d.to_text(&opts);
+        assert_eq!(txt, "// Dialogue (1)\n#[id:1]\n(): b\n\n// SFX (2)\n#[id:0]\nFX: a\n\n#[id:2]\nFX: c");
+
+        let back = d.rematch_txt(&txt).unwrap();
+        let tl: Vec<&str> = back.balloons.iter().map(|b| b.tl_content[0].as_str()).collect();
+        assert_eq!(tl, ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn export_filter_applies_to_text() {
+        let mut d = doc();
+        d.balloons[0].speaker = Some("Num".to_string());
+
+        let opts = ExportOptions {
+            layout: TextLayout::Script,
+            filter: ExportFilter { pr: false, speakers: false, ..ExportFilter::transcript() },
+            ..Default::default()
+        };
+        assert_eq!(d.to_text(&opts), "???:\nnum\nnam");
+
+        let filtered = d.filtered(&opts.filter);
+        assert!(filtered.balloons[0].balloon_img.is_none());
+        assert_eq!(filtered.export_report(Format::TXT).dropped, vec![Dropped::Metadata]);
+    }
+}
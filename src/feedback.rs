@@ -0,0 +1,172 @@
+//! Comment-only round trips with external reviewers.
+//!
+//! [`Document::export_comments`] gives a reviewer numbered balloons with the text that ends
+//! up on the page and nothing else: no images, no comments or notes of the team. Their
+//! answers come back through [`Document::apply_comment_feedback`] as comments on the right
+//! balloons. Balloon numbers are 1-based, as shown to readers.
+//!
+//! In txt, a balloon is a `#N` line followed by its text, indented, and a `>` line where
+//! the reviewer writes. More `>` lines can be added. In csv, a balloon is a row with
+//! `number`, `text` and an empty `comment` column.
+
+use crate::{Document, Error, XMLConvertResult};
+use crate::consts::Format;
+use crate::csv::{parse_rows, quote_cell};
+
+/// Start of comments added by [`Document::apply_comment_feedback`].
+pub const FEEDBACK_PREFIX: &str = "Review: ";
+
+// Balloon number and comment lines of a txt feedback file.
+fn txt_feedback(txt: &str) -> XMLConvertResult<Vec<(usize, String)>> {
+    let mut feedback: Vec<(usize, String)> = Vec::new();
+    let mut number: Option<usize> = None;
+
+    for line in txt.lines() {
+        if let Some(n) = line.strip_prefix('#') {
+            number = Some(n.trim().parse()?);
+        } else if let Some(comment) = line.strip_prefix('>') {
+            let n = number.ok_or("Comment before the first balloon!")?;
+            feedback.push((n, comment.trim().to_string()));
+        }
+    }
+
+    Ok(feedback)
+}
+
+// Balloon number and comment lines of a csv feedback file.
+fn csv_feedback(csv: &str) -> XMLConvertResult<Vec<(usize, String)>> {
+    let mut rows = parse_rows(csv, ',')?.into_iter();
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|c| c.trim().eq_ignore_ascii_case(name));
+    let (number, comment) = match (column("number"), column("comment")) {
+        (Some(n), Some(c)) => (n, c),
+        _ => return Err("Missing number or comment column!".into())
+    };
+
+    let mut feedback: Vec<(usize, String)> = Vec::new();
+    for row in rows {
+        let cell = |i: usize| row.get(i).map(|c| c.trim()).unwrap_or("");
+        if cell(comment).is_empty() {
+            continue;
+        }
+
+        let n = cell(number).parse()?;
+        feedback.extend(cell(comment).lines().map(|l| (n, l.trim().to_string())));
+    }
+
+    Ok(feedback)
+}
+
+impl Document {
+    /// Generates the file for an external reviewer in `format`, txt or csv.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::{Format, Track};
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Hi").with_comment("internal"));
+    ///
+    /// let txt = d.export_comments(Format::TXT).unwrap();
+    /// assert_eq!(txt, "#1\n  Hi\n>\n");
+    ///
+    /// let added = d.apply_comment_feedback_text("#1\n  Hi\n> Too formal?\n", Format::TXT).unwrap();
+    /// assert_eq!(added, 1);
+    /// assert_eq!(d.balloons[0].comments, ["internal", "Review: Too formal?"]);
+    /// ```
+    pub fn export_comments(&self, format: Format) -> XMLConvertResult<String> {
+        let mut out = String::new();
+
+        match format {
+            Format::TXT => {
+                let blocks: Vec<String> = self.balloons
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| {
+                        let text: String = b.effective_lines().iter().map(|l| format!("  {l}\n")).collect();
+                        format!("#{}\n{}>\n", i + 1, text)
+                    })
+                    .collect();
+                out.push_str(&blocks.join("\n"));
+            },
+            Format::CSV => {
+                out.push_str("number,text,comment\n");
+                for (i, b) in self.balloons.iter().enumerate() {
+                    out.push_str(format!("{},{},\n", i + 1, quote_cell(&b.effective_lines().join("\n"), ',')).as_str());
+                }
+            },
+            f => return Err(Error::UnsupportedExtension(f.extension().to_string()))
+        }
+
+        Ok(out)
+    }
+
+    /// Adds the comments of a reviewer's file made by [`Document::export_comments`] to the
+    /// balloons, starting with [`FEEDBACK_PREFIX`]. Comments a balloon already has are skipped,
+    /// so applying the same file twice changes nothing.
+    ///
+    /// Returns the number of added comments. Fails on numbers of balloons the document doesn't have.
+    pub fn apply_comment_feedback_text(&mut self, feedback: &str, format: Format) -> XMLConvertResult<usize> {
+        let feedback = match format {
+            Format::TXT => txt_feedback(feedback)?,
+            Format::CSV => csv_feedback(feedback)?,
+            f => return Err(Error::UnsupportedExtension(f.extension().to_string()))
+        };
+
+        if let Some((n, _)) = feedback.iter().find(|(n, _)| *n == 0 || *n > self.balloons.len()) {
+            return Err(format!("No balloon #{n}!").into());
+        }
+
+        let mut added = 0;
+        for (n, comment) in feedback.into_iter().filter(|(_, c)| !c.is_empty()) {
+            let comment = format!("{FEEDBACK_PREFIX}{comment}");
+            let comments = &mut self.balloons[n - 1].comments;
+            if !comments.contains(&comment) {
+                comments.push(comment);
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Opens a reviewer's txt or csv file and adds its comments, see
+    /// [`Document::apply_comment_feedback_text`].
+    #[cfg(feature = "std")]
+    pub fn apply_comment_feedback(&mut self, fp: &str) -> XMLConvertResult<usize> {
+        let format = crate::io::format_of(std::path::Path::new(fp))?;
+        self.apply_comment_feedback_text(&std::fs::read_to_string(fp)?, format)
+    }
+}
+
+#[cfg(test)]
+mod feedback_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    #[test]
+    fn feedback_csv_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "a, \"b\"").with_line(Track::TL, "c"));
+        d.balloons.push(Balloon::default().with_line(Track::PR, "d"));
+        let mut b = Balloon::default().with_line(Track::TL, "e");
+        b.add_image("png".to_string(), vec![1]);
+        d.balloons.push(b);
+
+        let csv = d.export_comments(Format::CSV).unwrap();
+        assert_eq!(csv, "number,text,comment\n1,\"a, \"\"b\"\"\nc\",\n2,d,\n3,e,\n");
+
+        let reviewed = csv.replace("2,d,", "2,d,\"typo\nand \"\"spacing\"\"\"");
+        assert_eq!(d.apply_comment_feedback_text(&reviewed, Format::CSV).unwrap(), 2);
+        assert_eq!(d.apply_comment_feedback_text(&reviewed, Format::CSV).unwrap(), 0);
+        assert_eq!(d.balloons[1].comments, ["Review: typo", "Review: and \"spacing\""]);
+
+        assert!(d.apply_comment_feedback_text("#4\n> ?", Format::TXT).is_err());
+        assert!(d.export_comments(Format::RAW).is_err());
+        assert_eq!(d.export_comments(Format::TXT).unwrap(), "#1\n  a, \"b\"\n  c\n>\n\n#2\n  d\n>\n\n#3\n  e\n>\n");
+    }
+}
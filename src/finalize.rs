@@ -0,0 +1,233 @@
+//! Frozen "final text" for typesetting.
+//!
+//! [`Document::finalize`] resolves the text that goes on the pages and locks the
+//! document. Edits made after that are rejected by [`Document::edit_balloon`] and
+//! reported by [`Document::late_edits`], so they don't silently miss the typeset pages.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+
+use std::fmt;
+
+/// One balloon of the final text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinalBalloon {
+    /// Index of the balloon in the document.
+    pub index: usize,
+    pub btype: TYPES,
+    pub page: Option<u32>,
+    pub panel: Option<u32>,
+    /// Effective text, whitespace normalized, one line per line break.
+    pub text: String,
+}
+
+/// The typesetting view produced by [`Document::finalize`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinalText {
+    balloons: Vec<FinalBalloon>,
+}
+
+impl FinalText {
+    /// Balloons in document order, including those without text.
+    pub fn balloons(&self) -> &[FinalBalloon] {
+        &self.balloons
+    }
+}
+
+/// Marks a finalized document: the balloon hashes at the time of the lock.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FinalLock {
+    hashes: Vec<u64>,
+}
+
+impl FinalLock {
+    /// Generates an xml string of the lock.
+    pub fn to_xml(&self) -> String {
+        let hashes: Vec<String> = self.hashes
+            .iter()
+            .map(|h| format!("{:016x}", h))
+            .collect();
+
+        format!("<Final balloons=\"{}\"/>", hashes.join(","))
+    }
+
+    // Parse a <Final> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<FinalLock> {
+        let mut hashes: Vec<u64> = Vec::new();
+
+        for h in node.attribute("balloons").unwrap_or("").split(',').filter(|h| !h.is_empty()) {
+            hashes.push(u64::from_str_radix(h, 16)?);
+        }
+
+        Ok(FinalLock { hashes })
+    }
+}
+
+/// Returned by [`Document::edit_balloon`] when the document is finalized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locked;
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "document is finalized, unlock it before editing")
+    }
+}
+
+impl std::error::Error for Locked {}
+
+// Collapse whitespace runs and drop empty lines.
+fn normalize(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|l| l.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl Document {
+    /// Resolves the final text (pr over tl, whitespace normalized) and locks the document.
+    /// Finalizing again refreshes the lock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("num".to_string());
+    /// b.pr_content.push("  Num,   nam! ".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let text = d.finalize();
+    /// assert_eq!(text.balloons()[0].text, "Num, nam!");
+    ///
+    /// // Late edits have to be explicit.
+    /// assert!(d.edit_balloon(0, |b| b.pr_content.clear()).is_err());
+    /// d.unlock();
+    /// assert!(d.edit_balloon(0, |b| b.pr_content.clear()).is_ok());
+    /// ```
+    pub fn finalize(&mut self) -> FinalText {
+        self.lock = Some(FinalLock { hashes: self.balloon_hashes() });
+
+        self.final_text()
+    }
+
+    /// The current text in the same form as [`Document::finalize`], without locking.
+    pub fn final_text(&self) -> FinalText {
+        let balloons = self.balloons
+            .iter()
+            .enumerate()
+            .map(|(index, b)| FinalBalloon {
+                index,
+                btype: b.btype.clone(),
+                page: b.page,
+                panel: b.panel,
+                text: normalize(b.effective_lines())
+            })
+            .collect();
+
+        FinalText { balloons }
+    }
+
+    /// `true` if the document was finalized and not unlocked since.
+    pub fn is_final(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Removes the lock set by [`Document::finalize`].
+    pub fn unlock(&mut self) {
+        self.lock = None;
+    }
+
+    /// Edits balloon `index` with `f`, unless the document is finalized.
+    /// Returns `Ok(false)` if there is no such balloon.
+    pub fn edit_balloon<F: FnOnce(&mut Balloon)>(&mut self, index: usize, f: F) -> Result<bool, Locked> {
+        if self.is_final() {
+            return Err(Locked);
+        }
+
+        match self.balloons.get_mut(index) {
+            None => Ok(false),
+            Some(b) => {
+                f(b);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Indices of balloons changed, added or removed since the document was finalized,
+    /// e.g. by writing the fields directly. Empty if the document is not finalized.
+    pub fn late_edits(&self) -> Vec<usize> {
+        let lock = match &self.lock {
+            None => return Vec::new(),
+            Some(l) => l
+        };
+
+        let hashes = self.balloon_hashes();
+
+        (0..hashes.len().max(lock.hashes.len()))
+            .filter(|&i| hashes.get(i) != lock.hashes.get(i))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod finalize_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn balloon(text: &str) -> Balloon {
+        let mut b = Balloon::default();
+        b.tl_content.push(text.to_string());
+        b
+    }
+
+    #[test]
+    fn finalize_normalizes_text() {
+        let mut d = Document::default();
+        let mut b = balloon("a");
+        b.tl_content.push("   ".to_string());
+        b.tl_content.push("b\t\tc".to_string());
+        d.balloons.push(b);
+
+        assert_eq!(d.finalize().balloons()[0].text, "a\nb c");
+    }
+
+    #[test]
+    fn finalize_reports_late_edits() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+        d.finalize();
+
+        assert!(d.late_edits().is_empty());
+
+        d.balloons[1].pr_content.push("B".to_string());
+        d.balloons.push(balloon("c"));
+        assert_eq!(d.late_edits(), vec![1, 2]);
+
+        // The lock survives saving
+        let parsed = Document::from_xml(&d.to_xml()).unwrap();
+        assert!(parsed.is_final());
+        assert_eq!(parsed.late_edits(), vec![1, 2]);
+
+        d.unlock();
+        assert!(d.late_edits().is_empty());
+    }
+
+    #[test]
+    fn finalize_lock_survives_whitespace_in_balloons() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("one\r\ntwo"));
+        d.balloons[0].speaker = Some("Ann\tMarie".to_string());
+        d.finalize();
+
+        let parsed = Document::from_xml(&d.to_xml()).unwrap();
+        assert!(parsed.late_edits().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+//! Does the text fit in its balloon?
+//!
+//! Given font metrics and the balloon's [`Balloon::bbox`], estimates the largest
+//! point size at which the effective text fits, so "too long for bubble" warnings
+//! are grounded in the actual geometry instead of a character count.
+//!
+//! All lengths are in page pixels; a point size of 20 means 20px tall glyphs.
+
+use crate::Document;
+use crate::balloon::Balloon;
+
+use std::collections::HashMap;
+
+/// Advance widths of a font, relative to its point size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontMetrics {
+    /// Width of each known character, e.g. `0.5` for a glyph half as wide as it is tall.
+    pub widths: HashMap<char, f32>,
+    /// Width of characters missing from `widths`.
+    pub default_width: f32,
+    /// Distance between baselines, relative to the point size.
+    pub line_height: f32,
+}
+
+impl FontMetrics {
+    /// Metrics where every character has the same width.
+    pub fn monospace(width: f32) -> Self {
+        Self { widths: HashMap::new(), default_width: width, line_height: 1.2 }
+    }
+
+    fn char_width(&self, c: char) -> f32 {
+        *self.widths.get(&c).unwrap_or(&self.default_width)
+    }
+
+    /// Width of `s` at point size `size`.
+    pub fn text_width(&self, s: &str, size: f32) -> f32 {
+        s.chars().map(|c| self.char_width(c)).sum::<f32>() * size
+    }
+}
+
+/// Limits of the search for a point size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FitOptions {
+    /// Smallest size the typesetter accepts.
+    pub min_size: f32,
+    /// Size tried first.
+    pub max_size: f32,
+    /// Space kept free on every side of the balloon area.
+    pub padding: f32,
+}
+
+impl Default for FitOptions {
+    /// ```notrust
+    /// min_size: 12.0,
+    /// max_size: 32.0,
+    /// padding: 4.0
+    /// ```
+    fn default() -> Self {
+        Self { min_size: 12.0, max_size: 32.0, padding: 4.0 }
+    }
+}
+
+/// Result of fitting a balloon's text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fit {
+    /// Largest size that fits, with the text wrapped at that size.
+    Fits { size: f32, lines: Vec<String> },
+    /// Does not fit even at `FitOptions::min_size`.
+    TooLong,
+}
+
+impl Fit {
+    pub fn fits(&self) -> bool {
+        matches!(self, Fit::Fits { .. })
+    }
+}
+
+// Greedy word wrap. `None` if a single word is wider than `width`.
+fn wrap(text: &[String], metrics: &FontMetrics, size: f32, width: f32) -> Option<Vec<String>> {
+    let mut lines: Vec<String> = Vec::new();
+
+    // Explicit line breaks are kept
+    for line in text {
+        let mut current = String::new();
+
+        for word in line.split_whitespace() {
+            if metrics.text_width(word, size) > width {
+                return None;
+            }
+
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{current} {word}")
+            };
+
+            if metrics.text_width(&candidate, size) <= width {
+                current = candidate;
+            } else {
+                lines.push(current);
+                current = word.to_string();
+            }
+        }
+
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+
+    Some(lines)
+}
+
+impl Balloon {
+    /// Fits the effective text into the balloon's area.
+    /// `None` if the balloon has no [`Balloon::bbox`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::Balloon;
+    /// use rsff::fit::{FitOptions, FontMetrics};
+    /// use rsff::layout::BBox;
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Num nam".to_string());
+    /// b.bbox = Some(BBox { x: 0, y: 0, w: 100, h: 60 });
+    ///
+    /// let fit = b.fit(&FontMetrics::monospace(0.6), &FitOptions::default()).unwrap();
+    /// assert!(fit.fits());
+    /// ```
+    pub fn fit(&self, metrics: &FontMetrics, opts: &FitOptions) -> Option<Fit> {
+        let bbox = self.bbox?;
+        let width = bbox.w as f32 - 2.0 * opts.padding;
+        let height = bbox.h as f32 - 2.0 * opts.padding;
+
+        // Half point steps, largest first
+        let mut size = opts.max_size;
+        while size >= opts.min_size {
+            if let Some(lines) = wrap(self.effective_lines(), metrics, size, width) {
+                if lines.len() as f32 * size * metrics.line_height <= height {
+                    return Some(Fit::Fits { size, lines });
+                }
+            }
+            size -= 0.5;
+        }
+
+        Some(Fit::TooLong)
+    }
+}
+
+impl Document {
+    /// Indices of balloons whose text does not fit their area. Balloons without an area are skipped.
+    pub fn too_long(&self, metrics: &FontMetrics, opts: &FitOptions) -> Vec<usize> {
+        self.balloons
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.fit(metrics, opts) == Some(Fit::TooLong))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod fit_tests {
+    use super::{Fit, FitOptions, FontMetrics};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::layout::BBox;
+
+    fn balloon(text: &str, w: u32, h: u32) -> Balloon {
+        let mut b = Balloon::default();
+        b.tl_content.push(text.to_string());
+        b.bbox = Some(BBox { x: 0, y: 0, w, h });
+        b
+    }
+
+    #[test]
+    fn fit_picks_largest_size() {
+        let m = FontMetrics::monospace(0.5);
+        let opts = FitOptions { min_size: 10.0, max_size: 20.0, padding: 0.0 };
+
+        // "ab cd" is 50px wide at size 20, a line takes 24px
+        let fit = balloon("ab cd", 50, 25).fit(&m, &opts).unwrap();
+        assert_eq!(fit, Fit::Fits { size: 20.0, lines: vec!["ab cd".to_string()] });
+
+        let fit = balloon("ab cd", 20, 49).fit(&m, &opts).unwrap();
+        assert_eq!(fit, Fit::Fits { size: 20.0, lines: vec!["ab".to_string(), "cd".to_string()] });
+
+        let fit = balloon("ab cd", 20, 25).fit(&m, &opts).unwrap();
+        assert_eq!(fit, Fit::Fits { size: 10.0, lines: vec!["ab".to_string(), "cd".to_string()] });
+    }
+
+    #[test]
+    fn fit_reports_too_long() {
+        let m = FontMetrics::monospace(0.5);
+        let opts = FitOptions::default();
+
+        let mut d = Document::default();
+        d.balloons.push(balloon("Num", 100, 100));
+        d.balloons.push(balloon("Supercalifragilistic", 40, 100));
+        d.balloons.push(Balloon::default());
+
+        assert_eq!(d.too_long(&m, &opts), vec![1]);
+        assert_eq!(d.balloons[2].fit(&m, &opts), None);
+    }
+
+    #[test]
+    fn fit_bbox_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("num", 10, 20));
+
+        let parsed = Document::from_xml(&d.to_xml()).unwrap();
+        assert_eq!(parsed.balloons[0].bbox, Some(BBox { x: 0, y: 0, w: 10, h: 20 }));
+    }
+}
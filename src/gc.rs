@@ -0,0 +1,126 @@
+//! Cleanup of data nobody uses anymore, so long-lived chapter files don't bloat forever.
+
+use crate::Document;
+
+use std::sync::Arc;
+
+/// What [`Document::gc`] removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Number of replaced images dropped from image histories.
+    pub history_removed: usize,
+    /// Raw image bytes no longer referenced by the document. Dropped history images that
+    /// are still the current image of a balloon don't count.
+    /// Bytes still shared with a [`SharedDocument`](crate::shared::SharedDocument) or a clone are only freed when it is dropped.
+    pub bytes_reclaimed: usize,
+}
+
+impl Document {
+    /// Drops the image history of every balloon (see `Balloon::replace_image`): images no
+    /// balloon shows anymore. Current images are always kept, even on balloons without text,
+    /// since that is the crop the translator works from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::{Balloon, BalloonImage};
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.add_image("jpg".to_string(), vec![0; 100]);
+    /// b.replace_image(BalloonImage::new("jpg", vec![1; 50]));
+    /// d.balloons.push(b);
+    ///
+    /// let report = d.gc();
+    /// assert_eq!(report.bytes_reclaimed, 100);
+    /// assert_eq!(d.balloons[0].balloon_img.as_ref().unwrap().data(), [1; 50]);
+    /// ```
+    pub fn gc(&mut self) -> GcReport {
+        let mut report = GcReport::default();
+
+        let dropped: Vec<_> = self.balloons
+            .iter_mut()
+            .flat_map(|b| b.image_history.drain(..))
+            .collect();
+
+        for img in dropped {
+            report.history_removed += 1;
+
+            let in_use = self.balloons
+                .iter()
+                .filter_map(|b| b.balloon_img.as_ref())
+                .any(|current| Arc::ptr_eq(&current.img_data, &img.img_data));
+            if !in_use {
+                report.bytes_reclaimed += img.img_data.len();
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod gc_tests {
+    use crate::Document;
+    use crate::balloon::{Balloon, BalloonImage, IMAGE_HISTORY_LEN};
+
+    #[test]
+    fn gc_keeps_current_images() {
+        let mut d = Document::default();
+
+        let mut with_text = Balloon::default();
+        with_text.comments.push("redraw".to_string());
+        with_text.add_image("jpg".to_string(), vec![0; 10]);
+
+        // Not translated yet, the crop is still needed
+        let mut empty = Balloon::default();
+        empty.add_image("png".to_string(), vec![0; 20]);
+
+        d.balloons.push(with_text);
+        d.balloons.push(empty);
+
+        assert_eq!(d.gc(), Default::default());
+        assert!(d.balloons[0].balloon_img.is_some());
+        assert!(d.balloons[1].balloon_img.is_some());
+    }
+
+    #[test]
+    fn gc_counts_only_unreferenced_bytes() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.add_image("png".to_string(), vec![0; 20]);
+        b.replace_image(BalloonImage::new("png", vec![1; 8]));
+        d.balloons.push(b);
+
+        // The old crop went to another balloon
+        let moved = d.balloons[0].image_history[0].clone();
+        d.balloons.push(Balloon { balloon_img: Some(moved), ..Default::default() });
+
+        let report = d.gc();
+        assert_eq!((report.history_removed, report.bytes_reclaimed), (1, 0));
+        assert_eq!(d.balloons[1].balloon_img.as_ref().unwrap().data(), [0; 20]);
+
+        // Nothing left to collect
+        assert_eq!(d.gc(), Default::default());
+    }
+
+    #[test]
+    fn gc_drops_image_history() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("Hi".to_string());
+        for n in 0..5 {
+            b.replace_image(BalloonImage { img_type: "png".to_string(), img_data: vec![n; 4].into(), compression: None });
+        }
+        assert_eq!(b.image_history.len(), IMAGE_HISTORY_LEN);
+        d.balloons.push(b);
+
+        let reopened = Document::from_xml(&d.to_xml()).unwrap();
+        assert_eq!(reopened.balloons[0].hash(), d.balloons[0].hash());
+
+        let report = d.gc();
+        assert_eq!((report.history_removed, report.bytes_reclaimed), (3, 12));
+        assert_eq!(*d.balloons[0].balloon_img.as_ref().unwrap().img_data, vec![4; 4]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Notes for the next role in the pipeline.
+//!
+//! Instead of a loose `notes.txt` next to the file, the translator leaves a note for the
+//! proofreader and the proofreader one for the typesetter in the document metadata.
+//! Txt exports for a role can start with its note, see [`ExportOptions::handoff`](crate::export::ExportOptions::handoff).
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_text;
+
+use std::collections::BTreeMap;
+
+/// A step where the file goes to the next role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Handoff {
+    /// Translator to proofreader.
+    TlToPr,
+    /// Proofreader to typesetter.
+    PrToTs,
+}
+
+impl Handoff {
+    fn xml_name(&self) -> &'static str {
+        match self {
+            Handoff::TlToPr => "tl-pr",
+            Handoff::PrToTs => "pr-ts"
+        }
+    }
+}
+
+/// Handoff notes of a document, at most one per [`Handoff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandoffNotes {
+    notes: BTreeMap<Handoff, String>,
+}
+
+impl HandoffNotes {
+    /// `true` if there are no notes.
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// The note left for `handoff`.
+    pub fn get(&self, handoff: Handoff) -> Option<&str> {
+        self.notes.get(&handoff).map(String::as_str)
+    }
+
+    /// Sets the note for `handoff`, replacing the previous one. An empty note removes it.
+    pub fn set(&mut self, handoff: Handoff, note: &str) {
+        if note.is_empty() {
+            self.notes.remove(&handoff);
+        } else {
+            self.notes.insert(handoff, note.to_string());
+        }
+    }
+
+    /// Generates an xml string of the notes.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Handoff>");
+
+        for (h, note) in &self.notes {
+            xml.push_str(format!("<Note step=\"{}\">{}</Note>", h.xml_name(), escape_text(note)).as_str());
+        }
+
+        xml.push_str("</Handoff>");
+
+        xml
+    }
+
+    // Parse a <Handoff> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<HandoffNotes> {
+        let mut notes = HandoffNotes::default();
+
+        for n in node.children().filter(|c| c.tag_name().name() == "Note") {
+            let handoff = match n.attribute("step") {
+                Some("tl-pr") => Handoff::TlToPr,
+                Some("pr-ts") => Handoff::PrToTs,
+                _ => return Err("Unknown handoff step!".into())
+            };
+            notes.set(handoff, n.text().unwrap_or(""));
+        }
+
+        Ok(notes)
+    }
+}
+
+impl Document {
+    /// The note left for `handoff`, stored in the metadata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::handoff::Handoff;
+    ///
+    /// let mut d = Document::default();
+    /// d.set_handoff_note(Handoff::TlToPr, "Kept the puns literal, see comments.");
+    ///
+    /// assert_eq!(d.handoff_note(Handoff::TlToPr), Some("Kept the puns literal, see comments."));
+    /// assert_eq!(d.handoff_note(Handoff::PrToTs), None);
+    /// ```
+    pub fn handoff_note(&self, handoff: Handoff) -> Option<&str> {
+        self.handoff.get(handoff)
+    }
+
+    /// Sets the note for `handoff`. An empty note removes it.
+    pub fn set_handoff_note(&mut self, handoff: Handoff, note: &str) {
+        self.handoff.set(handoff, note);
+    }
+}
+
+#[cfg(test)]
+mod handoff_tests {
+    use super::Handoff;
+    use crate::Document;
+    use crate::export::ExportOptions;
+
+    #[test]
+    fn handoff_notes_xml() {
+        let mut d = Document::default();
+        d.set_handoff_note(Handoff::PrToTs, "SFX on p.3 <keep> & redraw");
+        d.set_handoff_note(Handoff::TlToPr, "");
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<Handoff><Note step=\"pr-ts\">SFX on p.3 &lt;keep&gt; &amp; redraw</Note></Handoff></Metadata>"));
+        assert_eq!(Document::from_xml(&xml).unwrap().handoff, d.handoff);
+        assert!(!Document::default().to_xml().contains("<Handoff>"));
+    }
+
+    #[test]
+    fn handoff_note_on_top_of_txt() {
+        let mut d = Document::default();
+        d.balloons.push(crate::balloon::Balloon::default().with_line(crate::consts::Track::PR, "Hi"));
+        d.set_handoff_note(Handoff::PrToTs, "Use the bold font\nfor shouts");
+
+        let opts = ExportOptions { handoff: Some(Handoff::PrToTs), ..Default::default() };
+        let txt = d.to_text(&opts);
+        assert_eq!(txt, "// Use the bold font\n// for shouts\n\n(): Hi");
+        assert_eq!(Document::from_txt(&txt).unwrap().len(), 1);
+
+        // No note for the translator's handoff
+        let opts = ExportOptions { handoff: Some(Handoff::TlToPr), ..Default::default() };
+        assert_eq!(d.to_text(&opts), "(): Hi");
+    }
+}
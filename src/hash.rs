@@ -0,0 +1,107 @@
+//! Small, stable hashing helpers.
+//!
+//! `std`'s `DefaultHasher` may change between Rust releases, so anything that gets
+//! persisted or compared across machines is hashed with FNV-1a instead.
+
+use std::hash::Hasher;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// 64-bit FNV-1a.
+/// Integers are always written little endian, and `usize` as a `u64`, so results are
+/// platform independent.
+pub(crate) struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(FNV_OFFSET)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use std::hash::Hasher;
+
+    use super::Fnv64;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn fnv64_known_value() {
+        let mut h = Fnv64::default();
+        h.write(b"a");
+        assert_eq!(h.finish(), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn fnv64_integers_are_little_endian() {
+        let mut bytes = Fnv64::default();
+        bytes.write(&[1, 0, 0, 0, 2, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut ints = Fnv64::default();
+        ints.write_u32(1);
+        ints.write_u16(2);
+        ints.write_usize(3);
+
+        assert_eq!(ints.finish(), bytes.finish());
+    }
+
+    #[test]
+    fn balloon_hash_detects_changes() {
+        let mut b1 = Balloon::default();
+        b1.tl_content.push("num".to_string());
+        let mut b2 = b1.clone();
+
+        assert_eq!(b1.hash(), b2.hash());
+
+        b2.tl_content[0] = "nam".to_string();
+        assert_ne!(b1.hash(), b2.hash());
+
+        // Same text in another field is another balloon.
+        let mut b3 = Balloon::default();
+        b3.pr_content.push("num".to_string());
+        assert_ne!(b1.hash(), b3.hash());
+    }
+
+    #[test]
+    fn balloon_hash_tags_fields_apart() {
+        use crate::balloon::ImageCompression;
+
+        let mut commented = Balloon::default().with_comment("png");
+        commented.add_image("png".to_string(), vec![1]);
+        let mut compressed = Balloon::default();
+        compressed.add_image("png".to_string(), vec![1]);
+        compressed.balloon_img.as_mut().unwrap().compression = Some(ImageCompression::Png);
+
+        assert_ne!(commented.hash(), compressed.hash());
+    }
+}
@@ -0,0 +1,151 @@
+//! Read-only HTML preview, for sharing a script with people who don't have the app.
+//!
+//! A single self-contained page: balloons as cards with a badge of their type, tl and pr
+//! side by side (tlc in between if there is any), comments below, and balloon images
+//! embedded as data URIs. Export only.
+
+use crate::Document;
+use crate::balloon::{BalloonImage, escape_text};
+use crate::consts::{TYPES, Track};
+
+use base64::Engine as _;
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:960px;margin:2em auto;padding:0 1em;color:#222}\
+.balloon{border:1px solid #ddd;border-radius:6px;padding:.8em;margin:1em 0}\
+.balloon img{max-width:100%;max-height:320px;display:block;margin:.5em 0}\
+.n{font-weight:bold;margin-right:.5em}\
+.badge{border-radius:3px;padding:.1em .5em;font-size:.8em;color:#fff;background:#555}\
+.dialogue{background:#2a7ae2}.square{background:#6b5b95}.st{background:#888}.ot{background:#c77c02}\
+.thinking{background:#2aa198}.narration{background:#8a6d3b}.sfx{background:#d33}\
+.tracks{display:flex;gap:1em}.tracks>div{flex:1}.tracks h4{margin:.3em 0;color:#888}\
+.comments{color:#666;font-size:.9em}";
+
+// Css class of a type's badge.
+fn badge_class(t: &TYPES) -> String {
+    t.xml_name().to_lowercase()
+}
+
+// Data URI of a balloon image. Image types come from the file, only known ones make it
+// into the page.
+fn data_uri(img: &BalloonImage) -> String {
+    let mime = match img.img_type.trim_start_matches('.').to_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream"
+    };
+
+    format!("data:{mime};base64,{}", base64::engine::general_purpose::STANDARD.encode(img.img_data.as_slice()))
+}
+
+impl Document {
+    /// Generates an HTML page of the balloons, see [`crate::html`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::{TYPES, Track};
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::new(TYPES::SFX).with_line(Track::TL, "Boom").with_line(Track::PR, "BOOM"));
+    ///
+    /// let html = d.to_html();
+    /// assert!(html.contains("<span class=\"badge sfx\">SFX</span>"));
+    /// ```
+    pub fn to_html(&self) -> String {
+        let title = if self.METADATA_INFO.is_empty() { "Script" } else { self.METADATA_INFO.as_str() };
+        let mut html = format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title><style>{STYLE}</style></head>\
+            <body><h1>{0}</h1>",
+            escape_text(title)
+        );
+        let mut page: Option<u32> = None;
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            if b.page.is_some() && b.page != page {
+                html.push_str(format!("<h2>Page {}</h2>", b.page.unwrap_or_default()).as_str());
+            }
+            page = b.page;
+
+            html.push_str(format!(
+                "<section class=\"balloon\"><header><span class=\"n\">#{}</span><span class=\"badge {}\">{}</span></header>",
+                i + 1,
+                badge_class(&b.btype),
+                b.btype.xml_name()
+            ).as_str());
+
+            if let Some(img) = &b.balloon_img {
+                html.push_str(format!("<img src=\"{}\" alt=\"Balloon {}\">", data_uri(img), i + 1).as_str());
+            }
+
+            html.push_str("<div class=\"tracks\">");
+            for (track, name) in [(Track::TL, "TL"), (Track::TLC, "TLC"), (Track::PR, "PR")] {
+                let lines = b.lines(track);
+                // Tl and pr always get a column so they line up between balloons
+                if lines.is_empty() && track == Track::TLC {
+                    continue;
+                }
+
+                let text: Vec<String> = lines.iter().map(|l| escape_text(l).into_owned()).collect();
+                html.push_str(format!("<div><h4>{name}</h4><p>{}</p></div>", text.join("<br>")).as_str());
+            }
+            html.push_str("</div>");
+
+            if !b.comments.is_empty() {
+                html.push_str("<ul class=\"comments\">");
+                for c in &b.comments {
+                    html.push_str(format!("<li>{}</li>", escape_text(c)).as_str());
+                }
+                html.push_str("</ul>");
+            }
+
+            html.push_str("</section>");
+        }
+
+        html.push_str("</body></html>");
+
+        html
+    }
+}
+
+#[cfg(test)]
+mod html_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, TYPES, Track};
+
+    #[test]
+    fn html_preview() {
+        let mut b = Balloon::new(TYPES::THINKING).with_line(Track::TL, "<b>a</b>").with_line(Track::TL, "c").with_comment("x & y");
+        b.add_image(".JPG".to_string(), vec![0, 1, 2]);
+        b.page = Some(3);
+
+        let mut d = Document::default();
+        d.balloons.push(b);
+        d.balloons.push(Balloon::default().with_line(Track::TLC, "t"));
+
+        let html = d.to_html();
+        assert!(html.starts_with("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Num</title>"));
+        assert!(html.contains("<h2>Page 3</h2><section class=\"balloon\"><header><span class=\"n\">#1</span><span class=\"badge thinking\">Thinking</span></header>"));
+        assert!(html.contains("<img src=\"data:image/jpeg;base64,AAEC\" alt=\"Balloon 1\">"));
+        assert!(html.contains("<div class=\"tracks\"><div><h4>TL</h4><p>&lt;b&gt;a&lt;/b&gt;<br>c</p></div><div><h4>PR</h4><p></p></div></div>"));
+        assert!(html.contains("<li>x &amp; y</li>"));
+        assert!(html.contains("<div><h4>TL</h4><p></p></div><div><h4>TLC</h4><p>t</p></div>"));
+        assert!(html.ends_with("</section></body></html>"));
+
+        // Image types can't break out of the attribute
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default());
+        d.balloons[0].add_image("png\"><script>alert(1)</script>".to_string(), vec![0]);
+        assert!(d.to_html().contains("<img src=\"data:application/octet-stream;base64,AA==\" alt=\"Balloon 1\">"));
+
+        assert_eq!(Format::from_extension("html"), Some(Format::HTML));
+        assert!(Format::HTML.supports_images() && !Format::HTML.is_lossless());
+    }
+}
@@ -0,0 +1,382 @@
+//! Metadata stripping for balloon images.
+//!
+//! Crops taken straight from phone screenshots carry EXIF/ICC data and an orientation
+//! flag that some viewers ignore. Sanitizing applies the orientation to the pixels and
+//! re-encodes the image without any metadata.
+//!
+//! Pixels are not converted between color spaces. An sRGB profile is dropped, as sRGB is
+//! what viewers assume without one. Any other profile (e.g. Display P3 from phones) is
+//! kept in png and jpeg output, so colors don't shift; other formats with such a profile
+//! are refused.
+//!
+//! Images can also be recompressed to png or jpeg, see [`BalloonImage::recompress`].
+//!
+//! Only compiled with the `image-sanitize` feature.
+
+use crate::Document;
+use crate::balloon::{Balloon, BalloonImage, ImageCompression};
+
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageOutputFormat, ImageResult};
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+
+// Jpeg is re-encoded, keep the quality loss low.
+const JPEG_QUALITY: u8 = 95;
+
+// Read the EXIF orientation tag (1-8) if the image has one.
+fn exif_orientation(data: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()?;
+
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+// The embedded ICC profile, if it isn't sRGB.
+fn color_profile(data: &[u8], format: ImageFormat) -> ImageResult<Option<Vec<u8>>> {
+    let icc = match format {
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data))?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(Cursor::new(data))?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(data))?.icc_profile(),
+        _ => None
+    };
+
+    Ok(icc.filter(|icc| !is_srgb(icc)))
+}
+
+// Whether the description tag of an ICC profile names sRGB, in ascii (v2 `desc`) or
+// UTF-16 (v4 `mluc`).
+fn is_srgb(icc: &[u8]) -> bool {
+    let be = |at: usize| icc.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    let Some(count) = be(128) else { return false };
+    (0..count)
+        .map(|i| 132 + i * 12)
+        .take_while(|at| at + 12 <= icc.len())
+        .find(|at| &icc[*at..at + 4] == b"desc")
+        .and_then(|at| icc.get(be(at + 4)?..be(at + 4)?.checked_add(be(at + 8)?)?))
+        .is_some_and(|desc| desc.windows(4).any(|w| w == b"sRGB") || desc.windows(8).any(|w| w == b"\0s\0R\0G\0B"))
+}
+
+// Adds `icc` to an encoded png (iCCP chunk after IHDR) or jpeg (APP2 segments after SOI).
+// Other formats are refused.
+fn embed_profile(encoded: Vec<u8>, format: &ImageOutputFormat, icc: &[u8]) -> ImageResult<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::with_capacity(encoded.len() + icc.len() + 64);
+
+    match format {
+        ImageOutputFormat::Png => {
+            // Signature (8) and IHDR chunk (4 + 4 + 13 + 4)
+            let ihdr_end = 33;
+            let mut z = ZlibEncoder::new(Vec::from(*b"ICC\0\0"), Compression::default());
+            z.write_all(icc)?;
+            let data = z.finish()?;
+
+            let mut crc = flate2::Crc::new();
+            crc.update(b"iCCP");
+            crc.update(&data);
+
+            out.extend_from_slice(&encoded[..ihdr_end]);
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            out.extend_from_slice(b"iCCP");
+            out.extend_from_slice(&data);
+            out.extend_from_slice(&crc.sum().to_be_bytes());
+            out.extend_from_slice(&encoded[ihdr_end..]);
+        },
+        ImageOutputFormat::Jpeg(_) => {
+            // Segment length (2) and "ICC_PROFILE\0" with the chunk number and count (14)
+            let chunks: Vec<&[u8]> = icc.chunks(u16::MAX as usize - 16).collect();
+
+            out.extend_from_slice(&encoded[..2]);
+            for (i, chunk) in chunks.iter().enumerate() {
+                out.extend_from_slice(&[0xFF, 0xE2]);
+                out.extend_from_slice(&((chunk.len() + 16) as u16).to_be_bytes());
+                out.extend_from_slice(b"ICC_PROFILE\0");
+                out.extend_from_slice(&[i as u8 + 1, chunks.len() as u8]);
+                out.extend_from_slice(chunk);
+            }
+            out.extend_from_slice(&encoded[2..]);
+        },
+        _ => return Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+            ImageFormatHint::Unknown,
+            UnsupportedErrorKind::GenericFeature("non-sRGB color profile".to_string())
+        )))
+    }
+
+    Ok(out)
+}
+
+// Encodes `img`, with the color profile `icc` if there is one.
+fn encode(img: &DynamicImage, out_format: ImageOutputFormat, icc: Option<&[u8]>) -> ImageResult<Vec<u8>> {
+    let mut buff: Vec<u8> = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buff), out_format.clone())?;
+
+    match icc {
+        Some(icc) => embed_profile(buff, &out_format, icc),
+        None => Ok(buff)
+    }
+}
+
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img
+    }
+}
+
+impl BalloonImage {
+    /// Strips EXIF/ICC metadata and normalizes orientation.
+    ///
+    /// The image is decoded, rotated/flipped according to its EXIF orientation and
+    /// re-encoded in the same format. Jpeg images are re-encoded with quality 95.
+    ///
+    /// Pixels are not converted between color spaces. An sRGB profile is dropped, as sRGB is
+    /// what viewers assume without one. Any other profile (e.g. Display P3 from phones) is
+    /// kept, so colors don't shift.
+    ///
+    /// Fails if `img_data` is not an encoded image the `image` crate can read, or has a
+    /// profile other than sRGB and is neither png nor jpeg.
+    pub fn sanitize(&mut self) -> ImageResult<()> {
+        let format = image::guess_format(&self.img_data)?;
+        let decoded = image::load_from_memory_with_format(&self.img_data, format)?;
+        let icc = color_profile(&self.img_data, format)?;
+
+        let oriented = match exif_orientation(&self.img_data) {
+            Some(o) => apply_orientation(decoded, o),
+            None => decoded
+        };
+
+        let out_format = match format {
+            ImageFormat::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+            f => ImageOutputFormat::from(f)
+        };
+
+        self.img_data = Arc::new(encode(&oriented, out_format, icc.as_deref())?);
+
+        Ok(())
+    }
+
+    /// Re-encodes the image following `c` and marks it [`ImageCompression::Original`],
+    /// so saving it again doesn't lose quality again. The type becomes `png` or `jpg`.
+    /// Color profiles are handled like in [`BalloonImage::sanitize`].
+    pub fn recompress(&mut self, c: ImageCompression) -> ImageResult<()> {
+        let (out_format, img_type) = match c {
+            ImageCompression::Original => return Ok(()),
+            ImageCompression::Png => (ImageOutputFormat::Png, "png"),
+            ImageCompression::Jpeg(q) => (ImageOutputFormat::Jpeg(q.clamp(1, 100)), "jpg")
+        };
+
+        let format = image::guess_format(&self.img_data)?;
+        let mut decoded = image::load_from_memory_with_format(&self.img_data, format)?;
+        let icc = color_profile(&self.img_data, format)?;
+        // Jpeg has no alpha channel
+        if img_type == "jpg" {
+            decoded = DynamicImage::ImageRgb8(decoded.to_rgb8());
+        }
+
+        self.img_data = Arc::new(encode(&decoded, out_format, icc.as_deref())?);
+        self.img_type = img_type.to_string();
+        self.compression = Some(ImageCompression::Original);
+
+        Ok(())
+    }
+}
+
+impl Balloon {
+    /// Same as [`Balloon::add_image`], but sanitizes the image first.
+    /// See [`BalloonImage::sanitize`].
+    pub fn add_image_sanitized(&mut self, img_type: String, img_data: Vec<u8>) -> ImageResult<()> {
+        let mut img = BalloonImage {img_type, img_data: Arc::new(img_data), compression: None};
+        img.sanitize()?;
+        self.balloon_img = Some(img);
+
+        Ok(())
+    }
+}
+
+impl Document {
+    // Copy with every image recompressed following its own compression or `default`.
+    // `None` if no image needs it.
+    #[cfg(feature = "std")]
+    pub(crate) fn recompressed(&self, default: Option<ImageCompression>) -> ImageResult<Option<Document>> {
+        let todo = |img: &BalloonImage| {
+            img.compression.or(default).is_some_and(|c| c != ImageCompression::Original)
+        };
+        if !self.balloons.iter().any(|b| b.balloon_img.as_ref().is_some_and(todo)) {
+            return Ok(None);
+        }
+
+        let mut d = self.clone();
+        for img in d.balloons.iter_mut().filter_map(|b| b.balloon_img.as_mut()) {
+            if let Some(c) = img.compression.or(default) {
+                img.recompress(c)?;
+            }
+        }
+
+        Ok(Some(d))
+    }
+
+    /// Sanitizes every balloon image of the document, e.g. right before saving.
+    /// See [`BalloonImage::sanitize`].
+    pub fn sanitize_images(&mut self) -> ImageResult<()> {
+        for b in self.balloons.iter_mut() {
+            if let Some(img) = b.balloon_img.as_mut() {
+                img.sanitize()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod image_sanitize_tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+
+    use image::ImageDecoder;
+    use image::codecs::jpeg::JpegDecoder;
+    use image::codecs::png::PngDecoder;
+
+    use super::{embed_profile, exif_orientation, is_srgb};
+    use crate::balloon::{Balloon, ImageCompression};
+
+    // Minimal ICC profile: empty header and a v2 `desc` tag.
+    fn profile(desc: &str) -> Vec<u8> {
+        let mut icc = vec![0; 128];
+        icc.extend_from_slice(&1u32.to_be_bytes());
+        icc.extend_from_slice(b"desc");
+        icc.extend_from_slice(&144u32.to_be_bytes());
+        icc.extend_from_slice(&(12 + desc.len() as u32).to_be_bytes());
+        icc.extend_from_slice(b"desc\0\0\0\0");
+        icc.extend_from_slice(&(desc.len() as u32).to_be_bytes());
+        icc.extend_from_slice(desc.as_bytes());
+        icc
+    }
+
+    fn png_with_profile(icc: &[u8]) -> Vec<u8> {
+        let mut png: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(2, 1)
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        embed_profile(png, &ImageOutputFormat::Png, icc).unwrap()
+    }
+
+    // 2x1 jpeg with an EXIF APP1 segment saying "rotate 90 degrees clockwise".
+    fn rotated_jpeg() -> Vec<u8> {
+        let mut jpeg: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(2, 1)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(90))
+            .unwrap();
+
+        let tiff: Vec<u8> = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8, // header, big endian, IFD at 8
+            0, 1, // one entry
+            0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, // Orientation, SHORT, 1, value 6
+            0, 0, 0, 0 // no next IFD
+        ];
+        let mut app1: Vec<u8> = vec![0xFF, 0xE1];
+        let len = (2 + 6 + tiff.len()) as u16;
+        app1.extend_from_slice(&len.to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+
+        let mut out = jpeg[0..2].to_vec();
+        out.extend_from_slice(&app1);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    #[test]
+    fn sanitize_applies_orientation_and_strips_exif() {
+        let data = rotated_jpeg();
+        assert_eq!(exif_orientation(&data), Some(6));
+
+        let mut b = Balloon::default();
+        b.add_image_sanitized("jpg".to_string(), data).unwrap();
+
+        let img = b.balloon_img.unwrap();
+        assert_eq!(exif_orientation(&img.img_data), None);
+        assert_eq!(image::load_from_memory(&img.img_data).unwrap().dimensions(), (1, 2));
+    }
+
+    #[test]
+    fn sanitize_keeps_non_srgb_profiles() {
+        let p3 = profile("Display P3");
+        let mut b = Balloon::default();
+        b.add_image_sanitized("png".to_string(), png_with_profile(&p3)).unwrap();
+
+        let img = b.balloon_img.as_mut().unwrap();
+        assert_eq!(PngDecoder::new(Cursor::new(img.data())).unwrap().icc_profile(), Some(p3.clone()));
+
+        img.recompress(ImageCompression::Jpeg(90)).unwrap();
+        assert_eq!(JpegDecoder::new(Cursor::new(img.data())).unwrap().icc_profile(), Some(p3));
+
+        // sRGB is what viewers assume anyway
+        let mut b = Balloon::default();
+        b.add_image_sanitized("png".to_string(), png_with_profile(&profile("sRGB IEC61966-2.1"))).unwrap();
+        let img = b.balloon_img.unwrap();
+        assert_eq!(PngDecoder::new(Cursor::new(img.data())).unwrap().icc_profile(), None);
+    }
+
+    #[test]
+    fn srgb_profile_names() {
+        assert!(is_srgb(&profile("sRGB built-in")));
+        assert!(!is_srgb(&profile("Adobe RGB (1998)")));
+        assert!(!is_srgb(b"short"));
+
+        // v4 profiles name themselves in UTF-16
+        assert!(is_srgb(&profile("\0s\0R\0G\0B")));
+    }
+
+    #[test]
+    fn sanitize_rejects_raw_pixels() {
+        let mut b = Balloon::default();
+        assert!(b.add_image_sanitized("jpg".to_string(), vec![0, 1, 2, 3]).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn save_recompresses_images() {
+        use crate::Document;
+        use crate::consts::Format;
+        use crate::export::ExportOptions;
+
+        let mut d = Document::default();
+        for _ in 0..2 {
+            let mut b = Balloon::default();
+            b.add_image("jpg".to_string(), rotated_jpeg());
+            d.balloons.push(b);
+        }
+        d.balloons[0].balloon_img.as_mut().unwrap().compression = Some(ImageCompression::Original);
+
+        let opts = ExportOptions { image_compression: Some(ImageCompression::Png), ..Default::default() };
+        let path = d.save_with(Format::RAW, "save_recompresses_images", &opts).unwrap();
+        let saved = Document::from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // The cover crop is untouched, the other image became a png
+        let (kept, converted) = (saved.balloons[0].balloon_img.as_ref().unwrap(), saved.balloons[1].balloon_img.as_ref().unwrap());
+        assert_eq!(kept.img_data, d.balloons[0].balloon_img.as_ref().unwrap().img_data);
+        assert_eq!((converted.img_type.as_str(), converted.compression), ("png", Some(ImageCompression::Original)));
+        assert_eq!(image::guess_format(&converted.img_data).unwrap(), image::ImageFormat::Png);
+
+        // The document itself keeps the original bytes
+        assert_eq!(d.balloons[1].balloon_img.as_ref().unwrap().img_type, "jpg");
+    }
+}
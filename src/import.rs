@@ -0,0 +1,152 @@
+//! Importing balloons from another document.
+//!
+//! Imported balloons are appended and imported panels get ids that are free in the
+//! target document. The returned [`ImportMap`] tells where everything went, so
+//! external references (task trackers, QC reports) can be updated.
+
+use crate::Document;
+use crate::layout::Page;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How imported panel ids are chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdPolicy {
+    /// Keep the id if it is free, otherwise use the next free id after the largest one.
+    #[default]
+    KeepIfFree,
+    /// Always use new ids after the largest one, in reading order of the imported pages.
+    Renumber,
+}
+
+/// Where imported balloons and panels ended up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportMap {
+    /// Balloon index in the imported document → index in the target document.
+    pub balloons: BTreeMap<usize, usize>,
+    /// Panel id in the imported document → id in the target document.
+    pub panels: BTreeMap<u32, u32>,
+}
+
+impl Document {
+    /// Appends the balloons, pages and panels of `other`.
+    ///
+    /// Pages with a number the document already has are merged: notes, panels and region
+    /// comments are appended.
+    /// The mapping only depends on both documents and `policy`, so importing the same
+    /// file twice into equal documents gives the same result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::import::IdPolicy;
+    /// use rsff::layout::{Page, Panel};
+    ///
+    /// let mut page = Page::new(1);
+    /// page.panels.push(Panel { id: 1, ..Default::default() });
+    ///
+    /// let mut d = Document::default();
+    /// d.pages.push(page.clone());
+    /// d.balloons.push(Balloon { page: Some(1), panel: Some(1), ..Default::default() });
+    ///
+    /// let other = d.clone();
+    /// let map = d.import(&other, IdPolicy::KeepIfFree);
+    ///
+    /// assert_eq!(map.balloons[&0], 1);
+    /// assert_eq!(map.panels[&1], 2);
+    /// assert_eq!(d.balloons[1].panel, Some(2));
+    /// ```
+    pub fn import(&mut self, other: &Document, policy: IdPolicy) -> ImportMap {
+        let mut map = ImportMap::default();
+
+        let mut taken: BTreeSet<u32> = self.pages
+            .iter()
+            .flat_map(|p| p.panels.iter().map(|panel| panel.id))
+            .collect();
+        let mut next = taken.iter().next_back().map_or(1, |id| id + 1);
+
+        for page in &other.pages {
+            let target = match self.pages.iter().position(|p| p.number == page.number) {
+                Some(i) => &mut self.pages[i],
+                None => {
+                    self.pages.push(Page::new(page.number));
+                    self.pages.last_mut().unwrap()
+                }
+            };
+            target.notes.extend(page.notes.iter().cloned());
+            target.regions.extend(page.regions.iter().cloned());
+
+            for panel in &page.panels {
+                let id = if policy == IdPolicy::KeepIfFree && !taken.contains(&panel.id) {
+                    panel.id
+                } else {
+                    next
+                };
+                taken.insert(id);
+                next = next.max(id + 1);
+                map.panels.insert(panel.id, id);
+
+                let mut panel = panel.clone();
+                panel.id = id;
+                target.panels.push(panel);
+            }
+        }
+        self.pages.sort_by_key(|p| p.number);
+
+        for (i, b) in other.balloons.iter().enumerate() {
+            let mut b = b.clone();
+            b.panel = b.panel.map(|id| *map.panels.get(&id).unwrap_or(&id));
+
+            map.balloons.insert(i, self.balloons.len());
+            self.balloons.push(b);
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::IdPolicy;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::layout::{Page, Panel};
+
+    fn doc(page: u32, panels: &[u32]) -> Document {
+        let mut p = Page::new(page);
+        for id in panels {
+            p.panels.push(Panel { id: *id, ..Default::default() });
+        }
+
+        let mut d = Document::default();
+        d.pages.push(p);
+        for id in panels {
+            d.balloons.push(Balloon { page: Some(page), panel: Some(*id), ..Default::default() });
+        }
+        d
+    }
+
+    #[test]
+    fn import_keeps_free_ids() {
+        let mut d = doc(1, &[1, 2]);
+        let map = d.import(&doc(2, &[2, 5]), IdPolicy::KeepIfFree);
+
+        assert_eq!(map.panels.into_iter().collect::<Vec<_>>(), vec![(2, 3), (5, 5)]);
+        assert_eq!(d.pages.len(), 2);
+        assert_eq!(d.balloons[2].panel, Some(3));
+        assert_eq!(d.balloons[3].panel, Some(5));
+    }
+
+    #[test]
+    fn import_renumbers() {
+        let mut d = doc(1, &[1]);
+        let map = d.import(&doc(1, &[7, 1]), IdPolicy::Renumber);
+
+        assert_eq!(map.panels.into_iter().collect::<Vec<_>>(), vec![(1, 3), (7, 2)]);
+        assert_eq!(d.pages.len(), 1);
+        assert_eq!(d.pages[0].panels.len(), 3);
+        assert_eq!(map.balloons.into_iter().collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Reading the metadata of a file without opening the whole document.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::XMLConvertResult;
+use crate::consts::Format;
+use crate::io::format_of;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+
+const METADATA_END: &[u8] = b"</Metadata>";
+
+/// What [`inspect`] found out about a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileInfo {
+    pub format: Format,
+    /// Size of the file in bytes.
+    pub byte_size: u64,
+    /// `true` for zlib (and zstd) compressed files.
+    pub compressed: bool,
+    /// Script version, app version and info. `None` for txt and csv files, which have no metadata.
+    pub script_version: Option<String>,
+    pub app_version: Option<String>,
+    pub info: Option<String>,
+    /// Balloon count stored in the metadata. `None` for txt and csv files.
+    pub balloon_count: Option<usize>,
+}
+
+// Read from `r` until the end of the metadata section.
+fn read_metadata(mut r: impl Read) -> XMLConvertResult<String> {
+    let mut buff: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let n = r.read(&mut chunk)?;
+        if n == 0 {
+            return Err("No metadata found!".into());
+        }
+
+        // Only search the new bytes, plus enough of the old ones for a split tag
+        let from = buff.len().saturating_sub(METADATA_END.len());
+        buff.extend_from_slice(&chunk[..n]);
+
+        if let Some(pos) = buff[from..].windows(METADATA_END.len()).position(|w| w == METADATA_END) {
+            buff.truncate(from + pos + METADATA_END.len());
+            break;
+        }
+    }
+
+    let mut xml = String::from_utf8(buff)?;
+    xml.push_str("</Document>");
+
+    Ok(xml)
+}
+
+/// Reads the format and metadata of the file at `fp` without parsing balloons or decoding images.
+/// Meant for file browser listings.
+///
+/// # Examples
+///
+/// ```
+/// let info = rsff::inspect("test.sffz").unwrap();
+///
+/// assert!(info.compressed);
+/// assert_eq!(info.balloon_count, Some(2));
+/// ```
+pub fn inspect(fp: &str) -> XMLConvertResult<FileInfo> {
+    let p = Path::new(fp);
+    let format = format_of(p)?;
+    let f = File::open(p)?;
+    let byte_size = f.metadata()?.len();
+
+    inspect_reader(format, byte_size, f)
+}
+
+// Inspect a file in `format` of `byte_size` bytes, read from `r`.
+pub(crate) fn inspect_reader(format: Format, byte_size: u64, r: impl Read) -> XMLConvertResult<FileInfo> {
+    let mut info = FileInfo {
+        format,
+        byte_size,
+        compressed: format == Format::ZLIB,
+        script_version: None,
+        app_version: None,
+        info: None,
+        balloon_count: None,
+    };
+
+    let xml = match format {
+        Format::TXT | Format::CSV | Format::MARKDOWN | Format::HTML | Format::ASS => return Ok(info),
+        #[cfg(feature = "xlsx")]
+        Format::XLSX => return Ok(info),
+        #[cfg(feature = "odt")]
+        Format::ODT => return Ok(info),
+        Format::RAW => read_metadata(r)?,
+        Format::ZLIB => read_metadata(ZlibDecoder::new(r))?,
+        #[cfg(feature = "zstd")]
+        Format::ZSTD => {
+            info.compressed = true;
+            read_metadata(zstd::Decoder::new(r)?)?
+        },
+        // No header to stop after, the whole document is read
+        #[cfg(feature = "json")]
+        Format::JSON => {
+            let d: crate::Document = serde_json::from_reader(r)?;
+            info.script_version = Some(d.METADATA_SCRIPT_VERSION);
+            info.app_version = Some(d.METADATA_APP_VERSION);
+            info.info = Some(d.METADATA_INFO);
+            info.balloon_count = Some(d.balloons.len());
+            return Ok(info);
+        },
+        // The document record, then one line per balloon
+        #[cfg(feature = "json")]
+        Format::JSONL => {
+            let mut lines = std::io::BufRead::lines(std::io::BufReader::new(r));
+            let head = lines.next().ok_or("No document record found!")??;
+            let d: crate::Document = serde_json::from_str(&head)?;
+            info.script_version = Some(d.METADATA_SCRIPT_VERSION);
+            info.app_version = Some(d.METADATA_APP_VERSION);
+            info.info = Some(d.METADATA_INFO);
+            let mut count = 0;
+            for l in lines {
+                if !l?.trim().is_empty() {
+                    count += 1;
+                }
+            }
+            info.balloon_count = Some(count);
+            return Ok(info);
+        }
+    };
+
+    let tree = roxmltree::Document::parse(&xml)?;
+    let md = tree.root_element()
+        .children()
+        .find(|c| c.tag_name().name() == "Metadata")
+        .ok_or("No metadata found!")?;
+    let text = |name: &str| {
+        md.children()
+            .find(|c| c.tag_name().name() == name)
+            .map(|c| c.text().unwrap_or("").to_string())
+    };
+
+    info.script_version = text("Script");
+    info.app_version = text("App");
+    info.info = text("Info");
+    info.balloon_count = text("BalloonCount").map(|c| c.parse()).transpose()?;
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod inspect_tests {
+    use super::{inspect, read_metadata};
+    use crate::consts::Format;
+
+    #[test]
+    fn inspect_raw_file() {
+        let info = inspect("test.sffx").unwrap();
+
+        assert_eq!(info.format, Format::RAW);
+        assert!(!info.compressed);
+        assert_eq!(info.script_version.as_deref(), Some("Scanlation Script File v0.2.0"));
+        assert_eq!(info.balloon_count, Some(2));
+
+        assert!(inspect("test.txt").unwrap().balloon_count.is_none());
+        assert!(inspect("nope.sffx").is_err());
+    }
+
+    #[test]
+    fn inspect_stops_after_metadata() {
+        // The rest is never read, so it doesn't have to be valid
+        let xml = read_metadata(&b"<Document><Metadata><Info>a</Info></Metadata><Balloons><Bal"[..]).unwrap();
+        assert_eq!(xml, "<Document><Metadata><Info>a</Info></Metadata></Document>");
+
+        assert!(read_metadata(&b"<Document><Metadata>"[..]).is_err());
+    }
+}
@@ -0,0 +1,713 @@
+//! Filesystem and compression side of [`Document`](crate::Document).
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Document, Error, XMLConvertResult};
+use crate::consts::Format;
+use crate::export::{CompressionLevel, ExportFilter, ExportOptions};
+use crate::metrics::{self, Op};
+use crate::rematch;
+
+use std::ffi::OsStr;
+use std::io::{self, BufWriter, Write, Read};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use flate2::write::ZlibEncoder;
+use flate2::read::ZlibDecoder;
+use flate2::Compression;
+
+// Xml of the raw content of a sffx or sffz file.
+pub(crate) fn bytes_to_xml(format: Format, bytes: Vec<u8>) -> XMLConvertResult<String> {
+    match format {
+        Format::ZLIB => {
+            let mut xml = String::new();
+            let mut decoder = ZlibDecoder::new(&*bytes);
+            decoder.read_to_string(&mut xml).map_err(Error::Decompression)?;
+            Ok(xml)
+        },
+        #[cfg(feature = "zstd")]
+        Format::ZSTD => {
+            let xml = zstd::decode_all(&*bytes).map_err(Error::Decompression)?;
+            Ok(String::from_utf8(xml)?)
+        },
+        Format::RAW => Ok(String::from_utf8(bytes)?),
+        Format::TXT => Err("Text files are not xml!".into()),
+        Format::CSV => Err("Csv files are not xml!".into()),
+        Format::MARKDOWN => Err("Markdown files are not xml!".into()),
+        Format::HTML => Err("Html files are not xml!".into()),
+        Format::ASS => Err("Ass files are not xml!".into()),
+        #[cfg(feature = "json")]
+        Format::JSON => Err("JSON files are not xml!".into()),
+        #[cfg(feature = "json")]
+        Format::JSONL => Err("JSON Lines files are not xml!".into()),
+        #[cfg(feature = "xlsx")]
+        Format::XLSX => Err("Xlsx files are not xml!".into()),
+        #[cfg(feature = "odt")]
+        Format::ODT => Err("Odt files are not xml!".into())
+    }
+}
+
+// Reader of the xml in `r`, a sffx, sffz or sffzst file.
+pub(crate) fn xml_reader<'a, R: Read + 'a>(format: Format, r: R) -> XMLConvertResult<Box<dyn Read + 'a>> {
+    match format {
+        Format::RAW => Ok(Box::new(r)),
+        Format::ZLIB => Ok(Box::new(ZlibDecoder::new(r))),
+        #[cfg(feature = "zstd")]
+        Format::ZSTD => Ok(Box::new(zstd::Decoder::new(r)?)),
+        _ => Err(Error::UnsupportedExtension(format.extension().to_string()))
+    }
+}
+
+// Write the xml written by `xml` to `w` as a sffx, sffz or sffzst file.
+pub(crate) fn write_xml_as<W, F>(format: Format, level: CompressionLevel, mut w: W, xml: F) -> XMLConvertResult<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> XMLConvertResult<()>
+{
+    match format {
+        Format::RAW => xml(&mut w),
+        Format::ZLIB => {
+            let mut enc = ZlibEncoder::new(w, Compression::new(level.zlib()));
+            xml(&mut enc)?;
+            enc.finish()?;
+            Ok(())
+        },
+        #[cfg(feature = "zstd")]
+        Format::ZSTD => {
+            let mut enc = zstd::Encoder::new(w, level.zstd())?;
+            xml(&mut enc)?;
+            enc.finish()?;
+            Ok(())
+        },
+        _ => Err(Error::UnsupportedExtension(format.extension().to_string()))
+    }
+}
+
+// Type headers of balloon lines in txt files.
+const TXT_HEADERS: [&str; 7] = ["():", "OT:", "[]:", "ST:", "{}:", "NR:", "FX:"];
+
+// `true` if `text` has balloon lines and nothing but balloon lines, `//` lines and id markers.
+fn looks_like_txt(text: &str) -> bool {
+    let mut lines = text.lines()
+        .map(|l| l.trim_end_matches('\r'))
+        .filter(|l| !l.trim().is_empty())
+        .filter(|l| !l.starts_with("//") && rematch::parse_marker(l).is_none())
+        .peekable();
+
+    lines.peek().is_some() && lines.all(|l| TXT_HEADERS.iter().any(|h| l.starts_with(h)))
+}
+
+// `true` if the first line of `text` is a whole json object and more lines follow.
+// Json documents are written on one line, or start with a lone `{` when pretty printed.
+#[cfg(feature = "json")]
+fn looks_like_jsonl(text: &[u8]) -> bool {
+    let mut lines = text.split(|b| *b == b'\n').filter(|l| !l.trim_ascii().is_empty());
+
+    lines.next().is_some_and(|l| l.trim_ascii().len() > 1 && l.trim_ascii().ends_with(b"}")) && lines.next().is_some()
+}
+
+// Format of the content of a file, from its first bytes: sffx, sffz, sffzst (`zstd`),
+// json or json lines (`json`), or txt if it only has balloon lines.
+pub(crate) fn sniff_format(bytes: &[u8]) -> Option<Format> {
+    #[cfg(feature = "zstd")]
+    if bytes.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        return Some(Format::ZSTD);
+    }
+
+    // Zlib header: deflate method, and the first two bytes are a multiple of 31
+    if let [0x78, flags, ..] = bytes {
+        if (0x7800 | *flags as u16).is_multiple_of(31) {
+            return Some(Format::ZLIB);
+        }
+    }
+
+    let text = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match text.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'<') => Some(Format::RAW),
+        #[cfg(feature = "json")]
+        Some(b'{') if looks_like_jsonl(text) => Some(Format::JSONL),
+        #[cfg(feature = "json")]
+        Some(b'{') => Some(Format::JSON),
+        _ => std::str::from_utf8(text).is_ok_and(looks_like_txt).then_some(Format::TXT)
+    }
+}
+
+// Format of a file path, from its extension.
+pub(crate) fn format_of(p: &Path) -> XMLConvertResult<Format> {
+    let ext = p.extension()
+        .and_then(OsStr::to_str)
+        .ok_or(Error::MissingExtension)?;
+
+    Format::from_extension(ext).ok_or_else(|| Error::UnsupportedExtension(ext.to_string()))
+}
+
+// Write `bytes` to a temporary file next to `path`, then rename it over `path`.
+// Readers see either the old or the new file, never a half-written one.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> XMLConvertResult<()> {
+    write_atomic_with(path, |f| Ok(f.write_all(bytes)?))
+}
+
+// Numbers the temporary files of this process, so threads saving the same path don't
+// write into each other's file.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Like `write_atomic`, with the content streamed into the file by `content`.
+pub(crate) fn write_atomic_with<F>(path: &Path, content: F) -> XMLConvertResult<()>
+where
+    F: FnOnce(&mut dyn Write) -> XMLConvertResult<()>
+{
+    let name = path.file_name()
+        .and_then(OsStr::to_str)
+        .ok_or("Invalid file name!")?;
+    let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_file_name(format!(".{name}.{}.{n}.tmp", std::process::id()));
+
+    let write = || -> XMLConvertResult<()> {
+        let mut f = BufWriter::new(File::create(&tmp)?);
+        content(&mut f)?;
+        let f = f.into_inner().map_err(io::IntoInnerError::into_error)?;
+        f.sync_all()?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    };
+
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+impl Document {
+    // Compress the xml of the document.
+    pub(crate) fn to_zlib_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        write_xml_as(Format::ZLIB, CompressionLevel::Best, &mut bytes, |w| self.write_xml(w)).unwrap();
+        bytes
+    }
+
+    /// Save your document as raw xml, compressed xml or .txt file.
+    /// `fp` is the path without extension, the extension of `out_type` is added.
+    /// 
+    /// The file is written to a temporary file in the same directory first and then renamed
+    /// over the target, so a crash while saving never leaves a half-written file behind.
+    /// 
+    /// Returns the path of the written file. Counted in the [`metrics`](crate::metrics).
+    /// Use [`Document::export_report`](crate::Document::export_report) before saving to know
+    /// what a lossy `out_type` drops.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    /// 
+    /// let d = Document::default();
+    /// 
+    /// // Save as raw xml:
+    /// let path = d.save(Format::RAW, "raw_xml").unwrap();
+    /// assert_eq!(path.to_str(), Some("raw_xml.sffx"));
+    /// 
+    /// // Save as ZLIB compressed xml:
+    /// d.save(Format::ZLIB, "compressed_xml").unwrap();
+    /// 
+    /// // Save as raw text:
+    /// d.save(Format::TXT, "raw_text").unwrap();
+    /// ```
+    pub fn save(&self, out_type: Format, fp: &str) -> XMLConvertResult<PathBuf> {
+        self.save_with(out_type, fp, &ExportOptions::default())
+    }
+
+    /// [`Document::save`] with export options, e.g. a script layout for txt files
+    /// or an [`ExportFilter`](crate::export::ExportFilter) applied to every format.
+    ///
+    /// Balloon images are recompressed following their own
+    /// [`compression`](crate::balloon::BalloonImage::compression) or `opts.image_compression`
+    /// (`image-sanitize` feature only). The document itself keeps the original images.
+    ///
+    /// To know what a lossy format drops on top of the filter, use
+    /// `d.filtered(&opts.filter).export_report(out_type)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    /// use rsff::export::{ExportFilter, ExportOptions, TextLayout};
+    ///
+    /// let d = Document::default();
+    ///
+    /// // Script for voice actors
+    /// d.save_with(Format::TXT, "dub_script", &ExportOptions::default().with_layout(TextLayout::Script)).unwrap();
+    /// # std::fs::remove_file("dub_script.txt").unwrap();
+    ///
+    /// // Public copy without comments, images and notes
+    /// let opts = ExportOptions::default().with_filter(ExportFilter::transcript());
+    /// d.save_with(Format::ZLIB, "public_copy", &opts).unwrap();
+    /// # std::fs::remove_file("public_copy.sffz").unwrap();
+    /// ```
+    pub fn save_with(&self, out_type: Format, fp: &str, opts: &ExportOptions) -> XMLConvertResult<PathBuf> {
+        let started = Instant::now();
+        let path = self.write_file(out_type, fp, opts);
+        metrics::record(Op::Save, Some(out_type), started, path.is_ok());
+
+        path
+    }
+
+    /// Writes the document in `out` format to `w` instead of a file, e.g. an http response
+    /// body or an upload stream. Sffx, sffz and sffzst are streamed, see [`Document::write_xml`].
+    ///
+    /// Counted in the [`metrics`](crate::metrics) as a save.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    ///
+    /// let d = Document::from_file("test.sffx").unwrap();
+    ///
+    /// let mut body: Vec<u8> = Vec::new();
+    /// d.write_to(&mut body, Format::ZLIB).unwrap();
+    /// assert_eq!(Document::from_bytes(&body).unwrap().to_xml(), d.to_xml());
+    /// ```
+    pub fn write_to<W: Write>(&self, w: W, out: Format) -> XMLConvertResult<()> {
+        self.write_to_with(w, out, &ExportOptions::default())
+    }
+
+    /// [`Document::write_to`] with export options, see [`Document::save_with`].
+    pub fn write_to_with<W: Write>(&self, mut w: W, out: Format, opts: &ExportOptions) -> XMLConvertResult<()> {
+        let started = Instant::now();
+        let written = self.write_export(out, &mut w, opts);
+        metrics::record(Op::Save, Some(out), started, written.is_ok());
+
+        written
+    }
+
+    // Save without counting it in the metrics.
+    fn write_file(&self, out_type: Format, fp: &str, opts: &ExportOptions) -> XMLConvertResult<PathBuf> {
+        let path = PathBuf::from(format!("{fp}.{}", out_type.extension()));
+        write_atomic_with(&path, |f| self.write_export(out_type, f, opts))?;
+
+        Ok(path)
+    }
+
+    // Write the export to `w` without counting it in the metrics.
+    fn write_export(&self, out_type: Format, w: &mut dyn Write, opts: &ExportOptions) -> XMLConvertResult<()> {
+        let filtered;
+        let d = if opts.filter == ExportFilter::default() {
+            self
+        } else {
+            filtered = self.filtered(&opts.filter);
+            &filtered
+        };
+
+        #[cfg(feature = "image-sanitize")]
+        let recompressed = d.recompressed(opts.image_compression)?;
+        #[cfg(feature = "image-sanitize")]
+        let d = recompressed.as_ref().unwrap_or(d);
+
+        // Xml is streamed, images and all
+        let bytes = match out_type {
+            Format::RAW | Format::ZLIB => return write_xml_as(out_type, opts.compression, w, |x| d.write_xml(x)),
+            #[cfg(feature = "zstd")]
+            Format::ZSTD => return write_xml_as(out_type, opts.compression, w, |x| d.write_xml(x)),
+            #[cfg(feature = "json")]
+            Format::JSONL => return d.write_jsonl(w),
+            Format::TXT => self.to_text(opts).into_bytes(),
+            Format::CSV => d.to_csv(',').into_bytes(),
+            Format::MARKDOWN => d.to_markdown().into_bytes(),
+            Format::HTML => d.to_html().into_bytes(),
+            Format::ASS => d.to_ass().into_bytes(),
+            #[cfg(feature = "json")]
+            Format::JSON => d.to_json()?.into_bytes(),
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => d.to_xlsx()?,
+            #[cfg(feature = "odt")]
+            Format::ODT => self.to_odt(opts)?
+        };
+
+        Ok(w.write_all(&bytes)?)
+    }
+
+    // Open a file and return it's byte content.
+    fn file_to_bytes(p: &Path) -> XMLConvertResult<Vec<u8>> {
+        let mut buff: Vec<u8> = Vec::new();
+        let mut f = File::open(p)?;
+        f.read_to_end(&mut buff)?;
+
+        Ok(buff)
+    }
+
+    // Generate a document from the raw content of a file in `format`.
+    pub(crate) fn bytes_to_doc(format: Format, bytes: Vec<u8>) -> XMLConvertResult<Document> {
+        match format {
+            Format::TXT => Document::from_txt(&String::from_utf8(bytes)?),
+            Format::CSV => Document::from_csv(&String::from_utf8(bytes)?, ','),
+            Format::ASS => Document::from_ass(&String::from_utf8(bytes)?),
+            Format::MARKDOWN | Format::HTML => Err(Error::UnsupportedExtension(format.extension().to_string())),
+            #[cfg(feature = "json")]
+            Format::JSON => Document::from_json(&String::from_utf8(bytes)?),
+            #[cfg(feature = "json")]
+            Format::JSONL => Document::from_jsonl(&String::from_utf8(bytes)?),
+            #[cfg(feature = "xlsx")]
+            Format::XLSX => Err(Error::UnsupportedExtension(format.extension().to_string())),
+            #[cfg(feature = "odt")]
+            Format::ODT => Err(Error::UnsupportedExtension(format.extension().to_string())),
+            _ => Document::from_xml(&bytes_to_xml(format, bytes)?)
+        }
+    }
+
+    /// Open a supported sffx, sffz, txt or csv file (or json with the `json` feature) and generate a document.
+    #[deprecated(note = "use `Document::from_file`")]
+    pub fn open(&mut self, fp: &str) -> XMLConvertResult<Document> {
+        Document::from_file(fp)
+    }
+
+    /// Open a supported sffx, sffz, txt or csv file (or json with the `json` feature) and generate a document.
+    /// 
+    /// `fp`: full path for the file.
+    /// 
+    /// With the `archive` feature, `fp` can also point inside a zip/cbz archive: `archive.cbz!chapter.sffz`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use rsff::Document;
+    /// 
+    /// let d: Document = Document::from_file("test.sffx").unwrap();
+    /// ```
+    ///
+    /// Counted in the [`metrics`](crate::metrics).
+    ///
+    /// Files without a known extension (renamed, downloaded without a name...) are opened
+    /// if their content is recognized, see [`Document::from_bytes`]. Use
+    /// [`Document::from_file_as`] when the content is ambiguous, e.g. for csv files.
+    ///
+    /// Match on [`Error`] to tell a missing file ([`Error::Io`]) from an unsupported
+    /// one ([`Error::UnsupportedExtension`]) or a broken one ([`Error::Parse`]...).
+    pub fn from_file(fp: &str) -> XMLConvertResult<Document> {
+        let started = Instant::now();
+        let d = Document::open_file(fp);
+        metrics::record(Op::Open, format_of(Path::new(fp)).ok(), started, d.is_ok());
+
+        d
+    }
+
+    /// Generates a document from the content of a `format` file read from `r`, e.g. a
+    /// network stream or a database blob, without writing it to a file first.
+    ///
+    /// Counted in the [`metrics`](crate::metrics) like [`Document::from_file`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    ///
+    /// let f = std::fs::File::open("test.sffx").unwrap();
+    /// let d = Document::from_reader(f, Format::RAW).unwrap();
+    /// assert_eq!(d.to_xml(), Document::from_file("test.sffx").unwrap().to_xml());
+    /// ```
+    pub fn from_reader<R: Read>(mut r: R, format: Format) -> XMLConvertResult<Document> {
+        let started = Instant::now();
+        let mut bytes: Vec<u8> = Vec::new();
+        let d = r.read_to_end(&mut bytes)
+            .map_err(Error::Io)
+            .and_then(|_| Document::bytes_to_doc(format, bytes));
+        metrics::record(Op::Open, Some(format), started, d.is_ok());
+
+        d
+    }
+
+    /// Generates a document from the bytes of a sffx, sffz or sffzst file (`zstd`
+    /// feature), json (`json` feature) or txt. The format is detected from the content,
+    /// other formats have to go through [`Document::from_reader`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    ///
+    /// let d = Document::from_file("test.sffx").unwrap();
+    /// let blob = d.to_bytes();
+    ///
+    /// assert_eq!(Document::from_bytes(&blob).unwrap().to_xml(), d.to_xml());
+    /// assert!(Document::from_bytes(b"not a document").is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> XMLConvertResult<Document> {
+        let format = sniff_format(bytes).ok_or("Unknown document format!")?;
+
+        Document::from_reader(bytes, format)
+    }
+
+    /// [`Document::from_file`] with the format of the file given instead of taken from its
+    /// extension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    ///
+    /// std::fs::copy("test.sffx", "open_as_example.bak").unwrap();
+    ///
+    /// let d = Document::from_file_as("open_as_example.bak", Format::RAW).unwrap();
+    /// assert!(!d.is_empty());
+    /// # std::fs::remove_file("open_as_example.bak").unwrap();
+    /// ```
+    pub fn from_file_as(fp: &str, format: Format) -> XMLConvertResult<Document> {
+        let started = Instant::now();
+        let d = Document::file_to_bytes(Path::new(fp))
+            .and_then(|bytes| Document::bytes_to_doc(format, bytes));
+        metrics::record(Op::Open, Some(format), started, d.is_ok());
+
+        d
+    }
+
+    // Open without counting it in the metrics.
+    fn open_file(fp: &str) -> XMLConvertResult<Document> {
+        #[cfg(feature = "archive")]
+        if let Some((archive, entry)) = crate::archive::split_archive_path(fp) {
+            return Document::open_archive_entry(archive, entry);
+        }
+
+        let p = Path::new(fp);
+        let (format, bytes) = match format_of(p) {
+            Ok(format) => (format, Document::file_to_bytes(p)?),
+            // Fall back on the content, or report the extension
+            Err(e) => {
+                let sniffed = Document::file_to_bytes(p)
+                    .ok()
+                    .and_then(|bytes| sniff_format(&bytes).map(|format| (format, bytes)));
+                sniffed.ok_or(e)?
+            }
+        };
+
+        Document::bytes_to_doc(format, bytes)
+    }
+}
+
+#[cfg(test)]
+mod document_io {
+    use std::io::Read;
+    use std::fs::File;
+    use flate2::read::ZlibDecoder;
+
+    use crate::{Document, Error};
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Format, Track};
+    use crate::export::{CompressionLevel, ExportOptions};
+
+    #[test]
+    fn document_to_string() {
+        let mut d = Document::default();
+        let mut b1 = Balloon::default();
+        let mut b2 = Balloon::default();
+
+        b1.tl_content.push(String::from("num"));
+        b1.tl_content.push(String::from("nam"));
+        b1.pr_content.push(String::from("numnam"));
+        b1.btype = TYPES::OT;
+
+        b2.tl_content.push(String::from("num"));
+
+        d.balloons.push(b1);
+        d.balloons.push(b2);
+
+        d.save(Format::TXT, "test").unwrap();
+
+        let mut s = String::new();
+        let mut f = File::open("test.txt").unwrap();
+        f.read_to_string(&mut s).unwrap();
+
+        assert_eq!(
+            s,
+            String::from("OT: numnam\n\n(): num")
+        )
+    }
+
+    #[test]
+    fn document_to_xml() {
+        let mut d = Document::default();
+        let mut b1 = Balloon::default();
+        let mut b2 = Balloon::default();
+
+        b1.tl_content.push(String::from("num"));
+        b1.tl_content.push(String::from("nam"));
+        b1.pr_content.push(String::from("numnam"));
+        b1.btype = TYPES::OT;
+
+        b2.tl_content.push(String::from("num"));
+
+        d.balloons.push(b1);
+        d.balloons.push(b2);
+
+        d.save(Format::RAW, "test").unwrap();
+
+        let num = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
+        let mut created = String::new();
+        let mut f = File::open("test.sffx").unwrap();
+        f.read_to_string(&mut created).unwrap();
+
+        assert_eq!(num, created)
+    }
+
+    #[test]
+    fn document_to_compressed() {
+        let mut d = Document::default();
+        let mut b1 = Balloon::default();
+        let mut b2 = Balloon::default();
+
+        b1.tl_content.push(String::from("num"));
+        b1.tl_content.push(String::from("nam"));
+        b1.pr_content.push(String::from("numnam"));
+        b1.btype = TYPES::OT;
+
+        b2.tl_content.push(String::from("num"));
+
+        d.balloons.push(b1);
+        d.balloons.push(b2);
+
+        d.save(Format::ZLIB, "test").unwrap();
+
+        let num = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
+        let mut created = String::new();
+        let mut f = File::open("test.sffz").unwrap();
+        let mut encoded = Vec::new();
+        f.read_to_end(&mut encoded).unwrap();
+        let mut decoder = ZlibDecoder::new(&*encoded);
+        decoder.read_to_string(&mut created).unwrap();
+
+        assert_eq!(num, created)
+    }
+
+    #[test]
+    fn document_open_txt() {
+        let d = Document::from_file("test.txt").unwrap();
+
+        assert_eq!(d.line_count(), 2);
+        assert_eq!(d.balloons.len(), 2);
+        assert_eq!(d.balloons[0].btype, TYPES::OT);
+        assert_eq!(d.balloons[0].tl_content[0], "numnam");
+        assert_eq!(d.balloons[1].btype, TYPES::DIALOGUE);
+        assert_eq!(d.balloons[1].tl_content[0], "num");
+    }
+
+    #[test]
+    fn document_open_sffx() {
+        let d = Document::from_file("test.sffx").unwrap();
+        let case = r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#;
+        assert_eq!(
+            d.to_xml(),
+            case
+        );
+    }
+
+    #[test]
+    fn document_open_sffz() {
+        let d = Document::from_file("test.sffz").unwrap();
+        let case = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
+        assert_eq!(
+            d.to_xml(),
+            case
+        );
+    }
+
+    #[test]
+    fn document_save_is_atomic() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default());
+        let path = d.save(Format::RAW, "document_save_is_atomic").unwrap();
+
+        // Saving over an existing file replaces it, no temporary file is left behind
+        d.balloons.clear();
+        assert_eq!(d.save(Format::RAW, "document_save_is_atomic").unwrap(), path);
+        assert!(Document::from_file("document_save_is_atomic.sffx").unwrap().is_empty());
+        let tmp = format!(".document_save_is_atomic.sffx.{}.", std::process::id());
+        assert!(!std::fs::read_dir(".").unwrap().any(|e| e.unwrap().file_name().to_string_lossy().starts_with(&tmp)));
+
+        // Threads saving the same file don't share a temporary file
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| d.save(Format::RAW, "document_save_is_atomic").unwrap());
+            }
+        });
+        assert!(Document::from_file("document_save_is_atomic.sffx").unwrap().is_empty());
+
+        // The target directory doesn't exist
+        assert!(matches!(d.save(Format::RAW, "no_such_dir/doc"), Err(Error::Io(_))));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn document_unsupported_file_ext() {
+        assert!(matches!(Document::from_file("test.test"), Err(Error::UnsupportedExtension(e)) if e == "test"));
+        assert!(matches!(Document::from_file("test"), Err(Error::MissingExtension)));
+        assert!(matches!(Document::from_file("missing.sffx"), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn document_open_sniffs_content() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "num"));
+
+        let sffz = d.save(Format::ZLIB, "document_open_sniffs_content").unwrap();
+        std::fs::rename(&sffz, "document_open_sniffs_content").unwrap();
+        assert_eq!(Document::from_file("document_open_sniffs_content").unwrap().to_xml(), d.to_xml());
+
+        std::fs::write("document_open_sniffs_content", d.to_string()).unwrap();
+        assert_eq!(Document::from_file("document_open_sniffs_content").unwrap().len(), 1);
+
+        // Not recognized: the extension error stays
+        std::fs::write("document_open_sniffs_content", "index,tl\n0,num").unwrap();
+        assert!(matches!(Document::from_file("document_open_sniffs_content"), Err(Error::MissingExtension)));
+        assert_eq!(Document::from_file_as("document_open_sniffs_content", Format::CSV).unwrap().len(), 1);
+
+        assert!(!super::looks_like_txt("// note only\n#[id:0]"));
+        assert!(super::looks_like_txt("// note\r\n\r\nFX: Boom\r\n//\r\nFX: Bam"));
+
+        std::fs::remove_file("document_open_sniffs_content").unwrap();
+    }
+
+    #[test]
+    fn document_from_bytes_sniffs_format() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default());
+
+        assert_eq!(super::sniff_format(b"\xEF\xBB\xBF\n<Document>"), Some(Format::RAW));
+        assert_eq!(super::sniff_format(b"xyz"), None);
+
+        let sffz = d.to_zlib_bytes();
+        assert_eq!(super::sniff_format(&sffz), Some(Format::ZLIB));
+        assert_eq!(Document::from_bytes(&sffz).unwrap().len(), 1);
+
+        assert!(Document::from_reader(&b"(): num"[..], Format::TXT).is_ok());
+        assert!(matches!(Document::from_bytes(b""), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn document_save_compression_level() {
+        let mut d = Document::default();
+        for i in 0..50 {
+            d.balloons.push(Balloon::default().with_line(Track::TL, format!("line {i}").as_str()));
+        }
+
+        let stored = ExportOptions { compression: CompressionLevel::Level(0), ..Default::default() };
+        let mut fast: Vec<u8> = Vec::new();
+        d.write_to_with(&mut fast, Format::ZLIB, &stored).unwrap();
+        assert!(fast.len() > d.to_zlib_bytes().len());
+        assert_eq!(Document::from_bytes(&fast).unwrap().to_xml(), d.to_xml());
+
+        #[cfg(feature = "zstd")]
+        for level in [CompressionLevel::Fast, CompressionLevel::Best, CompressionLevel::Level(40)] {
+            let opts = ExportOptions { compression: level, ..Default::default() };
+            let mut zst: Vec<u8> = Vec::new();
+            d.write_to_with(&mut zst, Format::ZSTD, &opts).unwrap();
+            assert_eq!(Document::from_bytes(&zst).unwrap().to_xml(), d.to_xml());
+        }
+    }
+}
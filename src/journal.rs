@@ -0,0 +1,306 @@
+//! Differential autosave.
+//!
+//! Instead of rewriting a whole sffz on every autosave, a [`Journal`] appends
+//! balloon-level change records to a sidecar `.sffj` file. After a crash the
+//! records are replayed on top of the last full save.
+//!
+//! Records are one per line: `H <header>` (everything but the balloons), `S <index> <balloon>`
+//! (set, or push if `index` is the balloon count) and `T <count>` (truncate).
+//! Header and balloon are base64 encoded xml. Every record is an absolute state,
+//! so replaying a journal twice is harmless.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{B64, Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::hash::Fnv64;
+
+use std::fs::{File, OpenOptions};
+use std::hash::Hasher;
+use std::io::{self, Write, Read};
+use std::path::{Path, PathBuf};
+
+use base64::Engine as _;
+
+/// Extension of journal files.
+pub const JOURNAL_EXTENSION: &str = "sffj";
+
+// Everything but the balloons: metadata, pages, checkpoints and the final lock.
+fn header_xml(doc: &Document) -> String {
+    let header = Document {
+        METADATA_SCRIPT_VERSION: doc.METADATA_SCRIPT_VERSION.clone(),
+        METADATA_APP_VERSION: doc.METADATA_APP_VERSION.clone(),
+        METADATA_INFO: doc.METADATA_INFO.clone(),
+        chapter: doc.chapter.clone(),
+        budgets: doc.budgets,
+        settings: doc.settings.clone(),
+        archival: doc.archival,
+        handoff: doc.handoff.clone(),
+        pages: doc.pages.clone(),
+        balloons: Vec::new(),
+        checkpoints: doc.checkpoints.clone(),
+        lock: doc.lock.clone()
+    };
+
+    header.to_xml()
+}
+
+fn header_hash(doc: &Document) -> u64 {
+    let mut h = Fnv64::default();
+    h.write(header_xml(doc).as_bytes());
+    h.finish()
+}
+
+// Hashes that find the balloons to journal. Unlike `Document::balloon_hashes` they
+// include the balloon numbers, so a renumbering is journaled too.
+fn record_hashes(doc: &Document) -> Vec<u64> {
+    doc.balloons.iter().map(|b| b.full_hash()).collect()
+}
+
+fn journal_path(fp: &str) -> PathBuf {
+    PathBuf::from(format!("{fp}.{JOURNAL_EXTENSION}"))
+}
+
+/// Write-ahead journal of a document saved at `fp`.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::balloon::Balloon;
+/// use rsff::consts::Format;
+/// use rsff::journal::Journal;
+///
+/// let mut d = Document::default();
+/// d.save(Format::ZLIB, "journal_example").unwrap();
+/// let mut journal = Journal::create("journal_example", &d).unwrap();
+///
+/// // Cheap autosave: only the new balloon is written.
+/// d.balloons.push(Balloon::default());
+/// assert_eq!(journal.record(&d).unwrap(), 1);
+///
+/// // After a crash:
+/// let mut recovered = Document::from_file("journal_example.sffz").unwrap();
+/// Journal::replay("journal_example", &mut recovered).unwrap();
+/// assert_eq!(recovered.len(), 1);
+/// # std::fs::remove_file("journal_example.sffz").unwrap();
+/// # std::fs::remove_file("journal_example.sffj").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    header: u64,
+    hashes: Vec<u64>,
+}
+
+impl Journal {
+    /// Starts journaling `doc`, which was just saved at `fp` (path without extension, as for `save`).
+    /// An existing journal for `fp` is emptied.
+    pub fn create(fp: &str, doc: &Document) -> io::Result<Journal> {
+        let path = journal_path(fp);
+        File::create(&path)?;
+
+        Ok(Journal { path, header: header_hash(doc), hashes: record_hashes(doc) })
+    }
+
+    /// Path of the sidecar journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends records for everything that changed since the last call and returns how many were written.
+    pub fn record(&mut self, doc: &Document) -> io::Result<usize> {
+        let mut records: Vec<String> = Vec::new();
+
+        let header = header_hash(doc);
+        if header != self.header {
+            records.push(format!("H {}", B64.encode(header_xml(doc))));
+        }
+
+        let hashes = record_hashes(doc);
+        for (i, h) in hashes.iter().enumerate() {
+            if self.hashes.get(i) != Some(h) {
+                records.push(format!("S {} {}", i, B64.encode(doc.balloons[i].to_xml())));
+            }
+        }
+        if hashes.len() < self.hashes.len() {
+            records.push(format!("T {}", hashes.len()));
+        }
+
+        if !records.is_empty() {
+            let mut f = OpenOptions::new().append(true).create(true).open(&self.path)?;
+            f.write_all(format!("{}\n", records.join("\n")).as_bytes())?;
+            f.sync_data()?;
+        }
+
+        self.header = header;
+        self.hashes = hashes;
+
+        Ok(records.len())
+    }
+
+    /// Empties the journal. Call it after a full save of `doc`.
+    pub fn checkpoint(&mut self, doc: &Document) -> io::Result<()> {
+        File::create(&self.path)?;
+        self.header = header_hash(doc);
+        self.hashes = record_hashes(doc);
+
+        Ok(())
+    }
+
+    /// Replays the journal left next to `fp` onto `doc`, freshly opened from `fp`.
+    /// Returns the number of records applied, `0` if there is no journal.
+    ///
+    /// A truncated last record (crash in the middle of an autosave) is ignored.
+    pub fn replay(fp: &str, doc: &mut Document) -> XMLConvertResult<usize> {
+        let path = journal_path(fp);
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let mut text = String::new();
+        File::open(&path)?.read_to_string(&mut text)?;
+
+        let mut applied = 0;
+        let complete = text.ends_with('\n');
+        let lines: Vec<&str> = text.lines().collect();
+
+        for (n, line) in lines.iter().enumerate() {
+            match apply(line, doc) {
+                Ok(()) => applied += 1,
+                Err(_) if n + 1 == lines.len() && !complete => break,
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(applied)
+    }
+}
+
+// Apply a single journal record.
+fn apply(line: &str, doc: &mut Document) -> XMLConvertResult<()> {
+    let mut parts = line.splitn(3, ' ');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("H"), Some(header), None) => {
+            let xml = String::from_utf8(B64.decode(header)?)?;
+            let h = Document::from_xml(&xml)?;
+            doc.METADATA_SCRIPT_VERSION = h.METADATA_SCRIPT_VERSION;
+            doc.METADATA_APP_VERSION = h.METADATA_APP_VERSION;
+            doc.METADATA_INFO = h.METADATA_INFO;
+            doc.chapter = h.chapter;
+            doc.budgets = h.budgets;
+            doc.settings = h.settings;
+            doc.archival = h.archival;
+            doc.handoff = h.handoff;
+            doc.pages = h.pages;
+            doc.checkpoints = h.checkpoints;
+            doc.lock = h.lock;
+        },
+        (Some("S"), Some(i), Some(balloon)) => {
+            let i: usize = i.parse()?;
+            let xml = String::from_utf8(B64.decode(balloon)?)?;
+            let tree = roxmltree::Document::parse(&xml)?;
+            let b = Balloon::from_xml_node(tree.root_element())?;
+
+            if i < doc.balloons.len() {
+                doc.balloons[i] = b;
+            } else if i == doc.balloons.len() {
+                doc.balloons.push(b);
+            } else {
+                return Err(format!("journal sets balloon {i} of {}", doc.balloons.len()).into());
+            }
+        },
+        (Some("T"), Some(n), None) => {
+            doc.balloons.truncate(n.parse()?);
+        },
+        _ => return Err(format!("invalid journal record: {line}").into())
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use super::Journal;
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn balloon(text: &str) -> Balloon {
+        let mut b = Balloon::default();
+        b.tl_content.push(text.to_string());
+        b
+    }
+
+    #[test]
+    fn journal_replays_changes() {
+        let fp = "journal_replays_changes";
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+        d.balloons.push(balloon("c"));
+        let saved = d.clone();
+
+        let mut j = Journal::create(fp, &d).unwrap();
+        assert_eq!(j.record(&d).unwrap(), 0);
+
+        d.balloons[1] = balloon("B");
+        d.balloons.pop();
+        d.METADATA_INFO = "Ch. 2".to_string();
+        assert_eq!(j.record(&d).unwrap(), 3);
+
+        d.balloons.push(balloon("d"));
+        assert_eq!(j.record(&d).unwrap(), 1);
+
+        let mut recovered = saved.clone();
+        assert_eq!(Journal::replay(fp, &mut recovered).unwrap(), 4);
+        assert_eq!(recovered.to_xml(), d.to_xml());
+
+        j.checkpoint(&d).unwrap();
+        let mut untouched = saved.clone();
+        assert_eq!(Journal::replay(fp, &mut untouched).unwrap(), 0);
+
+        std::fs::remove_file(j.path()).unwrap();
+    }
+
+    #[test]
+    fn journal_records_renumbering() {
+        let fp = "journal_records_renumbering";
+        let mut d = Document::default();
+        d.balloons.push(balloon("a"));
+        d.balloons.push(balloon("b"));
+        let saved = d.clone();
+
+        let mut j = Journal::create(fp, &d).unwrap();
+        d.assign_indices();
+        assert_eq!(j.record(&d).unwrap(), 2);
+
+        let mut recovered = saved.clone();
+        Journal::replay(fp, &mut recovered).unwrap();
+        assert_eq!(recovered.to_xml(), d.to_xml());
+
+        std::fs::remove_file(j.path()).unwrap();
+    }
+
+    #[test]
+    fn journal_ignores_torn_last_record() {
+        let fp = "journal_ignores_torn_last_record";
+        let mut d = Document::default();
+        let mut j = Journal::create(fp, &d).unwrap();
+
+        d.balloons.push(balloon("a"));
+        j.record(&d).unwrap();
+
+        let mut f = OpenOptions::new().append(true).open(j.path()).unwrap();
+        f.write_all(b"S 1 PEJhbGxv").unwrap();
+
+        let mut recovered = Document::default();
+        assert_eq!(Journal::replay(fp, &mut recovered).unwrap(), 1);
+        assert_eq!(recovered.len(), 1);
+
+        std::fs::remove_file(j.path()).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! The JSON document format, for web dashboards that handle JSON better than xml.
+//!
+//! Built on the `serde` derives: lossless, with balloon images as base64 strings.
+//!
+//! [JSON Lines](https://jsonlines.org) (`.jsonl`) holds the same data one record per line
+//! for jq, duckdb or dataset tooling: the document without its balloons first, then one
+//! balloon per line.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+
+use serde_json::Value;
+
+impl Document {
+    /// Generates a JSON string of the document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(rsff::consts::Track::TL, "num"));
+    ///
+    /// let json = d.to_json().unwrap();
+    /// assert_eq!(Document::from_json(&json).unwrap().tl_chars(), 3);
+    /// ```
+    pub fn to_json(&self) -> XMLConvertResult<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Generates a document from a JSON string made by [`Document::to_json`].
+    /// Like xml, text is kept as written; call [`Document::sanitize`] on JSON from other
+    /// tools.
+    pub fn from_json(json: &str) -> XMLConvertResult<Document> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Writes the document as JSON Lines to `w`: the document without its balloons on the
+    /// first line, then one balloon per line.
+    pub fn write_jsonl<W: std::io::Write>(&self, mut w: W) -> XMLConvertResult<()> {
+        let mut head = serde_json::to_value(self)?;
+        let balloons = std::mem::replace(&mut head["balloons"], Value::Array(Vec::new()));
+
+        serde_json::to_writer(&mut w, &head)?;
+        w.write_all(b"\n")?;
+
+        if let Value::Array(balloons) = balloons {
+            for b in balloons {
+                serde_json::to_writer(&mut w, &b)?;
+                w.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates a JSON Lines string of the document, see [`Document::write_jsonl`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "num"));
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "nam"));
+    ///
+    /// let jsonl = d.to_jsonl().unwrap();
+    /// assert_eq!(jsonl.lines().count(), 3);
+    /// assert_eq!(Document::from_jsonl(&jsonl).unwrap().tl_chars(), 6);
+    /// ```
+    pub fn to_jsonl(&self) -> XMLConvertResult<String> {
+        let mut buff: Vec<u8> = Vec::new();
+        self.write_jsonl(&mut buff)?;
+
+        Ok(String::from_utf8(buff)?)
+    }
+
+    /// Generates a document from JSON Lines made by [`Document::to_jsonl`]. Blank lines
+    /// are skipped. Text is kept as written, see [`Document::from_json`].
+    pub fn from_jsonl(jsonl: &str) -> XMLConvertResult<Document> {
+        let mut lines = jsonl.lines().filter(|l| !l.trim().is_empty());
+
+        let head = lines.next().ok_or("No document record found!")?;
+        let mut d: Document = serde_json::from_str(head)?;
+        for l in lines {
+            let b: Balloon = serde_json::from_str(l)?;
+            d.balloons.push(b);
+        }
+
+        Ok(d)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod json_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    #[test]
+    fn json_save_and_open() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        b.pr_content.push("Hi & <bye>".to_string());
+        d.balloons.push(b);
+
+        let path = d.save(Format::JSON, "json_save_and_open").unwrap();
+        assert_eq!(path.to_str(), Some("json_save_and_open.json"));
+
+        let back = Document::from_file("json_save_and_open.json").unwrap();
+        let info = crate::inspect("json_save_and_open.json").unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(back.to_xml(), d.to_xml());
+        assert_eq!(info.balloon_count, Some(1));
+        assert!(Format::JSON.is_lossless());
+    }
+
+    #[test]
+    fn jsonl_save_and_open() {
+        let mut d = Document::default();
+        d.chapter.series = Some("Series".to_string());
+        for text in ["one", "two"] {
+            let mut b = Balloon::default();
+            b.tl_content.push(text.to_string());
+            d.balloons.push(b);
+        }
+        d.balloons[1].add_image("png".to_string(), vec![4, 5]);
+
+        let path = d.save(Format::JSONL, "jsonl_save_and_open").unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let head: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        assert_eq!(head["balloons"], serde_json::json!([]));
+        assert_eq!(head["chapter"]["series"], "Series");
+
+        // Opened by extension, or by content without one
+        let back = Document::from_file("jsonl_save_and_open.jsonl").unwrap();
+        let info = crate::inspect("jsonl_save_and_open.jsonl").unwrap();
+        std::fs::rename(&path, "jsonl_save_and_open").unwrap();
+        let sniffed = Document::from_file("jsonl_save_and_open").unwrap();
+        std::fs::remove_file("jsonl_save_and_open").unwrap();
+
+        assert_eq!(back.to_xml(), d.to_xml());
+        assert_eq!(sniffed.to_xml(), d.to_xml());
+        assert_eq!(info.balloon_count, Some(2));
+        assert_eq!(info.info.as_deref(), Some("Num"));
+        assert_eq!(Document::from_jsonl("").unwrap_err().to_string(), "No document record found!");
+    }
+
+    #[test]
+    fn json_keeps_line_breaks() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "line1\r\nline2\tend\n"));
+
+        let json = Document::from_json(&d.to_json().unwrap()).unwrap();
+        let jsonl = Document::from_jsonl(&d.to_jsonl().unwrap()).unwrap();
+
+        assert_eq!(json.balloons[0].tl_content, ["line1\r\nline2\tend\n"]);
+        assert_eq!(jsonl.balloons[0].tl_content, ["line1\r\nline2\tend\n"]);
+    }
+}
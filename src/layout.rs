@@ -0,0 +1,381 @@
+//! Pages and panels.
+//!
+//! Balloons stay in one flat list; each balloon can point at the page (1-based)
+//! and the panel it belongs to. Pages hold their panels in reading order, so
+//! typesetting and QC can be organized per panel.
+//!
+//! Balloons can also carry a stable number on their page,
+//! [`Balloon::order`](crate::balloon::Balloon::order), given by [`Document::assign_indices`].
+//! Unlike positions in the list, it doesn't move when balloons are inserted, so other
+//! tools can keep pointing at "balloon 12 of page 4".
+//!
+//! All of it is optional: documents without pages serialize exactly as before.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_text;
+use crate::region::RegionComment;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A rectangle in page pixel coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BBox {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl BBox {
+    /// Area in square pixels.
+    pub fn area(&self) -> u64 {
+        self.w as u64 * self.h as u64
+    }
+
+    /// Area shared with `other`, in square pixels.
+    pub fn overlap(&self, other: &BBox) -> u64 {
+        // Edges in u64, a box can reach past u32::MAX
+        let span = |a: u32, a_len: u32, b: u32, b_len: u32| {
+            (a as u64 + a_len as u64).min(b as u64 + b_len as u64).saturating_sub(a.max(b) as u64)
+        };
+
+        span(self.x, self.w, other.x, other.w) * span(self.y, self.h, other.y, other.h)
+    }
+}
+
+/// A panel on a page. Balloons refer to it through `Balloon::panel`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Panel {
+    /// Unique in the document.
+    pub id: u32,
+    pub bbox: BBox,
+    /// Instructions that don't belong to any balloon, e.g. "redraw the sign".
+    pub notes: Vec<String>,
+}
+
+impl Panel {
+    /// Generates an xml string of the panel.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!(
+            "<Panel id=\"{}\" x=\"{}\" y=\"{}\" w=\"{}\" h=\"{}\"",
+            self.id, self.bbox.x, self.bbox.y, self.bbox.w, self.bbox.h
+        );
+
+        if self.notes.is_empty() {
+            xml.push_str("/>");
+        } else {
+            xml.push('>');
+            xml.push_str(notes_to_xml(&self.notes).as_str());
+            xml.push_str("</Panel>");
+        }
+
+        xml
+    }
+}
+
+/// A page of the chapter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Page {
+    /// Page number, 1-based. Balloons refer to it through `Balloon::page`.
+    pub number: u32,
+    /// Panels in reading order.
+    pub panels: Vec<Panel>,
+    /// Instructions for the whole page.
+    pub notes: Vec<String>,
+    /// Comments on areas of the page, see [`crate::region`].
+    pub regions: Vec<RegionComment>,
+}
+
+impl Page {
+    /// Creates a page without panels.
+    pub fn new(number: u32) -> Self {
+        Self { number, ..Default::default() }
+    }
+
+    /// Generates an xml string of the page.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!("<Page number=\"{}\">", self.number);
+        xml.push_str(notes_to_xml(&self.notes).as_str());
+
+        for panel in &self.panels {
+            xml.push_str(panel.to_xml().as_str());
+        }
+
+        for region in &self.regions {
+            xml.push_str(region.to_xml().as_str());
+        }
+
+        xml.push_str("</Page>");
+
+        xml
+    }
+}
+
+fn notes_to_xml(notes: &[String]) -> String {
+    notes
+        .iter()
+        .map(|n| format!("<Note>{}</Note>", escape_text(n)))
+        .collect()
+}
+
+fn notes_from_xml(node: roxmltree::Node) -> Vec<String> {
+    node.children()
+        .filter(|c| c.tag_name().name() == "Note")
+        .map(|c| c.text().unwrap_or("").to_string())
+        .collect()
+}
+
+// Parse a numeric attribute if it is there.
+pub(crate) fn num_attr(node: roxmltree::Node, name: &str) -> XMLConvertResult<Option<u32>> {
+    Ok(node.attribute(name).map(str::parse).transpose()?)
+}
+
+// Parse x/y/w/h attributes, `None` if the node has no `w`.
+pub(crate) fn bbox_attr(node: roxmltree::Node) -> XMLConvertResult<Option<BBox>> {
+    if node.attribute("w").is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(BBox {
+        x: num_attr(node, "x")?.unwrap_or(0),
+        y: num_attr(node, "y")?.unwrap_or(0),
+        w: num_attr(node, "w")?.unwrap_or(0),
+        h: num_attr(node, "h")?.unwrap_or(0),
+    }))
+}
+
+// Parse the children of a <Pages> tag.
+pub(crate) fn pages_from_xml(node: roxmltree::Node) -> XMLConvertResult<Vec<Page>> {
+    let mut pages: Vec<Page> = Vec::new();
+
+    for p in node.children().filter(|c| c.tag_name().name() == "Page") {
+        let mut page = Page::new(num_attr(p, "number")?.unwrap_or(0));
+        page.notes = notes_from_xml(p);
+
+        for panel in p.children().filter(|c| c.tag_name().name() == "Panel") {
+            page.panels.push(Panel {
+                id: num_attr(panel, "id")?.unwrap_or(0),
+                bbox: bbox_attr(panel)?.unwrap_or_default(),
+                notes: notes_from_xml(panel),
+            });
+        }
+
+        for region in p.children().filter(|c| c.tag_name().name() == "Region") {
+            page.regions.push(RegionComment::from_xml_node(region)?);
+        }
+
+        pages.push(page);
+    }
+
+    Ok(pages)
+}
+
+impl Document {
+    /// The page with this number, if the document has it.
+    pub fn page(&self, number: u32) -> Option<&Page> {
+        self.pages.iter().find(|p| p.number == number)
+    }
+
+    /// The panel with this id and the number of the page it is on.
+    pub fn panel(&self, id: u32) -> Option<(u32, &Panel)> {
+        self.pages
+            .iter()
+            .find_map(|p| {
+                p.panels
+                    .iter()
+                    .find(|panel| panel.id == id)
+                    .map(|panel| (p.number, panel))
+            })
+    }
+
+    /// Number of page and panel notes.
+    pub fn note_count(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|p| p.notes.len() + p.panels.iter().map(|panel| panel.notes.len()).sum::<usize>())
+            .sum()
+    }
+
+    /// Indices of the balloons in panel `id`, in reading order.
+    pub fn panel_balloons(&self, id: u32) -> Vec<usize> {
+        self.balloons
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.panel == Some(id))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Balloon indices in reading order.
+    ///
+    /// Pages come in ascending number, panels in the order of their page's `panels`,
+    /// and balloons of the same panel in document order. On each page, balloons without
+    /// a panel come after the panels; balloons without a page come last.
+    /// A document without pages is read in document order.
+    pub fn reading_order(&self) -> Vec<usize> {
+        let panel_pos = |page: u32, id: u32| -> usize {
+            self.page(page)
+                .and_then(|p| p.panels.iter().position(|panel| panel.id == id))
+                .unwrap_or(usize::MAX)
+        };
+
+        let mut order: Vec<usize> = (0..self.balloons.len()).collect();
+        order.sort_by_key(|i| {
+            let b = &self.balloons[*i];
+            let page = b.page.unwrap_or(u32::MAX);
+            let panel = match (b.page, b.panel) {
+                (Some(page), Some(id)) => panel_pos(page, id),
+                _ => usize::MAX
+            };
+            (page, panel, *i)
+        });
+
+        order
+    }
+
+    /// Gives every balloon without an [`order`](crate::balloon::Balloon::order) the next
+    /// free number of its page, in [reading order](Document::reading_order), and returns
+    /// how many were numbered. Numbers already given are kept, so they stay valid when
+    /// balloons are inserted; a number used twice on a page (e.g. a copied balloon) is only
+    /// kept by the first balloon. Balloons without a page are numbered together.
+    ///
+    /// A document numbered for the first time is numbered from 1 in reading order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// for _ in 0..3 {
+    ///     d.balloons.push(Balloon { page: Some(4), ..Default::default() });
+    /// }
+    /// assert_eq!(d.assign_indices(), 3);
+    ///
+    /// // A balloon inserted mid-page gets a new number, the others keep theirs
+    /// d.balloons.insert(1, Balloon { page: Some(4), ..Default::default() });
+    /// assert_eq!(d.assign_indices(), 1);
+    /// assert_eq!(d.balloons.iter().map(|b| b.order.unwrap()).collect::<Vec<_>>(), [1, 4, 2, 3]);
+    /// assert_eq!(d.balloon_by_order(Some(4), 2), Some(2));
+    /// ```
+    pub fn assign_indices(&mut self) -> usize {
+        let order = self.reading_order();
+
+        // Highest number and numbers in use, by page
+        let mut used: BTreeMap<Option<u32>, (u32, BTreeSet<u32>)> = BTreeMap::new();
+        let mut pending: Vec<usize> = Vec::new();
+        for i in order {
+            let b = &self.balloons[i];
+            let (max, numbers) = used.entry(b.page).or_default();
+
+            match b.order {
+                Some(n) if numbers.insert(n) => *max = (*max).max(n),
+                _ => pending.push(i)
+            }
+        }
+
+        for i in &pending {
+            let b = &mut self.balloons[*i];
+            let (max, _) = used.entry(b.page).or_default();
+            *max += 1;
+            b.order = Some(*max);
+        }
+
+        pending.len()
+    }
+
+    /// Index of the balloon numbered `order` on `page`, see [`Document::assign_indices`].
+    pub fn balloon_by_order(&self, page: Option<u32>, order: u32) -> Option<usize> {
+        self.balloons
+            .iter()
+            .position(|b| b.page == page && b.order == Some(order))
+    }
+}
+
+#[cfg(test)]
+mod layout_tests {
+    use super::{BBox, Page, Panel};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn doc() -> Document {
+        let mut d = Document::default();
+
+        let mut p1 = Page::new(1);
+        p1.notes.push("Page note".to_string());
+        p1.panels.push(Panel { id: 2, bbox: BBox { x: 0, y: 0, w: 100, h: 50 }, notes: vec!["redraw the sign".to_string()] });
+        p1.panels.push(Panel { id: 1, bbox: BBox { x: 0, y: 50, w: 100, h: 50 }, ..Default::default() });
+        d.pages.push(p1);
+
+        for (page, panel) in [(Some(1), Some(1)), (Some(1), Some(2)), (None, None), (Some(1), None), (Some(1), Some(2))] {
+            d.balloons.push(Balloon { page, panel, ..Default::default() });
+        }
+
+        d
+    }
+
+    #[test]
+    fn layout_reading_order() {
+        assert_eq!(doc().reading_order(), vec![1, 4, 0, 3, 2]);
+        assert_eq!(doc().panel_balloons(2), vec![1, 4]);
+        assert_eq!(doc().panel(1).unwrap().0, 1);
+    }
+
+    #[test]
+    fn bbox_overlap_near_u32_max() {
+        let a = BBox { x: u32::MAX - 10, y: 0, w: 100, h: 2 };
+        let b = BBox { x: u32::MAX - 5, y: 1, w: u32::MAX, h: u32::MAX };
+
+        assert_eq!(a.overlap(&b), 95);
+        assert_eq!(b.overlap(&a), 95);
+        assert_eq!(b.overlap(&b), b.area());
+    }
+
+    #[test]
+    fn layout_xml_round_trip() {
+        let d = doc();
+        let xml = d.to_xml();
+        let parsed = Document::from_xml(&xml).unwrap();
+
+        assert_eq!(parsed.pages, d.pages);
+        assert_eq!(parsed.note_count(), 2);
+        assert_eq!(parsed.balloons[3].page, Some(1));
+        assert_eq!(parsed.balloons[3].panel, None);
+        assert_eq!(parsed.to_xml(), xml);
+    }
+
+    #[test]
+    fn layout_assign_indices() {
+        // Reading order is 1, 4, 0, 3 on page 1, then 2
+        let mut d = doc();
+        assert_eq!(d.assign_indices(), 5);
+        let orders = |d: &Document| d.balloons.iter().map(|b| b.order).collect::<Vec<_>>();
+        assert_eq!(orders(&d), [Some(3), Some(1), Some(1), Some(4), Some(2)]);
+        assert_eq!(d.assign_indices(), 0);
+
+        // Indices are saved
+        let mut d = Document::from_xml(&d.to_xml()).unwrap();
+        assert_eq!(orders(&d), [Some(3), Some(1), Some(1), Some(4), Some(2)]);
+
+        // Inserted and copied balloons get the next free numbers of their page
+        d.balloons.insert(0, Balloon { page: Some(1), panel: Some(2), ..Default::default() });
+        let copy = d.balloons[1].clone();
+        d.balloons.push(copy);
+        assert_eq!(d.assign_indices(), 2);
+        assert_eq!(d.balloons[0].order, Some(5));
+        assert_eq!(d.balloons[6].order, Some(6));
+        assert_eq!(d.balloon_by_order(Some(1), 3), Some(1));
+        assert_eq!(d.balloon_by_order(None, 1), Some(3));
+        assert_eq!(d.balloon_by_order(Some(2), 1), None);
+
+        // Numbering is not an edit
+        let mut d = doc();
+        d.finalize();
+        d.assign_indices();
+        assert!(d.late_edits().is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+//! Opening large documents without loading every balloon.
+//!
+//! [`Document::open_lazy`] reads the metadata, pages and the skeleton of every
+//! balloon right away; text and images of a balloon are only parsed (and images
+//! decoded) when the balloon is first accessed. Opening only runs a tokenizer over the
+//! balloons to find where they are, no tree of the whole file is built.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::io::{bytes_to_xml, format_of};
+use crate::layout::{BBox, bbox_attr, num_attr};
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use xmlparser::{ElementEnd, Token};
+
+/// What is known about a balloon before it is loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalloonSkeleton {
+    pub btype: TYPES,
+    pub page: Option<u32>,
+    pub panel: Option<u32>,
+    pub order: Option<u32>,
+    pub bbox: Option<BBox>,
+    pub has_image: bool,
+}
+
+/// A document whose balloons are loaded on first access.
+///
+/// Can be shared between threads. Two threads loading the same balloon at once may both
+/// parse it; only one result is kept.
+#[derive(Debug)]
+pub struct LazyDocument {
+    xml: String,
+    header: Document,
+    skeletons: Vec<BalloonSkeleton>,
+    // Byte range of every <Balloon> in `xml`
+    ranges: Vec<Range<usize>>,
+    loaded: Vec<OnceLock<Balloon>>,
+}
+
+// A `<Balloon>` found by the scan: byte ranges of its start tag and of the whole element.
+struct Found {
+    start_tag: Range<usize>,
+    range: Range<usize>,
+    has_image: bool,
+}
+
+// Finds `<Balloons>` and the balloons in it with a tokenizer, without building a tree of
+// the file. Returns the range of `<Balloons>` and the balloons.
+fn scan(xml: &str) -> XMLConvertResult<(Range<usize>, Vec<Found>)> {
+    let mut balloons: Option<Range<usize>> = None;
+    let mut found: Vec<Found> = Vec::new();
+
+    // Names of the open elements, and of the element whose start tag is being read
+    let mut open: Vec<&str> = Vec::new();
+    let mut reading: Option<&str> = None;
+
+    // `open` is the path of the element being read or closed
+    let is_balloons = |open: &[&str], name: &str| open.len() == 1 && name == "Balloons";
+    let is_balloon = |open: &[&str], name: &str| open.len() == 2 && open[1] == "Balloons" && name == "Balloon";
+
+    for token in xmlparser::Tokenizer::from(xml) {
+        match token? {
+            Token::ElementStart { local, span, .. } => {
+                let name = local.as_str();
+                if is_balloons(&open, name) {
+                    balloons = Some(span.start()..span.start());
+                } else if is_balloon(&open, name) {
+                    let at = span.start()..span.start();
+                    found.push(Found { start_tag: at.clone(), range: at, has_image: false });
+                } else if name == "img" && open.len() == 3 && is_balloon(&open[..2], open[2]) {
+                    if let Some(b) = found.last_mut() {
+                        b.has_image = true;
+                    }
+                }
+                reading = Some(name);
+            },
+            Token::ElementEnd { end: ElementEnd::Open, span } => {
+                let name = reading.take().ok_or("Malformed xml!")?;
+                if is_balloon(&open, name) {
+                    if let Some(b) = found.last_mut() {
+                        b.start_tag.end = span.end();
+                    }
+                }
+                open.push(name);
+            },
+            Token::ElementEnd { end: ElementEnd::Empty, span } => {
+                let name = reading.take().ok_or("Malformed xml!")?;
+                if is_balloons(&open, name) {
+                    if let Some(r) = balloons.as_mut() {
+                        r.end = span.end();
+                    }
+                } else if is_balloon(&open, name) {
+                    if let Some(b) = found.last_mut() {
+                        b.start_tag.end = span.end();
+                        b.range.end = span.end();
+                    }
+                }
+            },
+            Token::ElementEnd { end: ElementEnd::Close(..), span } => {
+                let name = open.pop().ok_or("Malformed xml!")?;
+                if is_balloons(&open, name) {
+                    if let Some(r) = balloons.as_mut() {
+                        r.end = span.end();
+                    }
+                } else if is_balloon(&open, name) {
+                    if let Some(b) = found.last_mut() {
+                        b.range.end = span.end();
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok((balloons.ok_or("No balloons found!")?, found))
+}
+
+impl LazyDocument {
+    fn parse(xml: String) -> XMLConvertResult<LazyDocument> {
+        let (balloons, found) = scan(&xml)?;
+
+        let mut skeletons: Vec<BalloonSkeleton> = Vec::with_capacity(found.len());
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(found.len());
+        for f in found {
+            // Only the start tag is parsed, the attributes are read the usual way
+            let start_tag = &xml[f.start_tag];
+            let start_tag = if start_tag.ends_with("/>") {
+                start_tag.to_string()
+            } else {
+                format!("{start_tag}</Balloon>")
+            };
+            let tree = roxmltree::Document::parse(&start_tag)?;
+            let c = tree.root_element();
+
+            skeletons.push(BalloonSkeleton {
+                btype: TYPES::from_xml_attrs(c.attribute("type").unwrap_or(""), c.attribute("subtype")),
+                page: num_attr(c, "page")?,
+                panel: num_attr(c, "panel")?,
+                order: num_attr(c, "order")?,
+                bbox: bbox_attr(c)?,
+                has_image: f.has_image,
+            });
+            ranges.push(f.range);
+        }
+
+        // Everything but the balloons, parsed the usual way
+        let rest = format!("{}<Balloons></Balloons>{}", &xml[..balloons.start], &xml[balloons.end..]);
+        let header = Document::from_xml(&rest)?;
+
+        let loaded = ranges.iter().map(|_| OnceLock::new()).collect();
+
+        Ok(LazyDocument { xml, header, skeletons, ranges, loaded })
+    }
+
+    /// Metadata, pages, checkpoints etc. of the document, without balloons.
+    pub fn header(&self) -> &Document {
+        &self.header
+    }
+
+    /// Skeletons of all balloons, in order.
+    pub fn skeletons(&self) -> &[BalloonSkeleton] {
+        &self.skeletons
+    }
+
+    /// Balloon count.
+    pub fn len(&self) -> usize {
+        self.skeletons.len()
+    }
+
+    /// Returns `true` if the document has no balloons.
+    pub fn is_empty(&self) -> bool {
+        self.skeletons.is_empty()
+    }
+
+    /// Number of balloons loaded so far.
+    pub fn loaded(&self) -> usize {
+        self.loaded.iter().filter(|b| b.get().is_some()).count()
+    }
+
+    /// Balloon `index`, loading it if it wasn't yet. `None` if there is no such balloon.
+    pub fn balloon(&self, index: usize) -> Option<XMLConvertResult<&Balloon>> {
+        let cell = self.loaded.get(index)?;
+
+        if cell.get().is_none() {
+            let parsed = roxmltree::Document::parse(&self.xml[self.ranges[index].clone()])
+                .map_err(|e| e.into())
+                .and_then(|tree| Balloon::from_xml_node(tree.root_element()));
+
+            match parsed {
+                Ok(b) => { let _ = cell.set(b); },
+                Err(e) => return Some(Err(e))
+            }
+        }
+
+        cell.get().map(Ok)
+    }
+
+    /// Loads every balloon that isn't loaded yet and returns the full document.
+    pub fn into_document(self) -> XMLConvertResult<Document> {
+        let mut d = self.header.clone();
+
+        for i in 0..self.len() {
+            d.balloons.push(self.balloon(i).unwrap()?.clone());
+        }
+
+        Ok(d)
+    }
+}
+
+impl Document {
+    /// Opens a sffx or sffz file lazily, see [`LazyDocument`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    ///
+    /// let lazy = Document::open_lazy("test.sffz").unwrap();
+    /// assert_eq!(lazy.len(), 2);
+    /// assert_eq!(lazy.loaded(), 0);
+    ///
+    /// let b = lazy.balloon(1).unwrap().unwrap();
+    /// assert_eq!(b.tl_content[0], "num");
+    /// assert_eq!(lazy.loaded(), 1);
+    /// ```
+    pub fn open_lazy(fp: &str) -> XMLConvertResult<LazyDocument> {
+        let format = format_of(Path::new(fp))?;
+
+        LazyDocument::parse(bytes_to_xml(format, fs::read(fp)?)?)
+    }
+}
+
+#[cfg(test)]
+mod lazy_tests {
+    use super::LazyDocument;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::TYPES;
+
+    #[test]
+    fn lazy_matches_full_parse() {
+        let mut d = Document { METADATA_INFO: String::from("Ch. 1"), ..Default::default() };
+        let mut b = Balloon { btype: TYPES::OT, page: Some(3), ..Default::default() };
+        b.tl_content.push("num".to_string());
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        d.balloons.push(b);
+        d.balloons.push(Balloon::default());
+        d.checkpoint("v1");
+
+        let lazy = LazyDocument::parse(d.to_xml()).unwrap();
+        assert_eq!(lazy.header().METADATA_INFO, "Ch. 1");
+        assert_eq!(lazy.header().checkpoints.len(), 1);
+        assert!(lazy.skeletons()[0].has_image);
+        assert_eq!((lazy.skeletons()[0].btype.clone(), lazy.skeletons()[0].page), (TYPES::OT, Some(3)));
+        assert!(lazy.balloon(2).is_none());
+
+        assert_eq!(lazy.into_document().unwrap().to_xml(), d.to_xml());
+    }
+
+    #[test]
+    fn lazy_scans_hand_written_xml() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(crate::consts::Track::TL, "pooled"));
+        d.checkpoint("v1");
+        let pool = d.checkpoints.to_xml();
+        let full = d.to_xml();
+        let meta = &full[full.find("<Metadata").unwrap()..full.find("</Metadata>").unwrap() + 11];
+
+        // Pretty printed, a comment, an empty balloon and balloons of checkpoints
+        let xml = format!(
+            "<?xml version=\"1.0\"?>\n<Document>\n  {meta}\n  <!-- <Balloon> -->\n  <Balloons>\n    \
+            <Balloon type=\"OT\" page=\"2\">\n      <TL><![CDATA[</Balloon>]]></TL>\n      <img type=\"png\">AQID</img>\n    </Balloon>\n    \
+            <Balloon type=\"SFX\" w=\"5\" h=\"6\"/>\n  </Balloons>\n  {pool}\n</Document>"
+        );
+        let lazy = LazyDocument::parse(xml.clone()).unwrap();
+
+        assert_eq!(lazy.len(), 2);
+        assert_eq!((lazy.skeletons()[0].btype.clone(), lazy.skeletons()[0].page), (TYPES::OT, Some(2)));
+        assert!(lazy.skeletons()[0].has_image && !lazy.skeletons()[1].has_image);
+        assert_eq!(lazy.skeletons()[1].bbox.map(|b| b.h), Some(6));
+        assert_eq!(lazy.balloon(0).unwrap().unwrap().tl_content, ["</Balloon>"]);
+        assert_eq!(lazy.header().checkpoints.len(), 1);
+        assert_eq!(lazy.into_document().unwrap().to_xml(), Document::from_xml(&xml).unwrap().to_xml());
+
+        assert!(LazyDocument::parse("<Document><Balloons>".to_string()).is_err());
+        assert!(LazyDocument::parse("<Document></Document>".to_string()).is_err());
+    }
+
+    #[test]
+    fn lazy_rejects_txt() {
+        assert!(Document::open_lazy("test.txt").is_err());
+    }
+}
@@ -2,30 +2,161 @@
 //! 
 //! `rsff` (scanlation file format) is the core library of an application designed to 
 //! facilitate the work of teams translating content such as manga, manhwa, manhua, webtoons, etc.
-
-use balloon::{Balloon, BalloonImage};
-use consts::{OUT, TYPES};
-
-use std::ffi::OsStr;
-use std::io::{Write, Read};
-use std::fs::File;
-use std::path::Path;
-
-use flate2::write::ZlibEncoder;
-use flate2::read::ZlibDecoder;
-use flate2::Compression;
-
-use base64::{engine, Engine as _, alphabet};
-
+//!
+//! ## Features
+//!
+//! - `std` *(default)*: saving/opening files and zlib compression.
+//! - `core`: only the data model (documents, balloons, counting, XML (de)serialization).
+//!   Builds without `std::fs`, so it can be used in sandboxed plugin environments and wasm workers.
+//! - `archive`: open documents inside zip/cbz archives (`archive.cbz!chapter.sffz`), save cbz-like bundles
+//!   and [`.sffp` packages](package) with balloon images stored as files.
+//! - `remote`: read-only access through range reads (e.g. HTTP range requests) for previewing remote files.
+//! - `plugins`: host for WASM plugins providing QC rules, transforms and exporters, on top of the app's WASM runtime.
+//! - `json`: JSON payloads, e.g. progress updates for Discord/Slack webhooks, and the lossless
+//!   [`Format::JSON`](consts::Format) document format, and JSON Lines (`.jsonl`) for data
+//!   pipelines. Enables `serde`.
+//! - `xlsx`: Excel export of the script with formatted columns, see [`Document::to_xlsx`].
+//! - `odt`: OpenDocument text export with the txt layout, see [`Document::to_odt`].
+//! - `zstd`: the zstd compressed [`Format::ZSTD`](consts::Format) container (`.sffzst`),
+//!   lossless like sffz and faster to open. Convert existing files with [`transcode`].
+//! - `parallel`: [`Document::to_xml`] and [`Document::write_xml`] serialize balloons and
+//!   base64 encode their images on rayon's thread pool. The output is the same.
+//! - `serde`: `Serialize`/`Deserialize` for [`Document`] and everything it contains.
+//!   Image bytes are base64 strings, as in the xml.
+//! - `image-sanitize`: strip EXIF metadata and sRGB color profiles from balloon images and normalize their orientation.
+//! - `preview`: PNG [previews](preview) of a page of the HTML review sheet, without a browser engine.
+//!
+//! ## Threads
+//!
+//! Documents and everything in them are plain owned data and `Send + Sync`, as are
+//! [`Error`], [`project::Project`] and `lazy::LazyDocument` (`std`). Balloon images are
+//! shared behind an `Arc`, so cloning a document for another thread is cheap. Nothing
+//! locks internally: share a document read-only with `Arc<Document>`, and put it behind
+//! an `RwLock` only where it is written to.
+
+use archival::ArchivalPolicy;
+use balloon::Balloon;
+use budget::Budgets;
+use chapter::ChapterInfo;
+use checkpoint::Checkpoints;
+use finalize::FinalLock;
+use handoff::HandoffNotes;
+use layout::{Page, pages_from_xml};
+use metadata::Metadata;
+use settings::DocumentSettings;
+use txt::TxtImportOptions;
+
+use std::fmt;
+
+use base64::{engine, alphabet};
+
+pub mod archival;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod ass;
+#[cfg(feature = "std")]
+pub mod backup;
+pub mod badge;
 pub mod balloon;
+pub mod budget;
+#[cfg(feature = "std")]
+pub mod catalog;
+pub mod chapter;
+pub mod checkpoint;
+pub mod cjk;
+pub mod confidence;
 pub mod consts;
+#[cfg(feature = "std")]
+pub mod crops;
+pub mod csv;
+pub mod dataset;
+pub mod delta;
+pub mod diff;
+pub mod emphasis;
+pub mod error;
+pub mod export;
+pub mod feedback;
+pub mod finalize;
+pub mod fit;
+pub mod gc;
+pub mod handoff;
+pub mod html;
+pub mod import;
+#[cfg(feature = "std")]
+pub mod journal;
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod lazy;
+pub mod markdown;
+pub mod merge;
+pub mod metadata;
+#[cfg(feature = "std")]
+pub mod metrics;
+pub mod missed;
+#[cfg(feature = "odt")]
+pub mod odt;
+#[cfg(feature = "archive")]
+pub mod package;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod po;
+pub mod prelude;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod progress;
+pub mod project;
+pub mod provenance;
+pub mod qc;
+pub mod query;
+pub mod reflow;
+pub mod region;
+pub mod release;
+pub mod rematch;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod sample;
+pub mod sanitize;
+pub mod script;
+pub mod settings;
+pub mod shared;
+pub mod stats;
+pub mod submission;
+pub mod subtitle;
+pub mod template;
+pub mod txt;
+pub mod typography;
+#[cfg(feature = "json")]
+pub mod webhook;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+mod hash;
+mod proofing;
+
+#[cfg(feature = "std")]
+mod inspect;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "std")]
+mod repair;
+#[cfg(feature = "std")]
+mod transcode;
+#[cfg(feature = "image-sanitize")]
+mod image_sanitize;
+
+pub use error::Error;
+#[cfg(feature = "std")]
+pub use inspect::{FileInfo, inspect};
+#[cfg(feature = "std")]
+pub use repair::{Fix, RepairReport, repair};
+#[cfg(feature = "std")]
+pub use transcode::transcode;
 
 const B64: engine::GeneralPurpose = engine::GeneralPurpose::new(&alphabet::URL_SAFE, engine::general_purpose::NO_PAD);
 
-type XMLConvertResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
-
-#[derive(Clone, Debug)]
-struct FileDoesNotExists;
+type XMLConvertResult<T> = std::result::Result<T, Error>;
 
 /// A document containing all of your translation data.
 /// 
@@ -48,7 +179,8 @@ struct FileDoesNotExists;
 /// d.balloons.push(b);
 /// ```
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Document {
     /// sff (Scanlation File Format) version. No big changes expected.
     pub METADATA_SCRIPT_VERSION: String,
@@ -56,8 +188,24 @@ pub struct Document {
     pub METADATA_APP_VERSION: String,
     /// Some other info you want to give/specify.
     pub METADATA_INFO: String,
+    /// Series, chapter number and languages, stored in the metadata. See [`chapter`].
+    pub chapter: ChapterInfo,
+    /// Publisher limits, stored in the metadata. See [`Document::over_budget`].
+    pub budgets: Budgets,
+    /// Renderer preferences, stored in the metadata. See [`settings`].
+    pub settings: DocumentSettings,
+    /// Set for files saved with [`Document::save_archival`], stored in the metadata.
+    pub archival: Option<ArchivalPolicy>,
+    /// Notes for the next role, stored in the metadata. See [`Document::handoff_note`].
+    pub handoff: HandoffNotes,
+    /// Pages with their panels. Empty if the document is not split into pages.
+    pub pages: Vec<Page>,
     /// There is your balloons m8.
-    pub balloons: Vec<Balloon>
+    pub balloons: Vec<Balloon>,
+    /// Named versions of the balloons. See [`Document::checkpoint`].
+    pub checkpoints: Checkpoints,
+    /// Set by [`Document::finalize`], `None` while the text is still being worked on.
+    pub lock: Option<FinalLock>
 }
 
 impl Default for Document {
@@ -65,19 +213,63 @@ impl Default for Document {
     /// METADATA_SCRIPT_VERSION: String::from("Scanlation Script File v0.2.0"),
     /// METADATA_APP_VERSION: String::new(),
     /// METADATA_INFO: String::from("Num"),
-    /// balloons: Vec::new()
+    /// chapter: ChapterInfo::default(),
+    /// budgets: Budgets::default(),
+    /// settings: DocumentSettings::default(),
+    /// archival: None,
+    /// handoff: HandoffNotes::default(),
+    /// pages: Vec::new(),
+    /// balloons: Vec::new(),
+    /// checkpoints: Checkpoints::default(),
+    /// lock: None
     /// ```
     fn default() -> Self {    
         Self {
             METADATA_SCRIPT_VERSION: String::from("Scanlation Script File v0.2.0"),
             METADATA_APP_VERSION: String::new(),
             METADATA_INFO: String::from("Num"),
-            balloons: Vec::new()
+            chapter: ChapterInfo::default(),
+            budgets: Budgets::default(),
+            settings: DocumentSettings::default(),
+            archival: None,
+            handoff: HandoffNotes::default(),
+            pages: Vec::new(),
+            balloons: Vec::new(),
+            checkpoints: Checkpoints::default(),
+            lock: None
         }
     }
 }
 
 impl Document {
+    /// Creates an empty document with your app's version and an info text.
+    /// The script version is the current one, like in [`Document::default`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    ///
+    /// let d = Document::new("MyApp 2.1", "Ch. 45");
+    /// assert_eq!(d.METADATA_APP_VERSION, "MyApp 2.1");
+    /// assert_eq!(d.METADATA_INFO, "Ch. 45");
+    /// ```
+    pub fn new(app_version: &str, info: &str) -> Self {
+        Self {
+            METADATA_APP_VERSION: app_version.to_string(),
+            METADATA_INFO: info.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates an empty default document with room for `n` balloons.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            balloons: Vec::with_capacity(n),
+            ..Default::default()
+        }
+    }
+
     /// Total character count of all translation content.
     /// *(Spaces included.)*
     pub fn tl_chars(&self) -> usize {
@@ -88,6 +280,16 @@ impl Document {
             }).sum()
     }
 
+    /// Total character count of all translation check content.
+    /// *(Spaces included.)*
+    pub fn tlc_chars(&self) -> usize {
+        self.balloons
+            .iter()
+            .map(|b| {
+                b.tlc_chars()
+            }).sum()
+    }
+
     /// Total character count of all proofread content.
     /// *(Spaces included.)*
     pub fn pr_chars(&self) -> usize {
@@ -109,7 +311,7 @@ impl Document {
     }
 
     /// Total line count of the whole document.
-    /// Counts pr content lines if balloon has pr content, otherwise counts tl content lines.
+    /// Counts the lines of each balloon's effective track, see [`Balloon::effective_lines`].
     pub fn line_count(&self) -> usize {
         self.balloons
             .iter()
@@ -123,322 +325,281 @@ impl Document {
         self.balloons.len()
     }
 
-    /// Generates stringified version of the document.
-    /// Use this with caution because of data loss.
-    /// 
-    /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
-    pub fn to_string(&self) -> String {
-        let mut all_text: Vec<String> = Vec::new();
+    /// Returns `true` if the document has no balloons.
+    pub fn is_empty(&self) -> bool {
+        self.balloons.is_empty()
+    }
 
-        // No metadata, images etc. Just clean formatted string.
+    /// The balloon at `index`.
+    pub fn balloon(&self, index: usize) -> Option<&Balloon> {
+        self.balloons.get(index)
+    }
+
+    /// Mutable access to the balloon at `index`.
+    pub fn balloon_mut(&mut self, index: usize) -> Option<&mut Balloon> {
+        self.balloons.get_mut(index)
+    }
+
+    /// All balloons, in order.
+    pub fn balloons(&self) -> std::slice::Iter<'_, Balloon> {
+        self.balloons.iter()
+    }
+
+    /// Mutable iterator over all balloons, in order.
+    pub fn balloons_mut(&mut self) -> std::slice::IterMut<'_, Balloon> {
+        self.balloons.iter_mut()
+    }
+
+    /// Adds a balloon at the end.
+    pub fn push(&mut self, b: Balloon) {
+        self.balloons.push(b);
+    }
+
+    /// [`Balloon::hash`] of every balloon, in order.
+    /// Compare against previously stored hashes to find balloons that changed since then.
+    pub fn balloon_hashes(&self) -> Vec<u64> {
         self.balloons
             .iter()
-            .for_each(|b| {
-                all_text.push(
-                    b.to_string()
-                );
-            });
-
-        return all_text.join("\n\n");
+            .map(|b| b.hash())
+            .collect()
     }
 
     /// Generates an xml string of the balloon. No data loss so you can use this whenever you want.
     /// 
     /// **Note:** Raw image data will be converted to a b64 encoded string.
     pub fn to_xml(&self) -> String{
-        let mut xml = String::from("<Document><Metadata>");
-
-        // Add script and app related data
-        xml.push_str(format!(
-            "<Script>{}</Script>\
-            <App>{}</App>\
-            <Info>{}</Info>",
-            self.METADATA_SCRIPT_VERSION,
-            self.METADATA_APP_VERSION,
-            self.METADATA_INFO
-        ).as_str());
-
-        // Add other data
-        xml.push_str(format!(
-            "<TLLength>{}</TLLength>\
-            <PRLength>{}</PRLength>\
-            <CMLength>{}</CMLength>\
-            <BalloonCount>{}</BalloonCount>\
-            <LineCount>{}</LineCount>",
-            self.tl_chars(),
-            self.pr_chars(),
-            self.comment_chars(),
-            self.balloons.len(),
-            self.line_count()
-        ).as_str());
-
-        xml.push_str("</Metadata>");
-        xml.push_str("<Balloons>");
+        let mut xml: Vec<u8> = Vec::new();
+        // Writing to a vec can't fail and everything written is utf-8
+        self.write_xml(&mut xml).unwrap();
+        String::from_utf8(xml).unwrap()
+    }
 
-        // Add all balloons
-        self.balloons
-            .iter()
-            .for_each(|b| {
-                xml.push_str(b.to_xml().as_str());
-            });
-        
-        xml.push_str("</Balloons>");
-        xml.push_str("</Document>");
-        
-        return xml;
-    }
-
-    // Save as a raw xml file.
-    fn save_raw(&self, fp: &str) {
-        let mut file = File::create(
-            format!("{fp}.sffx")
-        ).unwrap();
-        file.write(self.to_xml().as_bytes()).unwrap();
-    }
-
-    // Save as a compressed xml file.
-    fn save_zlib(&self, fp: &str) {
-        let mut f = File::create(format!("{fp}.sffz")).unwrap();
-        let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
-        enc.write_all(self.to_xml().as_bytes()).unwrap();
-        let encoded = enc.finish().unwrap();
-        f.write(&encoded).unwrap();
-    }
-
-    /// Save your document as raw xml, compressed xml or .txt file.
-    /// 
+    /// Writes the xml of [`Document::to_xml`] to `w` tag by tag, without building the whole
+    /// string first. Images are b64 encoded in chunks, see [`Balloon::write_xml`].
+    ///
+    /// `w` is written to in small pieces, wrap files in a `BufWriter`.
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
     /// use rsff::Document;
-    /// use rsff::consts::OUT;
-    /// 
-    /// let d = Document::default();
-    /// 
-    /// // Save as raw xml:
-    /// d.save(OUT::RAW, "raw_xml");
-    /// 
-    /// // Save as ZLIB compressed xml:
-    /// d.save(OUT::ZLIB, "compressed_xml");
-    /// 
-    /// // Save as raw text:
-    /// d.save(OUT::TXT, "raw_text");
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Hi"));
+    ///
+    /// let mut xml: Vec<u8> = Vec::new();
+    /// d.write_xml(&mut xml).unwrap();
+    /// assert_eq!(xml, d.to_xml().into_bytes());
     /// ```
-    pub fn save(&self, out_type: OUT, fp: &str) {
-        match out_type {
-            OUT::RAW => self.save_raw(fp),
-            OUT::TXT => {
-                let f_name = format!("{}.txt", fp);
-                let mut f = File::create(f_name).unwrap();
-                f.write(self.to_string().as_bytes()).unwrap();
-            },
-            OUT::ZLIB => self.save_zlib(fp)
+    pub fn write_xml<W: std::io::Write>(&self, w: W) -> XMLConvertResult<()> {
+        self.write_xml_with(w, |_| None)
+    }
+
+    // Like `write_xml`, with the image of balloon `i` written as a reference to
+    // `img_src(i)` if set, see `package`.
+    pub(crate) fn write_xml_with<W, F>(&self, mut w: W, img_src: F) -> XMLConvertResult<()>
+    where
+        W: std::io::Write,
+        F: Fn(usize) -> Option<String> + Sync
+    {
+        w.write_all(b"<Document>")?;
+
+        w.write_all(self.metadata().to_xml().as_bytes())?;
+
+        // Pages are optional, don't write an empty tag
+        if !self.pages.is_empty() {
+            w.write_all(b"<Pages>")?;
+            for p in &self.pages {
+                w.write_all(p.to_xml().as_bytes())?;
+            }
+            w.write_all(b"</Pages>")?;
+        }
+
+        w.write_all(b"<Balloons>")?;
+
+        // Add all balloons
+        self.write_balloons(&mut w, img_src)?;
+
+        w.write_all(b"</Balloons>")?;
+
+        if let Some(l) = &self.lock {
+            w.write_all(l.to_xml().as_bytes())?;
+        }
+
+        // Checkpoints are optional too
+        if !self.checkpoints.is_empty() {
+            w.write_all(self.checkpoints.to_xml().as_bytes())?;
         }
+
+        w.write_all(b"</Document>")?;
+
+        Ok(())
     }
 
-    // Generate text of the whole document.
-    fn file_to_string(&self, p: &Path) -> String {
-        let mut s = String::new();
-        let mut f = File::open(p).unwrap();
-        f.read_to_string(&mut s).unwrap();
+    #[cfg(not(feature = "parallel"))]
+    fn write_balloons<W, F>(&self, mut w: W, img_src: F) -> XMLConvertResult<()>
+    where
+        W: std::io::Write,
+        F: Fn(usize) -> Option<String> + Sync
+    {
+        for (i, b) in self.balloons.iter().enumerate() {
+            b.write_xml_with(&mut w, img_src(i).as_deref())?;
+        }
 
-        return s;
+        Ok(())
     }
 
-    // Open a file and return it's byte content.
-    fn file_to_bytes(&self, p: &Path) -> Vec<u8> {
-        let mut buff: Vec<u8> = Vec::new();
-        let mut f = File::open(p).unwrap();
-        f.read_to_end(&mut buff).unwrap();
+    // Balloons are serialized a few per thread at a time and written in order, so only
+    // that many are held in memory.
+    #[cfg(feature = "parallel")]
+    fn write_balloons<W, F>(&self, mut w: W, img_src: F) -> XMLConvertResult<()>
+    where
+        W: std::io::Write,
+        F: Fn(usize) -> Option<String> + Sync
+    {
+        use rayon::prelude::*;
+
+        let chunk = rayon::current_num_threads() * 4;
+        for (c, balloons) in self.balloons.chunks(chunk).enumerate() {
+            let xml = balloons
+                .par_iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    let mut xml: Vec<u8> = Vec::new();
+                    b.write_xml_with(&mut xml, img_src(c * chunk + i).as_deref())?;
+                    Ok(xml)
+                })
+                .collect::<XMLConvertResult<Vec<Vec<u8>>>>()?;
+
+            for x in xml {
+                w.write_all(&x)?;
+            }
+        }
+
+        Ok(())
+    }
 
-        return buff;
+    /// Canonical byte representation of the document (raw xml, utf-8).
+    /// Does not touch the filesystem, so it is available with the `core` feature alone.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_xml().into_bytes()
     }
 
-    // Generate a document from xml string.
+    /// Generate a document from xml string.
+    #[deprecated(note = "use `Document::from_xml`")]
     pub fn xml_to_doc(&mut self, xml: String) -> XMLConvertResult<Document> {
-        // Create an empty document
-        let mut d = Document::default();
+        Document::from_xml(&xml)
+    }
 
+    /// Generates a document from an xml string, e.g. the content of a sffx file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Hi"));
+    /// assert_eq!(Document::from_xml(&d.to_xml()).unwrap().to_xml(), d.to_xml());
+    /// ```
+    pub fn from_xml(xml: &str) -> XMLConvertResult<Document> {
         // Parse xml string
-        let tree = roxmltree::Document::parse(&xml)?;
+        let tree = roxmltree::Document::parse(xml)?;
+
+        Document::from_xml_tree(&tree)
+    }
+
+    // Generate a document from a parsed xml document.
+    pub(crate) fn from_xml_tree(tree: &roxmltree::Document) -> XMLConvertResult<Document> {
+        // Create an empty document
+        let mut d = Document::default();
 
         // Find metadata tag
-        let md = tree.descendants().find(|d| {d.tag_name().name() == "Metadata"}).unwrap();
+        let md = tree.root_element().children().find(|d| {d.tag_name().name() == "Metadata"}).ok_or("Missing Metadata tag!")?;
 
         // Register file's metadata as document's metadata
-        // Note: Some other metadata like tl_chars / tl_content are dynamically 
+        // Note: Counts like tl_chars are computed from the balloons,
         // thus no need to register them.
-        d.METADATA_SCRIPT_VERSION = md.children().find(|c| {c.tag_name().name() == "Script"}).unwrap().text().unwrap_or("").to_string();
-        d.METADATA_APP_VERSION = md.children().find(|c| {c.tag_name().name() == "App"}).unwrap().text().unwrap_or("").to_string();
-        d.METADATA_INFO = md.children().find(|c| {c.tag_name().name() == "Info"}).unwrap().text().unwrap_or("").to_string();
+        d.set_metadata(Metadata::from_xml_node(md)?);
+
+        // Find Pages tag, older files don't have it
+        if let Some(ps) = tree.root_element().children().find(|c| {c.tag_name().name() == "Pages"}) {
+            d.pages = pages_from_xml(ps)?;
+        }
 
         // Find Balloons tag
-        let bs = tree.descendants().find(|c| {c.tag_name().name() == "Balloons"}).unwrap();
+        // Checkpoints hold balloons too, so only look at direct children of the document
+        let bs = tree.root_element().children().find(|c| {c.tag_name().name() == "Balloons"}).ok_or("Missing Balloons tag!")?;
 
         // Iterate over all xml balloons and generate Balloon struct, then add those structs to document
-        for c in bs.children() {
-            let mut b = Balloon {
-                btype: match c.attribute("type").unwrap() {
-                    "Dialogue" => TYPES::DIALOGUE,
-                    "Square" => TYPES::SQUARE,
-                    "ST" => TYPES::ST,
-                    "OT" => TYPES::OT,
-                    "Thinking" => TYPES::THINKING,
-                    _ => TYPES::DIALOGUE
-                },
-                ..Default::default()
-            };
-
-            let tls = c.children().filter(|c| {c.tag_name().name() == "TL"});
-            let prs = c.children().filter(|c| {c.tag_name().name() == "PR"});
-            let comments = c.children().filter(|c| {c.tag_name().name() == "Comment"});
-            let img = c.children().find(|c| {c.tag_name().name() == "img"});
-
-            for tl in tls {
-                let content = match tl.text() {
-                    Some(t) => t.to_string(),
-                    None => String::new()
-                };
-                b.tl_content.push(content);
-            }
-
-            for pr in prs {
-                let content = match pr.text() {
-                    Some(t) => t.to_string(),
-                    None => String::new()
-                };
-                b.pr_content.push(content);
-            }
+        for c in bs.children().filter(|c| c.has_tag_name("Balloon")) {
+            let b = Balloon::from_xml_node(c)?;
 
-            for comment in comments {
-                let content = match comment.text() {
-                    Some(t) => t.to_string(),
-                    None => String::new()
-                };
-                b.comments.push(content);
-            }
+            d.balloons.push(b);
+        }
 
-            if img.is_some() {
-                let i = BalloonImage {
-                    img_type: img.unwrap().attribute("type").unwrap().to_string(),
-                    img_data: B64.decode(img.unwrap().text().unwrap())?
-                };
-                b.balloon_img = Some(i);
-            } else {
-                b.balloon_img = None;
-            }
+        // Find Final tag, only there if the document is finalized
+        if let Some(f) = tree.root_element().children().find(|c| {c.tag_name().name() == "Final"}) {
+            d.lock = Some(FinalLock::from_xml_node(f)?);
+        }
 
-            d.balloons.push(b);
+        // Find Checkpoints tag, optional
+        if let Some(cs) = tree.root_element().children().find(|c| {c.tag_name().name() == "Checkpoints"}) {
+            d.checkpoints = Checkpoints::from_xml_node(cs)?;
         }
 
-        return Ok(d);
+        Ok(d)
     }
 
-    fn decide_b_type_from_txt_line_headers(&self, ln: &str) -> TYPES {
-        let s = &ln[0..2];
-
-        match s {
-            "()" => TYPES::DIALOGUE,
-            "OT" => TYPES::OT,
-            "[]" => TYPES::SQUARE,
-            "ST" => TYPES::ST,
-            "{}" => TYPES::THINKING,
-            _ => TYPES::DIALOGUE
-        }
+    /// Generate a document from lossy text.
+    #[deprecated(note = "use `Document::from_txt`")]
+    pub fn txt_to_doc(&self, txt: String) -> XMLConvertResult<Document> {
+        Document::from_txt(&txt)
     }
 
-    // Generate a document from lossy text.
+    /// Generate a document from lossy text, e.g. the content of a txt export. How multi-line
+    /// balloons are written is detected from the text, see [`txt`].
     // Why did i write this?
     // This is probably most unnecessary code ib this crate.
-    fn txt_to_doc(&self, txt: String) -> XMLConvertResult<Document> {
-        let mut d = Document::default();
-        let mut texts: Vec<String> = Vec::with_capacity(10);
-
-        let splitted = txt.split("\n").filter(|s| {!s.is_empty()}).collect::<Vec<&str>>();
-        let mut is_previous_double_slash: bool = false;
-
-        for i in 0..splitted.len() {
-            if splitted[i].contains("//") {continue;}
-
-            let current = splitted[i];
-
-            let mut b = Balloon::default();
-            b.btype = self.decide_b_type_from_txt_line_headers(current);
-            
-            let next = splitted.get(i+1).unwrap_or(&"");
-
-            if !next.contains("//") {
-                if is_previous_double_slash {
-                    texts.push(current[4..current.len()].trim().to_string());
-                    b.tl_content = texts.clone();
-                    d.balloons.push(b);
-                    is_previous_double_slash = false;
-                    continue;
-                } else {
-                    b.tl_content.push(current[4..current.len()].trim().to_string());
-                    d.balloons.push(b);
-                    is_previous_double_slash = false;
-                    continue;
-                }
-            } else {
-                texts.push(current[4..current.len()].trim().to_string());
-                is_previous_double_slash = true;
-            }         
-        }
-
-        return Ok(d);
+    pub fn from_txt(txt: &str) -> XMLConvertResult<Document> {
+        Document::from_txt_with(txt, &TxtImportOptions::default())
     }
+}
 
-    /// Open a supported sffx, sffz or txt file and generate a document.
-    /// 
-    /// `fp`: full path for the file.
-    /// 
-    /// # Examples
-    /// 
-    /// ```
-    /// use rsff::Document;
-    /// 
-    /// let mut d: Document = Document::default().open("test.sffx").unwrap().unwrap();
-    /// ```
+impl fmt::Display for Document {
+    /// Generates stringified version of the document.
+    /// Use this with caution because of data loss.
     /// 
-    /// **Note:** I messed up this absolutely shitty method and will change it in the future definitely.
-    pub fn open(&mut self, fp: &str) -> Result<XMLConvertResult<Document>, &str> {
-        let p = Path::new(fp);
-
-        if !p.exists() {return Err("File does not exists!")}
-
-        match p.extension() {
-            None => {return Err("No file ext!");},
-            Some(e) => {
-                if e == OsStr::new("txt") {
-                    let text = self.file_to_string(p);
-                    return Ok(self.txt_to_doc(text));
-                } else if e == OsStr::new("sffx") {
-                    let xml = self.file_to_string(p);
-                    return Ok(self.xml_to_doc(xml));
-                } else if e == OsStr::new("sffz") {
-                    let compressed = self.file_to_bytes(p);
-                    let mut xml = String::new();
-                    let mut decoder = ZlibDecoder::new(&*compressed);
-                    decoder.read_to_string(&mut xml).unwrap();
-                    return Ok(self.xml_to_doc(xml));
-                } else {
-                    return Err("Unsupported file type!");
-                }
-            }
-        }
+    /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // No metadata, images etc. Just clean formatted string.
+        let all_text: Vec<String> = self.balloons
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+
+        write!(f, "{}", all_text.join("\n\n"))
     }
 }
 
 #[cfg(test)]
 mod document_related {
-    use std::io::Read;
-    use std::fs::File;
-    use flate2::read::ZlibDecoder;
-
     use crate::Document;
     use crate::balloon::Balloon;
-    use crate::consts::{TYPES, OUT};
+
+    #[test]
+    fn document_constructors() {
+        let d = Document::with_capacity(64);
+        assert!(d.balloons.capacity() >= 64);
+        assert!(d.is_empty());
+
+        let d = Document::new("app", "info");
+        assert_eq!(d.METADATA_SCRIPT_VERSION, Document::default().METADATA_SCRIPT_VERSION);
+    }
 
     #[test]
     fn document_tl_chars() {
@@ -459,6 +620,26 @@ mod document_related {
         )
     }
 
+    #[test]
+    fn document_tlc_chars() {
+        let mut d = Document::default();
+        let mut b1 = Balloon::default();
+        let mut b2 = Balloon::default();
+
+        b1.tlc_content.push(String::from("num"));
+        b2.tl_content.push(String::from("num"));
+        b2.tlc_content.push(String::from("namnam"));
+
+        d.balloons.push(b1);
+        d.balloons.push(b2);
+
+        assert_eq!(
+            d.tlc_chars(),
+            9
+        );
+        assert!(d.to_xml().contains("<TLCLength>9</TLCLength>"));
+    }
+
     #[test]
     fn document_pr_chars() {
         let mut d = Document::default();
@@ -532,126 +713,68 @@ mod document_related {
     }
 
     #[test]
-    fn document_to_string() {
-        let mut d = Document::default();
-        let mut b1 = Balloon::default();
-        let mut b2 = Balloon::default();
-
-        b1.tl_content.push(String::from("num"));
-        b1.tl_content.push(String::from("nam"));
-        b1.pr_content.push(String::from("numnam"));
-        b1.btype = TYPES::OT;
-
-        b2.tl_content.push(String::from("num"));
-
-        d.balloons.push(b1);
-        d.balloons.push(b2);
-
-        d.save(OUT::TXT, "test");
-
-        let mut s = String::new();
-        let mut f = File::open("test.txt").unwrap();
-        f.read_to_string(&mut s).unwrap();
-
-        assert_eq!(
-            s,
-            String::from("OT: numnam\n\n(): num")
-        )
-    }
-
-    #[test]
-    fn document_to_xml() {
-        let mut d = Document::default();
-        let mut b1 = Balloon::default();
-        let mut b2 = Balloon::default();
-
-        b1.tl_content.push(String::from("num"));
-        b1.tl_content.push(String::from("nam"));
-        b1.pr_content.push(String::from("numnam"));
-        b1.btype = TYPES::OT;
-
-        b2.tl_content.push(String::from("num"));
-
-        d.balloons.push(b1);
-        d.balloons.push(b2);
+    fn document_is_send_sync() {
+        use std::sync::Arc;
 
-        d.save(OUT::RAW, "test");
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Document>();
+        send_sync::<Balloon>();
+        send_sync::<crate::Error>();
+        send_sync::<crate::project::Project>();
+        #[cfg(feature = "std")]
+        send_sync::<crate::lazy::LazyDocument>();
 
-        let num = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
-        let mut created = String::new();
-        let mut f = File::open("test.sffx").unwrap();
-        f.read_to_string(&mut created).unwrap();
-
-        assert_eq!(num, created)
-    }
-
-    #[test]
-    fn document_to_compressed() {
         let mut d = Document::default();
-        let mut b1 = Balloon::default();
-        let mut b2 = Balloon::default();
-
-        b1.tl_content.push(String::from("num"));
-        b1.tl_content.push(String::from("nam"));
-        b1.pr_content.push(String::from("numnam"));
-        b1.btype = TYPES::OT;
-
-        b2.tl_content.push(String::from("num"));
-
-        d.balloons.push(b1);
-        d.balloons.push(b2);
-
-        d.save(OUT::ZLIB, "test");
-
-        let num = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
-        let mut created = String::new();
-        let mut f = File::open("test.sffz").unwrap();
-        let mut encoded = Vec::new();
-        f.read_to_end(&mut encoded).unwrap();
-        let mut decoder = ZlibDecoder::new(&*encoded);
-        decoder.read_to_string(&mut created).unwrap();
-
-        assert_eq!(num, created)
+        d.balloons.push(Balloon::default().with_line(crate::consts::Track::TL, "num"));
+        let d = Arc::new(d);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let d = Arc::clone(&d);
+                std::thread::spawn(move || d.tl_chars())
+            })
+            .collect();
+        assert!(readers.into_iter().all(|r| r.join().unwrap() == 3));
     }
 
+    #[cfg(feature = "parallel")]
     #[test]
-    fn document_open_txt() {
-        let d = Document::default().open("test.txt").unwrap().unwrap();
+    fn document_parallel_xml_keeps_order() {
+        use crate::consts::Track;
 
-        assert_eq!(d.line_count(), 2);
-        assert_eq!(d.balloons.len(), 2);
-        assert_eq!(d.balloons[0].btype, TYPES::OT);
-        assert_eq!(d.balloons[0].tl_content[0], "numnam");
-        assert_eq!(d.balloons[1].btype, TYPES::DIALOGUE);
-        assert_eq!(d.balloons[1].tl_content[0], "num");
-    }
+        let mut d = Document::default();
+        for i in 0..1000u32 {
+            let mut b = Balloon::default().with_line(Track::TL, format!("line {i}"));
+            if i % 3 == 0 {
+                b.add_image("png".to_string(), i.to_le_bytes().to_vec());
+            }
+            d.balloons.push(b);
+        }
 
-    #[test]
-    fn document_open_sffx() {
-        let d = Document::default().open("test.sffx").unwrap().unwrap();
-        let case = r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#;
-        assert_eq!(
-            d.to_xml(),
-            case
-        );
+        let balloons: String = d.balloons.iter().map(|b| b.to_xml()).collect();
+        assert!(d.to_xml().contains(&format!("<Balloons>{balloons}</Balloons>")));
+        assert_eq!(Document::from_xml(&d.to_xml()).unwrap().to_xml(), d.to_xml());
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn document_open_sffz() {
-        let d = Document::default().open("test.sffz").unwrap().unwrap();
-        let case = String::from(r#"<Document><Metadata><Script>Scanlation Script File v0.2.0</Script><App></App><Info>Num</Info><TLLength>9</TLLength><PRLength>6</PRLength><CMLength>0</CMLength><BalloonCount>2</BalloonCount><LineCount>2</LineCount></Metadata><Balloons><Balloon type="OT"><TL>num</TL><TL>nam</TL><PR>numnam</PR></Balloon><Balloon type="Dialogue"><TL>num</TL></Balloon></Balloons></Document>"#);
-        assert_eq!(
-            d.to_xml(),
-            case
-        );
-    }
+    fn document_serde_json() {
+        use crate::consts::{TYPES, Track};
+        use crate::provenance::Provenance;
 
-    #[test]
-    fn document_unsupported_file_ext() {
         let mut d = Document::default();
-        let r = d.open("test.test");
-        if r.is_err() {
-            assert!(true)
-        }
+        let mut b = Balloon::new(TYPES::SFX);
+        b.push_line(Track::TL, "Boom".to_string(), Provenance::Mt { provider: "deepl".to_string(), model: "v2".to_string() });
+        b.add_image("png".to_string(), vec![1, 2, 3]);
+        d.balloons.push(b);
+        d.settings.fonts.insert(TYPES::SFX, "Komika".to_string());
+        d.checkpoint("tl done");
+
+        let json = serde_json::to_string(&d).unwrap();
+        assert!(json.contains("\"img_data\":\"AQID\""));
+        assert!(json.contains("\"btype\":\"SFX\""));
+
+        let back: Document = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_xml(), d.to_xml());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,110 @@
+//! Markdown export, so scripts can be reviewed in pull requests and wikis.
+//!
+//! Every balloon gets a heading, with a page heading before the first balloon of each
+//! page. Tl, tlc and pr lines are blockquotes under a bold track name, and comments are
+//! footnotes of the balloon heading, listed at the end of the file. Export only.
+
+use crate::Document;
+use crate::balloon::escape;
+use crate::consts::Track;
+
+// Characters that would turn text into markup, backslash-escaped. `&` would start an
+// html entity.
+const MARKDOWN_ESCAPES: [(char, &str); 11] = [
+    ('\\', "\\\\"), ('*', "\\*"), ('_', "\\_"), ('`', "\\`"), ('[', "\\["), (']', "\\]"),
+    ('<', "\\<"), ('>', "\\>"), ('#', "\\#"), ('|', "\\|"), ('&', "\\&"),
+];
+
+impl Document {
+    /// Generates a Markdown string of the balloons, see [`crate::markdown`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::{TYPES, Track};
+    ///
+    /// let mut b = Balloon::new(TYPES::SFX).with_line(Track::TL, "Boom").with_comment("Keep it big");
+    /// b.page = Some(1);
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(b);
+    ///
+    /// assert_eq!(
+    ///     d.to_markdown(),
+    ///     "## Page 1\n\n### Balloon 1 (SFX)[^1]\n\n**TL**\n\n> Boom\n\n[^1]: Keep it big\n"
+    /// );
+    /// ```
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let mut footnotes: Vec<String> = Vec::new();
+        let mut page: Option<u32> = None;
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            if b.page.is_some() && b.page != page {
+                md.push_str(format!("## Page {}\n\n", b.page.unwrap_or_default()).as_str());
+            }
+            page = b.page;
+
+            md.push_str(format!("### Balloon {} ({})", i + 1, b.btype.xml_name()).as_str());
+            for comment in &b.comments {
+                footnotes.push(comment.replace('\n', " "));
+                md.push_str(format!("[^{}]", footnotes.len()).as_str());
+            }
+            md.push_str("\n\n");
+
+            for (track, name) in [(Track::TL, "TL"), (Track::TLC, "TLC"), (Track::PR, "PR")] {
+                let lines = b.lines(track);
+                if lines.is_empty() {
+                    continue;
+                }
+
+                md.push_str(format!("**{name}**\n\n").as_str());
+                for line in lines {
+                    if line.is_empty() {
+                        md.push_str(">\n");
+                    } else {
+                        md.push_str(format!("> {}\n", escape(line, &MARKDOWN_ESCAPES)).as_str());
+                    }
+                }
+                md.push('\n');
+            }
+        }
+
+        for (n, text) in footnotes.iter().enumerate() {
+            md.push_str(format!("[^{}]: {}\n", n + 1, escape(text, &MARKDOWN_ESCAPES)).as_str());
+        }
+
+        md
+    }
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    #[test]
+    fn markdown_pages_and_footnotes() {
+        let on = |page: Option<u32>, b: Balloon| Balloon { page, ..b };
+
+        let mut d = Document::default();
+        d.balloons.push(on(Some(1), Balloon::default().with_line(Track::TL, "a *b*").with_line(Track::PR, "A").with_comment("x")));
+        d.balloons.push(on(Some(1), Balloon::default().with_comment("y").with_comment("z")));
+        d.balloons.push(on(Some(2), Balloon::default().with_line(Track::TLC, "c").with_line(Track::TLC, "")));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "# d &amp;"));
+
+        assert_eq!(d.to_markdown(), "## Page 1\n\n\
+            ### Balloon 1 (Dialogue)[^1]\n\n**TL**\n\n> a \\*b\\*\n\n**PR**\n\n> A\n\n\
+            ### Balloon 2 (Dialogue)[^2][^3]\n\n\
+            ## Page 2\n\n\
+            ### Balloon 3 (Dialogue)\n\n**TLC**\n\n> c\n>\n\n\
+            ### Balloon 4 (Dialogue)\n\n**TL**\n\n> \\# d \\&amp;\n\n\
+            [^1]: x\n[^2]: y\n[^3]: z\n");
+
+        assert_eq!(Format::from_extension("md"), Some(Format::MARKDOWN));
+        assert!(!d.export_report(Format::MARKDOWN).dropped.iter().any(|x| matches!(x, crate::export::Dropped::Comments(_))));
+    }
+}
@@ -0,0 +1,284 @@
+//! Three-way merge of documents.
+//!
+//! Balloons are matched by position. For every position the side that changed
+//! compared to the common base wins; if both sides changed it differently the
+//! balloon is a conflict. Metadata fields and pages are merged the same way, one
+//! field at a time.
+
+use crate::Document;
+use crate::balloon::Balloon;
+
+use std::error::Error;
+use std::fmt;
+
+/// Comment added to our side of a conflicting balloon pair.
+pub const CONFLICT_OURS: &str = "<<<<<<< ours";
+/// Comment added to their side of a conflicting balloon pair.
+pub const CONFLICT_THEIRS: &str = ">>>>>>> theirs";
+
+/// What to do when both sides changed the same balloon differently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnConflict {
+    /// Fail with a [`MergeConflict`].
+    Abort,
+    /// Keep both variants in the merged document, git-style:
+    /// our balloon commented with [`CONFLICT_OURS`] followed by
+    /// their balloon commented with [`CONFLICT_THEIRS`].
+    Annotate,
+}
+
+/// Result of a successful merge.
+#[derive(Debug)]
+pub struct Merged {
+    pub document: Document,
+    /// Indices of the annotated conflict pairs in `document` (the "ours" balloon of each pair).
+    /// Always empty with [`OnConflict::Abort`].
+    pub conflicts: Vec<usize>,
+    /// Document fields (e.g. `"chapter"`) both sides changed differently, ours was kept.
+    /// Always empty with [`OnConflict::Abort`].
+    pub field_conflicts: Vec<&'static str>,
+}
+
+/// Both sides changed the balloons at these positions, or these document fields, differently.
+#[derive(Debug)]
+pub struct MergeConflict {
+    pub positions: Vec<usize>,
+    pub fields: Vec<&'static str>,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.positions.is_empty(), self.fields.is_empty()) {
+            (_, true) => write!(f, "conflicting changes in balloons {:?}", self.positions),
+            (true, false) => write!(f, "conflicting changes in fields {:?}", self.fields),
+            (false, false) => write!(f, "conflicting changes in balloons {:?} and fields {:?}", self.positions, self.fields)
+        }
+    }
+}
+
+impl Error for MergeConflict {}
+
+// Compare balloons through their content hash.
+fn same(a: Option<&Balloon>, b: Option<&Balloon>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.hash() == b.hash(),
+        (None, None) => true,
+        _ => false
+    }
+}
+
+// The side that changed `name` compared to `base`, ours if both changed it differently.
+fn pick<T: Clone + PartialEq>(name: &'static str, base: &T, ours: &T, theirs: &T, fields: &mut Vec<&'static str>) -> T {
+    if ours == base {
+        theirs.clone()
+    } else {
+        if theirs != base && theirs != ours {
+            fields.push(name);
+        }
+        ours.clone()
+    }
+}
+
+fn annotated(b: &Balloon, marker: &str) -> Balloon {
+    let mut b = b.clone();
+    b.comments.push(marker.to_string());
+    b
+}
+
+/// Three-way merges `ours` and `theirs`, both edited from `base`.
+///
+/// Each metadata field and the pages are taken from the side that changed them; if both
+/// changed one differently it is a conflict and `ours` is kept. Checkpoints and the final lock are local state and always taken from `ours`.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::balloon::Balloon;
+/// use rsff::merge::{merge, OnConflict};
+///
+/// let mut base = Document::default();
+/// base.balloons.push(Balloon::default());
+/// base.balloons.push(Balloon::default());
+///
+/// let mut ours = base.clone();
+/// ours.balloons[0].tl_content.push("num".to_string());
+///
+/// let mut theirs = base.clone();
+/// theirs.balloons[1].tl_content.push("nam".to_string());
+///
+/// let merged = merge(&base, &ours, &theirs, OnConflict::Abort).unwrap();
+/// assert_eq!(merged.document.tl_chars(), 6);
+/// ```
+pub fn merge(base: &Document, ours: &Document, theirs: &Document, on_conflict: OnConflict) -> Result<Merged, MergeConflict> {
+    let mut d = Document::default();
+    let mut conflicts: Vec<usize> = Vec::new();
+    let mut positions: Vec<usize> = Vec::new();
+
+    let mut fields: Vec<&'static str> = Vec::new();
+
+    d.METADATA_SCRIPT_VERSION = pick("METADATA_SCRIPT_VERSION", &base.METADATA_SCRIPT_VERSION, &ours.METADATA_SCRIPT_VERSION, &theirs.METADATA_SCRIPT_VERSION, &mut fields);
+    d.METADATA_APP_VERSION = pick("METADATA_APP_VERSION", &base.METADATA_APP_VERSION, &ours.METADATA_APP_VERSION, &theirs.METADATA_APP_VERSION, &mut fields);
+    d.METADATA_INFO = pick("METADATA_INFO", &base.METADATA_INFO, &ours.METADATA_INFO, &theirs.METADATA_INFO, &mut fields);
+    d.chapter = pick("chapter", &base.chapter, &ours.chapter, &theirs.chapter, &mut fields);
+    d.budgets = pick("budgets", &base.budgets, &ours.budgets, &theirs.budgets, &mut fields);
+    d.settings = pick("settings", &base.settings, &ours.settings, &theirs.settings, &mut fields);
+    d.archival = pick("archival", &base.archival, &ours.archival, &theirs.archival, &mut fields);
+    d.handoff = pick("handoff", &base.handoff, &ours.handoff, &theirs.handoff, &mut fields);
+    d.pages = pick("pages", &base.pages, &ours.pages, &theirs.pages, &mut fields);
+    d.checkpoints = ours.checkpoints.clone();
+    d.lock = ours.lock.clone();
+
+    let len = base.len().max(ours.len()).max(theirs.len());
+
+    for i in 0..len {
+        let b = base.balloons.get(i);
+        let o = ours.balloons.get(i);
+        let t = theirs.balloons.get(i);
+
+        // None means the balloon was deleted (or never added) on that side.
+        let taken = if same(o, t) || same(b, t) {
+            o
+        } else if same(b, o) {
+            t
+        } else {
+            positions.push(i);
+
+            if on_conflict == OnConflict::Annotate {
+                conflicts.push(d.balloons.len());
+                // A deletion conflicting with an edit keeps the edited balloon only.
+                if let Some(o) = o {
+                    d.balloons.push(annotated(o, CONFLICT_OURS));
+                }
+                if let Some(t) = t {
+                    d.balloons.push(annotated(t, CONFLICT_THEIRS));
+                }
+            }
+            continue;
+        };
+
+        if let Some(balloon) = taken {
+            d.balloons.push(balloon.clone());
+        }
+    }
+
+    if on_conflict == OnConflict::Abort {
+        if !positions.is_empty() || !fields.is_empty() {
+            return Err(MergeConflict { positions, fields });
+        }
+        return Ok(Merged { document: d, conflicts, field_conflicts: Vec::new() });
+    }
+
+    Ok(Merged { document: d, conflicts, field_conflicts: fields })
+}
+
+impl Balloon {
+    /// Returns `true` if the balloon still carries a conflict marker comment left by [`merge`].
+    pub fn is_conflicted(&self) -> bool {
+        self.comments
+            .iter()
+            .any(|c| c == CONFLICT_OURS || c == CONFLICT_THEIRS)
+    }
+}
+
+#[cfg(test)]
+mod merge_tests {
+    use super::{merge, OnConflict};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::layout::Page;
+
+    fn base() -> Document {
+        let mut d = Document::default();
+        for text in ["num", "nam"] {
+            let mut b = Balloon::default();
+            b.tl_content.push(text.to_string());
+            d.balloons.push(b);
+        }
+        d
+    }
+
+    #[test]
+    fn merge_without_conflicts() {
+        let base = base();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+
+        ours.balloons[0].pr_content.push("numnam".to_string());
+        theirs.balloons.push(Balloon::default());
+        theirs.METADATA_INFO = String::from("Ch. 2");
+
+        let merged = merge(&base, &ours, &theirs, OnConflict::Abort).unwrap();
+
+        assert_eq!(merged.document.len(), 3);
+        assert_eq!(merged.document.balloons[0].pr_content[0], "numnam");
+        assert_eq!(merged.document.METADATA_INFO, "Ch. 2");
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge_conflict_abort() {
+        let base = base();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+
+        ours.balloons[1].tl_content[0] = "ours".to_string();
+        theirs.balloons[1].tl_content[0] = "theirs".to_string();
+
+        let err = merge(&base, &ours, &theirs, OnConflict::Abort).unwrap_err();
+        assert_eq!(err.positions, vec![1]);
+        assert!(err.fields.is_empty());
+    }
+
+    #[test]
+    fn merge_metadata_per_field() {
+        let base = base();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+
+        ours.chapter.series = Some("Series".to_string());
+        ours.pages.push(Page::default());
+        theirs.METADATA_INFO = String::from("Ch. 2");
+        theirs.budgets.chars_per_balloon = Some(30);
+
+        let merged = merge(&base, &ours, &theirs, OnConflict::Abort).unwrap();
+        let d = merged.document;
+        assert_eq!(d.chapter.series.as_deref(), Some("Series"));
+        assert_eq!(d.pages.len(), 1);
+        assert_eq!(d.METADATA_INFO, "Ch. 2");
+        assert_eq!(d.budgets.chars_per_balloon, Some(30));
+
+        // Both changed the same field
+        theirs.chapter.series = Some("Other".to_string());
+        let err = merge(&base, &ours, &theirs, OnConflict::Abort).unwrap_err();
+        assert!(err.positions.is_empty());
+        assert_eq!(err.fields, ["chapter"]);
+        assert_eq!(err.to_string(), "conflicting changes in fields [\"chapter\"]");
+
+        let merged = merge(&base, &ours, &theirs, OnConflict::Annotate).unwrap();
+        assert_eq!(merged.field_conflicts, ["chapter"]);
+        assert_eq!(merged.document.chapter.series.as_deref(), Some("Series"));
+        assert_eq!(merged.document.METADATA_INFO, "Ch. 2");
+    }
+
+    #[test]
+    fn merge_conflict_annotate() {
+        let base = base();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+
+        ours.balloons[0].tl_content[0] = "ours".to_string();
+        theirs.balloons[0].tl_content[0] = "theirs".to_string();
+
+        let merged = merge(&base, &ours, &theirs, OnConflict::Annotate).unwrap();
+        let d = merged.document;
+
+        assert_eq!(merged.conflicts, vec![0]);
+        assert_eq!(d.len(), 3);
+        assert_eq!(d.balloons[0].tl_content[0], "ours");
+        assert_eq!(d.balloons[1].tl_content[0], "theirs");
+        assert!(d.balloons[0].is_conflicted());
+        assert!(d.balloons[1].is_conflicted());
+        assert!(!d.balloons[2].is_conflicted());
+    }
+}
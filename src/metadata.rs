@@ -0,0 +1,252 @@
+//! The metadata section of a document on its own.
+//!
+//! Servers indexing uploaded files only need the header, and tools editing it don't need
+//! to decode every balloon and image: [`Metadata::from_xml`] only parses the `<Metadata>`
+//! section and [`Metadata::replace_in`] writes it back leaving the rest of the xml as it is.
+
+use crate::{Document, XMLConvertResult};
+use crate::archival::ArchivalPolicy;
+use crate::balloon::escape_text;
+use crate::budget::Budgets;
+use crate::chapter::ChapterInfo;
+use crate::handoff::HandoffNotes;
+use crate::settings::DocumentSettings;
+
+const METADATA_START: &str = "<Metadata>";
+const METADATA_END: &str = "</Metadata>";
+
+/// Counts stored in the metadata. Computed from the balloons when a document is saved,
+/// so changing them only matters for [`Metadata::replace_in`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub tl_chars: usize,
+    pub tlc_chars: usize,
+    pub pr_chars: usize,
+    pub comment_chars: usize,
+    pub balloons: usize,
+    pub lines: usize,
+}
+
+/// The metadata of a document. See [`Document::metadata`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    pub script_version: String,
+    pub app_version: String,
+    pub info: String,
+    pub chapter: ChapterInfo,
+    pub budgets: Budgets,
+    pub settings: DocumentSettings,
+    pub archival: Option<ArchivalPolicy>,
+    pub handoff: HandoffNotes,
+    pub counts: Counts,
+}
+
+// Byte range of the metadata section in a document's xml.
+fn section(xml: &str) -> XMLConvertResult<(usize, usize)> {
+    let start = xml.find(METADATA_START).ok_or("Missing Metadata tag!")?;
+    // Text is escaped, the first closing tag is the real one
+    let end = xml[start..].find(METADATA_END).ok_or("Missing Metadata tag!")? + start + METADATA_END.len();
+
+    Ok((start, end))
+}
+
+impl Metadata {
+    /// Parses the metadata of a document's xml, or of a lone `<Metadata>` tag.
+    /// Balloons are not parsed, so they don't even have to be valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::metadata::Metadata;
+    ///
+    /// let xml = std::fs::read_to_string("test.sffx").unwrap();
+    /// let md = Metadata::from_xml(&xml).unwrap();
+    ///
+    /// assert_eq!(md.script_version, "Scanlation Script File v0.2.0");
+    /// assert_eq!(md.counts.balloons, 2);
+    /// ```
+    pub fn from_xml(xml: &str) -> XMLConvertResult<Metadata> {
+        let (start, end) = section(xml)?;
+        let tree = roxmltree::Document::parse(&xml[start..end])?;
+
+        Metadata::from_xml_node(tree.root_element())
+    }
+
+    // Parse a <Metadata> tag.
+    pub(crate) fn from_xml_node(md: roxmltree::Node) -> XMLConvertResult<Metadata> {
+        let child = |name: &str| md.children().find(|c| c.tag_name().name() == name);
+        let text = |name: &str| -> XMLConvertResult<String> {
+            let tag = child(name).ok_or_else(|| format!("Missing {name} tag!"))?;
+            Ok(tag.text().unwrap_or("").to_string())
+        };
+        let count = |name: &str| -> XMLConvertResult<usize> {
+            match child(name) {
+                Some(c) => Ok(c.text().unwrap_or("").parse()?),
+                None => Ok(0)
+            }
+        };
+
+        Ok(Metadata {
+            script_version: text("Script")?,
+            app_version: text("App")?,
+            info: text("Info")?,
+            chapter: child("Chapter").map(ChapterInfo::from_xml_node).transpose()?.unwrap_or_default(),
+            budgets: child("Budgets").map(Budgets::from_xml_node).transpose()?.unwrap_or_default(),
+            settings: child("Settings").map(DocumentSettings::from_xml_node).transpose()?.unwrap_or_default(),
+            archival: child("Archival").map(ArchivalPolicy::from_xml_node).transpose()?,
+            handoff: child("Handoff").map(HandoffNotes::from_xml_node).transpose()?.unwrap_or_default(),
+            counts: Counts {
+                tl_chars: count("TLLength")?,
+                tlc_chars: count("TLCLength")?,
+                pr_chars: count("PRLength")?,
+                comment_chars: count("CMLength")?,
+                balloons: count("BalloonCount")?,
+                lines: count("LineCount")?
+            }
+        })
+    }
+
+    /// Generates an xml string of the metadata, the `<Metadata>` tag of a document.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(METADATA_START);
+
+        // Add script and app related data
+        xml.push_str(format!(
+            "<Script>{}</Script>\
+            <App>{}</App>\
+            <Info>{}</Info>",
+            escape_text(&self.script_version),
+            escape_text(&self.app_version),
+            escape_text(&self.info)
+        ).as_str());
+
+        // Add other data
+        xml.push_str(format!(
+            "<TLLength>{}</TLLength>\
+            <PRLength>{}</PRLength>\
+            <CMLength>{}</CMLength>\
+            <BalloonCount>{}</BalloonCount>\
+            <LineCount>{}</LineCount>",
+            self.counts.tl_chars,
+            self.counts.pr_chars,
+            self.counts.comment_chars,
+            self.counts.balloons,
+            self.counts.lines
+        ).as_str());
+
+        // Only teams with a tlc pass have tlc content, keep older files unchanged
+        if self.counts.tlc_chars > 0 {
+            xml.push_str(format!("<TLCLength>{}</TLCLength>", self.counts.tlc_chars).as_str());
+        }
+
+        if !self.chapter.is_empty() {
+            xml.push_str(self.chapter.to_xml().as_str());
+        }
+
+        if !self.budgets.is_empty() {
+            xml.push_str(self.budgets.to_xml().as_str());
+        }
+
+        if !self.settings.is_empty() {
+            xml.push_str(self.settings.to_xml().as_str());
+        }
+
+        if let Some(a) = &self.archival {
+            xml.push_str(a.to_xml().as_str());
+        }
+
+        if !self.handoff.is_empty() {
+            xml.push_str(self.handoff.to_xml().as_str());
+        }
+
+        xml.push_str(METADATA_END);
+
+        xml
+    }
+
+    /// Replaces the metadata section of a document's xml with this one.
+    /// The rest of the xml is copied as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::metadata::Metadata;
+    ///
+    /// let xml = std::fs::read_to_string("test.sffx").unwrap();
+    /// let mut md = Metadata::from_xml(&xml).unwrap();
+    /// md.app_version = "MyApp 2.1".to_string();
+    ///
+    /// let updated = md.replace_in(&xml).unwrap();
+    /// let d = rsff::Document::from_xml(&updated).unwrap();
+    /// assert_eq!(d.METADATA_APP_VERSION, "MyApp 2.1");
+    /// ```
+    pub fn replace_in(&self, xml: &str) -> XMLConvertResult<String> {
+        let (start, end) = section(xml)?;
+
+        Ok(format!("{}{}{}", &xml[..start], self.to_xml(), &xml[end..]))
+    }
+}
+
+impl Document {
+    /// The metadata of the document, with counts computed from the balloons.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            script_version: self.METADATA_SCRIPT_VERSION.clone(),
+            app_version: self.METADATA_APP_VERSION.clone(),
+            info: self.METADATA_INFO.clone(),
+            chapter: self.chapter.clone(),
+            budgets: self.budgets,
+            settings: self.settings.clone(),
+            archival: self.archival,
+            handoff: self.handoff.clone(),
+            counts: Counts {
+                tl_chars: self.tl_chars(),
+                tlc_chars: self.tlc_chars(),
+                pr_chars: self.pr_chars(),
+                comment_chars: self.comment_chars(),
+                balloons: self.balloons.len(),
+                lines: self.line_count()
+            }
+        }
+    }
+
+    /// Replaces the metadata of the document. Counts are ignored, they always follow the balloons.
+    pub fn set_metadata(&mut self, md: Metadata) {
+        self.METADATA_SCRIPT_VERSION = md.script_version;
+        self.METADATA_APP_VERSION = md.app_version;
+        self.METADATA_INFO = md.info;
+        self.chapter = md.chapter;
+        self.budgets = md.budgets;
+        self.settings = md.settings;
+        self.archival = md.archival;
+        self.handoff = md.handoff;
+    }
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::Metadata;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::Track;
+
+    #[test]
+    fn metadata_ignores_balloons() {
+        let mut d = Document { METADATA_INFO: "a < b".to_string(), ..Default::default() };
+        d.balloons.push(Balloon::default().with_line(Track::TL, "num"));
+        d.budgets.chars_per_balloon = Some(40);
+
+        // Broken balloons don't matter
+        let xml = d.to_xml().replace("</Balloons>", "<Balloon");
+        let mut md = Metadata::from_xml(&xml).unwrap();
+        assert_eq!(md, d.metadata());
+        assert_eq!(md.counts.tl_chars, 3);
+        assert!(Metadata::from_xml("<Document><Balloons/></Document>").is_err());
+
+        md.info = "</Metadata>".to_string();
+        let updated = md.replace_in(&d.to_xml()).unwrap();
+        let back = Document::from_xml(&updated).unwrap();
+        assert_eq!(back.METADATA_INFO, "</Metadata>");
+        assert_eq!(back.balloons[0].hash(), d.balloons[0].hash());
+    }
+}
@@ -0,0 +1,185 @@
+//! Local usage metrics.
+//!
+//! [`Document::from_file`](crate::Document::from_file) and [`Document::save`](crate::Document::save) count themselves in process-wide counters,
+//! so host apps can show "project activity" views without wrapping every call.
+//! Nothing is sent anywhere: [`metrics`] returns the numbers and the app decides
+//! whether to display or persist them.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::consts::Format;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// An operation counted by the metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Op {
+    Open,
+    Save,
+}
+
+// Index of a format in the per-format counters.
+fn format_index(f: Format) -> usize {
+    Format::ALL
+        .iter()
+        .position(|x| *x == f)
+        .expect("every format is in Format::ALL")
+}
+
+// Number of per-format counters.
+const FORMATS: usize = Format::ALL.len();
+
+struct Counters {
+    calls: AtomicU64,
+    failures: AtomicU64,
+    nanos: AtomicU64,
+    formats: [AtomicU64; FORMATS],
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+            formats: [const { AtomicU64::new(0) }; FORMATS],
+        }
+    }
+
+    fn snapshot(&self) -> OpMetrics {
+        OpMetrics {
+            count: self.calls.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            time: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+            formats: std::array::from_fn(|i| self.formats[i].load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+        self.nanos.store(0, Ordering::Relaxed);
+        for f in &self.formats {
+            f.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+static OPENS: Counters = Counters::new();
+static SAVES: Counters = Counters::new();
+
+// Count an operation that started at `started`. `format` is `None` if it failed before the format was known.
+pub(crate) fn record(op: Op, format: Option<Format>, started: Instant, ok: bool) {
+    let c = match op {
+        Op::Open => &OPENS,
+        Op::Save => &SAVES
+    };
+
+    c.calls.fetch_add(1, Ordering::Relaxed);
+    c.nanos.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    if !ok {
+        c.failures.fetch_add(1, Ordering::Relaxed);
+    }
+    if let Some(f) = format {
+        c.formats[format_index(f)].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counters of one kind of operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpMetrics {
+    /// Number of calls, failed ones included.
+    pub count: u64,
+    pub failures: u64,
+    /// Total time spent.
+    pub time: Duration,
+    formats: [u64; FORMATS],
+}
+
+impl OpMetrics {
+    /// Number of calls with `format`.
+    pub fn format_count(&self, format: Format) -> u64 {
+        self.formats[format_index(format)]
+    }
+
+    /// Average time of a call, zero without calls.
+    pub fn average(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            n => self.time / n as u32
+        }
+    }
+}
+
+/// Usage of the library since the process started or the last [`reset_metrics`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// [`Document::from_file`](crate::Document::from_file), [`Document::from_file_as`](crate::Document::from_file_as)
+    /// and [`Document::from_reader`](crate::Document::from_reader) calls.
+    pub opens: OpMetrics,
+    /// [`Document::save`](crate::Document::save) and [`Document::save_with`](crate::Document::save_with) calls.
+    pub saves: OpMetrics,
+}
+
+/// Current metrics of the process.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::consts::Format;
+/// use rsff::metrics::metrics;
+///
+/// Document::default().save(Format::ZLIB, "metrics_example").unwrap();
+///
+/// let m = metrics();
+/// assert!(m.saves.format_count(Format::ZLIB) >= 1);
+/// println!("{} saves, {:?} on average", m.saves.count, m.saves.average());
+/// # std::fs::remove_file("metrics_example.sffz").unwrap();
+/// ```
+pub fn metrics() -> Metrics {
+    Metrics { opens: OPENS.snapshot(), saves: SAVES.snapshot() }
+}
+
+/// Sets every counter back to zero, e.g. after the app persisted them.
+pub fn reset_metrics() {
+    OPENS.reset();
+    SAVES.reset();
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::{Op, OpMetrics, metrics, record};
+    use crate::Document;
+    use crate::consts::Format;
+
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn metrics_count_operations() {
+        // Other tests open and save at the same time, only check what this one adds
+        let before = metrics();
+        record(Op::Open, Some(Format::TXT), Instant::now(), true);
+        assert!(Document::from_file("metrics_count_operations.sffx").is_err());
+
+        let after = metrics();
+        assert!(after.opens.count >= before.opens.count + 2);
+        assert!(after.opens.failures > before.opens.failures);
+        assert!(after.opens.format_count(Format::TXT) > before.opens.format_count(Format::TXT));
+
+        let m = OpMetrics { count: 4, time: Duration::from_millis(10), ..Default::default() };
+        assert_eq!(m.average(), Duration::from_micros(2500));
+    }
+
+    #[test]
+    fn metrics_count_every_format() {
+        let before = metrics();
+        for f in Format::ALL {
+            record(Op::Save, Some(*f), Instant::now(), true);
+        }
+
+        let after = metrics();
+        assert!(Format::ALL.iter().all(|f| after.saves.format_count(*f) > before.saves.format_count(*f)));
+    }
+}
@@ -0,0 +1,139 @@
+//! Heuristics for text the team may have missed, mostly untranslated SFX and signs.
+//!
+//! Finding text regions on page images is the app's job (OCR, a detector model...);
+//! [`Document::missed_text`] compares the regions it found with the balloons. It flags
+//! balloons that were cut out but never got text, regions no balloon covers, and pages
+//! with far fewer balloons than regions.
+
+use crate::Document;
+use crate::consts::TYPES;
+use crate::layout::BBox;
+
+use std::collections::BTreeMap;
+
+/// Thresholds of [`Document::missed_text`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MissedTextOptions {
+    /// Share of a region's area balloons must cover for it to count as handled.
+    pub min_coverage: f32,
+    /// Pages with fewer balloons than this many per region are flagged.
+    pub min_ratio: f32,
+}
+
+impl Default for MissedTextOptions {
+    fn default() -> Self {
+        Self { min_coverage: 0.5, min_ratio: 0.75 }
+    }
+}
+
+/// Something that looks like text left out of the script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MissedText {
+    /// A balloon with an image or an area but no text.
+    Untranslated { balloon: usize, btype: TYPES },
+    /// A detected region no balloon of its page covers.
+    Uncovered { page: u32, region: BBox },
+    /// A page with suspiciously few balloons for its regions.
+    FewBalloons { page: u32, balloons: usize, regions: usize },
+}
+
+impl Document {
+    /// Compares text regions detected on each page, keyed by page number, with the balloons.
+    ///
+    /// Results are balloons first, then pages in order. Balloons without a page or an area
+    /// can't be matched to regions, they only count for [`MissedText::FewBalloons`] if
+    /// they have a page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::layout::BBox;
+    /// use rsff::missed::{MissedText, MissedTextOptions};
+    ///
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut b = Balloon::default().with_line(Track::TL, "Hi");
+    /// b.page = Some(1);
+    /// b.bbox = Some(BBox { x: 0, y: 0, w: 100, h: 50 });
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(b);
+    ///
+    /// // The detector also found a sign nobody translated
+    /// let sign = BBox { x: 300, y: 400, w: 80, h: 40 };
+    /// let regions = BTreeMap::from([(1, vec![BBox { x: 10, y: 5, w: 80, h: 40 }, sign])]);
+    ///
+    /// let missed = d.missed_text(&regions, &MissedTextOptions::default());
+    /// assert_eq!(missed[0], MissedText::Uncovered { page: 1, region: sign });
+    /// ```
+    pub fn missed_text(&self, regions: &BTreeMap<u32, Vec<BBox>>, opts: &MissedTextOptions) -> Vec<MissedText> {
+        let mut missed: Vec<MissedText> = self.balloons
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.effective_lines().is_empty() && (b.balloon_img.is_some() || b.bbox.is_some()))
+            .map(|(i, b)| MissedText::Untranslated { balloon: i, btype: b.btype.clone() })
+            .collect();
+
+        for (page, page_regions) in regions {
+            let on_page: Vec<_> = self.balloons.iter().filter(|b| b.page == Some(*page)).collect();
+            let areas: Vec<BBox> = on_page.iter().filter_map(|b| b.bbox).collect();
+
+            for region in page_regions {
+                // Balloons don't overlap much, summing them is close enough
+                let covered: u64 = areas.iter().map(|a| a.overlap(region)).sum();
+                if region.area() > 0 && (covered as f32) < region.area() as f32 * opts.min_coverage {
+                    missed.push(MissedText::Uncovered { page: *page, region: *region });
+                }
+            }
+
+            if (on_page.len() as f32) < page_regions.len() as f32 * opts.min_ratio {
+                missed.push(MissedText::FewBalloons { page: *page, balloons: on_page.len(), regions: page_regions.len() });
+            }
+        }
+
+        missed
+    }
+}
+
+#[cfg(test)]
+mod missed_tests {
+    use super::{MissedText, MissedTextOptions};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+    use crate::layout::BBox;
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn missed_sfx_and_sparse_pages() {
+        let area = |x: u32| BBox { x, y: 0, w: 10, h: 10 };
+        let on = |page: u32, bbox: BBox, b: Balloon| Balloon { page: Some(page), bbox: Some(bbox), ..b };
+
+        let mut d = Document::default();
+        d.balloons.push(on(1, area(0), Balloon::default().with_line(Track::TL, "a")));
+        d.balloons.push(on(1, area(100), Balloon::new(TYPES::SFX)));
+        d.balloons.push(Balloon::default());
+        d.balloons.push(on(2, area(0), Balloon::default().with_line(Track::PR, "b")));
+
+        // Page 1: both regions handled, one of them without text. Page 2: four regions, one balloon
+        let regions = BTreeMap::from([
+            (1, vec![area(2), area(100)]),
+            (2, vec![area(0), area(6), area(50), BBox::default()]),
+        ]);
+
+        let missed = d.missed_text(&regions, &MissedTextOptions::default());
+        assert_eq!(missed, vec![
+            MissedText::Untranslated { balloon: 1, btype: TYPES::SFX },
+            MissedText::Uncovered { page: 2, region: area(6) },
+            MissedText::Uncovered { page: 2, region: area(50) },
+            MissedText::FewBalloons { page: 2, balloons: 1, regions: 4 },
+        ]);
+
+        assert_eq!(area(0).overlap(&area(6)), 40);
+        assert_eq!(area(0).overlap(&area(50)), 0);
+    }
+}
@@ -0,0 +1,110 @@
+//! OpenDocument text export, for teams working in LibreOffice.
+//!
+//! The document has the layout of the txt export ([`Document::to_text`]): type headers and
+//! lines, one paragraph per line, so it reads the same as the txt in a word processor.
+//!
+//! Written by hand as the minimal set of ODF parts, so only compiled with the `odt`
+//! feature, which brings the zip dependency.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_text;
+use crate::export::ExportOptions;
+
+use std::io::{Cursor, Write};
+
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::FileOptions;
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const MANIFEST: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" manifest:version=\"1.2\">\
+<manifest:file-entry manifest:full-path=\"/\" manifest:media-type=\"application/vnd.oasis.opendocument.text\"/>\
+<manifest:file-entry manifest:full-path=\"content.xml\" manifest:media-type=\"text/xml\"/>\
+</manifest:manifest>";
+
+const CONTENT_START: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\" office:version=\"1.2\">\
+<office:body><office:text>";
+
+const CONTENT_END: &str = "</office:text></office:body></office:document-content>";
+
+impl Document {
+    // The content part: a paragraph per txt line.
+    fn odt_content(&self, opts: &ExportOptions) -> String {
+        let mut xml = String::from(CONTENT_START);
+
+        for line in self.to_text(opts).lines() {
+            if line.is_empty() {
+                xml.push_str("<text:p/>");
+            } else {
+                xml.push_str(format!("<text:p>{}</text:p>", escape_text(line)).as_str());
+            }
+        }
+
+        xml.push_str(CONTENT_END);
+
+        xml
+    }
+
+    /// Generates an odt document of the txt export made with `opts`, see [`crate::odt`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    /// use rsff::export::ExportOptions;
+    ///
+    /// let d = Document::from_file("test.sffx").unwrap();
+    /// let bytes = d.to_odt(&ExportOptions::default()).unwrap();
+    ///
+    /// // Or straight to a file
+    /// d.save(Format::ODT, "odt_example").unwrap();
+    /// # std::fs::remove_file("odt_example.odt").unwrap();
+    /// ```
+    pub fn to_odt(&self, opts: &ExportOptions) -> XMLConvertResult<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        // The mimetype comes first and uncompressed, so tools can sniff it
+        zip.start_file("mimetype", FileOptions::default().compression_method(CompressionMethod::Stored))?;
+        zip.write_all(MIMETYPE.as_bytes())?;
+
+        let options = FileOptions::default();
+        for (name, content) in [("META-INF/manifest.xml", MANIFEST.to_string()), ("content.xml", self.odt_content(opts))] {
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod odt_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+    use crate::export::ExportOptions;
+
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn odt_has_txt_layout() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::new(TYPES::SFX).with_line(Track::TL, "a & b"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "c"));
+
+        let bytes = d.to_odt(&ExportOptions::default()).unwrap();
+        assert_eq!(&bytes[30..38], b"mimetype");
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(zip.by_index(0).unwrap().compression(), zip::CompressionMethod::Stored);
+
+        let mut content = String::new();
+        zip.by_name("content.xml").unwrap().read_to_string(&mut content).unwrap();
+        roxmltree::Document::parse(&content).unwrap();
+        assert!(content.contains("<office:text><text:p>FX: a &amp; b</text:p><text:p/><text:p>(): c</text:p></office:text>"));
+    }
+}
@@ -0,0 +1,199 @@
+//! The `.sffp` package: a zip with the xml and the balloon images as files.
+//!
+//! Sffx and sffz files embed images as base64, a third bigger than the images and decoded
+//! into memory with the rest of the xml. A package stores the xml as `document.sffx`
+//! without the image data, and every balloon image as `assets/<balloon index>.<type>`.
+//! Balloons point at their image with `<img type="png" src="assets/3.png"/>`.
+//!
+//! Images replaced with [`Balloon::replace_image`](crate::balloon::Balloon::replace_image)
+//! stay in the xml, they are only kept for undo.
+//!
+//! Only compiled with the `archive` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::io::write_atomic;
+
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::FileOptions;
+
+/// Extension of package files, without the leading dot.
+pub const PACKAGE_EXTENSION: &str = "sffp";
+
+const MANIFEST: &str = "document.sffx";
+
+// Zip entry of the image of balloon `i`. Image types are free text, only keep what is
+// safe in a file name.
+fn asset_name(i: usize, img_type: &str) -> String {
+    let ext: String = img_type.chars().filter(char::is_ascii_alphanumeric).collect();
+    let ext = if ext.is_empty() { "bin".to_string() } else { ext.to_lowercase() };
+
+    format!("assets/{i}.{ext}")
+}
+
+impl Document {
+    /// Saves the document as a package (`<fp>.sffp`), see [`crate::package`]. Like
+    /// [`Document::save`], the file is replaced atomically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.add_image("png".to_string(), vec![1, 2, 3]);
+    /// d.balloons.push(b);
+    ///
+    /// let path = d.save_package("package_example").unwrap();
+    /// assert_eq!(path.to_str(), Some("package_example.sffp"));
+    ///
+    /// let back = Document::open_package("package_example.sffp").unwrap();
+    /// assert_eq!(back.to_xml(), d.to_xml());
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn save_package(&self, fp: &str) -> XMLConvertResult<PathBuf> {
+        let path = PathBuf::from(format!("{fp}.{PACKAGE_EXTENSION}"));
+
+        let xml_options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        // Images are compressed already
+        let img_options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        zip.start_file(MANIFEST, xml_options)?;
+        self.write_xml_with(&mut zip, |i| {
+            self.balloons[i].balloon_img.as_ref().map(|img| asset_name(i, &img.img_type))
+        })?;
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            if let Some(img) = &b.balloon_img {
+                zip.start_file(asset_name(i, &img.img_type), img_options)?;
+                zip.write_all(&img.img_data)?;
+            }
+        }
+
+        let bytes = zip.finish()?.into_inner();
+        write_atomic(&path, &bytes)?;
+
+        Ok(path)
+    }
+
+    /// Opens a package saved with [`Document::save_package`].
+    pub fn open_package(fp: &str) -> XMLConvertResult<Document> {
+        let mut zip = ZipArchive::new(File::open(Path::new(fp))?)?;
+
+        let mut xml = String::new();
+        zip.by_name(MANIFEST)?.read_to_string(&mut xml)?;
+        let tree = roxmltree::Document::parse(&xml)?;
+        let mut d = Document::from_xml_tree(&tree)?;
+
+        // Fill in the images, balloons are in the same order as in the xml
+        let balloons = tree.root_element()
+            .children()
+            .find(|c| c.tag_name().name() == "Balloons")
+            .ok_or("Missing Balloons tag!")?;
+        let nodes: Vec<roxmltree::Node> = balloons.children().filter(|n| n.has_tag_name("Balloon")).collect();
+        if nodes.len() != d.balloons.len() {
+            return Err("Balloon count of the package doesn't match its xml!".into());
+        }
+
+        for (b, node) in d.balloons.iter_mut().zip(nodes) {
+            let src = node.children()
+                .find(|c| c.tag_name().name() == "img")
+                .and_then(|img| img.attribute("src"));
+
+            if let (Some(src), Some(img)) = (src, b.balloon_img.as_mut()) {
+                let mut data: Vec<u8> = Vec::new();
+                zip.by_name(src)?.read_to_end(&mut data)?;
+                img.img_data = Arc::new(data);
+            }
+        }
+
+        Ok(d)
+    }
+}
+
+#[cfg(test)]
+mod package_tests {
+    use super::asset_name;
+    use crate::Document;
+    use crate::balloon::{Balloon, ImageCompression};
+    use crate::consts::Track;
+
+    use std::io::Read;
+
+    #[test]
+    fn package_keeps_images_out_of_the_xml() {
+        assert_eq!(asset_name(4, "../JPG"), "assets/4.jpg");
+        assert_eq!(asset_name(0, ""), "assets/0.bin");
+
+        let mut d = Document::default();
+        for i in 0..3u8 {
+            let mut b = Balloon::default().with_line(Track::TL, format!("line {i}"));
+            match i {
+                0 => b.add_image("png".to_string(), vec![i; 4096]),
+                2 => b.add_image("jpg".to_string(), vec![i; 4096]),
+                _ => {}
+            }
+            d.balloons.push(b);
+        }
+        d.balloons[2].balloon_img.as_mut().unwrap().compression = Some(ImageCompression::Jpeg(80));
+
+        let path = d.save_package("package_test").unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["assets/0.png", "assets/2.jpg", "document.sffx"]);
+
+        let mut xml = String::new();
+        zip.by_name("document.sffx").unwrap().read_to_string(&mut xml).unwrap();
+        assert!(xml.contains("<img type=\"png\" src=\"assets/0.png\"/>"));
+        assert!(xml.contains("<img type=\"jpg\" compression=\"jpeg:80\" src=\"assets/2.jpg\"/>"));
+        assert!(xml.len() < 4096);
+
+        let back = Document::open_package(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(back.to_xml(), d.to_xml());
+        assert!(Document::open_package("package_test.sffp").is_err());
+    }
+
+    #[test]
+    fn package_reads_pretty_printed_xml() {
+        use std::io::Write;
+        use zip::write::FileOptions;
+
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "no image"));
+        let mut b = Balloon::default();
+        b.add_image("png".to_string(), vec![7; 16]);
+        d.balloons.push(b);
+        let path = d.save_package("package_pretty_test").unwrap();
+
+        // Same package with the manifest reformatted by an xml tool
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut xml = String::new();
+        zip.by_name("document.sffx").unwrap().read_to_string(&mut xml).unwrap();
+        let mut img: Vec<u8> = Vec::new();
+        zip.by_name("assets/1.png").unwrap().read_to_end(&mut img).unwrap();
+
+        let mut pretty = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        pretty.start_file("document.sffx", FileOptions::default()).unwrap();
+        pretty.write_all(xml.replace("<Balloon ", "\n    <Balloon ").as_bytes()).unwrap();
+        pretty.start_file("assets/1.png", FileOptions::default()).unwrap();
+        pretty.write_all(&img).unwrap();
+        pretty.finish().unwrap();
+
+        let back = Document::open_package(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(back.balloons[0].balloon_img.is_none());
+        assert_eq!(*back.balloons[1].balloon_img.as_ref().unwrap().img_data, vec![7; 16]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Host for WASM plugins.
+//!
+//! Plugins are small WASM modules exporting one or more hook functions. Every hook takes
+//! and returns bytes:
+//!
+//! | Export           | Input        | Output                                           |
+//! |------------------|--------------|--------------------------------------------------|
+//! | `rsff_qc`        | document xml | one issue per line: `<balloon index>\t<message>` |
+//! | `rsff_transform` | document xml | document xml                                     |
+//! | `rsff_export`    | document xml | exported file content                            |
+//!
+//! rsff doesn't bundle a WASM runtime. Apps implement [`WasmEngine`] on top of the
+//! runtime they already use (wasmtime, wasmer, a browser...), which also decides how
+//! bytes are passed through the module's memory and which limits apply.
+//!
+//! Only compiled with the `plugins` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::qc::QcRule;
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+/// Export name of the QC hook.
+pub const QC_HOOK: &str = "rsff_qc";
+/// Export name of the transform hook.
+pub const TRANSFORM_HOOK: &str = "rsff_transform";
+/// Export name of the exporter hook.
+pub const EXPORT_HOOK: &str = "rsff_export";
+
+/// An instantiated plugin module.
+pub trait Plugin: Send {
+    /// `true` if the module exports the hook `name`.
+    fn has_hook(&self, name: &str) -> bool;
+
+    /// Calls the hook `name` with `input`.
+    fn call(&mut self, name: &str, input: &[u8]) -> XMLConvertResult<Vec<u8>>;
+}
+
+/// A WASM runtime able to instantiate plugin modules.
+pub trait WasmEngine {
+    fn instantiate(&self, wasm: &[u8]) -> XMLConvertResult<Box<dyn Plugin>>;
+}
+
+/// A loaded plugin with its name.
+pub struct LoadedPlugin {
+    /// File name without extension.
+    pub name: String,
+    plugin: Mutex<Box<dyn Plugin>>,
+}
+
+impl LoadedPlugin {
+    /// Wraps an instantiated plugin.
+    pub fn new(name: &str, plugin: Box<dyn Plugin>) -> Self {
+        Self { name: name.to_string(), plugin: Mutex::new(plugin) }
+    }
+
+    /// `true` if the plugin exports the hook `name`.
+    pub fn has_hook(&self, name: &str) -> bool {
+        // The exports of a module don't change, even if a call panicked
+        self.plugin.lock().unwrap_or_else(PoisonError::into_inner).has_hook(name)
+    }
+
+    fn call(&self, name: &str, d: &Document) -> XMLConvertResult<Vec<u8>> {
+        // A call that panicked may have left the module's memory half written
+        let mut plugin = self.plugin
+            .lock()
+            .map_err(|_| format!("Plugin \"{}\" panicked in an earlier call!", self.name))?;
+        if !plugin.has_hook(name) {
+            return Err(format!("Plugin \"{}\" has no {name} hook!", self.name).into());
+        }
+
+        plugin.call(name, d.to_xml().as_bytes())
+    }
+
+    /// Runs the transform hook on `d` and returns the transformed document.
+    pub fn transform(&self, d: &Document) -> XMLConvertResult<Document> {
+        let xml = String::from_utf8(self.call(TRANSFORM_HOOK, d)?)?;
+
+        Document::from_xml(&xml)
+    }
+
+    /// Runs the exporter hook on `d`.
+    pub fn export(&self, d: &Document) -> XMLConvertResult<Vec<u8>> {
+        self.call(EXPORT_HOOK, d)
+    }
+
+    /// Runs the QC hook on `d`.
+    pub fn qc(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+        let out = String::from_utf8(self.call(QC_HOOK, d)?)?;
+        let mut issues: Vec<(usize, String)> = Vec::new();
+
+        for line in out.lines().filter(|l| !l.is_empty()) {
+            let (i, message) = line.split_once('\t').ok_or("Malformed plugin qc output!")?;
+            issues.push((i.parse()?, message.to_string()));
+        }
+
+        Ok(issues)
+    }
+}
+
+impl QcRule for LoadedPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+        self.qc(d)
+    }
+}
+
+/// Loads and keeps plugins.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::{Document, Error};
+/// use rsff::plugin::{Plugin, PluginHost, WasmEngine};
+/// use rsff::qc::QcRule;
+///
+/// // Stand-in for a real runtime: every module flags balloon 0.
+/// struct Engine;
+/// struct Flagger;
+///
+/// impl Plugin for Flagger {
+///     fn has_hook(&self, name: &str) -> bool { name == "rsff_qc" }
+///     fn call(&mut self, _: &str, _: &[u8]) -> Result<Vec<u8>, Error> { Ok(b"0\tcheck me".to_vec()) }
+/// }
+///
+/// impl WasmEngine for Engine {
+///     fn instantiate(&self, _: &[u8]) -> Result<Box<dyn Plugin>, Error> { Ok(Box::new(Flagger)) }
+/// }
+///
+/// let mut host = PluginHost::new(Engine);
+/// host.load_bytes("flagger", b"\0asm").unwrap();
+///
+/// let rules = host.qc_rules();
+/// let results = Document::default().run_qc(&rules).unwrap();
+/// assert_eq!(results[&0].iter().next().unwrap().rule, "flagger");
+/// ```
+pub struct PluginHost<E: WasmEngine> {
+    engine: E,
+    pub plugins: Vec<LoadedPlugin>,
+}
+
+impl<E: WasmEngine> PluginHost<E> {
+    /// Creates a host without plugins.
+    pub fn new(engine: E) -> Self {
+        Self { engine, plugins: Vec::new() }
+    }
+
+    /// Instantiates a module and adds it as `name`.
+    pub fn load_bytes(&mut self, name: &str, wasm: &[u8]) -> XMLConvertResult<&LoadedPlugin> {
+        let plugin = self.engine.instantiate(wasm)?;
+        self.plugins.push(LoadedPlugin::new(name, plugin));
+
+        Ok(self.plugins.last().unwrap())
+    }
+
+    /// Loads the `.wasm` file at `fp`, named after the file.
+    pub fn load(&mut self, fp: &str) -> XMLConvertResult<&LoadedPlugin> {
+        let name = Path::new(fp)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or("Invalid plugin file name!")?
+            .to_string();
+
+        self.load_bytes(&name, &fs::read(fp)?)
+    }
+
+    /// Loads every `.wasm` file in `dir`, in file name order. Returns the number of loaded plugins.
+    pub fn load_dir(&mut self, dir: &str) -> XMLConvertResult<usize> {
+        let mut files: Vec<String> = Vec::new();
+        for e in fs::read_dir(dir)? {
+            let p = e?.path();
+            if p.extension().is_some_and(|e| e == "wasm") {
+                files.push(p.to_string_lossy().to_string());
+            }
+        }
+        files.sort();
+
+        for f in &files {
+            self.load(f)?;
+        }
+
+        Ok(files.len())
+    }
+
+    /// The plugin named `name`.
+    pub fn get(&self, name: &str) -> Option<&LoadedPlugin> {
+        self.plugins.iter().find(|p| p.name == name)
+    }
+
+    /// Every plugin with a QC hook, ready for [`Document::run_qc`].
+    pub fn qc_rules(&self) -> Vec<&dyn QcRule> {
+        self.plugins
+            .iter()
+            .filter(|p| p.has_hook(QC_HOOK))
+            .map(|p| p as &dyn QcRule)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod plugin_tests {
+    use super::{LoadedPlugin, Plugin};
+    use crate::{Document, XMLConvertResult};
+    use crate::qc::QcRule;
+
+    // Uppercases the document, has no other hooks.
+    struct Upper;
+
+    impl Plugin for Upper {
+        fn has_hook(&self, name: &str) -> bool {
+            name == super::TRANSFORM_HOOK
+        }
+
+        fn call(&mut self, _: &str, input: &[u8]) -> XMLConvertResult<Vec<u8>> {
+            let xml = String::from_utf8(input.to_vec())?;
+            Ok(xml.replace("<Info>Num</Info>", "<Info>NUM</Info>").into_bytes())
+        }
+    }
+
+    #[test]
+    fn plugin_hooks() {
+        let p = LoadedPlugin::new("upper", Box::new(Upper));
+
+        assert_eq!(p.transform(&Document::default()).unwrap().METADATA_INFO, "NUM");
+        assert!(p.export(&Document::default()).is_err());
+    }
+
+    // Panics on every call.
+    struct Crashing;
+
+    impl Plugin for Crashing {
+        fn has_hook(&self, _: &str) -> bool {
+            true
+        }
+
+        fn call(&mut self, _: &str, _: &[u8]) -> XMLConvertResult<Vec<u8>> {
+            panic!("out of fuel");
+        }
+    }
+
+    #[test]
+    fn plugin_errors_reach_qc() {
+        let p = LoadedPlugin::new("crashing", Box::new(Crashing));
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| p.qc(&Document::default()))).is_err());
+
+        // Poisoned by the panic
+        assert!(p.has_hook(super::QC_HOOK));
+        let err = Document::default().run_qc(&[&p as &dyn QcRule]).unwrap_err();
+        assert!(err.to_string().contains("panicked"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Gettext PO files, for editing scripts in CAT tools.
+//!
+//! Every balloon is an entry: tl lines in `msgid`, pr lines in `msgstr`, comments as `#.`
+//! extracted comments, and the balloon's index and type in `msgctxt` (`"3:SFX"`), which
+//! also keeps entries with the same text apart. Lines are joined with `\n`.
+//!
+//! Balloons with tlc lines and no pr lines have the tlc lines in `msgstr`, with the `fuzzy`
+//! flag: checked, not proofread yet. Like in txt exports, tlc lines of balloons with pr
+//! lines are not written, see [`Dropped::TlcLines`](crate::export::Dropped::TlcLines).
+//!
+//! On import, `#` translator comments added in the CAT tool become comments too, and the
+//! `msgstr` of fuzzy entries becomes tlc lines.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+
+const PO_HEADER: &str = "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n";
+
+// Quote and escape a PO string.
+fn po_quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+// Content of a quoted PO string.
+fn po_unquote(s: &str) -> XMLConvertResult<String> {
+    let inner = s.trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("Invalid PO string: {s}"))?;
+
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => return Err("PO string ends with a backslash!".into())
+        }
+    }
+
+    Ok(out)
+}
+
+// Lines of a joined PO string, none for an empty one.
+fn po_lines(s: &str) -> Vec<String> {
+    if s.is_empty() {
+        return Vec::new();
+    }
+    s.split('\n').map(String::from).collect()
+}
+
+// An entry being parsed.
+#[derive(Default)]
+struct Entry {
+    comments: Vec<String>,
+    msgctxt: Option<String>,
+    msgid: String,
+    msgstr: String,
+    fuzzy: bool,
+}
+
+impl Entry {
+    // Header entries have an empty msgid and no context.
+    fn is_header(&self) -> bool {
+        self.msgctxt.is_none() && self.msgid.is_empty()
+    }
+
+    fn into_balloon(self) -> Balloon {
+        let btype = self.msgctxt
+            .as_deref()
+            .and_then(|c| c.split_once(':'))
+            .map(|(_, t)| TYPES::from_xml_name(t))
+            .unwrap_or_default();
+
+        let (tlc_content, pr_content) = match self.fuzzy {
+            true => (po_lines(&self.msgstr), Vec::new()),
+            false => (Vec::new(), po_lines(&self.msgstr))
+        };
+
+        Balloon {
+            btype,
+            tl_content: po_lines(&self.msgid),
+            tlc_content,
+            pr_content,
+            comments: self.comments,
+            ..Default::default()
+        }
+    }
+}
+
+impl Document {
+    /// Generates a PO file of the balloons, see [`crate::po`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::{TYPES, Track};
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::new(TYPES::SFX).with_line(Track::TL, "Boom").with_comment("big"));
+    ///
+    /// let po = d.to_po();
+    /// assert!(po.ends_with("#. big\nmsgctxt \"0:SFX\"\nmsgid \"Boom\"\nmsgstr \"\"\n"));
+    ///
+    /// // Proofread in a CAT tool
+    /// let po = po.replace("msgid \"Boom\"\nmsgstr \"\"", "msgid \"Boom\"\nmsgstr \"BOOM\"");
+    /// let back = Document::from_po(&po).unwrap();
+    /// assert_eq!(back.balloons[0].pr_content, ["BOOM"]);
+    /// ```
+    pub fn to_po(&self) -> String {
+        let mut po = String::from(PO_HEADER);
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            po.push('\n');
+            // A comment line without the prefix would end the entry
+            for line in b.comments.iter().flat_map(|c| c.lines()) {
+                po.push_str(format!("#. {line}\n").as_str());
+            }
+
+            let msgstr = if b.pr_content.is_empty() && !b.tlc_content.is_empty() {
+                po.push_str("#, fuzzy\n");
+                &b.tlc_content
+            } else {
+                &b.pr_content
+            };
+            po.push_str(format!(
+                "msgctxt {}\nmsgid {}\nmsgstr {}\n",
+                po_quote(&format!("{i}:{}", b.btype.xml_name())),
+                po_quote(&b.tl_content.join("\n")),
+                po_quote(&msgstr.join("\n"))
+            ).as_str());
+        }
+
+        po
+    }
+
+    /// Generates a document from a PO file made by [`Document::to_po`], balloons in the
+    /// order of the entries. Text is sanitized, see [`Document::sanitize`].
+    pub fn from_po(po: &str) -> XMLConvertResult<Document> {
+        let mut d = Document::default();
+        let mut entry = Entry::default();
+        // String a continuation line belongs to
+        let mut current: Option<&str> = None;
+
+        for line in po.lines().map(str::trim).chain(std::iter::once("")) {
+            // A blank line or a new comment after the strings ends the entry
+            if line.is_empty() || (line.starts_with('#') && current.is_some()) {
+                if current.is_some() && !entry.is_header() {
+                    d.balloons.push(std::mem::take(&mut entry).into_balloon());
+                }
+                entry = Entry::default();
+                current = None;
+                if line.is_empty() {
+                    continue;
+                }
+            }
+
+            if let Some(c) = line.strip_prefix("#.").or_else(|| line.strip_prefix("# ")) {
+                entry.comments.push(c.trim().to_string());
+            } else if let Some(flags) = line.strip_prefix("#,") {
+                entry.fuzzy |= flags.split(',').any(|f| f.trim() == "fuzzy");
+            } else if line.starts_with('#') {
+                // References and previous strings
+                continue;
+            } else if let Some(s) = line.strip_prefix("msgctxt ") {
+                entry.msgctxt = Some(po_unquote(s)?);
+                current = Some("msgctxt");
+            } else if let Some(s) = line.strip_prefix("msgid ") {
+                entry.msgid = po_unquote(s)?;
+                current = Some("msgid");
+            } else if let Some(s) = line.strip_prefix("msgstr ") {
+                entry.msgstr = po_unquote(s)?;
+                current = Some("msgstr");
+            } else if line.starts_with('"') {
+                let s = po_unquote(line)?;
+                match current {
+                    Some("msgctxt") => entry.msgctxt.get_or_insert_with(String::new).push_str(&s),
+                    Some("msgid") => entry.msgid.push_str(&s),
+                    Some("msgstr") => entry.msgstr.push_str(&s),
+                    _ => return Err("PO string outside of an entry!".into())
+                }
+            } else {
+                return Err(format!("Invalid PO line: {line}").into());
+            }
+        }
+
+        d.sanitize();
+
+        Ok(d)
+    }
+
+    /// Opens a PO file, see [`Document::from_po`].
+    #[cfg(feature = "std")]
+    pub fn from_po_file(fp: &str) -> XMLConvertResult<Document> {
+        Document::from_po(&std::fs::read_to_string(fp)?)
+    }
+}
+
+#[cfg(test)]
+mod po_tests {
+    use super::{po_quote, po_unquote};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    #[test]
+    fn po_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::new(TYPES::OT).with_line(Track::TL, "Say \"hi\"").with_line(Track::TL, "a\\b").with_comment("x"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "Same").with_line(Track::PR, "Same!"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "Same"));
+
+        let po = d.to_po();
+        assert!(po.contains("#. x\nmsgctxt \"0:OT\"\nmsgid \"Say \\\"hi\\\"\\na\\\\b\"\nmsgstr \"\"\n"));
+
+        let back = Document::from_po(&po).unwrap();
+        assert_eq!(back.len(), 3);
+        for (a, b) in back.balloons.iter().zip(&d.balloons) {
+            assert_eq!(a.hash(), b.hash());
+        }
+
+        // As CAT tools write it: wrapped strings, flags and translator comments
+        let edited = "msgid \"\"\nmsgstr \"\"\n\n# check this\n#, fuzzy\nmsgctxt \"0:SFX\"\nmsgid \"\"\n\"Bo\"\n\"om\"\nmsgstr \"BOOM\"\n";
+        let back = Document::from_po(edited).unwrap();
+        assert_eq!(back.balloons[0].btype, TYPES::SFX);
+        assert_eq!(back.balloons[0].tl_content, ["Boom"]);
+        assert_eq!(back.balloons[0].tlc_content, ["BOOM"]);
+        assert!(back.balloons[0].pr_content.is_empty());
+        assert_eq!(back.balloons[0].comments, ["check this"]);
+
+        assert_eq!(po_unquote(&po_quote("\t\"\\\n")).unwrap(), "\t\"\\\n");
+        assert!(Document::from_po("msgid oops").is_err());
+    }
+
+    #[test]
+    fn po_keeps_tlc_and_multi_line_comments() {
+        let mut d = Document::default();
+        d.balloons.push(
+            Balloon::default()
+                .with_line(Track::TL, "num")
+                .with_line(Track::TLC, "Num!")
+                .with_comment("first\nsecond")
+        );
+        d.balloons.push(Balloon::default().with_line(Track::TL, "nam").with_line(Track::PR, "Nam."));
+
+        let po = d.to_po();
+        assert!(po.contains("#. first\n#. second\n#, fuzzy\nmsgctxt \"0:Dialogue\"\nmsgid \"num\"\nmsgstr \"Num!\"\n"));
+
+        let back = Document::from_po(&po).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back.balloons[0].tlc_content, ["Num!"]);
+        assert_eq!(back.balloons[0].comments, ["first", "second"]);
+        assert_eq!(back.balloons[1].pr_content, ["Nam."]);
+        assert!(back.balloons[1].tlc_content.is_empty());
+    }
+}
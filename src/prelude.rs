@@ -0,0 +1,23 @@
+//! The stable public API.
+//!
+//! Everything re-exported here keeps working across minor versions. Prefer the accessors
+//! (`Document::balloon`, `Document::balloons`, `Document::push`, `Balloon::new`...) over
+//! the raw struct fields: fields may become private in the next major version, so the
+//! format can grow (ids, pages, status...) without breaking every consumer.
+//!
+//! ```
+//! use rsff::prelude::*;
+//!
+//! let mut d = Document::default();
+//! d.push(Balloon::new(TYPES::DIALOGUE).with_line(Track::TL, "Hello"));
+//!
+//! let texts: Vec<&[String]> = d.balloons().map(|b| b.effective_lines()).collect();
+//! assert_eq!(texts, [["Hello"]]);
+//! ```
+
+pub use crate::{Document, Error};
+pub use crate::balloon::{Balloon, BalloonImage};
+pub use crate::consts::{Format, TYPES, Track};
+pub use crate::export::{ExportFilter, ExportOptions, ExportReport, TextLayout};
+pub use crate::layout::{BBox, Page, Panel};
+pub use crate::query::Query;
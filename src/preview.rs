@@ -0,0 +1,292 @@
+//! PNG previews of the HTML review sheet.
+//!
+//! [`Document::render_preview`] draws the balloons of one page the way
+//! [`Document::to_html`] lays them out: cards with a badge of their type, the balloon
+//! image, tl and pr side by side (tlc in between if there is any) and comments below.
+//! Apps can show what the review sheet will look like without a browser engine.
+//!
+//! The layout is simplified: there is no font rasterizer, so text is drawn as one bar per
+//! word, as long as the word. Line breaks, wrapping and the length of every line are kept,
+//! which is what a reviewer looks at in a thumbnail.
+//!
+//! Balloon images are untrusted input. They are decoded with size limits, and images that
+//! can't be decoded are drawn as a grey box instead of failing the preview.
+//!
+//! Only compiled with the `preview` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::{Balloon, BalloonImage};
+use crate::consts::{TYPES, Track};
+
+use std::io::Cursor;
+
+use image::imageops::FilterType;
+use image::io::{Limits, Reader};
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage};
+
+const MARGIN: u32 = 16;
+const PADDING: u32 = 12;
+const GAP: u32 = 16;
+const LINE_HEIGHT: u32 = 16;
+const CHAR_WIDTH: u32 = 7;
+const BAR_HEIGHT: u32 = 8;
+// Same as `max-height` of images in the html
+const MAX_IMAGE_HEIGHT: u32 = 320;
+// Decoding limit for balloon images, crops are much smaller
+const MAX_IMAGE_SIDE: u32 = 8192;
+
+const WHITE: Rgb<u8> = Rgb([0xff, 0xff, 0xff]);
+const BORDER: Rgb<u8> = Rgb([0xdd, 0xdd, 0xdd]);
+const TEXT: Rgb<u8> = Rgb([0x22, 0x22, 0x22]);
+const HEADING: Rgb<u8> = Rgb([0x88, 0x88, 0x88]);
+const COMMENT: Rgb<u8> = Rgb([0x66, 0x66, 0x66]);
+const MISSING_IMAGE: Rgb<u8> = Rgb([0xee, 0xee, 0xee]);
+
+/// Options of [`Document::render_preview`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreviewOptions {
+    /// Width of the image in pixels. The height follows from the content.
+    pub width: u32,
+    /// Draw the balloon images.
+    pub images: bool,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self { width: 800, images: true }
+    }
+}
+
+// Badge colors of the html.
+fn badge_color(t: &TYPES) -> Rgb<u8> {
+    match t {
+        TYPES::DIALOGUE => Rgb([0x2a, 0x7a, 0xe2]),
+        TYPES::SQUARE => Rgb([0x6b, 0x5b, 0x95]),
+        TYPES::ST => Rgb([0x88, 0x88, 0x88]),
+        TYPES::OT => Rgb([0xc7, 0x7c, 0x02]),
+        TYPES::THINKING => Rgb([0x2a, 0xa1, 0x98]),
+        TYPES::NARRATION => Rgb([0x8a, 0x6d, 0x3b]),
+        TYPES::SFX => Rgb([0xdd, 0x33, 0x33])
+    }
+}
+
+// Decoded image, `None` if it is not an image or is too big.
+fn decode(img: &BalloonImage) -> Option<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+
+    let mut reader = Reader::new(Cursor::new(img.img_data.as_slice())).with_guessed_format().ok()?;
+    reader.limits(limits);
+
+    reader.decode().ok()
+}
+
+// Something to draw, positions are known before the canvas is.
+enum Shape {
+    Rect { x: u32, y: u32, w: u32, h: u32, color: Rgb<u8> },
+    Image { x: u32, y: u32, img: RgbImage },
+}
+
+// Shapes of the page, from top to bottom.
+#[derive(Default)]
+struct Layout {
+    shapes: Vec<Shape>,
+    height: u32,
+}
+
+impl Layout {
+    fn rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+        self.shapes.push(Shape::Rect { x, y, w, h, color });
+    }
+
+    // Word bars of `lines` wrapped to `width`, returns the y below them.
+    fn text(&mut self, lines: &[String], x: u32, mut y: u32, width: u32, color: Rgb<u8>) -> u32 {
+        let offset = (LINE_HEIGHT - BAR_HEIGHT) / 2;
+
+        for line in lines {
+            let mut cx = 0;
+            for word in line.split_whitespace() {
+                let w = (word.chars().count() as u32 * CHAR_WIDTH).min(width);
+                if cx > 0 && cx + w > width {
+                    cx = 0;
+                    y += LINE_HEIGHT;
+                }
+                self.rect(x + cx, y + offset, w, BAR_HEIGHT, color);
+                cx += w + CHAR_WIDTH;
+            }
+            y += LINE_HEIGHT;
+        }
+
+        y
+    }
+
+    // Card of balloon `n` (1-based) at `y`, returns the y below it.
+    fn balloon(&mut self, n: usize, b: &Balloon, y: u32, width: u32, opts: &PreviewOptions) -> u32 {
+        let x = MARGIN + PADDING;
+        let inner = width.saturating_sub(2 * (MARGIN + PADDING)).max(1);
+
+        // The border is a filled rect under the card, sized once the card is done
+        let card = self.shapes.len();
+        self.rect(0, 0, 0, 0, BORDER);
+        self.rect(0, 0, 0, 0, WHITE);
+
+        // Number and badge
+        let mut cy = y + PADDING;
+        let number = (n.to_string().len() as u32 + 1) * CHAR_WIDTH;
+        self.rect(x, cy + 4, number, BAR_HEIGHT, TEXT);
+        let badge = (b.btype.xml_name().len() as u32 + 2) * CHAR_WIDTH;
+        self.rect(x + number + CHAR_WIDTH, cy, badge, LINE_HEIGHT, badge_color(&b.btype));
+        cy += LINE_HEIGHT + PADDING / 2;
+
+        if let Some(img) = b.balloon_img.as_ref().filter(|_| opts.images) {
+            cy += PADDING / 2;
+            match decode(img) {
+                Some(decoded) => {
+                    let img = decoded.resize(inner, MAX_IMAGE_HEIGHT, FilterType::Triangle).to_rgb8();
+                    let h = img.height();
+                    self.shapes.push(Shape::Image { x, y: cy, img });
+                    cy += h;
+                },
+                None => {
+                    self.rect(x, cy, inner.min(MAX_IMAGE_HEIGHT), LINE_HEIGHT * 4, MISSING_IMAGE);
+                    cy += LINE_HEIGHT * 4;
+                }
+            }
+            cy += PADDING / 2;
+        }
+
+        // Tl and pr always get a column so they line up between balloons
+        let tracks: Vec<(Track, &str)> = [(Track::TL, "TL"), (Track::TLC, "TLC"), (Track::PR, "PR")]
+            .into_iter()
+            .filter(|(t, _)| *t != Track::TLC || !b.lines(*t).is_empty())
+            .collect();
+        let column = (inner.saturating_sub(PADDING * (tracks.len() as u32 - 1)) / tracks.len() as u32).max(1);
+        let mut bottom = cy;
+        for (i, (track, name)) in tracks.into_iter().enumerate() {
+            let cx = x + i as u32 * (column + PADDING);
+            self.rect(cx, cy + 4, name.len() as u32 * CHAR_WIDTH, BAR_HEIGHT, HEADING);
+            let end = self.text(b.lines(track), cx, cy + LINE_HEIGHT + 4, column, TEXT);
+            bottom = bottom.max(end);
+        }
+        cy = bottom;
+
+        if !b.comments.is_empty() {
+            cy = self.text(&b.comments, x + PADDING, cy + PADDING / 2, inner.saturating_sub(PADDING).max(1), COMMENT);
+        }
+
+        let bottom = cy + PADDING;
+        let w = width.saturating_sub(2 * MARGIN);
+        self.shapes[card] = Shape::Rect { x: MARGIN, y, w, h: bottom - y, color: BORDER };
+        self.shapes[card + 1] = Shape::Rect { x: MARGIN + 1, y: y + 1, w: w.saturating_sub(2), h: bottom - y - 2, color: WHITE };
+
+        bottom
+    }
+
+    fn draw(self, width: u32) -> RgbImage {
+        let mut canvas = RgbImage::from_pixel(width, self.height.max(1), WHITE);
+
+        for shape in self.shapes {
+            match shape {
+                Shape::Rect { x, y, w, h, color } => {
+                    for py in y..(y + h).min(canvas.height()) {
+                        for px in x..(x + w).min(width) {
+                            canvas.put_pixel(px, py, color);
+                        }
+                    }
+                },
+                Shape::Image { x, y, img } => image::imageops::overlay(&mut canvas, &img, x as i64, y as i64)
+            }
+        }
+
+        canvas
+    }
+}
+
+impl Document {
+    /// Renders the balloons of `page` (`None` for balloons without a page) as they appear
+    /// in the HTML review sheet, as a PNG image. See [`crate::preview`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::preview::PreviewOptions;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon { page: Some(2), ..Default::default() }.with_line(Track::TL, "Hello there"));
+    ///
+    /// let png = d.render_preview(Some(2), &PreviewOptions::default()).unwrap();
+    /// assert!(png.starts_with(b"\x89PNG"));
+    /// ```
+    pub fn render_preview(&self, page: Option<u32>, opts: &PreviewOptions) -> XMLConvertResult<Vec<u8>> {
+        let width = opts.width.max(2 * (MARGIN + PADDING) + 1);
+
+        let mut layout = Layout::default();
+        let mut y = MARGIN;
+        for (i, b) in self.balloons.iter().enumerate().filter(|(_, b)| b.page == page) {
+            y = layout.balloon(i + 1, b, y, width, opts) + GAP;
+        }
+        layout.height = y.saturating_sub(GAP).max(MARGIN) + MARGIN;
+
+        let mut png: Vec<u8> = Vec::new();
+        DynamicImage::ImageRgb8(layout.draw(width)).write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+
+        Ok(png)
+    }
+}
+
+#[cfg(test)]
+mod preview_tests {
+    use super::{BORDER, PreviewOptions, WHITE, badge_color};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+
+    fn render(d: &Document, page: Option<u32>, opts: &PreviewOptions) -> RgbImage {
+        let png = d.render_preview(page, opts).unwrap();
+        image::load_from_memory(&png).unwrap().to_rgb8()
+    }
+
+    #[test]
+    fn preview_follows_the_review_sheet() {
+        let mut crop: Vec<u8> = Vec::new();
+        let red = image::DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 100, Rgb([255, 0, 0])));
+        red.write_to(&mut Cursor::new(&mut crop), ImageOutputFormat::Png).unwrap();
+
+        let mut d = Document::default();
+        let mut b = Balloon { page: Some(1), ..Balloon::new(TYPES::SFX) }
+            .with_line(Track::TL, "Boom")
+            .with_line(Track::PR, "BOOM")
+            .with_comment("louder");
+        b.add_image("png".to_string(), crop);
+        d.balloons.push(b);
+        let mut broken = Balloon { page: Some(1), ..Default::default() }.with_line(Track::TL, "a ".repeat(200));
+        broken.add_image("png".to_string(), vec![1, 2, 3]);
+        d.balloons.push(broken);
+        d.balloons.push(Balloon::default().with_line(Track::TL, "No page"));
+
+        let opts = PreviewOptions { width: 400, ..Default::default() };
+        let page = render(&d, Some(1), &opts);
+        assert_eq!(page.width(), 400);
+        assert_eq!(*page.get_pixel(16, 16), BORDER);
+        assert_eq!(*page.get_pixel(0, 0), WHITE);
+        assert!(page.pixels().any(|p| *p == badge_color(&TYPES::SFX)));
+        assert!(page.pixels().any(|p| *p == Rgb([255, 0, 0])));
+
+        // Images left out, long lines wrap instead of widening the page
+        let no_images = render(&d, Some(1), &PreviewOptions { images: false, ..opts });
+        assert!(!no_images.pixels().any(|p| *p == Rgb([255, 0, 0])));
+        assert!(no_images.height() < page.height());
+
+        let no_page = render(&d, None, &opts);
+        assert!(no_page.height() < no_images.height());
+        assert_eq!(render(&d, Some(9), &opts).height(), 32);
+    }
+}
@@ -0,0 +1,109 @@
+//! How far along a document is.
+
+use crate::Document;
+use crate::balloon::Balloon;
+
+/// How far along a single balloon is: the latest text track it has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// No tl, tlc or pr content.
+    Untranslated,
+    /// Has tl content only.
+    Translated,
+    /// Has tlc content, but no pr content.
+    Checked,
+    /// Has pr content.
+    Proofread,
+}
+
+/// Translation and proofreading progress of a document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Total balloon count.
+    pub balloons: usize,
+    /// Balloons with tl, tlc or pr content.
+    pub translated: usize,
+    /// Balloons with pr content.
+    pub proofread: usize,
+    /// Balloons still carrying merge conflict markers.
+    pub conflicts: usize,
+}
+
+// Percentage, rounded down. An empty document is done.
+fn percent(part: usize, total: usize) -> u8 {
+    (part * 100).checked_div(total).unwrap_or(100) as u8
+}
+
+impl Progress {
+    /// Translated balloons in percent, rounded down.
+    pub fn translated_percent(&self) -> u8 {
+        percent(self.translated, self.balloons)
+    }
+
+    /// Proofread balloons in percent, rounded down.
+    pub fn proofread_percent(&self) -> u8 {
+        percent(self.proofread, self.balloons)
+    }
+}
+
+impl Balloon {
+    /// Status of the balloon, see [`Status`].
+    pub fn status(&self) -> Status {
+        if !self.pr_content.is_empty() {
+            Status::Proofread
+        } else if !self.tlc_content.is_empty() {
+            Status::Checked
+        } else if !self.tl_content.is_empty() {
+            Status::Translated
+        } else {
+            Status::Untranslated
+        }
+    }
+}
+
+impl Document {
+    /// Computes the translation and proofreading progress.
+    pub fn progress(&self) -> Progress {
+        let mut p = Progress { balloons: self.balloons.len(), ..Default::default() };
+
+        for b in &self.balloons {
+            let status = b.status();
+            if status != Status::Untranslated {
+                p.translated += 1;
+            }
+            if status == Status::Proofread {
+                p.proofread += 1;
+            }
+            if b.is_conflicted() {
+                p.conflicts += 1;
+            }
+        }
+
+        p
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn progress_counts() {
+        let mut d = Document::default();
+        assert_eq!(d.progress().proofread_percent(), 100);
+
+        let mut b1 = Balloon::default();
+        b1.tl_content.push("num".to_string());
+        let mut b2 = b1.clone();
+        b2.pr_content.push("nam".to_string());
+        d.balloons.push(b1);
+        d.balloons.push(b2);
+        d.balloons.push(Balloon::default());
+
+        let p = d.progress();
+        assert_eq!((p.balloons, p.translated, p.proofread, p.conflicts), (3, 2, 1, 0));
+        assert_eq!(p.translated_percent(), 66);
+        assert_eq!(p.proofread_percent(), 33);
+    }
+}
@@ -0,0 +1,47 @@
+//! A series made of several chapter documents.
+
+use crate::Document;
+
+/// One chapter of a project.
+#[derive(Clone, Debug, Default)]
+pub struct Chapter {
+    /// Short label shown to people, e.g. "Ch. 45".
+    pub label: String,
+    pub document: Document,
+}
+
+/// A series: its chapters in release order.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::project::Project;
+///
+/// let mut p = Project::new("My Series");
+/// p.add_chapter("Ch. 1", Document::default());
+///
+/// assert_eq!(p.chapters.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Project {
+    pub name: String,
+    pub chapters: Vec<Chapter>,
+}
+
+impl Project {
+    /// Creates a project without chapters.
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), chapters: Vec::new() }
+    }
+
+    /// Adds a chapter at the end.
+    pub fn add_chapter(&mut self, label: &str, document: Document) {
+        self.chapters.push(Chapter { label: label.to_string(), document });
+    }
+
+    /// The chapter with this label.
+    pub fn chapter(&self, label: &str) -> Option<&Chapter> {
+        self.chapters.iter().find(|c| c.label == label)
+    }
+}
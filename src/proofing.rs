@@ -0,0 +1,139 @@
+//! Plain text export for proofreaders, organized by page and panel.
+
+use crate::Document;
+
+use std::collections::BTreeSet;
+
+impl Document {
+    /// Generates a lossy text of the document for proofreading.
+    ///
+    /// Balloons are written like [`Document`]'s `Display` output, but in reading order under
+    /// `== Page n ==` and `-- Panel id --` headers, each followed by its notes (`Note: ...`).
+    /// Balloons without a page come last under `== No page ==`.
+    /// A document without pages is written in document order without any header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::layout::Page;
+    ///
+    /// let mut d = Document::default();
+    /// let mut p = Page::new(1);
+    /// p.notes.push("Redraw the sign".to_string());
+    /// d.pages.push(p);
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("num".to_string());
+    /// b.page = Some(1);
+    /// d.balloons.push(b);
+    ///
+    /// assert_eq!(d.proofing_text(), "== Page 1 ==\nNote: Redraw the sign\n(): num");
+    /// ```
+    pub fn proofing_text(&self) -> String {
+        let mut out: Vec<String> = Vec::new();
+
+        // Pages with notes but no balloons are listed too.
+        let page_numbers: BTreeSet<u32> = self.pages
+            .iter()
+            .map(|p| p.number)
+            .chain(self.balloons.iter().filter_map(|b| b.page))
+            .collect();
+
+        for number in &page_numbers {
+            out.push(format!("== Page {} ==", number));
+
+            let page = self.page(*number);
+            let panels = page.map(|p| p.panels.as_slice()).unwrap_or(&[]);
+
+            if let Some(p) = page {
+                out.extend(p.notes.iter().map(|n| format!("Note: {}", n)));
+            }
+
+            for panel in panels {
+                out.push(format!("-- Panel {} --", panel.id));
+                out.extend(panel.notes.iter().map(|n| format!("Note: {}", n)));
+                out.extend(
+                    self.balloons
+                        .iter()
+                        .filter(|b| b.page == Some(*number) && b.panel == Some(panel.id))
+                        .map(|b| b.to_string())
+                );
+            }
+
+            // Balloons on the page outside of a known panel
+            out.extend(
+                self.balloons
+                    .iter()
+                    .filter(|b| b.page == Some(*number))
+                    .filter(|b| !panels.iter().any(|panel| b.panel == Some(panel.id)))
+                    .map(|b| b.to_string())
+            );
+        }
+
+        let mut unpaged = self.balloons
+            .iter()
+            .filter(|b| b.page.is_none())
+            .peekable();
+
+        if unpaged.peek().is_some() && !page_numbers.is_empty() {
+            out.push(String::from("== No page =="));
+        }
+        out.extend(unpaged.map(|b| b.to_string()));
+
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod proofing_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::layout::{Page, Panel};
+
+    fn balloon(text: &str, page: Option<u32>, panel: Option<u32>) -> Balloon {
+        let mut b = Balloon { page, panel, ..Default::default() };
+        b.tl_content.push(text.to_string());
+        b
+    }
+
+    #[test]
+    fn proofing_text_without_pages() {
+        let mut d = Document::default();
+        d.balloons.push(balloon("num", None, None));
+        d.balloons.push(balloon("nam", None, None));
+
+        assert_eq!(d.proofing_text(), "(): num\n(): nam");
+    }
+
+    #[test]
+    fn proofing_text_with_notes() {
+        let mut d = Document::default();
+
+        let mut p1 = Page::new(1);
+        p1.panels.push(Panel { id: 7, notes: vec!["redraw the sign".to_string()], ..Default::default() });
+        d.pages.push(p1);
+
+        let mut p2 = Page::new(2);
+        p2.notes.push("clean the sfx".to_string());
+        d.pages.push(p2);
+
+        d.balloons.push(balloon("outside", Some(1), None));
+        d.balloons.push(balloon("inside", Some(1), Some(7)));
+        d.balloons.push(balloon("lost", None, None));
+
+        assert_eq!(
+            d.proofing_text(),
+            "== Page 1 ==\n\
+            -- Panel 7 --\n\
+            Note: redraw the sign\n\
+            (): inside\n\
+            (): outside\n\
+            == Page 2 ==\n\
+            Note: clean the sfx\n\
+            == No page ==\n\
+            (): lost"
+        );
+    }
+}
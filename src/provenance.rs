@@ -0,0 +1,192 @@
+//! Where each line of text came from.
+//!
+//! Provenance is stored per line (`<TL src="mt" provider="deepl" model="v2">`) and kept
+//! as it is when the text is edited, so audits like "which lines are still raw machine
+//! output" stay possible. Lines without a recorded source have no provenance.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::{Balloon, escape_attr};
+use crate::consts::Track;
+
+/// How a line came to be.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Provenance {
+    /// Typed by a person.
+    Human,
+    /// Text recognition.
+    Ocr,
+    /// Machine translation.
+    Mt { provider: String, model: String },
+    /// Taken from a translation memory.
+    Tm { id: String },
+}
+
+impl Provenance {
+    // Attributes of a line tag, starting with a space.
+    pub(crate) fn xml_attrs(&self) -> String {
+        match self {
+            Provenance::Human => String::from(" src=\"human\""),
+            Provenance::Ocr => String::from(" src=\"ocr\""),
+            Provenance::Mt { provider, model } => format!(
+                " src=\"mt\" provider=\"{}\" model=\"{}\"",
+                escape_attr(provider),
+                escape_attr(model)
+            ),
+            Provenance::Tm { id } => format!(" src=\"tm\" match=\"{}\"", escape_attr(id))
+        }
+    }
+
+    // Read the attributes of a line tag. `None` if it has no `src`.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<Option<Provenance>> {
+        let attr = |name: &str| node.attribute(name).unwrap_or("").to_string();
+
+        Ok(match node.attribute("src") {
+            None => None,
+            Some("human") => Some(Provenance::Human),
+            Some("ocr") => Some(Provenance::Ocr),
+            Some("mt") => Some(Provenance::Mt { provider: attr("provider"), model: attr("model") }),
+            Some("tm") => Some(Provenance::Tm { id: attr("match") }),
+            Some(_) => return Err("Unknown line source!".into())
+        })
+    }
+}
+
+// Provenance of a balloon as a list of `[[track, line], provenance]` entries,
+// json object keys can't be tuples.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_entries {
+    use super::Provenance;
+    use crate::consts::Track;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    type Entries = BTreeMap<(Track, usize), Provenance>;
+
+    pub(crate) fn serialize<S: Serializer>(map: &Entries, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(map)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Entries, D::Error> {
+        Vec::<((Track, usize), Provenance)>::deserialize(d).map(|v| v.into_iter().collect())
+    }
+}
+
+/// A line of a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRef {
+    /// Index of the balloon.
+    pub balloon: usize,
+    pub track: Track,
+    /// Index of the line in the track.
+    pub line: usize,
+}
+
+impl Balloon {
+    /// Provenance of line `line` of `track`, if recorded.
+    pub fn provenance(&self, track: Track, line: usize) -> Option<&Provenance> {
+        self.provenance.get(&(track, line))
+    }
+
+    /// Appends a line to `track` and records where it came from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::provenance::Provenance;
+    ///
+    /// let mut b = Balloon::default();
+    /// b.push_line(Track::TL, "Hello".to_string(), Provenance::Tm { id: "42".to_string() });
+    ///
+    /// assert_eq!(b.provenance(Track::TL, 0), Some(&Provenance::Tm { id: "42".to_string() }));
+    /// ```
+    pub fn push_line(&mut self, track: Track, text: String, provenance: Provenance) {
+        let lines = match track {
+            Track::TL => &mut self.tl_content,
+            Track::TLC => &mut self.tlc_content,
+            Track::PR => &mut self.pr_content
+        };
+        lines.push(text);
+
+        self.provenance.insert((track, lines.len() - 1), provenance);
+    }
+
+    // Provenance attributes for a line tag, empty if there is none.
+    pub(crate) fn provenance_attrs(&self, track: Track, line: usize) -> String {
+        self.provenance(track, line)
+            .map(Provenance::xml_attrs)
+            .unwrap_or_default()
+    }
+}
+
+impl Document {
+    /// Every existing line whose provenance matches `f`, in document order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::provenance::Provenance;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.push_line(Track::TL, "Hi".to_string(), Provenance::Mt { provider: "deepl".to_string(), model: "v2".to_string() });
+    /// b.push_line(Track::TL, "there".to_string(), Provenance::Human);
+    /// d.balloons.push(b);
+    ///
+    /// // Lines still waiting for a human pass
+    /// let raw = d.lines_with_provenance(|p| matches!(p, Provenance::Mt { .. }));
+    /// assert_eq!(raw.len(), 1);
+    /// assert_eq!(raw[0].line, 0);
+    /// ```
+    pub fn lines_with_provenance<F: Fn(&Provenance) -> bool>(&self, f: F) -> Vec<LineRef> {
+        let mut lines: Vec<LineRef> = Vec::new();
+
+        for (balloon, b) in self.balloons.iter().enumerate() {
+            // Sorted by track, then line
+            for ((track, line), p) in &b.provenance {
+                if *line < b.lines(*track).len() && f(p) {
+                    lines.push(LineRef { balloon, track: *track, line: *line });
+                }
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod provenance_tests {
+    use super::Provenance;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::Track;
+
+    #[test]
+    fn provenance_roundtrip() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("no source".to_string());
+        b.push_line(Track::TL, "a".to_string(), Provenance::Mt { provider: "\"x\" & co".to_string(), model: "m1".to_string() });
+        b.push_line(Track::PR, "b".to_string(), Provenance::Human);
+        d.balloons.push(b);
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<TL>no source</TL><TL src=\"mt\" provider=\"&quot;x&quot; &amp; co\" model=\"m1\">a</TL>"));
+
+        let back = Document::from_xml(&xml).unwrap();
+        assert_eq!(back.balloons[0].provenance, d.balloons[0].provenance);
+        assert_eq!(back.balloons[0].hash(), d.balloons[0].hash());
+        assert!(back.balloons[0].provenance(Track::TL, 0).is_none());
+    }
+
+    #[test]
+    fn provenance_rejects_unknown_source() {
+        let xml = Document::default().to_xml().replace("<Balloons>", "<Balloons><Balloon type=\"OT\"><TL src=\"magic\">a</TL></Balloon>");
+        assert!(Document::from_xml(&xml).is_err());
+    }
+}
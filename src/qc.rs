@@ -0,0 +1,303 @@
+//! Quality checks over documents and whole projects.
+//!
+//! A [`QcRule`] looks at a document and reports issues per balloon. Rules run on a single
+//! document with [`Document::run_qc`], or on every chapter of a project at once with
+//! `Project::run_qc_parallel` (`std` feature).
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::budget::OverBudget;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Something a rule found wrong with a balloon.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QcIssue {
+    /// Name of the rule, see [`QcRule::name`].
+    pub rule: String,
+    pub message: String,
+}
+
+/// A quality check. Rules are shared between threads, so they must be `Sync`.
+pub trait QcRule: Sync {
+    /// Short name shown in reports.
+    fn name(&self) -> &str;
+
+    /// Index and message of every balloon of `d` that breaks the rule.
+    /// Reporting the same balloon and message twice is fine, results are de-duplicated.
+    /// An error means the rule couldn't run at all, e.g. a plugin that crashed.
+    fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>>;
+}
+
+// Index of every balloon for which `f` gives a message.
+fn per_balloon<F: Fn(&Balloon) -> Option<String>>(d: &Document, f: F) -> Vec<(usize, String)> {
+    d.balloons
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| f(b).map(|m| (i, m)))
+        .collect()
+}
+
+/// Balloons without tl, tlc or pr content.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Untranslated;
+
+impl QcRule for Untranslated {
+    fn name(&self) -> &str {
+        "untranslated"
+    }
+
+    fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+        Ok(per_balloon(d, |b| b.effective_lines().is_empty().then(|| String::from("no text"))))
+    }
+}
+
+/// Effective lines with doubled spaces or surrounding whitespace.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Whitespace;
+
+impl QcRule for Whitespace {
+    fn name(&self) -> &str {
+        "whitespace"
+    }
+
+    fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+        let mut issues: Vec<(usize, String)> = Vec::new();
+
+        for (i, b) in d.balloons.iter().enumerate() {
+            for (n, line) in b.effective_lines().iter().enumerate() {
+                if line.contains("  ") {
+                    issues.push((i, format!("line {n}: double space")));
+                }
+                if line.trim() != line {
+                    issues.push((i, format!("line {n}: leading/trailing whitespace")));
+                }
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+/// Balloons over the document's [`Budgets`](crate::budget::Budgets). A page with too many
+/// balloons is reported on every balloon of the page.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget;
+
+impl QcRule for Budget {
+    fn name(&self) -> &str {
+        "budget"
+    }
+
+    fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+        let mut issues: Vec<(usize, String)> = Vec::new();
+
+        for over in d.over_budget() {
+            match over {
+                OverBudget::Chars { index, .. } | OverBudget::Lines { index, .. } => {
+                    issues.push((index, over.to_string()));
+                }
+                OverBudget::Balloons { page, .. } => {
+                    let message = over.to_string();
+                    issues.extend(per_balloon(d, |b| (b.page == Some(page)).then(|| message.clone())));
+                }
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+/// Issues of a document by balloon index.
+pub type QcResults = BTreeMap<usize, BTreeSet<QcIssue>>;
+
+impl Document {
+    /// Runs every rule on the document. Balloons without issues are not listed.
+    /// Stops at the first rule that fails, with its error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::qc::{QcRule, Untranslated, Whitespace};
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default());
+    ///
+    /// let rules: [&dyn QcRule; 2] = [&Untranslated, &Whitespace];
+    /// let results = d.run_qc(&rules).unwrap();
+    ///
+    /// assert_eq!(results[&0].iter().next().unwrap().rule, "untranslated");
+    /// ```
+    pub fn run_qc(&self, rules: &[&dyn QcRule]) -> XMLConvertResult<QcResults> {
+        let mut results = QcResults::new();
+
+        for rule in rules {
+            for (i, message) in rule.check(self)? {
+                results
+                    .entry(i)
+                    .or_default()
+                    .insert(QcIssue { rule: rule.name().to_string(), message });
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::project::Project {
+    /// Runs every rule on every chapter on a pool of worker threads, one per available core.
+    /// Issues are keyed by (chapter index, balloon index). If a rule fails, the chapters left
+    /// are skipped and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::project::Project;
+    /// use rsff::qc::{QcRule, Untranslated};
+    ///
+    /// let mut ch2 = Document::default();
+    /// ch2.balloons.push(Balloon::default());
+    ///
+    /// let mut p = Project::new("My Series");
+    /// p.add_chapter("Ch. 1", Document::default());
+    /// p.add_chapter("Ch. 2", ch2);
+    ///
+    /// let results = p.run_qc_parallel(&[&Untranslated as &dyn QcRule]).unwrap();
+    /// assert_eq!(results.keys().collect::<Vec<_>>(), vec![&(1, 0)]);
+    /// ```
+    pub fn run_qc_parallel(&self, rules: &[&dyn QcRule]) -> XMLConvertResult<BTreeMap<(usize, usize), BTreeSet<QcIssue>>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let next = AtomicUsize::new(0);
+        let results: Mutex<BTreeMap<(usize, usize), BTreeSet<QcIssue>>> = Mutex::new(BTreeMap::new());
+        let failed: Mutex<Option<crate::Error>> = Mutex::new(None);
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(self.chapters.len());
+
+        std::thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| {
+                    // Take chapters one by one, so a huge chapter doesn't hold up the rest
+                    loop {
+                        let c = next.fetch_add(1, Ordering::Relaxed);
+                        let chapter = match self.chapters.get(c) {
+                            Some(ch) if failed.lock().unwrap().is_none() => ch,
+                            _ => break
+                        };
+
+                        let chapter_results = match chapter.document.run_qc(rules) {
+                            Ok(r) => r,
+                            Err(e) => {
+                                failed.lock().unwrap().get_or_insert(e);
+                                break;
+                            }
+                        };
+
+                        let mut all = results.lock().unwrap();
+                        for (i, issues) in chapter_results {
+                            all.entry((c, i)).or_default().extend(issues);
+                        }
+                    }
+                });
+            }
+        });
+
+        match failed.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(results.into_inner().unwrap())
+        }
+    }
+}
+
+#[cfg(test)]
+mod qc_tests {
+    use super::{QcRule, Whitespace};
+    use crate::{Document, XMLConvertResult};
+    use crate::balloon::Balloon;
+
+    // Reports every balloon twice.
+    struct Noisy;
+
+    impl QcRule for Noisy {
+        fn name(&self) -> &str {
+            "noisy"
+        }
+
+        fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+            Ok((0..d.len()).chain(0..d.len()).map(|i| (i, String::from("again"))).collect())
+        }
+    }
+
+    // Fails on documents with balloons.
+    struct Broken;
+
+    impl QcRule for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn check(&self, d: &Document) -> XMLConvertResult<Vec<(usize, String)>> {
+            match d.is_empty() {
+                true => Ok(Vec::new()),
+                false => Err("rule crashed".into())
+            }
+        }
+    }
+
+    #[test]
+    fn qc_deduplicates() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push(" a  b".to_string());
+        d.balloons.push(b);
+
+        let results = d.run_qc(&[&Noisy, &Whitespace]).unwrap();
+        assert_eq!(results[&0].len(), 3);
+        assert_eq!(d.run_qc(&[&Noisy, &Broken]).unwrap_err().to_string(), "rule crashed");
+    }
+
+    #[test]
+    fn qc_budget() {
+        let mut d = Document::default();
+        d.budgets.lines_per_balloon = Some(1);
+        d.budgets.balloons_per_page = Some(2);
+        for page in [1, 1, 1, 2] {
+            d.balloons.push(Balloon { page: Some(page), ..Default::default() });
+        }
+        d.balloons[3].tl_content = vec!["a".to_string(), "b".to_string()];
+
+        let results = d.run_qc(&[&super::Budget]).unwrap();
+        assert_eq!(results.keys().copied().collect::<Vec<usize>>(), vec![0, 1, 2, 3]);
+        assert_eq!(results[&0].iter().next().unwrap().message, "page 1: 3/2 balloons");
+        assert_eq!(results[&3].iter().next().unwrap().message, "balloon 3: 2/1 lines");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn qc_parallel_matches_sequential() {
+        let mut p = crate::project::Project::new("p");
+        for n in 0..20 {
+            let mut d = Document::default();
+            for _ in 0..n % 3 {
+                d.balloons.push(Balloon::default());
+            }
+            p.add_chapter(&format!("Ch. {n}"), d);
+        }
+
+        let rules: [&dyn QcRule; 2] = [&Noisy, &super::Untranslated];
+        let results = p.run_qc_parallel(&rules).unwrap();
+
+        let expected: usize = p.chapters.iter().map(|c| c.document.run_qc(&rules).unwrap().len()).sum();
+        assert_eq!(results.len(), expected);
+        assert!(results.values().all(|issues| issues.len() == 2));
+        assert!(p.run_qc_parallel(&[&Broken]).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! A tiny query language for filtering balloons.
+//!
+//! A query is a list of terms separated by spaces; a balloon matches if it matches every term.
+//! Prefix a term with `-` to negate it. Values with spaces go in double quotes.
+//!
+//! | Term | Matches balloons |
+//! |---|---|
+//! | `type:OT` | of this type (`dialogue`, `square`, `thinking`, `st`, `ot`, `narration`, `sfx`) |
+//! | `status:untranslated` | with this [`Status`], or `conflicted` |
+//! | `has:image` | with `image`, `tl`, `tlc`, `pr`, `comment`, `page`, `panel` or `bbox` |
+//! | `page:3`, `panel:2` | on this page / in this panel |
+//! | `text~"boss"` | whose tl, tlc or pr text contains `boss` (case insensitive) |
+//! | `comment~boss` | whose comments contain `boss` (case insensitive) |
+
+use crate::Document;
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::progress::Status;
+
+use std::fmt;
+
+/// A query that failed to parse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError(pub String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query: {}", self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Type(TYPES),
+    Status(Status),
+    Conflicted,
+    Has(String),
+    Page(u32),
+    Panel(u32),
+    Text(String),
+    Comment(String),
+}
+
+impl Term {
+    fn matches(&self, b: &Balloon) -> bool {
+        match self {
+            Term::Type(t) => b.btype == *t,
+            Term::Status(s) => b.status() == *s,
+            Term::Conflicted => b.is_conflicted(),
+            Term::Has(what) => match what.as_str() {
+                "image" => b.balloon_img.is_some(),
+                "tl" => !b.tl_content.is_empty(),
+                "tlc" => !b.tlc_content.is_empty(),
+                "pr" => !b.pr_content.is_empty(),
+                "comment" => !b.comments.is_empty(),
+                "page" => b.page.is_some(),
+                "panel" => b.panel.is_some(),
+                _ => b.bbox.is_some()
+            },
+            Term::Page(n) => b.page == Some(*n),
+            Term::Panel(id) => b.panel == Some(*id),
+            Term::Text(s) => [&b.tl_content, &b.tlc_content, &b.pr_content]
+                .iter()
+                .any(|lines| contains(lines, s)),
+            Term::Comment(s) => contains(&b.comments, s)
+        }
+    }
+}
+
+// `needle` is lowercase already.
+fn contains(lines: &[String], needle: &str) -> bool {
+    lines.iter().any(|l| l.to_lowercase().contains(needle))
+}
+
+// Split on spaces outside of double quotes, dropping the quotes.
+pub(crate) fn tokenize(q: &str) -> Result<Vec<String>, QueryError> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in q.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            },
+            c => current.push(c)
+        }
+    }
+
+    if quoted {
+        return Err(QueryError(String::from("unclosed quote")));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn parse_term(token: &str) -> Result<Term, QueryError> {
+    let err = || QueryError(format!("unknown term '{token}'"));
+    let num = |v: &str| v.parse::<u32>().map_err(|_| err());
+
+    if let Some((key, value)) = token.split_once('~') {
+        let value = value.to_lowercase();
+        return match key {
+            "text" => Ok(Term::Text(value)),
+            "comment" => Ok(Term::Comment(value)),
+            _ => Err(err())
+        };
+    }
+
+    let (key, value) = token.split_once(':').ok_or_else(err)?;
+    let value = value.to_lowercase();
+
+    match (key, value.as_str()) {
+        ("type", v) => TYPES::all()
+            .iter()
+            .find(|t| t.xml_name().eq_ignore_ascii_case(v))
+            .map(|t| Term::Type(t.clone()))
+            .ok_or_else(err),
+        ("status", "untranslated") => Ok(Term::Status(Status::Untranslated)),
+        ("status", "translated") => Ok(Term::Status(Status::Translated)),
+        ("status", "checked") => Ok(Term::Status(Status::Checked)),
+        ("status", "proofread") => Ok(Term::Status(Status::Proofread)),
+        ("status", "conflicted") => Ok(Term::Conflicted),
+        ("has", "image" | "tl" | "tlc" | "pr" | "comment" | "page" | "panel" | "bbox") => Ok(Term::Has(value)),
+        ("page", v) => Ok(Term::Page(num(v)?)),
+        ("panel", v) => Ok(Term::Panel(num(v)?)),
+        _ => Err(err())
+    }
+}
+
+/// A parsed query. See the [module docs](self) for the syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    // (negated, term)
+    terms: Vec<(bool, Term)>,
+}
+
+impl Query {
+    /// Parses `q`. An empty query matches every balloon.
+    pub fn parse(q: &str) -> Result<Query, QueryError> {
+        let mut terms: Vec<(bool, Term)> = Vec::new();
+
+        for token in tokenize(q)? {
+            match token.strip_prefix('-') {
+                Some(t) => terms.push((true, parse_term(t)?)),
+                None => terms.push((false, parse_term(&token)?))
+            }
+        }
+
+        Ok(Query { terms })
+    }
+
+    /// `true` if `b` matches every term.
+    pub fn matches(&self, b: &Balloon) -> bool {
+        self.terms
+            .iter()
+            .all(|(negated, t)| t.matches(b) != *negated)
+    }
+}
+
+impl Document {
+    /// Indices of the balloons matching the query `q`, in document order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::TYPES;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon { btype: TYPES::OT, ..Default::default() };
+    /// b.comments.push("The boss speaks here".to_string());
+    /// d.balloons.push(b);
+    /// d.balloons.push(Balloon::default());
+    ///
+    /// assert_eq!(d.query("type:OT status:untranslated comment~\"boss speaks\"").unwrap(), vec![0]);
+    /// assert_eq!(d.query("-type:OT").unwrap(), vec![1]);
+    /// ```
+    pub fn query(&self, q: &str) -> Result<Vec<usize>, QueryError> {
+        let query = Query::parse(q)?;
+
+        Ok(self.balloons
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| query.matches(b))
+            .map(|(i, _)| i)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod query_tests {
+    use super::Query;
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn doc() -> Document {
+        let mut d = Document::default();
+
+        let mut b = Balloon { page: Some(1), ..Default::default() };
+        b.tl_content.push("Where is the Boss?".to_string());
+        d.balloons.push(b);
+
+        let mut b = Balloon { page: Some(2), ..Default::default() };
+        b.tl_content.push("num".to_string());
+        b.pr_content.push("nam".to_string());
+        b.add_image("jpg".to_string(), vec![1]);
+        d.balloons.push(b);
+
+        d
+    }
+
+    #[test]
+    fn query_filters() {
+        let d = doc();
+
+        assert_eq!(d.query("").unwrap(), vec![0, 1]);
+        assert_eq!(d.query("text~\"the boss\"").unwrap(), vec![0]);
+        assert_eq!(d.query("has:image status:proofread").unwrap(), vec![1]);
+        assert_eq!(d.query("status:translated -page:2").unwrap(), vec![0]);
+        assert_eq!(d.query("page:3").unwrap(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn query_errors() {
+        assert!(Query::parse("text~\"boss").is_err());
+        assert!(Query::parse("has:everything").is_err());
+        assert!(Query::parse("page:x").is_err());
+        assert!(Query::parse("boss").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Cleaning up line splits of imported scripts.
+//!
+//! Lines are only ever split at whitespace, words are never cut. The same greedy packing
+//! gives [`wrap_preview`], so apps preview breaks exactly as exports make them.
+
+use crate::balloon::Balloon;
+
+// Greedy packing of `words` into lines of at most `width` chars. Longer words get their own line.
+fn pack<'a>(words: &[&'a str], width: usize) -> Vec<Vec<&'a str>> {
+    let mut lines: Vec<Vec<&'a str>> = Vec::new();
+    let mut len = 0;
+
+    for w in words {
+        let w_len = w.chars().count();
+
+        match lines.last_mut() {
+            Some(line) if len + 1 + w_len <= width => {
+                line.push(w);
+                len += 1 + w_len;
+            },
+            _ => {
+                lines.push(vec![w]);
+                len = w_len;
+            }
+        }
+    }
+
+    lines
+}
+
+/// How `text` breaks into lines of at most `max_width_chars` characters.
+///
+/// Explicit line breaks are kept, words longer than the width get a line of their own.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::reflow::wrap_preview;
+///
+/// assert_eq!(wrap_preview("Where do you think\nyou're going?", 10), ["Where do", "you think", "you're", "going?"]);
+/// ```
+pub fn wrap_preview(text: &str, max_width_chars: usize) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                return vec![String::new()];
+            }
+
+            pack(&words, max_width_chars)
+                .iter()
+                .map(|l| l.join(" "))
+                .collect()
+        })
+        .collect()
+}
+
+impl Balloon {
+    /// [`wrap_preview`] of the effective lines, how the text would break in the bubble.
+    pub fn wrap_preview(&self, max_width_chars: usize) -> Vec<String> {
+        wrap_preview(&self.effective_lines().join("\n"), max_width_chars)
+    }
+
+    /// Merges all tl lines into a single line, joined with `separator`.
+    /// Surrounding whitespace of the lines is removed, empty lines are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content = vec!["I don't ".to_string(), "".to_string(), "know".to_string()];
+    ///
+    /// b.join_lines(" ");
+    /// assert_eq!(b.tl_content, vec!["I don't know"]);
+    /// ```
+    pub fn join_lines(&mut self, separator: &str) {
+        if self.tl_content.is_empty() {
+            return;
+        }
+
+        let joined = self.tl_content
+            .iter()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<&str>>()
+            .join(separator);
+
+        self.tl_content = vec![joined];
+    }
+
+    /// Redistributes the words of the tl content over at most `target_lines` lines,
+    /// keeping the longest line as short as possible. Gives fewer lines if there are
+    /// fewer words. A `target_lines` of 0 is treated as 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut b = Balloon::default();
+    /// b.tl_content = vec!["Where".to_string(), "do you think".to_string(), "you're going?".to_string()];
+    ///
+    /// b.reflow_tl(2);
+    /// assert_eq!(b.tl_content, vec!["Where do you think", "you're going?"]);
+    /// ```
+    pub fn reflow_tl(&mut self, target_lines: usize) {
+        let text = self.tl_content.join(" ");
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            return;
+        }
+
+        let target_lines = target_lines.max(1);
+
+        // Smallest width that still fits into the target line count
+        let longest = words.iter().map(|w| w.chars().count()).max().unwrap_or(0);
+        let mut low = longest;
+        let mut high = text.chars().count();
+        while low < high {
+            let mid = (low + high) / 2;
+            if pack(&words, mid).len() <= target_lines {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        self.tl_content = pack(&words, low)
+            .iter()
+            .map(|line| line.join(" "))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod reflow_tests {
+    use super::{pack, wrap_preview};
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn reflow_pack() {
+        assert_eq!(pack(&["a", "bb", "ccc"], 4), vec![vec!["a", "bb"], vec!["ccc"]]);
+        assert_eq!(pack(&["looooong", "a"], 3), vec![vec!["looooong"], vec!["a"]]);
+    }
+
+    #[test]
+    fn reflow_wrap_preview() {
+        assert_eq!(wrap_preview("a bb\n\nccc dd", 4), vec!["a bb", "", "ccc", "dd"]);
+        assert!(wrap_preview("", 4).is_empty());
+
+        let b = Balloon::default().with_line(crate::consts::Track::TL, "a bb ccc").with_line(crate::consts::Track::PR, "dd eee");
+        assert_eq!(b.wrap_preview(2), vec!["dd", "eee"]);
+
+        // Txt exports wrap the same way
+        let mut d = crate::Document::default();
+        d.balloons.push(b);
+        let opts = crate::export::ExportOptions { wrap: Some(2), ..Default::default() };
+        assert_eq!(d.to_text(&opts), "(): dd\n//\n(): eee");
+    }
+
+    #[test]
+    fn reflow_limits() {
+        let mut b = Balloon { tl_content: vec!["one two".to_string(), "three".to_string()], ..Default::default() };
+
+        b.reflow_tl(0);
+        assert_eq!(b.tl_content, vec!["one two three"]);
+
+        b.reflow_tl(5);
+        assert_eq!(b.tl_content, vec!["one", "two", "three"]);
+
+        let mut empty = Balloon::default();
+        empty.reflow_tl(2);
+        empty.join_lines(" ");
+        assert!(empty.tl_content.is_empty());
+    }
+}
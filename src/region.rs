@@ -0,0 +1,139 @@
+//! Comments on an area of a page instead of a balloon.
+//!
+//! Proofreaders point at things that have no balloon yet, like "this sign in the top-left
+//! needs translating". A [`RegionComment`] keeps the rectangle with the comment, so apps
+//! can highlight it and QC can list what is still open until someone resolves it.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_text;
+use crate::layout::{BBox, Page, bbox_attr};
+
+/// A comment anchored to a rectangle of a page.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionComment {
+    pub bbox: BBox,
+    pub text: String,
+    /// Set once the comment was taken care of. Resolved comments are kept for the record.
+    pub resolved: bool,
+}
+
+impl RegionComment {
+    /// Creates an open comment.
+    pub fn new(bbox: BBox, text: &str) -> Self {
+        Self { bbox, text: text.to_string(), resolved: false }
+    }
+
+    /// Generates an xml string of the comment.
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<Region x=\"{}\" y=\"{}\" w=\"{}\" h=\"{}\"{}>{}</Region>",
+            self.bbox.x, self.bbox.y, self.bbox.w, self.bbox.h,
+            if self.resolved { " resolved=\"true\"" } else { "" },
+            escape_text(&self.text)
+        )
+    }
+
+    // Parse a <Region> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<RegionComment> {
+        Ok(RegionComment {
+            bbox: bbox_attr(node)?.unwrap_or_default(),
+            text: node.text().unwrap_or("").to_string(),
+            resolved: node.attribute("resolved") == Some("true"),
+        })
+    }
+}
+
+impl Document {
+    /// Adds a comment on an area of page `page`, adding the page if the document doesn't
+    /// have it. Returns the index of the comment in the page's
+    /// [`regions`](crate::layout::Page::regions).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::layout::BBox;
+    ///
+    /// let mut d = Document::default();
+    /// let i = d.add_region_comment(3, BBox { x: 10, y: 10, w: 200, h: 80 }, "Sign needs translating");
+    /// assert_eq!(d.open_region_comments().len(), 1);
+    ///
+    /// assert!(d.resolve_region_comment(3, i));
+    /// assert!(d.open_region_comments().is_empty());
+    /// ```
+    pub fn add_region_comment(&mut self, page: u32, bbox: BBox, text: &str) -> usize {
+        let i = match self.pages.iter().position(|p| p.number == page) {
+            Some(i) => i,
+            None => {
+                self.pages.push(Page::new(page));
+                self.pages.sort_by_key(|p| p.number);
+                self.pages.iter().position(|p| p.number == page).unwrap_or_default()
+            }
+        };
+
+        let regions = &mut self.pages[i].regions;
+        regions.push(RegionComment::new(bbox, text));
+
+        regions.len() - 1
+    }
+
+    /// Marks comment `index` of page `page` as resolved. `false` if there is no such comment.
+    pub fn resolve_region_comment(&mut self, page: u32, index: usize) -> bool {
+        match self.pages.iter_mut().find(|p| p.number == page).and_then(|p| p.regions.get_mut(index)) {
+            Some(r) => {
+                r.resolved = true;
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Comments not resolved yet, with their page number and index, by page.
+    pub fn open_region_comments(&self) -> Vec<(u32, usize, &RegionComment)> {
+        self.pages
+            .iter()
+            .flat_map(|p| {
+                p.regions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r)| !r.resolved)
+                    .map(move |(i, r)| (p.number, i, r))
+            })
+            .collect()
+    }
+
+    /// Number of region comments, resolved or not.
+    pub fn region_comment_count(&self) -> usize {
+        self.pages.iter().map(|p| p.regions.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod region_tests {
+    use crate::Document;
+    use crate::consts::Format;
+    use crate::export::{Dropped, ExportFilter};
+    use crate::layout::BBox;
+
+    #[test]
+    fn region_comments_round_trip() {
+        let mut d = Document::default();
+        d.add_region_comment(2, BBox { x: 1, y: 2, w: 3, h: 4 }, "a < b");
+        d.add_region_comment(1, BBox::default(), "sign");
+        d.add_region_comment(2, BBox::default(), "sfx");
+        d.resolve_region_comment(2, 0);
+        assert!(!d.resolve_region_comment(1, 5));
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<Region x=\"1\" y=\"2\" w=\"3\" h=\"4\" resolved=\"true\">a &lt; b</Region>"));
+
+        let back = Document::from_xml(&xml).unwrap();
+        assert_eq!(back.pages, d.pages);
+        let open: Vec<(u32, usize)> = back.open_region_comments().iter().map(|(p, i, _)| (*p, *i)).collect();
+        assert_eq!(open, vec![(1, 0), (2, 1)]);
+
+        assert!(d.export_report(Format::TXT).dropped.contains(&Dropped::RegionComments(3)));
+        assert_eq!(d.filtered(&ExportFilter::transcript()).region_comment_count(), 0);
+    }
+}
@@ -0,0 +1,199 @@
+//! Human readable comparison of two releases of the same chapter.
+//!
+//! Only the text that ends up on the page (see [`Balloon::effective_lines`]) is compared,
+//! so the report is suitable for "v2 release" changelog notes.
+
+use crate::Document;
+use crate::balloon::Balloon;
+
+use std::fmt;
+
+/// One textual change between two releases.
+/// Balloon numbers are 1-based, as shown to readers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A balloon only in the new release, numbered in the new release.
+    Added { number: usize, text: String },
+    /// A balloon only in the old release, numbered in the old release.
+    Removed { number: usize, text: String },
+    /// A balloon whose text changed, numbered in the new release.
+    Edited { number: usize, old: String, new: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { number, text } => write!(f, "+ #{number}: \"{text}\""),
+            Change::Removed { number, text } => write!(f, "- #{number}: \"{text}\""),
+            Change::Edited { number, old, new } => write!(f, "~ #{number}: \"{old}\" -> \"{new}\"")
+        }
+    }
+}
+
+/// Summary of the textual changes between two releases.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseReport {
+    pub added: usize,
+    pub removed: usize,
+    pub edited: usize,
+    pub unchanged: usize,
+    /// Changes in reading order.
+    pub changes: Vec<Change>,
+}
+
+impl ReleaseReport {
+    /// `true` if the releases have the same text.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ReleaseReport {
+    /// A summary line followed by one line per change.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} edited, {} added, {} removed", self.edited, self.added, self.removed)?;
+
+        for c in &self.changes {
+            write!(f, "\n{c}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Text of a balloon as it appears in a changelog line.
+fn released_text(b: &Balloon) -> String {
+    b.effective_lines().join(" / ")
+}
+
+// Longest common subsequence table, lcs[i][j] is for old[i..] and new[j..].
+fn lcs_table(old: &[String], new: &[String]) -> Vec<Vec<usize>> {
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    lcs
+}
+
+// Pair up removed and added balloons between two unchanged ones as edits.
+fn flush(report: &mut ReleaseReport, removed: &mut Vec<(usize, String)>, added: &mut Vec<(usize, String)>) {
+    let edits = removed.len().min(added.len());
+
+    for ((_, old), (number, new)) in removed.drain(..edits).zip(added.drain(..edits)) {
+        report.edited += 1;
+        report.changes.push(Change::Edited { number, old, new });
+    }
+    for (number, text) in removed.drain(..) {
+        report.removed += 1;
+        report.changes.push(Change::Removed { number, text });
+    }
+    for (number, text) in added.drain(..) {
+        report.added += 1;
+        report.changes.push(Change::Added { number, text });
+    }
+}
+
+/// Compares two releases of the same chapter.
+///
+/// Balloons are aligned on their text, so inserting or removing a balloon
+/// does not show every following balloon as edited.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::balloon::Balloon;
+/// use rsff::release::compare_releases;
+///
+/// let mut v1 = Document::default();
+/// let mut b = Balloon::default();
+/// b.tl_content.push("Hello".to_string());
+/// v1.balloons.push(b);
+///
+/// let mut v2 = v1.clone();
+/// v2.balloons[0].pr_content.push("Hello!".to_string());
+///
+/// let report = compare_releases(&v1, &v2);
+/// assert_eq!(report.to_string(), "1 edited, 0 added, 0 removed\n~ #1: \"Hello\" -> \"Hello!\"");
+/// ```
+pub fn compare_releases(old: &Document, new: &Document) -> ReleaseReport {
+    let old_texts: Vec<String> = old.balloons.iter().map(released_text).collect();
+    let new_texts: Vec<String> = new.balloons.iter().map(released_text).collect();
+    let lcs = lcs_table(&old_texts, &new_texts);
+
+    let mut report = ReleaseReport::default();
+    let mut removed: Vec<(usize, String)> = Vec::new();
+    let mut added: Vec<(usize, String)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_texts.len() || j < new_texts.len() {
+        if i < old_texts.len() && j < new_texts.len() && old_texts[i] == new_texts[j] {
+            flush(&mut report, &mut removed, &mut added);
+            report.unchanged += 1;
+            i += 1;
+            j += 1;
+        } else if j < new_texts.len() && (i == old_texts.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push((j + 1, new_texts[j].clone()));
+            j += 1;
+        } else {
+            removed.push((i + 1, old_texts[i].clone()));
+            i += 1;
+        }
+    }
+    flush(&mut report, &mut removed, &mut added);
+
+    report
+}
+
+#[cfg(test)]
+mod release_tests {
+    use super::{compare_releases, Change};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    fn doc(texts: &[&str]) -> Document {
+        let mut d = Document::default();
+        for t in texts {
+            let mut b = Balloon::default();
+            b.tl_content.push(t.to_string());
+            d.balloons.push(b);
+        }
+        d
+    }
+
+    #[test]
+    fn compare_identical_releases() {
+        let d = doc(&["num", "nam"]);
+        let r = compare_releases(&d, &d.clone());
+
+        assert!(r.is_empty());
+        assert_eq!(r.unchanged, 2);
+    }
+
+    #[test]
+    fn compare_insert_is_not_an_edit() {
+        let r = compare_releases(&doc(&["a", "b", "c"]), &doc(&["a", "new", "b", "c"]));
+
+        assert_eq!((r.added, r.removed, r.edited, r.unchanged), (1, 0, 0, 3));
+        assert_eq!(r.changes, vec![Change::Added { number: 2, text: "new".to_string() }]);
+    }
+
+    #[test]
+    fn compare_edit_and_remove() {
+        let r = compare_releases(&doc(&["a", "b", "c", "d"]), &doc(&["a", "B", "d"]));
+
+        assert_eq!((r.added, r.removed, r.edited, r.unchanged), (0, 1, 1, 2));
+        assert_eq!(
+            r.to_string(),
+            "1 edited, 0 added, 1 removed\n~ #2: \"b\" -> \"B\"\n- #3: \"c\""
+        );
+    }
+}
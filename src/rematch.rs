@@ -0,0 +1,202 @@
+//! Txt round trips that keep balloon identity.
+//!
+//! With [`ExportOptions::ids`](crate::export::ExportOptions::ids), txt exports put an
+//! `#[id:N]` line before every balloon, N being its index. [`Document::rematch_txt`] reads
+//! the edited file back onto the original balloons, so images, comments and everything
+//! else txt doesn't show survive the trip, even if balloons were moved or deleted.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::{DEFAULT_PRECEDENCE, TYPES, Track};
+
+use std::collections::HashSet;
+
+/// Start of an id marker line, `#[id:42]`.
+pub const ID_MARKER: &str = "#[id:";
+
+// Balloon index of a marker line.
+pub(crate) fn parse_marker(line: &str) -> Option<usize> {
+    line.trim().strip_prefix(ID_MARKER)?.strip_suffix(']')?.parse().ok()
+}
+
+// Type and text of a txt line, `None` if it has no type header.
+fn split_header(line: &str) -> (Option<TYPES>, &str) {
+    let btype = match line.get(..4) {
+        Some("(): ") => TYPES::DIALOGUE,
+        Some("OT: ") => TYPES::OT,
+        Some("[]: ") => TYPES::SQUARE,
+        Some("ST: ") => TYPES::ST,
+        Some("{}: ") => TYPES::THINKING,
+        Some("NR: ") => TYPES::NARRATION,
+        Some("FX: ") => TYPES::SFX,
+        _ => return (None, line.trim())
+    };
+
+    (Some(btype), line[4..].trim())
+}
+
+// A balloon of the edited txt.
+#[derive(Default)]
+struct Block {
+    id: Option<usize>,
+    btype: Option<TYPES>,
+    lines: Vec<String>,
+}
+
+fn blocks(txt: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut current = Block::default();
+
+    for line in txt.lines() {
+        let id = parse_marker(line);
+
+        // A marker or an empty line ends the balloon
+        if id.is_some() || line.trim().is_empty() {
+            if current.id.is_some() || !current.lines.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            current.id = id;
+            continue;
+        }
+
+        // Line separators and notes
+        if line.contains("//") {
+            continue;
+        }
+
+        let (btype, text) = split_header(line);
+        current.btype = current.btype.take().or(btype);
+        current.lines.push(text.to_string());
+    }
+
+    if current.id.is_some() || !current.lines.is_empty() {
+        blocks.push(current);
+    }
+
+    blocks
+}
+
+impl Document {
+    // Txt export with an id marker before every balloon.
+    pub(crate) fn to_string_with_ids(&self) -> String {
+        self.to_string_with_ids_of(0..self.balloons.len())
+    }
+
+    // Like `to_string_with_ids`, with the ids given, e.g. the indices of the balloons of
+    // a delta in the full document.
+    pub(crate) fn to_string_with_ids_of<I: IntoIterator<Item = usize>>(&self, ids: I) -> String {
+        self.balloons
+            .iter()
+            .zip(ids)
+            .map(|(b, i)| format!("{ID_MARKER}{i}]\n{b}"))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    /// Applies a txt file exported with ids to the balloons they come from.
+    ///
+    /// The result has the balloons in the order of the txt. Marked balloons keep everything
+    /// of the original and get the edited lines on the track the txt showed (see
+    /// [`Balloon::effective_lines`]); balloons without a marker are new; balloons whose marker
+    /// was deleted are dropped. A marker used twice only keeps the first balloon's identity.
+    ///
+    /// Fails on markers of balloons the document doesn't have.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    /// use rsff::export::ExportOptions;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Hi").with_comment("keep me"));
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Bye"));
+    ///
+    /// let txt = d.to_text(&ExportOptions::default().with_ids(true));
+    /// assert_eq!(txt, "#[id:0]\n(): Hi\n\n#[id:1]\n(): Bye");
+    ///
+    /// // The proofreader swapped both balloons and fixed one
+    /// let edited = "#[id:1]\n(): Bye!\n\n#[id:0]\n(): Hi";
+    /// let back = d.rematch_txt(edited).unwrap();
+    ///
+    /// assert_eq!(back.balloons[0].tl_content, ["Bye!"]);
+    /// assert_eq!(back.balloons[1].comments, ["keep me"]);
+    /// ```
+    pub fn rematch_txt(&self, txt: &str) -> XMLConvertResult<Document> {
+        let mut d = Document { balloons: Vec::new(), ..self.clone() };
+        let mut used: HashSet<usize> = HashSet::new();
+
+        for block in blocks(txt) {
+            let original = match block.id {
+                Some(id) if id >= self.balloons.len() => return Err(format!("Unknown balloon id {id}!").into()),
+                Some(id) if used.insert(id) => Some(&self.balloons[id]),
+                _ => None
+            };
+
+            let mut b = match original {
+                Some(o) => o.clone(),
+                None => Balloon::default()
+            };
+            if let Some(t) = block.btype {
+                b.btype = t;
+            }
+
+            if b.effective_lines() != block.lines.as_slice() {
+                let track = DEFAULT_PRECEDENCE
+                    .into_iter()
+                    .find(|t| !b.lines(*t).is_empty())
+                    .unwrap_or(Track::TL);
+                let lines = match track {
+                    Track::TL => &mut b.tl_content,
+                    Track::TLC => &mut b.tlc_content,
+                    Track::PR => &mut b.pr_content
+                };
+                *lines = block.lines;
+            }
+
+            d.balloons.push(b);
+        }
+
+        Ok(d)
+    }
+}
+
+#[cfg(test)]
+mod rematch_tests {
+    use super::parse_marker;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+    use crate::export::ExportOptions;
+
+    #[test]
+    fn rematch_keeps_identity() {
+        let mut d = Document::default();
+        let mut b = Balloon::new(TYPES::OT).with_line(Track::TL, "a").with_line(Track::PR, "A");
+        b.add_image("png".to_string(), vec![1]);
+        d.balloons.push(b);
+        d.balloons.push(Balloon::default().with_line(Track::TL, "b").with_line(Track::TL, "c"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "gone"));
+
+        let txt = d.to_text(&ExportOptions { ids: true, ..Default::default() });
+        assert_eq!(Document::from_txt(&txt).unwrap().len(), 3);
+
+        // Unchanged, balloon 2 deleted, a new balloon added
+        let edited = txt.replace("#[id:2]\n(): gone", "FX: Boom");
+        let back = d.rematch_txt(&edited).unwrap();
+        assert_eq!(back.balloons[0].hash(), d.balloons[0].hash());
+        assert_eq!(back.balloons[1].hash(), d.balloons[1].hash());
+        assert_eq!((back.balloons[2].btype.clone(), back.balloons[2].tl_content.clone()), (TYPES::SFX, vec!["Boom".to_string()]));
+
+        // Edits go to the track that was exported
+        let back = d.rematch_txt("#[id:0]\nST: Ah").unwrap();
+        assert_eq!((back.balloons[0].btype.clone(), back.balloons[0].pr_content.clone()), (TYPES::ST, vec!["Ah".to_string()]));
+        assert_eq!(back.balloons[0].tl_content, ["a"]);
+
+        assert!(d.rematch_txt("#[id:7]\n(): ?").is_err());
+        assert_eq!(parse_marker(" #[id:12] "), Some(12));
+        assert_eq!(parse_marker("#[id:x]"), None);
+    }
+}
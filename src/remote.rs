@@ -0,0 +1,289 @@
+//! Read-only access to files through range reads, e.g. HTTP range requests.
+//!
+//! Implement [`RangeSource`] on top of any HTTP client (`Range: bytes=<offset>-<offset + len - 1>`)
+//! to preview documents stored in object storage: [`inspect_remote`] only fetches the
+//! first chunks of a file, and [`RemoteArchive`] only fetches the central directory of a
+//! zip/cbz container plus the entries that are actually read.
+//!
+//! Zip64 archives are not supported.
+//!
+//! Only compiled with the `remote` feature.
+
+use crate::{Document, XMLConvertResult};
+use crate::inspect::{FileInfo, inspect_reader};
+use crate::io::format_of;
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+
+/// Size of the chunks [`inspect_remote`] fetches.
+pub const RANGE_CHUNK: u64 = 16 * 1024;
+
+/// Something that can read arbitrary byte ranges, like a server answering HTTP range requests.
+pub trait RangeSource {
+    /// Total size in bytes.
+    fn len(&mut self) -> io::Result<u64>;
+
+    /// Up to `len` bytes starting at `offset`; fewer only at the end of the source.
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>>;
+
+    /// Returns `true` if the source has no bytes.
+    fn is_empty(&mut self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl<S: RangeSource + ?Sized> RangeSource for &mut S {
+    fn len(&mut self) -> io::Result<u64> {
+        (**self).len()
+    }
+
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        (**self).read_range(offset, len)
+    }
+}
+
+impl RangeSource for File {
+    fn len(&mut self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut buff: Vec<u8> = Vec::new();
+        self.seek(SeekFrom::Start(offset))?;
+        self.take(len).read_to_end(&mut buff)?;
+
+        Ok(buff)
+    }
+}
+
+impl RangeSource for Vec<u8> {
+    fn len(&mut self) -> io::Result<u64> {
+        Ok(Vec::len(self) as u64)
+    }
+
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let start = (offset as usize).min(Vec::len(self));
+        let end = (offset.saturating_add(len) as usize).min(Vec::len(self));
+
+        Ok(self[start..end].to_vec())
+    }
+}
+
+// Sequential reads on top of range reads, one chunk at a time.
+struct RangeReader<'a, S: RangeSource> {
+    src: &'a mut S,
+    offset: u64,
+    buff: Vec<u8>,
+    pos: usize,
+}
+
+impl<S: RangeSource> Read for RangeReader<'_, S> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buff.len() {
+            self.buff = self.src.read_range(self.offset, RANGE_CHUNK)?;
+            self.offset += self.buff.len() as u64;
+            self.pos = 0;
+        }
+
+        let n = out.len().min(self.buff.len() - self.pos);
+        out[..n].copy_from_slice(&self.buff[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+/// [`crate::inspect`] for a remote sffx/sffz file. `name` is only used for its extension.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::remote::inspect_remote;
+///
+/// // Any RangeSource works, e.g. one backed by HTTP range requests.
+/// let src = std::fs::read("test.sffz").unwrap();
+///
+/// let info = inspect_remote(src, "chapters/test.sffz").unwrap();
+/// assert_eq!(info.balloon_count, Some(2));
+/// ```
+pub fn inspect_remote<S: RangeSource>(mut src: S, name: &str) -> XMLConvertResult<FileInfo> {
+    let format = format_of(Path::new(name))?;
+    let byte_size = src.len()?;
+
+    inspect_reader(format, byte_size, RangeReader { src: &mut src, offset: 0, buff: Vec::new(), pos: 0 })
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+const EOCD_SIG: u32 = 0x06054b50;
+const CENTRAL_SIG: u32 = 0x02014b50;
+const LOCAL_SIG: u32 = 0x04034b50;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    name: String,
+    deflated: bool,
+    compressed_size: u64,
+    header_offset: u64,
+}
+
+/// A zip/cbz archive read through range reads.
+#[derive(Debug)]
+pub struct RemoteArchive<S: RangeSource> {
+    src: S,
+    entries: Vec<Entry>,
+}
+
+impl<S: RangeSource> RemoteArchive<S> {
+    /// Reads the central directory of the archive: the end of the file, then the directory itself.
+    pub fn open(mut src: S) -> XMLConvertResult<Self> {
+        let len = src.len()?;
+
+        // End of central directory record: 22 bytes plus a comment of up to 65535 bytes
+        let tail_len = len.min(22 + 65535);
+        let tail = src.read_range(len - tail_len, tail_len)?;
+        let eocd = (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|i| u32_at(&tail, *i) == EOCD_SIG)
+            .ok_or("Not a zip archive!")?;
+
+        let count = u16_at(&tail, eocd + 10) as usize;
+        let dir_size = u32_at(&tail, eocd + 12) as u64;
+        let dir_offset = u32_at(&tail, eocd + 16) as u64;
+        let dir = src.read_range(dir_offset, dir_size)?;
+
+        let mut entries: Vec<Entry> = Vec::with_capacity(count);
+        let mut i = 0;
+        for _ in 0..count {
+            if i + 46 > dir.len() || u32_at(&dir, i) != CENTRAL_SIG {
+                return Err("Corrupt central directory!".into());
+            }
+
+            let name_len = u16_at(&dir, i + 28) as usize;
+            let extra_len = u16_at(&dir, i + 30) as usize;
+            let comment_len = u16_at(&dir, i + 32) as usize;
+            if i + 46 + name_len > dir.len() {
+                return Err("Corrupt central directory!".into());
+            }
+
+            entries.push(Entry {
+                name: String::from_utf8_lossy(&dir[i + 46..i + 46 + name_len]).to_string(),
+                deflated: match u16_at(&dir, i + 10) {
+                    0 => false,
+                    8 => true,
+                    _ => return Err("Unsupported compression method!".into())
+                },
+                compressed_size: u32_at(&dir, i + 20) as u64,
+                header_offset: u32_at(&dir, i + 42) as u64,
+            });
+
+            i += 46 + name_len + extra_len + comment_len;
+        }
+
+        Ok(Self { src, entries })
+    }
+
+    /// Names of all entries, in archive order.
+    pub fn entries(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    /// Fetches and decompresses the entry `name`.
+    pub fn read(&mut self, name: &str) -> XMLConvertResult<Vec<u8>> {
+        let e = self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or("No such entry!")?
+            .clone();
+
+        let header = self.src.read_range(e.header_offset, 30)?;
+        if header.len() < 30 || u32_at(&header, 0) != LOCAL_SIG {
+            return Err("Corrupt local header!".into());
+        }
+
+        // The local header can have a different extra field than the central directory
+        let data_offset = e.header_offset + 30 + u16_at(&header, 26) as u64 + u16_at(&header, 28) as u64;
+        let data = self.src.read_range(data_offset, e.compressed_size)?;
+
+        if e.deflated {
+            let mut out: Vec<u8> = Vec::new();
+            DeflateDecoder::new(&*data).read_to_end(&mut out)?;
+            Ok(out)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Fetches the sffx/sffz/txt entry `name` and generates a document.
+    pub fn document(&mut self, name: &str) -> XMLConvertResult<Document> {
+        let format = format_of(Path::new(name))?;
+        let bytes = self.read(name)?;
+
+        Document::bytes_to_doc(format, bytes)
+    }
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use super::{RangeSource, RemoteArchive, inspect_remote};
+
+    use std::io;
+
+    // Counts how many bytes were fetched.
+    struct Counting(Vec<u8>, u64);
+
+    impl RangeSource for Counting {
+        fn len(&mut self) -> io::Result<u64> {
+            Ok(self.0.len() as u64)
+        }
+
+        fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+            let r = self.0.read_range(offset, len)?;
+            self.1 += r.len() as u64;
+            Ok(r)
+        }
+    }
+
+    #[test]
+    fn remote_inspect_reads_little() {
+        let mut xml = std::fs::read("test.sffx").unwrap();
+        xml.extend(std::iter::repeat_n(b' ', 100_000));
+
+        let mut src = Counting(xml, 0);
+        let info = inspect_remote(&mut src, "test.sffx").unwrap();
+
+        assert_eq!(info.byte_size, 100_000 + std::fs::metadata("test.sffx").unwrap().len());
+        assert_eq!(info.balloon_count, Some(2));
+        assert_eq!(src.1, super::RANGE_CHUNK);
+    }
+
+    #[test]
+    fn remote_rejects_non_archives() {
+        assert!(RemoteArchive::open(b"not a zip".to_vec()).is_err());
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn remote_archive_entries() {
+        let d = crate::Document::default();
+        d.save_bundle("remote_archive_entries.cbz", &["testimg.jpg"]).unwrap();
+        let file = std::fs::File::open("remote_archive_entries.cbz").unwrap();
+
+        let mut archive = RemoteArchive::open(file).unwrap();
+        assert_eq!(archive.entries(), vec!["remote_archive_entries.sffz", "testimg.jpg"]);
+        assert_eq!(archive.document("remote_archive_entries.sffz").unwrap().to_xml(), d.to_xml());
+        assert_eq!(archive.read("testimg.jpg").unwrap(), std::fs::read("testimg.jpg").unwrap());
+
+        std::fs::remove_file("remote_archive_entries.cbz").unwrap();
+    }
+}
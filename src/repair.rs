@@ -0,0 +1,257 @@
+//! Salvaging files written by buggy older versions.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::{Error, XMLConvertResult};
+use crate::consts::Format;
+use crate::io::bytes_to_xml;
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+// Tags holding free text, where old versions wrote `&` and `<` unescaped.
+const TEXT_TAGS: [&str; 8] = ["Script", "App", "Info", "TL", "TLC", "PR", "Comment", "Note"];
+
+/// A defect [`repair`] fixed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fix {
+    /// Number of text elements with unescaped `&`, `<` or `>`.
+    UnescapedText(usize),
+    /// Number of balloons without a `type` attribute, now dialogues.
+    MissingType(usize),
+    /// The file had its extension twice (`ch1.sffz.sffz`) and was renamed.
+    DoubleExtension,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fix::UnescapedText(n) => write!(f, "escaped {n} text element(s)"),
+            Fix::MissingType(n) => write!(f, "added type to {n} balloon(s)"),
+            Fix::DoubleExtension => write!(f, "removed double extension")
+        }
+    }
+}
+
+/// What [`repair`] did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Where the corrected file is. Same as the input unless it was renamed.
+    pub path: PathBuf,
+    /// Empty if the file was fine and nothing was written.
+    pub fixes: Vec<Fix>,
+}
+
+// `true` if `s` starts with an xml entity reference, e.g. `&amp;`.
+fn starts_with_entity(s: &str) -> bool {
+    let end = match s.find(';') {
+        Some(e) if e > 1 => e,
+        _ => return false
+    };
+    let name = &s[1..end];
+
+    match name.strip_prefix('#') {
+        Some(n) => match n.strip_prefix('x') {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())
+        },
+        None => ["amp", "lt", "gt", "quot", "apos"].contains(&name)
+    }
+}
+
+fn escape_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '&' if !starts_with_entity(&s[i..]) => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            c => out.push(c)
+        }
+    }
+
+    out
+}
+
+// Escape the content of every `<tag>...</tag>`. Returns the number of changed elements.
+fn escape_elements(xml: &mut String, tag: &str) -> usize {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let mut changed = 0;
+    let mut from = 0;
+
+    while let Some(start) = xml[from..].find(&open).map(|i| from + i + open.len()) {
+        let end = match xml[start..].find(&close) {
+            Some(i) => start + i,
+            None => break
+        };
+
+        let escaped = escape_text(&xml[start..end]);
+        if escaped != xml[start..end] {
+            xml.replace_range(start..end, &escaped);
+            changed += 1;
+        }
+
+        from = start + escaped.len() + close.len();
+    }
+
+    changed
+}
+
+// Add `type="Dialogue"` to balloons without a type. Returns the number of fixed balloons.
+fn add_missing_types(xml: &mut String) -> usize {
+    let mut fixed = 0;
+    let mut from = 0;
+
+    while let Some(start) = xml[from..].find("<Balloon").map(|i| from + i + "<Balloon".len()) {
+        let end = match xml[start..].find('>') {
+            Some(i) => start + i,
+            None => break
+        };
+
+        // Skip <Balloons>
+        let is_balloon = matches!(xml[start..].chars().next(), Some(' ' | '>' | '/'));
+        if is_balloon && !xml[start..end].contains("type=") {
+            xml.insert_str(start, " type=\"Dialogue\"");
+            fixed += 1;
+        }
+
+        from = start;
+    }
+
+    fixed
+}
+
+// `ch1.sffz.sffz` → `ch1.sffz`
+fn collapse_extension(p: &Path) -> Option<PathBuf> {
+    let ext = p.extension()?;
+    let stem = Path::new(p.file_stem()?);
+
+    if stem.extension()? == ext {
+        Some(p.with_file_name(stem))
+    } else {
+        None
+    }
+}
+
+/// Detects and fixes known defects of files written by older versions, in place:
+/// unescaped `&`/`<`/`>` in text, balloons without a `type` and doubled extensions.
+///
+/// The original file is kept next to the corrected one with a `.bak` suffix.
+/// Files without defects are left untouched.
+///
+/// # Examples
+///
+/// ```
+/// std::fs::write(
+///     "repair_example.sffx",
+///     "<Document><Metadata><Script>v0.1</Script><App></App><Info>Tom & Jerry</Info></Metadata>\
+///     <Balloons><Balloon><TL>num</TL></Balloon></Balloons></Document>"
+/// ).unwrap();
+///
+/// let report = rsff::repair("repair_example.sffx").unwrap();
+/// assert_eq!(report.fixes.len(), 2);
+///
+/// let d = rsff::Document::from_file("repair_example.sffx").unwrap();
+/// assert_eq!(d.METADATA_INFO, "Tom & Jerry");
+/// # std::fs::remove_file("repair_example.sffx").unwrap();
+/// # std::fs::remove_file("repair_example.sffx.bak").unwrap();
+/// ```
+pub fn repair(fp: &str) -> XMLConvertResult<RepairReport> {
+    let p = Path::new(fp);
+    let format = crate::io::format_of(p)?;
+    if !matches!(format, Format::RAW | Format::ZLIB) {
+        return Err(Error::UnsupportedExtension(format.extension().to_string()));
+    }
+
+    let mut xml = bytes_to_xml(format, fs::read(p)?)?;
+    let mut fixes: Vec<Fix> = Vec::new();
+
+    let escaped: usize = TEXT_TAGS.iter().map(|t| escape_elements(&mut xml, t)).sum();
+    if escaped > 0 {
+        fixes.push(Fix::UnescapedText(escaped));
+    }
+
+    let typed = add_missing_types(&mut xml);
+    if typed > 0 {
+        fixes.push(Fix::MissingType(typed));
+    }
+
+    let path = match collapse_extension(p) {
+        Some(collapsed) => {
+            fixes.push(Fix::DoubleExtension);
+            collapsed
+        },
+        None => p.to_path_buf()
+    };
+
+    if fixes.is_empty() {
+        return Ok(RepairReport { path, fixes });
+    }
+
+    // Don't write anything we can't read back
+    roxmltree::Document::parse(&xml)?;
+
+    let bytes = match format {
+        Format::ZLIB => {
+            let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
+            enc.write_all(xml.as_bytes())?;
+            enc.finish()?
+        },
+        _ => xml.into_bytes()
+    };
+
+    fs::rename(p, format!("{fp}.bak"))?;
+    fs::write(&path, bytes)?;
+
+    Ok(RepairReport { path, fixes })
+}
+
+#[cfg(test)]
+mod repair_tests {
+    use super::{add_missing_types, collapse_extension, escape_text, repair, Fix};
+    use crate::Document;
+
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn repair_escapes_only_bare_characters() {
+        assert_eq!(escape_text("a & b &amp; &#38; &#x26; <3 &c"), "a &amp; b &amp; &#38; &#x26; &lt;3 &amp;c");
+    }
+
+    #[test]
+    fn repair_missing_types() {
+        let mut xml = String::from("<Balloons><Balloon><TL>a</TL></Balloon><Balloon type=\"OT\"/><Balloon/></Balloons>");
+        assert_eq!(add_missing_types(&mut xml), 2);
+        assert_eq!(xml, "<Balloons><Balloon type=\"Dialogue\"><TL>a</TL></Balloon><Balloon type=\"OT\"/><Balloon type=\"Dialogue\"/></Balloons>");
+    }
+
+    #[test]
+    fn repair_double_extension() {
+        assert_eq!(collapse_extension(Path::new("a/ch1.sffz.sffz")), Some(PathBuf::from("a/ch1.sffz")));
+        assert_eq!(collapse_extension(Path::new("a/ch1.sffz")), None);
+
+        let d = Document { METADATA_INFO: String::from("<b>"), ..Default::default() };
+        // Unescaped, like older versions wrote it
+        let old_xml = d.to_xml().replace("&lt;b&gt;", "<b>");
+        std::fs::write("repair_double_extension.sffx.sffx", old_xml).unwrap();
+
+        let report = repair("repair_double_extension.sffx.sffx").unwrap();
+        assert_eq!(report.fixes, vec![Fix::UnescapedText(1), Fix::DoubleExtension]);
+
+        let fixed = Document::from_file("repair_double_extension.sffx").unwrap();
+        assert_eq!(fixed.METADATA_INFO, "<b>");
+
+        // Nothing left to fix
+        assert!(repair("repair_double_extension.sffx").unwrap().fixes.is_empty());
+
+        std::fs::remove_file("repair_double_extension.sffx").unwrap();
+        std::fs::remove_file("repair_double_extension.sffx.sffx.bak").unwrap();
+    }
+}
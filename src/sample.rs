@@ -0,0 +1,141 @@
+//! Random balloon samples for spot-check proofreading.
+//!
+//! Huge chapters are rarely proofread line by line twice. A sample picks `n` balloons at
+//! random and comes back as a document, so it goes through the usual exporters. The same
+//! seed always gives the same sample, so a checker and a reviewer can look at the same
+//! balloons without sending the list around.
+
+use crate::Document;
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+
+use std::collections::HashMap;
+
+/// How likely each balloon is to be picked.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SampleWeight {
+    /// Every balloon is as likely.
+    #[default]
+    Uniform,
+    /// Balloons with more text are more likely, empty ones are never picked.
+    Length,
+    /// Weight per type. Missing types weigh 1, a weight of 0 leaves a type out.
+    Types(HashMap<TYPES, u32>),
+}
+
+impl SampleWeight {
+    fn weight(&self, b: &Balloon) -> f64 {
+        match self {
+            SampleWeight::Uniform => 1.0,
+            SampleWeight::Length => b.effective_lines().iter().map(|l| l.chars().count()).sum::<usize>() as f64,
+            SampleWeight::Types(w) => *w.get(&b.btype).unwrap_or(&1) as f64
+        }
+    }
+}
+
+// SplitMix64, small and the same on every platform.
+struct SplitMix(u64);
+
+impl SplitMix {
+    // A float in (0, 1].
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        ((z >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Document {
+    /// Indices of `n` balloons picked at random with `weight`, in document order.
+    /// Fewer if the document has fewer balloons that can be picked.
+    pub fn sample_indices(&self, n: usize, seed: u64, weight: &SampleWeight) -> Vec<usize> {
+        let mut rng = SplitMix(seed);
+
+        // Weighted sampling without replacement: the n largest u^(1/w)
+        let mut keys: Vec<(f64, usize)> = self.balloons
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| {
+                let u = rng.next();
+                let w = weight.weight(b);
+                (w > 0.0).then(|| (u.powf(1.0 / w), i))
+            })
+            .collect();
+        keys.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let mut picked: Vec<usize> = keys.into_iter().take(n).map(|(_, i)| i).collect();
+        picked.sort_unstable();
+
+        picked
+    }
+
+    /// Copy of the document with `n` balloons picked at random, see [`crate::sample`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// for i in 0..100 {
+    ///     d.balloons.push(Balloon::default().with_line(Track::TL, format!("line {i}").as_str()));
+    /// }
+    ///
+    /// let sample = d.sample_balloons(10, 42);
+    /// assert_eq!(sample.len(), 10);
+    /// assert_eq!(sample.to_string(), d.sample_balloons(10, 42).to_string());
+    /// ```
+    pub fn sample_balloons(&self, n: usize, seed: u64) -> Document {
+        self.sample_balloons_weighted(n, seed, &SampleWeight::Uniform)
+    }
+
+    /// [`Document::sample_balloons`] with balloons picked by `weight`.
+    pub fn sample_balloons_weighted(&self, n: usize, seed: u64, weight: &SampleWeight) -> Document {
+        let picked = self.sample_indices(n, seed, weight);
+
+        let mut d = self.clone();
+        d.balloons = picked.into_iter().map(|i| self.balloons[i].clone()).collect();
+
+        d
+    }
+}
+
+#[cfg(test)]
+mod sample_tests {
+    use super::SampleWeight;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn sample_is_reproducible() {
+        let mut d = Document::default();
+        for i in 0..50 {
+            let btype = if i % 2 == 0 { TYPES::SFX } else { TYPES::DIALOGUE };
+            d.balloons.push(Balloon::new(btype).with_line(Track::TL, "x".repeat(i).as_str()));
+        }
+
+        let a = d.sample_indices(5, 7, &SampleWeight::Uniform);
+        assert_eq!(a.len(), 5);
+        assert!(a.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(a, d.sample_indices(5, 7, &SampleWeight::Uniform));
+        assert_ne!(a, d.sample_indices(5, 8, &SampleWeight::Uniform));
+
+        // Empty balloons weigh nothing by length
+        assert!(!d.sample_indices(49, 1, &SampleWeight::Length).contains(&0));
+        assert_eq!(d.sample_indices(100, 1, &SampleWeight::Length).len(), 49);
+
+        let no_sfx = SampleWeight::Types(HashMap::from([(TYPES::SFX, 0)]));
+        let sample = d.sample_balloons_weighted(100, 3, &no_sfx);
+        assert_eq!(sample.len(), 25);
+        assert!(sample.balloons.iter().all(|b| b.btype == TYPES::DIALOGUE));
+    }
+}
@@ -0,0 +1,189 @@
+//! Cleaning text that comes from other tools.
+//!
+//! Csv, txt and PO imports may carry nulls, control characters or line breaks from whatever
+//! produced them. Such text can't be written to xml, and a line with a newline in it
+//! breaks txt exports. Those imports go through [`Document::sanitize`]; the lossless
+//! formats (xml, JSON) keep text as written.
+
+use std::collections::BTreeMap;
+
+use crate::Document;
+use crate::consts::Track;
+use crate::provenance::Provenance;
+
+// Characters xml 1.0 doesn't allow, tabs and carriage returns are fine.
+fn is_invalid(c: char) -> bool {
+    (c.is_control() && c != '\t' && c != '\r') || c == '\u{FFFE}' || c == '\u{FFFF}'
+}
+
+/// `line` without control characters (tabs are kept). Newlines (`\n` or `\r\n`) are
+/// removed too, use [`sanitize_lines`] to keep them as line breaks. A lone `\r` is kept,
+/// xml exports write it as `&#13;`.
+pub fn sanitize_line(line: &str) -> String {
+    line.replace("\r\n", "\n").chars().filter(|c| *c != '\n' && !is_invalid(*c)).collect()
+}
+
+/// `lines` with every character removed by [`sanitize_line`], except that newlines
+/// (`\n` or `\r\n`) inside a line split it in several lines.
+pub fn sanitize_lines(lines: &[String]) -> Vec<String> {
+    split_lines(lines).into_iter().map(|(_, l)| l).collect()
+}
+
+// The sanitized lines, each with the index of the line it came from.
+fn split_lines(lines: &[String]) -> Vec<(usize, String)> {
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, l)| l.split('\n').map(move |part| (i, sanitize_line(part.strip_suffix('\r').unwrap_or(part)))))
+        .collect()
+}
+
+/// What imports that trim lines do with leading and trailing whitespace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Trim it, for text typed by hand.
+    #[default]
+    Trim,
+    /// Keep it, e.g. for intentional indentation with spaces or em spaces in pr text.
+    /// Only a trailing `\r` of Windows line endings is removed.
+    Preserve,
+}
+
+impl Whitespace {
+    pub(crate) fn apply<'a>(&self, line: &'a str) -> &'a str {
+        match self {
+            Whitespace::Trim => line.trim(),
+            Whitespace::Preserve => line.strip_suffix('\r').unwrap_or(line)
+        }
+    }
+}
+
+impl Document {
+    /// Sanitizes the text and comments of every balloon, see [`sanitize_lines`]. Speakers
+    /// are kept on one line. Called by the csv, txt and PO imports, e.g.
+    /// [`Document::from_csv_with`]. Every part of a split line keeps its provenance.
+    ///
+    /// Returns the number of balloons that changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Hi\0\r\nthere\u{7}"));
+    ///
+    /// assert_eq!(d.sanitize(), 1);
+    /// assert_eq!(d.balloons[0].tl_content, ["Hi", "there"]);
+    /// ```
+    pub fn sanitize(&mut self) -> usize {
+        let mut changed = 0;
+
+        for b in self.balloons.iter_mut() {
+            let mut dirty = false;
+
+            for track in [Track::TL, Track::TLC, Track::PR] {
+                let lines = match track {
+                    Track::TL => &mut b.tl_content,
+                    Track::TLC => &mut b.tlc_content,
+                    Track::PR => &mut b.pr_content
+                };
+                let clean = split_lines(lines);
+                if clean.iter().map(|(_, l)| l).ne(lines.iter()) {
+                    // A split line moves the ones after it, every part keeps its source
+                    let sources: BTreeMap<usize, Provenance> = b.provenance.iter().filter(|((t, _), _)| *t == track).map(|((_, i), p)| (*i, p.clone())).collect();
+                    b.provenance.retain(|(t, _), _| *t != track);
+                    for (i, (from, _)) in clean.iter().enumerate() {
+                        if let Some(p) = sources.get(from) {
+                            b.provenance.insert((track, i), p.clone());
+                        }
+                    }
+
+                    *lines = clean.into_iter().map(|(_, l)| l).collect();
+                    dirty = true;
+                }
+            }
+
+            let clean = sanitize_lines(&b.comments);
+            if clean != b.comments {
+                b.comments = clean;
+                dirty = true;
+            }
+
+            if let Some(speaker) = &mut b.speaker {
+                let clean = sanitize_line(speaker);
+                if clean != *speaker {
+                    *speaker = clean;
+                    dirty = true;
+                }
+            }
+
+            if dirty {
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod sanitize_tests {
+    use super::Whitespace;
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+    use crate::provenance::Provenance;
+    use crate::txt::TxtImportOptions;
+
+    #[test]
+    fn imports_are_sanitized() {
+        let csv = "index,type,tl,comment\n0,SFX,\"a\0b\r\nc\rd\",\"x\u{1b}[31m\"\n";
+        let d = Document::from_csv(csv, ',').unwrap();
+        assert_eq!(d.balloons[0].btype, TYPES::SFX);
+        assert_eq!(d.balloons[0].tl_content, ["ab", "c\rd"]);
+        assert_eq!(d.balloons[0].comments, ["x[31m"]);
+
+        let d = Document::from_txt("(): a\u{b}\tb\u{FFFF}").unwrap();
+        assert_eq!(d.balloons[0].tl_content, ["a\tb"]);
+
+        // The result is valid xml
+        let back = Document::from_xml(&d.to_xml()).unwrap();
+        assert_eq!(back.balloons[0].tl_content, ["a\tb"]);
+    }
+
+    #[test]
+    fn split_lines_keep_provenance() {
+        let mut b = Balloon::default();
+        b.push_line(Track::TL, "one\ntwo".to_string(), Provenance::Ocr);
+        b.push_line(Track::TL, "three".to_string(), Provenance::Human);
+        b.push_line(Track::PR, "kept".to_string(), Provenance::Ocr);
+        let mut d = Document::default();
+        d.balloons.push(b);
+
+        assert_eq!(d.sanitize(), 1);
+        let b = &d.balloons[0];
+        assert_eq!(b.tl_content, ["one", "two", "three"]);
+        assert_eq!(b.provenance(Track::TL, 0), Some(&Provenance::Ocr));
+        assert_eq!(b.provenance(Track::TL, 1), Some(&Provenance::Ocr));
+        assert_eq!(b.provenance(Track::TL, 2), Some(&Provenance::Human));
+        assert_eq!(b.provenance(Track::PR, 0), Some(&Provenance::Ocr));
+        assert_eq!(b.provenance.len(), 4);
+    }
+
+    #[test]
+    fn whitespace_survives_round_trips() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::PR, "  indented").with_line(Track::PR, "\u{2003}em").with_line(Track::PR, "   "));
+
+        let xml = d.to_xml();
+        assert!(xml.contains("<PR xml:space=\"preserve\">  indented</PR><PR>\u{2003}em</PR>"));
+        assert_eq!(Document::from_xml(&xml).unwrap().balloons[0].pr_content, d.balloons[0].pr_content);
+
+        let opts = TxtImportOptions { whitespace: Whitespace::Preserve, ..Default::default() };
+        let txt = Document::from_txt_with(&d.to_string(), &opts).unwrap();
+        assert_eq!(txt.balloons[0].tl_content, ["  indented", "\u{2003}em", "   "]);
+    }
+}
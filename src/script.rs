@@ -0,0 +1,209 @@
+//! Small scripts for custom checks and bulk edits.
+//!
+//! A script is a list of commands, one per line. Commands work on a selection of
+//! balloons that starts with every balloon; `#` starts a comment line.
+//!
+//! | Command | Does |
+//! |---|---|
+//! | `filter <query>` | keeps the selected balloons matching the [query](crate::query) |
+//! | `all` | selects every balloon again |
+//! | `flag <message>` | reports the selected balloons with `message` |
+//! | `replace <from> <to>` | replaces text in tl, tlc and pr lines (quote values with spaces) |
+//! | `remove <text>` | removes text from tl, tlc and pr lines |
+//! | `trim` | removes surrounding whitespace of tl, tlc and pr lines |
+//! | `type <name>` | changes the balloon type (`Dialogue`, `Square`, `Thinking`, `ST`, `OT`, `Narration`, `SFX`) |
+//!
+//! Scripts can only touch balloon text and types, never files, so running scripts
+//! from other people is safe. Finalized documents (see [`crate::finalize`]) can only be
+//! checked: editing commands fail with [`Locked`]'s message.
+
+use crate::Document;
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::finalize::Locked;
+use crate::query::{Query, tokenize};
+
+use std::fmt;
+
+/// A script that failed, with its 1-based line number.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "script line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// What a script did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScriptReport {
+    /// Balloon index and message of every `flag`, in order.
+    pub flags: Vec<(usize, String)>,
+    /// Indices of the balloons that were edited, sorted.
+    pub changed: Vec<usize>,
+}
+
+// Apply `f` to every text line of `b`. `true` if a line changed.
+fn edit_lines<F: Fn(&str) -> String>(b: &mut Balloon, f: F) -> bool {
+    let mut changed = false;
+
+    for lines in [&mut b.tl_content, &mut b.tlc_content, &mut b.pr_content] {
+        for line in lines.iter_mut() {
+            let new = f(line);
+            if new != *line {
+                *line = new;
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+impl Document {
+    /// Runs the script `src` on the document, see [`crate::script`].
+    /// Nothing is changed if the script has an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut d = Document::default();
+    /// for line in ["Senpai!", "  Onii-chan...", "Hi"] {
+    ///     let mut b = Balloon::default();
+    ///     b.tl_content.push(line.to_string());
+    ///     d.balloons.push(b);
+    /// }
+    ///
+    /// let report = d.run_script("
+    ///     ## house style
+    ///     trim
+    ///     replace Onii-chan \"Big bro\"
+    ///     filter text~senpai
+    ///     flag honorific left in
+    /// ").unwrap();
+    ///
+    /// assert_eq!(d.balloons[1].tl_content[0], "Big bro...");
+    /// assert_eq!(report.flags, vec![(0, "honorific left in".to_string())]);
+    /// assert_eq!(report.changed, vec![1]);
+    /// ```
+    pub fn run_script(&mut self, src: &str) -> Result<ScriptReport, ScriptError> {
+        let mut d = self.clone();
+        let mut report = ScriptReport::default();
+        let mut selection: Vec<usize> = (0..d.len()).collect();
+
+        for (n, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |message: String| ScriptError { line: n + 1, message };
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let args = tokenize(rest).map_err(|e| err(e.0))?;
+
+            if d.is_final() && matches!(command, "replace" | "remove" | "trim" | "type") {
+                return Err(err(Locked.to_string()));
+            }
+
+            let mut edit = |f: &dyn Fn(&str) -> String| {
+                for i in &selection {
+                    if edit_lines(&mut d.balloons[*i], f) {
+                        report.changed.push(*i);
+                    }
+                }
+            };
+
+            match (command, args.as_slice()) {
+                ("filter", _) => {
+                    let q = Query::parse(rest).map_err(|e| err(e.to_string()))?;
+                    selection.retain(|i| q.matches(&d.balloons[*i]));
+                },
+                ("all", []) => selection = (0..d.len()).collect(),
+                ("flag", [_, ..]) => {
+                    report.flags.extend(selection.iter().map(|i| (*i, rest.to_string())));
+                },
+                ("replace", [from, to]) => edit(&|l| l.replace(from.as_str(), to)),
+                ("remove", [text]) => edit(&|l| l.replace(text.as_str(), "")),
+                ("trim", []) => edit(&|l| l.trim().to_string()),
+                ("type", [name]) => {
+                    let t = TYPES::all()
+                        .iter()
+                        .find(|t| t.xml_name().eq_ignore_ascii_case(name))
+                        .ok_or_else(|| err(format!("unknown type \"{name}\"")))?;
+                    for i in &selection {
+                        if d.balloons[*i].btype != *t {
+                            d.balloons[*i].btype = t.clone();
+                            report.changed.push(*i);
+                        }
+                    }
+                },
+                ("all" | "flag" | "replace" | "remove" | "trim" | "type", _) => {
+                    return Err(err(format!("wrong arguments for \"{command}\"")));
+                },
+                _ => return Err(err(format!("unknown command \"{command}\"")))
+            }
+        }
+
+        report.changed.sort_unstable();
+        report.changed.dedup();
+        *self = d;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::TYPES;
+
+    #[test]
+    fn script_types_and_selection() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon { btype: TYPES::ST, ..Default::default() });
+        d.balloons.push(Balloon::default());
+
+        let report = d.run_script("filter type:st\ntype OT\nall\nflag checked").unwrap();
+        assert_eq!(d.balloons[0].btype, TYPES::OT);
+        assert_eq!(report.changed, vec![0]);
+        assert_eq!(report.flags.len(), 2);
+    }
+
+    #[test]
+    fn script_errors_leave_document_alone() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon { btype: TYPES::ST, ..Default::default() });
+
+        let e = d.run_script("type OT\nreplace a").unwrap_err();
+        assert_eq!(e.to_string(), "script line 2: wrong arguments for \"replace\"");
+        assert_eq!(d.balloons[0].btype, TYPES::ST);
+
+        assert_eq!(d.run_script("\n\nexec rm").unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn script_refuses_finalized_documents() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(crate::consts::Track::TL, " a "));
+        d.finalize();
+
+        assert_eq!(d.run_script("flag checked").unwrap().flags.len(), 1);
+
+        let e = d.run_script("flag checked\ntrim").unwrap_err();
+        assert_eq!(e.line, 2);
+        assert!(e.message.contains("finalized"));
+        assert_eq!(d.balloons[0].tl_content[0], " a ");
+        assert!(d.late_edits().is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Renderer settings stored in the document metadata.
+//!
+//! Typesetting tools read their preferences from here instead of keeping a
+//! sidecar file next to every chapter.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_attr;
+use crate::consts::{Format, TYPES};
+use crate::layout::num_attr;
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Order in which pages and balloons are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReadingDirection {
+    /// Western comics.
+    LeftToRight,
+    /// Manga.
+    RightToLeft,
+    /// Webtoons.
+    TopToBottom,
+}
+
+impl ReadingDirection {
+    fn xml_name(&self) -> &'static str {
+        match self {
+            ReadingDirection::LeftToRight => "ltr",
+            ReadingDirection::RightToLeft => "rtl",
+            ReadingDirection::TopToBottom => "ttb"
+        }
+    }
+
+    fn from_xml_name(name: &str) -> Option<ReadingDirection> {
+        match name {
+            "ltr" => Some(ReadingDirection::LeftToRight),
+            "rtl" => Some(ReadingDirection::RightToLeft),
+            "ttb" => Some(ReadingDirection::TopToBottom),
+            _ => None
+        }
+    }
+}
+
+/// Per-document preferences for renderers. Everything is optional.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocumentSettings {
+    /// Preferred font size (pt) per balloon type.
+    pub font_sizes: HashMap<TYPES, u32>,
+    /// Font family per balloon type, e.g. `"CC Wild Words"` for dialogue.
+    pub fonts: HashMap<TYPES, String>,
+    /// Format to export to unless the user picks another one.
+    pub export: Option<Format>,
+    pub direction: Option<ReadingDirection>,
+}
+
+impl DocumentSettings {
+    /// `true` if nothing is set.
+    pub fn is_empty(&self) -> bool {
+        *self == DocumentSettings::default()
+    }
+
+    /// Preferred font size for balloons of type `t`, if set.
+    pub fn font_size(&self, t: &TYPES) -> Option<u32> {
+        self.font_sizes.get(t).copied()
+    }
+
+    /// Font family for balloons of type `t`, if set.
+    pub fn font(&self, t: &TYPES) -> Option<&str> {
+        self.fonts.get(t).map(String::as_str)
+    }
+
+    /// Generates an xml string of the settings.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Settings");
+
+        if let Some(d) = self.direction {
+            xml.push_str(format!(" direction=\"{}\"", d.xml_name()).as_str());
+        }
+        if let Some(e) = self.export {
+            xml.push_str(format!(" export=\"{}\"", e.extension()).as_str());
+        }
+
+        xml.push('>');
+
+        // Fixed order, so saving twice gives the same file
+        for t in TYPES::all() {
+            if let Some(size) = self.font_size(t) {
+                xml.push_str(format!("<FontSize type=\"{}\" size=\"{}\"/>", t.xml_name(), size).as_str());
+            }
+        }
+
+        for t in TYPES::all() {
+            if let Some(family) = self.font(t) {
+                xml.push_str(format!("<Font type=\"{}\" family=\"{}\"/>", t.xml_name(), escape_attr(family)).as_str());
+            }
+        }
+
+        xml.push_str("</Settings>");
+
+        xml
+    }
+
+    // Parse a <Settings> tag.
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> XMLConvertResult<DocumentSettings> {
+        let mut s = DocumentSettings {
+            direction: match node.attribute("direction") {
+                Some(d) => Some(ReadingDirection::from_xml_name(d).ok_or("Unknown reading direction!")?),
+                None => None
+            },
+            export: match node.attribute("export") {
+                Some(e) => Some(Format::from_extension(e).ok_or("Unknown export format!")?),
+                None => None
+            },
+            ..Default::default()
+        };
+
+        for c in node.children().filter(|c| c.tag_name().name() == "FontSize") {
+            let t = TYPES::from_xml_name(c.attribute("type").unwrap_or(""));
+            let size = num_attr(c, "size")?.ok_or("Font size without size!")?;
+            s.font_sizes.insert(t, size);
+        }
+
+        for c in node.children().filter(|c| c.tag_name().name() == "Font") {
+            let t = TYPES::from_xml_name(c.attribute("type").unwrap_or(""));
+            let family = c.attribute("family").ok_or("Font without family!")?;
+            s.fonts.insert(t, family.to_string());
+        }
+
+        Ok(s)
+    }
+}
+
+impl Document {
+    /// Preferred font size for balloon `i`, from the settings. `None` if the index is
+    /// out of bounds or no size is set for its type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::TYPES;
+    /// use rsff::settings::ReadingDirection;
+    ///
+    /// let mut d = Document::default();
+    /// d.settings.font_sizes.insert(TYPES::DIALOGUE, 24);
+    /// d.settings.direction = Some(ReadingDirection::RightToLeft);
+    /// d.balloons.push(Balloon::default());
+    ///
+    /// let back = Document::from_xml(&d.to_xml()).unwrap();
+    /// assert_eq!(back.font_size(0), Some(24));
+    /// assert_eq!(back.settings.direction, Some(ReadingDirection::RightToLeft));
+    /// ```
+    pub fn font_size(&self, i: usize) -> Option<u32> {
+        self.balloons.get(i).and_then(|b| self.settings.font_size(&b.btype))
+    }
+
+    /// Every font family the balloons of the document use, sorted and without duplicates,
+    /// so the typesetter knows what to install before starting the chapter.
+    /// Types without a font in the settings are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::TYPES;
+    ///
+    /// let mut d = Document::default();
+    /// d.settings.fonts.insert(TYPES::DIALOGUE, "CC Wild Words".to_string());
+    /// d.settings.fonts.insert(TYPES::THINKING, "CC Wild Words".to_string());
+    /// d.settings.fonts.insert(TYPES::SFX, "Blambot FX".to_string());
+    /// d.balloons.push(Balloon::new(TYPES::DIALOGUE));
+    /// d.balloons.push(Balloon::new(TYPES::THINKING));
+    ///
+    /// // No SFX balloon in this chapter
+    /// assert_eq!(d.required_fonts(), vec!["CC Wild Words"]);
+    /// ```
+    pub fn required_fonts(&self) -> Vec<String> {
+        let fonts: BTreeSet<&str> = self.balloons
+            .iter()
+            .filter_map(|b| self.settings.font(&b.btype))
+            .collect();
+
+        fonts.into_iter().map(String::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::{DocumentSettings, ReadingDirection};
+    use crate::Document;
+    use crate::consts::{Format, TYPES};
+
+    #[test]
+    fn settings_roundtrip() {
+        let mut settings = DocumentSettings {
+            export: Some(Format::RAW),
+            direction: Some(ReadingDirection::TopToBottom),
+            ..Default::default()
+        };
+        settings.font_sizes.insert(TYPES::OT, 30);
+        settings.font_sizes.insert(TYPES::DIALOGUE, 22);
+        settings.fonts.insert(TYPES::SFX, "Num & Nam".to_string());
+
+        assert_eq!(
+            settings.to_xml(),
+            "<Settings direction=\"ttb\" export=\"sffx\"><FontSize type=\"Dialogue\" size=\"22\"/><FontSize type=\"OT\" size=\"30\"/>\
+            <Font type=\"SFX\" family=\"Num &amp; Nam\"/></Settings>"
+        );
+
+        let d = Document { settings, ..Default::default() };
+        let back = Document::from_xml(&d.to_xml()).unwrap();
+        assert_eq!(back.settings, d.settings);
+    }
+
+    #[test]
+    fn settings_not_written_when_empty() {
+        assert!(!Document::default().to_xml().contains("<Settings"));
+
+        let xml = Document::default().to_xml().replace("</Metadata>", "<Settings direction=\"up\"></Settings></Metadata>");
+        assert!(Document::from_xml(&xml).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Read-only copies of a [`Document`] that threads can share.
+//!
+//! Renderers and exporters can work on a shared copy while editing continues on the
+//! original document.
+
+use crate::Document;
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A read-only copy of a document at a point in time, clonable without copying.
+///
+/// Making one clones the document once: balloon text and metadata are copied, image
+/// bytes are shared with the original (see [`BalloonImage`](crate::balloon::BalloonImage)).
+/// Cloning the `SharedDocument` afterwards only bumps a reference count. Later edits
+/// on the document never show up in it: image data is copied on write.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::balloon::Balloon;
+///
+/// let mut d = Document::default();
+/// d.balloons.push(Balloon::default());
+///
+/// let shared = d.to_shared();
+/// d.balloons.clear();
+///
+/// assert_eq!(shared.len(), 1);
+/// assert_eq!(d.len(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedDocument(Arc<Document>);
+
+impl SharedDocument {
+    /// Turns the shared copy back into an editable document.
+    /// Only copies the text if other clones of it are still alive.
+    pub fn into_document(self) -> Document {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl Deref for SharedDocument {
+    type Target = Document;
+
+    fn deref(&self) -> &Document {
+        &self.0
+    }
+}
+
+impl Document {
+    /// Clones the document into a [`SharedDocument`]. Copies the text, not the images.
+    pub fn to_shared(&self) -> SharedDocument {
+        SharedDocument(Arc::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod shared_tests {
+    use std::sync::Arc;
+
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn shared_document_shares_images() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        d.balloons.push(b);
+
+        let shared = d.to_shared();
+        let clone = shared.clone();
+
+        assert!(Arc::ptr_eq(
+            &d.balloons[0].balloon_img.as_ref().unwrap().img_data,
+            &shared.balloons[0].balloon_img.as_ref().unwrap().img_data
+        ));
+        assert!(std::ptr::eq(&*shared, &*clone));
+    }
+
+    #[test]
+    fn shared_document_copy_on_write() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.add_image("jpg".to_string(), vec![1, 2, 3]);
+        b.tl_content.push("num".to_string());
+        d.balloons.push(b);
+
+        let shared = d.to_shared();
+
+        d.balloons[0].balloon_img.as_mut().unwrap().data_mut().push(4);
+        d.balloons[0].tl_content[0] = "nam".to_string();
+
+        assert_eq!(*shared.balloons[0].balloon_img.as_ref().unwrap().img_data, vec![1, 2, 3]);
+        assert_eq!(shared.balloons[0].tl_content[0], "num");
+        assert_eq!(d.balloons[0].balloon_img.as_ref().unwrap().data(), [1, 2, 3, 4]);
+    }
+}
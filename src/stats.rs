@@ -0,0 +1,294 @@
+//! Document statistics in formats spreadsheets and charting tools understand.
+
+use crate::Document;
+use crate::csv::quote_cell;
+use crate::progress::Status;
+use crate::project::Project;
+
+use std::iter::Sum;
+use std::ops::Add;
+
+const CSV_HEADER: &str = "balloons,lines,tl_chars,tlc_chars,pr_chars,comment_chars,images,translated,proofread,over_budget";
+
+/// Counts of a document. Character counts include spaces, as [`Document::tl_chars`] does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DocumentStats {
+    pub balloons: usize,
+    /// See [`Document::line_count`].
+    pub lines: usize,
+    pub tl_chars: usize,
+    pub tlc_chars: usize,
+    pub pr_chars: usize,
+    pub comment_chars: usize,
+    /// Balloons with an image.
+    pub images: usize,
+    /// Balloons with tl, tlc or pr content.
+    pub translated: usize,
+    /// Balloons with pr content.
+    pub proofread: usize,
+    /// Exceeded budgets, see [`Document::over_budget`].
+    pub over_budget: usize,
+}
+
+impl DocumentStats {
+    // Values in CSV_HEADER order.
+    fn values(&self) -> [usize; 10] {
+        [
+            self.balloons,
+            self.lines,
+            self.tl_chars,
+            self.tlc_chars,
+            self.pr_chars,
+            self.comment_chars,
+            self.images,
+            self.translated,
+            self.proofread,
+            self.over_budget,
+        ]
+    }
+
+    fn csv_row(&self) -> String {
+        self.values()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    // Attributes named like the CSV header, starting with a space.
+    #[cfg(feature = "std")]
+    pub(crate) fn xml_attrs(&self) -> String {
+        CSV_HEADER
+            .split(',')
+            .zip(self.values())
+            .map(|(name, value)| format!(" {name}=\"{value}\""))
+            .collect()
+    }
+
+    // Read the attributes written by `xml_attrs`. Missing ones are 0.
+    #[cfg(feature = "std")]
+    pub(crate) fn from_xml_node(node: roxmltree::Node) -> crate::XMLConvertResult<DocumentStats> {
+        let attr = |name: &str| -> crate::XMLConvertResult<usize> {
+            Ok(node.attribute(name).map(str::parse).transpose()?.unwrap_or(0))
+        };
+
+        Ok(DocumentStats {
+            balloons: attr("balloons")?,
+            lines: attr("lines")?,
+            tl_chars: attr("tl_chars")?,
+            tlc_chars: attr("tlc_chars")?,
+            pr_chars: attr("pr_chars")?,
+            comment_chars: attr("comment_chars")?,
+            images: attr("images")?,
+            translated: attr("translated")?,
+            proofread: attr("proofread")?,
+            over_budget: attr("over_budget")?,
+        })
+    }
+
+    /// Header line and a single row of values, newline terminated.
+    pub fn to_csv(&self) -> String {
+        format!("{CSV_HEADER}\n{}\n", self.csv_row())
+    }
+
+    #[cfg(feature = "json")]
+    fn json_value(&self) -> serde_json::Value {
+        let mut obj = serde_json::Map::new();
+        for (name, value) in CSV_HEADER.split(',').zip(self.values()) {
+            obj.insert(name.to_string(), value.into());
+        }
+
+        serde_json::Value::Object(obj)
+    }
+
+    /// JSON object with the same keys as the CSV header.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        self.json_value().to_string()
+    }
+}
+
+impl Add for DocumentStats {
+    type Output = DocumentStats;
+
+    fn add(self, o: DocumentStats) -> DocumentStats {
+        DocumentStats {
+            balloons: self.balloons + o.balloons,
+            lines: self.lines + o.lines,
+            tl_chars: self.tl_chars + o.tl_chars,
+            tlc_chars: self.tlc_chars + o.tlc_chars,
+            pr_chars: self.pr_chars + o.pr_chars,
+            comment_chars: self.comment_chars + o.comment_chars,
+            images: self.images + o.images,
+            translated: self.translated + o.translated,
+            proofread: self.proofread + o.proofread,
+            over_budget: self.over_budget + o.over_budget,
+        }
+    }
+}
+
+impl Sum for DocumentStats {
+    fn sum<I: Iterator<Item = DocumentStats>>(iter: I) -> DocumentStats {
+        iter.fold(DocumentStats::default(), Add::add)
+    }
+}
+
+impl Document {
+    /// Counts of the document.
+    pub fn stats(&self) -> DocumentStats {
+        let mut s = DocumentStats {
+            balloons: self.len(),
+            lines: self.line_count(),
+            tl_chars: self.tl_chars(),
+            tlc_chars: self.tlc_chars(),
+            pr_chars: self.pr_chars(),
+            comment_chars: self.comment_chars(),
+            over_budget: self.over_budget().len(),
+            ..Default::default()
+        };
+
+        for b in &self.balloons {
+            if b.balloon_img.is_some() {
+                s.images += 1;
+            }
+
+            let status = b.status();
+            if status != Status::Untranslated {
+                s.translated += 1;
+            }
+            if status == Status::Proofread {
+                s.proofread += 1;
+            }
+        }
+
+        s
+    }
+}
+
+impl Project {
+    /// Stats of every chapter, in order, labeled with the chapter label.
+    pub fn stats(&self) -> Vec<(String, DocumentStats)> {
+        self.chapters
+            .iter()
+            .map(|c| (c.label.clone(), c.document.stats()))
+            .collect()
+    }
+
+    /// Sum of the stats of all chapters.
+    pub fn total_stats(&self) -> DocumentStats {
+        self.chapters
+            .iter()
+            .map(|c| c.document.stats())
+            .sum()
+    }
+
+    /// One row per chapter and a last `Total` row, with a leading `chapter` column.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::project::Project;
+    ///
+    /// let mut p = Project::new("My Series");
+    /// p.add_chapter("Ch. 1", Document::default());
+    ///
+    /// let csv = p.stats_to_csv();
+    /// assert_eq!(csv.lines().count(), 3);
+    /// assert!(csv.ends_with("Total,0,0,0,0,0,0,0,0,0,0\n"));
+    /// ```
+    pub fn stats_to_csv(&self) -> String {
+        let mut csv = format!("chapter,{CSV_HEADER}\n");
+
+        for (label, s) in self.stats() {
+            csv.push_str(format!("{},{}\n", quote_cell(&label, ','), s.csv_row()).as_str());
+        }
+        csv.push_str(format!("Total,{}\n", self.total_stats().csv_row()).as_str());
+
+        csv
+    }
+
+    /// `{"project": .., "chapters": [{"chapter": .., ..stats}], "total": {..stats}}`.
+    #[cfg(feature = "json")]
+    pub fn stats_to_json(&self) -> String {
+        let chapters: Vec<serde_json::Value> = self.stats()
+            .into_iter()
+            .map(|(label, s)| {
+                let mut v = s.json_value();
+                v["chapter"] = label.into();
+                v
+            })
+            .collect();
+
+        serde_json::json!({
+            "project": self.name,
+            "chapters": chapters,
+            "total": self.total_stats().json_value()
+        }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::project::Project;
+
+    fn doc() -> Document {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("num".to_string());
+        b.pr_content.push("namnam".to_string());
+        b.add_image("jpg".to_string(), vec![1]);
+        d.balloons.push(b);
+        d.balloons.push(Balloon::default());
+        d
+    }
+
+    #[test]
+    fn stats_csv() {
+        assert_eq!(
+            doc().stats().to_csv(),
+            "balloons,lines,tl_chars,tlc_chars,pr_chars,comment_chars,images,translated,proofread,over_budget\n\
+            2,1,3,0,6,0,1,1,1,0\n"
+        );
+    }
+
+    #[test]
+    fn stats_project_total() {
+        let mut p = Project::new("My Series");
+        p.add_chapter("Ch. 1, part 1", doc());
+        p.add_chapter("Ch. 2", doc());
+
+        assert_eq!(p.total_stats().balloons, 4);
+        assert!(p.stats_to_csv().contains("\n\"Ch. 1, part 1\",2,1,3,0,6,0,1,1,1,0\n"));
+    }
+
+    #[test]
+    fn stats_over_budget() {
+        let mut d = doc();
+        assert_eq!(d.stats().over_budget, 0);
+
+        d.budgets.chars_per_balloon = Some(4);
+        d.budgets.balloons_per_page = Some(1);
+        for b in &mut d.balloons {
+            b.page = Some(1);
+        }
+
+        assert_eq!(d.stats().over_budget, 2);
+        assert_eq!((d.stats() + d.stats()).over_budget, 4);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn stats_json() {
+        let v: serde_json::Value = serde_json::from_str(&doc().stats().to_json()).unwrap();
+        assert_eq!(v["pr_chars"], 6);
+
+        let mut p = Project::new("My Series");
+        p.add_chapter("Ch. 1", doc());
+        let v: serde_json::Value = serde_json::from_str(&p.stats_to_json()).unwrap();
+        assert_eq!(v["chapters"][0]["chapter"], "Ch. 1");
+        assert_eq!(v["total"]["images"], 1);
+    }
+}
@@ -0,0 +1,161 @@
+//! Checks against publisher rules for official localization submissions.
+//!
+//! Publishers reject files breaking their house rules, so [`Document::check_submission`]
+//! gives a pass/fail report before sending. Only the text that ends up on the page
+//! (see [`Balloon::effective_lines`](crate::balloon::Balloon::effective_lines)) is checked.
+
+use crate::Document;
+
+use std::fmt;
+
+/// Rules of a publisher.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionRules {
+    /// Maximum number of characters per balloon, line breaks not counted.
+    pub max_chars: Option<usize>,
+    /// Characters that must not appear anywhere, e.g. `~` or `♥`.
+    pub forbidden: Vec<char>,
+    /// Lines that must appear in some balloon, e.g. `"Translation: Num"`.
+    pub credits: Vec<String>,
+}
+
+/// A broken rule. Balloon numbers are 1-based, as shown to readers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    TooLong { number: usize, chars: usize, max: usize },
+    Forbidden { number: usize, c: char },
+    MissingCredit(String),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::TooLong { number, chars, max } => write!(f, "#{number}: {chars} characters, max {max}"),
+            Violation::Forbidden { number, c } => write!(f, "#{number}: forbidden character '{c}'"),
+            Violation::MissingCredit(line) => write!(f, "missing credit line \"{line}\"")
+        }
+    }
+}
+
+/// Result of [`Document::check_submission`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionReport {
+    /// In balloon order, missing credits last.
+    pub violations: Vec<Violation>,
+}
+
+impl SubmissionReport {
+    /// `true` if no rule is broken.
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for SubmissionReport {
+    /// `PASS`, or `FAIL` followed by one line per violation.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return write!(f, "PASS");
+        }
+
+        write!(f, "FAIL: {} violation(s)", self.violations.len())?;
+        for v in &self.violations {
+            write!(f, "\n{v}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Document {
+    /// Checks the document against `rules`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::submission::SubmissionRules;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("Senpai~!".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let rules = SubmissionRules {
+    ///     max_chars: Some(40),
+    ///     forbidden: vec!['~'],
+    ///     credits: vec!["Translation: Num".to_string()],
+    /// };
+    ///
+    /// let report = d.check_submission(&rules);
+    /// assert!(!report.passed());
+    /// assert_eq!(
+    ///     report.to_string(),
+    ///     "FAIL: 2 violation(s)\n#1: forbidden character '~'\nmissing credit line \"Translation: Num\""
+    /// );
+    /// ```
+    pub fn check_submission(&self, rules: &SubmissionRules) -> SubmissionReport {
+        let mut report = SubmissionReport::default();
+
+        for (i, b) in self.balloons.iter().enumerate() {
+            let lines = b.effective_lines();
+
+            if let Some(max) = rules.max_chars {
+                let chars: usize = lines.iter().map(|l| l.chars().count()).sum();
+                if chars > max {
+                    report.violations.push(Violation::TooLong { number: i + 1, chars, max });
+                }
+            }
+
+            // Each forbidden character once per balloon
+            for c in &rules.forbidden {
+                if lines.iter().any(|l| l.contains(*c)) {
+                    report.violations.push(Violation::Forbidden { number: i + 1, c: *c });
+                }
+            }
+        }
+
+        for credit in &rules.credits {
+            let found = self.balloons
+                .iter()
+                .any(|b| b.effective_lines().iter().any(|l| l.contains(credit.as_str())));
+            if !found {
+                report.violations.push(Violation::MissingCredit(credit.clone()));
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod submission_tests {
+    use super::{SubmissionRules, Violation};
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    #[test]
+    fn submission_counts_effective_text() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("a much too long first draft".to_string());
+        b.pr_content.push("Short".to_string());
+        b.pr_content.push("one".to_string());
+        d.balloons.push(b);
+
+        let mut credits = Balloon::default();
+        credits.tl_content.push("Translation: Num / Proofreading: Nam".to_string());
+        d.balloons.push(credits);
+
+        let rules = SubmissionRules {
+            max_chars: Some(8),
+            credits: vec!["Proofreading: Nam".to_string()],
+            ..Default::default()
+        };
+
+        let report = d.check_submission(&rules);
+        assert_eq!(report.violations, vec![Violation::TooLong { number: 2, chars: 36, max: 8 }]);
+        assert_eq!(Document::default().check_submission(&SubmissionRules::default()).to_string(), "PASS");
+    }
+}
@@ -0,0 +1,101 @@
+//! Subtitle exports, for groups reusing scripts in recap videos.
+//!
+//! Every balloon becomes a cue with its effective text. Balloons have no timing, so the
+//! caller passes how long each one stays on screen; cues follow each other without gaps
+//! and balloons without a duration get a default one. Srt is written here, ass by
+//! [`Document::to_ass_with`].
+
+use crate::Document;
+
+use std::time::Duration;
+
+/// Duration of cues without timing, the same as [`crate::ass::ASS_LINE_DURATION_CS`].
+pub const DEFAULT_CUE_DURATION: Duration = Duration::from_secs(5);
+
+// Start and end of the cue of every balloon: `timing[i]` long, or `default`.
+pub(crate) fn cue_times(count: usize, timing: &[Duration], default: Duration) -> Vec<(Duration, Duration)> {
+    let mut start = Duration::ZERO;
+
+    (0..count)
+        .map(|i| {
+            let end = start + *timing.get(i).unwrap_or(&default);
+            let cue = (start, end);
+            start = end;
+            cue
+        })
+        .collect()
+}
+
+// `hh:mm:ss,mmm`
+fn srt_time(t: Duration) -> String {
+    let ms = t.as_millis();
+    format!("{:02}:{:02}:{:02},{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+impl Document {
+    /// Generates an srt subtitle file with a cue per balloon, see [`crate::subtitle`].
+    /// `timing[i]` is how long balloon `i` is shown, balloons past its end get
+    /// [`DEFAULT_CUE_DURATION`]. Balloons without text take their time but get no cue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::consts::Track;
+    ///
+    /// use std::time::Duration;
+    ///
+    /// let mut d = Document::default();
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Previously").with_line(Track::TL, "on..."));
+    /// d.balloons.push(Balloon::default().with_line(Track::TL, "Num"));
+    ///
+    /// let srt = d.to_srt(&[Duration::from_millis(1500)]);
+    /// assert_eq!(srt, "1\n00:00:00,000 --> 00:00:01,500\nPreviously\non...\n\n2\n00:00:01,500 --> 00:00:06,500\nNum\n");
+    /// ```
+    pub fn to_srt(&self, timing: &[Duration]) -> String {
+        self.to_srt_with(timing, DEFAULT_CUE_DURATION)
+    }
+
+    /// [`Document::to_srt`] with `default` as the duration of balloons without timing.
+    pub fn to_srt_with(&self, timing: &[Duration], default: Duration) -> String {
+        let mut cues: Vec<String> = Vec::new();
+
+        for (b, (start, end)) in self.balloons.iter().zip(cue_times(self.balloons.len(), timing, default)) {
+            let lines = b.effective_lines();
+            if lines.is_empty() {
+                continue;
+            }
+
+            cues.push(format!("{}\n{} --> {}\n{}\n", cues.len() + 1, srt_time(start), srt_time(end), lines.join("\n")));
+        }
+
+        cues.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod subtitle_tests {
+    use super::{cue_times, srt_time};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    use std::time::Duration;
+
+    #[test]
+    fn subtitle_timing() {
+        let s = Duration::from_secs;
+        assert_eq!(cue_times(3, &[s(1), s(2)], s(4)), vec![(s(0), s(1)), (s(1), s(3)), (s(3), s(7))]);
+        assert_eq!(srt_time(Duration::from_millis(3_723_004)), "01:02:03,004");
+
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default());
+        d.balloons.push(Balloon::default().with_line(Track::PR, "a"));
+        assert_eq!(d.to_srt_with(&[], s(2)), "1\n00:00:02,000 --> 00:00:04,000\na\n");
+
+        let ass = d.to_ass_with(&[s(1)], s(2));
+        assert!(ass.contains("Dialogue: 0,0:00:01.00,0:00:03.00,Dialogue,,0,0,0,,a\n"));
+        assert_eq!(Format::from_extension("ass"), Some(Format::ASS));
+    }
+}
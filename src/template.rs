@@ -0,0 +1,140 @@
+//! Templates for recurring chapters.
+//!
+//! Metadata, balloon text, comments and notes can contain `{name}` variables
+//! (`{chapter}`, `{date}`, `{translator}`...) that [`Document::instantiate`] fills in.
+//! Names are made of ASCII letters, digits and `_`; anything else in braces is left alone.
+
+use crate::Document;
+
+use std::collections::{BTreeSet, HashMap};
+
+// Calls `f` with the name of every variable in `s` and replaces it with the result, if any.
+fn substitute<F: FnMut(&str) -> Option<String>>(s: &str, mut f: F) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let name = rest[1..]
+            .find('}')
+            .map(|end| &rest[1..end + 1])
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+
+        match name.and_then(|n| f(n).map(|v| (n.len(), v))) {
+            Some((len, value)) => {
+                out.push_str(&value);
+                rest = &rest[len + 2..];
+            },
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+impl Document {
+    // Every templatable string of the document.
+    fn template_fields_mut(&mut self) -> Vec<&mut String> {
+        let mut fields: Vec<&mut String> = vec![
+            &mut self.METADATA_SCRIPT_VERSION,
+            &mut self.METADATA_APP_VERSION,
+            &mut self.METADATA_INFO,
+        ];
+
+        for p in &mut self.pages {
+            fields.extend(p.notes.iter_mut());
+            for panel in &mut p.panels {
+                fields.extend(panel.notes.iter_mut());
+            }
+        }
+
+        for b in &mut self.balloons {
+            fields.extend(b.tl_content.iter_mut());
+            fields.extend(b.tlc_content.iter_mut());
+            fields.extend(b.pr_content.iter_mut());
+            fields.extend(b.comments.iter_mut());
+        }
+
+        fields
+    }
+
+    /// Names of all variables used in the document, sorted.
+    pub fn template_vars(&self) -> BTreeSet<String> {
+        let mut vars: BTreeSet<String> = BTreeSet::new();
+
+        for field in self.clone().template_fields_mut() {
+            substitute(field, |name| {
+                vars.insert(name.to_string());
+                None
+            });
+        }
+
+        vars
+    }
+
+    /// A copy of the template with every variable in `vars` replaced by its value.
+    /// Variables missing from `vars` are kept as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    ///
+    /// let mut template = Document::default();
+    /// template.METADATA_INFO = "Ch. {chapter} ({date})".to_string();
+    /// let mut credits = Balloon::default();
+    /// credits.tl_content.push("TL: {translator}".to_string());
+    /// template.balloons.push(credits);
+    ///
+    /// let vars = HashMap::from([("chapter", "45"), ("date", "2023-10-01"), ("translator", "Num")]);
+    /// let d = template.instantiate(&vars);
+    ///
+    /// assert_eq!(d.METADATA_INFO, "Ch. 45 (2023-10-01)");
+    /// assert_eq!(d.balloons[0].tl_content[0], "TL: Num");
+    /// ```
+    pub fn instantiate(&self, vars: &HashMap<&str, &str>) -> Document {
+        let mut d = self.clone();
+
+        for field in d.template_fields_mut() {
+            *field = substitute(field, |name| vars.get(name).map(|v| v.to_string()));
+        }
+
+        d
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::substitute;
+    use crate::Document;
+    use crate::balloon::Balloon;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn template_substitution() {
+        let vars = HashMap::from([("a", "1"), ("b_2", "{a}")]);
+        let f = |name: &str| vars.get(name).map(|v| v.to_string());
+
+        assert_eq!(substitute("{a}{b_2}{c} {} { a} {", f), "1{a}{c} {} { a} {");
+        assert_eq!(substitute("ü{a}ü", f), "ü1ü");
+    }
+
+    #[test]
+    fn template_vars_listed() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.comments.push("{translator} / {date}".to_string());
+        d.balloons.push(b);
+
+        assert_eq!(d.template_vars().into_iter().collect::<Vec<_>>(), vec!["date", "translator"]);
+    }
+}
@@ -0,0 +1,88 @@
+//! Converting between the xml containers without opening the document.
+//!
+//! Only compiled with the `std` feature.
+
+use crate::XMLConvertResult;
+use crate::consts::Format;
+use crate::export::CompressionLevel;
+use crate::io::{format_of, write_atomic_with, write_xml_as, xml_reader};
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Converts a sffx, sffz (or sffzst, `zstd` feature) file to `dst_format`, writing it next
+/// to the source with the new extension. Returns the path of the new file.
+///
+/// The xml is decompressed and recompressed on the fly: no [`Document`](crate::Document)
+/// is built and the xml is not checked, so moving an archive of thousands of chapters to
+/// another container is fast. Open the files afterwards to validate them.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::Document;
+/// use rsff::consts::Format;
+///
+/// let d = Document::from_file("test.sffx").unwrap();
+/// d.save(Format::RAW, "transcode_example").unwrap();
+///
+/// let path = rsff::transcode("transcode_example.sffx", Format::ZLIB).unwrap();
+/// assert_eq!(path.to_str(), Some("transcode_example.sffz"));
+/// assert_eq!(Document::from_file("transcode_example.sffz").unwrap().to_xml(), d.to_xml());
+/// # std::fs::remove_file("transcode_example.sffx").unwrap();
+/// # std::fs::remove_file("transcode_example.sffz").unwrap();
+/// ```
+pub fn transcode(src: &str, dst_format: Format) -> XMLConvertResult<PathBuf> {
+    let src = Path::new(src);
+    let src_format = format_of(src)?;
+    let dst = src.with_extension(dst_format.extension());
+
+    // Nothing to convert, and the source would be overwritten by itself
+    if src_format == dst_format {
+        return Ok(dst);
+    }
+
+    let mut xml = xml_reader(src_format, BufReader::new(File::open(src)?))?;
+    write_atomic_with(&dst, |f| {
+        write_xml_as(dst_format, CompressionLevel::Default, f, |w| {
+            io::copy(&mut xml, w)?;
+            Ok(())
+        })
+    })?;
+
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod transcode_tests {
+    use super::transcode;
+    use crate::{Document, Error};
+    use crate::balloon::Balloon;
+    use crate::consts::{Format, Track};
+
+    use std::fs;
+
+    #[test]
+    fn transcode_round_trip() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::default().with_line(Track::TL, "num"));
+        d.save(Format::ZLIB, "transcode_test").unwrap();
+
+        let raw = transcode("transcode_test.sffz", Format::RAW).unwrap();
+        assert_eq!(fs::read_to_string(&raw).unwrap(), d.to_xml());
+
+        #[cfg(feature = "zstd")]
+        {
+            let zst = transcode("transcode_test.sffx", Format::ZSTD).unwrap();
+            assert_eq!(Document::from_file(zst.to_str().unwrap()).unwrap().to_xml(), d.to_xml());
+            fs::remove_file(zst).unwrap();
+        }
+
+        assert!(matches!(transcode("transcode_test.sffx", Format::TXT), Err(Error::UnsupportedExtension(_))));
+        assert!(!std::path::Path::new("transcode_test.txt").exists());
+
+        fs::remove_file("transcode_test.sffz").unwrap();
+        fs::remove_file(raw).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+//! Reading txt scripts.
+//!
+//! Every line of a balloon starts with its type header (`(): `, `OT: `...), but tools
+//! disagree on how a balloon with several lines is told apart from the next balloon.
+//! Files of this crate put a `//` line between the lines of a balloon; other tools leave
+//! a blank line between balloons, or number every line with its balloon. [`TxtSeparator`]
+//! picks the convention, by default it is detected from the file.
+//!
+//! Lines starting with `//` are notes and `#[id:N]` lines are markers, both are skipped.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::rematch::parse_marker;
+use crate::sanitize::Whitespace;
+
+/// How the lines of a balloon are told apart from the next balloon.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TxtSeparator {
+    /// Detect it from the file, see [`TxtSeparator::detect`].
+    #[default]
+    Auto,
+    /// Files of this crate: every line is a balloon, unless a `//` line joins it to the
+    /// previous one.
+    DoubleSlash,
+    /// Balloons are separated by blank lines, all the lines in between are one balloon.
+    BlankLine,
+    /// Every line starts with the number of its balloon (`12. `, `12) `, `12: `, or
+    /// `12a `/`12.1 ` for sub-lines), lines of the same number are one balloon. Lines
+    /// without a number continue the previous line's balloon.
+    Numbered,
+}
+
+/// Options for [`Document::from_txt_with`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxtImportOptions {
+    pub separator: TxtSeparator,
+    pub whitespace: Whitespace,
+}
+
+// A balloon of the txt: its type, if a line had a header, and its lines.
+struct Block<'a> {
+    btype: Option<TYPES>,
+    lines: Vec<&'a str>,
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// A lone `//` joining two lines of a balloon.
+fn is_separator(line: &str) -> bool {
+    line.trim() == "//"
+}
+
+// Notes and id markers.
+fn is_skipped(line: &str) -> bool {
+    line.trim_start().starts_with("//") || parse_marker(line).is_some()
+}
+
+// Type and text of a line, `None` if it has no type header. One space after the header
+// belongs to it.
+fn split_type_header(line: &str) -> (Option<TYPES>, &str) {
+    let btype = match line.get(..3) {
+        Some("():") => TYPES::DIALOGUE,
+        Some("OT:") => TYPES::OT,
+        Some("[]:") => TYPES::SQUARE,
+        Some("ST:") => TYPES::ST,
+        Some("{}:") => TYPES::THINKING,
+        Some("NR:") => TYPES::NARRATION,
+        Some("FX:") => TYPES::SFX,
+        _ => return (None, line)
+    };
+
+    let text = &line[3..];
+    (Some(btype), text.strip_prefix(' ').unwrap_or(text))
+}
+
+// Balloon number and the rest of a numbered line.
+fn split_number(line: &str) -> Option<(u64, &str)> {
+    let s = line.trim_start();
+    let rest = s.trim_start_matches(|c: char| c.is_ascii_digit());
+    let number = s[..s.len() - rest.len()].parse().ok()?;
+
+    // Sub-line: 12.1, 12-1 or 12a
+    let sub = rest
+        .strip_prefix(['.', '-'])
+        .filter(|r| r.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|r| r.trim_start_matches(|c: char| c.is_ascii_digit()))
+        .or_else(|| rest.strip_prefix(|c: char| c.is_ascii_lowercase()));
+    let rest = sub.unwrap_or(rest);
+    let rest = rest.strip_prefix(['.', ')', ':']).unwrap_or(rest);
+
+    if rest.is_empty() {
+        return Some((number, rest));
+    }
+    rest.strip_prefix([' ', '\t']).map(|text| (number, text))
+}
+
+impl TxtSeparator {
+    /// The convention of `txt`: [`TxtSeparator::DoubleSlash`] if it has `//` separator
+    /// lines, [`TxtSeparator::Numbered`] if it starts with a numbered line and at least
+    /// half of its lines are, [`TxtSeparator::BlankLine`] if blank lines separate blocks of
+    /// several lines. Otherwise every line is a balloon, read as `DoubleSlash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::txt::TxtSeparator;
+    ///
+    /// assert_eq!(TxtSeparator::detect("(): Hi\n//\n(): there\n\n(): Bye"), TxtSeparator::DoubleSlash);
+    /// assert_eq!(TxtSeparator::detect("(): Hi\n(): there\n\n(): Bye"), TxtSeparator::BlankLine);
+    /// assert_eq!(TxtSeparator::detect("1. Hi\n1. there\n2. Bye"), TxtSeparator::Numbered);
+    /// ```
+    pub fn detect(txt: &str) -> TxtSeparator {
+        if txt.lines().any(is_separator) {
+            return TxtSeparator::DoubleSlash;
+        }
+
+        let text: Vec<&str> = txt.lines().filter(|l| !is_blank(l) && !is_skipped(l)).collect();
+        let numbered = text.iter().filter(|l| split_number(l).is_some()).count();
+        if text.first().is_some_and(|l| split_number(l).is_some()) && numbered * 2 >= text.len() {
+            return TxtSeparator::Numbered;
+        }
+
+        let lines: Vec<&str> = txt.lines().filter(|l| !is_skipped(l)).collect();
+        let has_blank = lines.iter().any(|l| is_blank(l));
+        let has_block = lines.windows(2).any(|w| !is_blank(w[0]) && !is_blank(w[1]));
+        if has_blank && has_block {
+            TxtSeparator::BlankLine
+        } else {
+            TxtSeparator::DoubleSlash
+        }
+    }
+
+    // The balloons of `txt`, `self` being detected already.
+    fn blocks(self, txt: &str) -> Vec<Block<'_>> {
+        let mut blocks: Vec<Block> = Vec::new();
+        // The next line goes to the last block
+        let mut joined = false;
+        let mut last_number: Option<u64> = None;
+
+        for line in txt.lines() {
+            if is_blank(line) {
+                joined = false;
+                continue;
+            }
+            if self == TxtSeparator::DoubleSlash && is_separator(line) {
+                joined = !blocks.is_empty();
+                continue;
+            }
+            if is_skipped(line) {
+                continue;
+            }
+
+            let (line, continues) = match self {
+                TxtSeparator::Numbered => match split_number(line) {
+                    Some((n, text)) => {
+                        let same = last_number == Some(n);
+                        last_number = Some(n);
+                        (text, same)
+                    },
+                    None => (line, true)
+                },
+                _ => (line, joined)
+            };
+            let (btype, text) = split_type_header(line);
+
+            match blocks.last_mut() {
+                Some(b) if continues => {
+                    b.btype = b.btype.take().or(btype);
+                    b.lines.push(text);
+                },
+                _ => blocks.push(Block { btype, lines: vec![text] })
+            }
+
+            joined = self == TxtSeparator::BlankLine;
+        }
+
+        blocks
+    }
+}
+
+impl Document {
+    /// [`Document::from_txt`] with options: how balloons are separated and whether lines
+    /// are trimmed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::sanitize::Whitespace;
+    /// use rsff::txt::TxtImportOptions;
+    ///
+    /// let txt = "(): \u{2003}indented  \r\n";
+    /// assert_eq!(Document::from_txt(txt).unwrap().balloons[0].tl_content, ["indented"]);
+    ///
+    /// let opts = TxtImportOptions { whitespace: Whitespace::Preserve, ..Default::default() };
+    /// let d = Document::from_txt_with(txt, &opts).unwrap();
+    /// assert_eq!(d.balloons[0].tl_content, ["\u{2003}indented  "]);
+    ///
+    /// // Blank lines between balloons, detected
+    /// let d = Document::from_txt("(): Wait,\n(): what?\n\nOT: Bam").unwrap();
+    /// assert_eq!(d.balloons[0].tl_content, ["Wait,", "what?"]);
+    ///
+    /// // Numbered lines, detected too
+    /// let d = Document::from_txt("1. (): Hi\n1. (): there\n2. FX: Bam").unwrap();
+    /// assert_eq!(d.len(), 2);
+    /// ```
+    pub fn from_txt_with(txt: &str, opts: &TxtImportOptions) -> XMLConvertResult<Document> {
+        let separator = match opts.separator {
+            TxtSeparator::Auto => TxtSeparator::detect(txt),
+            s => s
+        };
+
+        let mut d = Document::default();
+        for block in separator.blocks(txt) {
+            let mut b = Balloon::new(block.btype.unwrap_or_default());
+            b.tl_content = block.lines
+                .into_iter()
+                .map(|l| opts.whitespace.apply(l).to_string())
+                .collect();
+            d.balloons.push(b);
+        }
+
+        d.sanitize();
+
+        Ok(d)
+    }
+}
+
+#[cfg(test)]
+mod txt_tests {
+    use super::{TxtImportOptions, TxtSeparator, split_number};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    fn lines(d: &Document) -> Vec<Vec<String>> {
+        d.balloons.iter().map(|b| b.tl_content.clone()).collect()
+    }
+
+    #[test]
+    fn txt_separators() {
+        assert_eq!(split_number("12. text"), Some((12, "text")));
+        assert_eq!(split_number("12.1 text"), Some((12, "text")));
+        assert_eq!(split_number("3b) FX: Bam"), Some((3, "FX: Bam")));
+        assert_eq!(split_number("2,000 people"), None);
+        assert_eq!(split_number("(): 1"), None);
+
+        // Our own files, with notes, markers and a link that isn't a note
+        let mut d = Document::default();
+        d.balloons.push(Balloon::new(TYPES::SQUARE).with_line(Track::TL, "one").with_line(Track::TL, "two"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "see https://example.com"));
+        d.balloons.push(Balloon::default().with_line(Track::TL, "a").with_line(Track::TL, "b"));
+        let txt = format!("// handoff note\n#[id:0]\n{d}");
+        assert_eq!(TxtSeparator::detect(&txt), TxtSeparator::DoubleSlash);
+        let back = Document::from_txt(&txt).unwrap();
+        assert_eq!(lines(&back), lines(&d));
+        assert_eq!(back.balloons[0].btype, TYPES::SQUARE);
+
+        // Blank lines, headers only on some lines
+        let txt = "(): Wait\nfor me!\n\n\nOT: Boom\n\n[]: Meanwhile\n[]: at home";
+        let d = Document::from_txt(txt).unwrap();
+        assert_eq!(lines(&d), [vec!["Wait", "for me!"], vec!["Boom"], vec!["Meanwhile", "at home"]]);
+        assert_eq!(d.balloons[1].btype, TYPES::OT);
+
+        // One balloon per line without blank lines stays that way
+        assert_eq!(Document::from_txt("(): a\n(): b\nFX: c").unwrap().len(), 3);
+
+        // Numbered, with a wrapped line
+        let txt = "1) (): Hello\n1) (): there\n2) FX: Bam\n3. Long line\nwrapped\n10a Sub\n10b line";
+        let d = Document::from_txt(txt).unwrap();
+        assert_eq!(lines(&d), [vec!["Hello", "there"], vec!["Bam"], vec!["Long line", "wrapped"], vec!["Sub", "line"]]);
+        assert_eq!(d.balloons[1].btype, TYPES::SFX);
+
+        // Forced convention
+        let opts = TxtImportOptions { separator: TxtSeparator::DoubleSlash, ..Default::default() };
+        assert_eq!(Document::from_txt_with("(): a\n(): b\n\n(): c", &opts).unwrap().len(), 3);
+        assert_eq!(Document::from_txt("OT:").unwrap().balloons[0].tl_content, [""]);
+    }
+}
@@ -0,0 +1,226 @@
+//! Smart punctuation: straight quotes, apostrophes and dashes to their typographic forms.
+//!
+//! Conversion follows a [`Language`] profile and works field by field, with the same
+//! report of changed lines as [`Document::normalize_cjk`].
+//!
+//! Code-like segments are left alone: text between backticks, `{placeholders}`
+//! and `<tags>`, so template variables and markup keep their straight quotes.
+
+use crate::Document;
+use crate::cjk::{Cleaned, Field};
+
+/// Narrow no-break space, used by French spacing.
+pub const NNBSP: char = '\u{202F}';
+
+/// Typographic conventions of a language.
+///
+/// | Profile | Quotes | Nested quotes | Spacing |
+/// |---|---|---|---|
+/// | English | “ ” | ‘ ’ | none |
+/// | Spanish | « » | “ ” | none |
+/// | French | « » | “ ” | narrow no-break space inside « » and before `; : ! ?` |
+///
+/// All profiles turn apostrophes into `’`, `--` into an em dash and a spaced ` - ` into an en dash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+    French,
+}
+
+impl Language {
+    // Opening and closing double quotes.
+    fn quotes(self) -> (char, char) {
+        match self {
+            Language::English => ('“', '”'),
+            Language::Spanish | Language::French => ('«', '»')
+        }
+    }
+
+    // Opening and closing single quotes.
+    fn single_quotes(self) -> (char, char) {
+        match self {
+            Language::English => ('‘', '’'),
+            Language::Spanish | Language::French => ('“', '”')
+        }
+    }
+}
+
+// Closing character of a protected segment starting with `c`.
+fn protected_end(c: char) -> Option<char> {
+    match c {
+        '`' => Some('`'),
+        '{' => Some('}'),
+        '<' => Some('>'),
+        _ => None
+    }
+}
+
+// A quote opens at the start of the text or after whitespace and opening punctuation.
+fn opens(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{—–-“‘«".contains(c)
+    }
+}
+
+/// Converts `s` following `profile`.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::typography::{Language, smart_punctuation};
+///
+/// assert_eq!(smart_punctuation("\"It's {name}--again.\"", Language::English), "“It’s {name}—again.”");
+/// assert_eq!(smart_punctuation("\"Quoi ?\"", Language::French), "«\u{202F}Quoi\u{202F}?\u{202F}»");
+/// ```
+pub fn smart_punctuation(s: &str, profile: Language) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::with_capacity(s.len());
+    // Last converted character, protected segments don't count for quotes
+    let mut ctx: Option<char> = None;
+    // An opening single quote waits for its closing one
+    let mut single_open = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let prev = out.chars().next_back();
+        let next = chars.get(i + 1).copied();
+
+        // Copy protected segments as they are, if they are closed
+        if let Some(end) = protected_end(c) {
+            if let Some(len) = chars[i + 1..].iter().position(|x| *x == end) {
+                out.extend(&chars[i..=i + 1 + len]);
+                i += len + 2;
+                continue;
+            }
+        }
+
+        match c {
+            '"' => {
+                let (open, close) = profile.quotes();
+                if opens(ctx) {
+                    out.push(open);
+                    if profile == Language::French {
+                        out.push(NNBSP);
+                    }
+                } else {
+                    if profile == Language::French {
+                        if prev == Some(' ') {
+                            out.pop();
+                        }
+                        if !out.ends_with(NNBSP) {
+                            out.push(NNBSP);
+                        }
+                    }
+                    out.push(close);
+                }
+            },
+            '\'' => {
+                let (open, close) = profile.single_quotes();
+                // "it's", or "dogs'" without an open quote
+                let after_word = ctx.is_some_and(char::is_alphanumeric);
+                let apostrophe = after_word && (next.is_some_and(char::is_alphanumeric) || !single_open);
+
+                if apostrophe {
+                    out.push('’');
+                } else if opens(ctx) {
+                    out.push(open);
+                    single_open = true;
+                } else {
+                    out.push(close);
+                    single_open = false;
+                }
+            },
+            '-' if next == Some('-') => {
+                out.push('—');
+                i += 1;
+            },
+            '-' if prev == Some(' ') && next == Some(' ') => out.push('–'),
+            // The space after « is already there
+            ' ' if profile == Language::French && prev == Some(NNBSP) => (),
+            ';' | ':' | '!' | '?' if profile == Language::French => {
+                let time = c == ':' && prev.is_some_and(|p| p.is_ascii_digit()) && next.is_some_and(|n| n.is_ascii_digit());
+                let after_word = prev.is_some_and(|p| !"!?;:".contains(p));
+
+                if !time && after_word {
+                    if prev == Some(' ') {
+                        out.pop();
+                    }
+                    if out.chars().next_back().is_some_and(|p| p != NNBSP) {
+                        out.push(NNBSP);
+                    }
+                }
+                out.push(c);
+            },
+            c => out.push(c)
+        }
+
+        ctx = out.chars().next_back();
+        i += 1;
+    }
+
+    out
+}
+
+impl Document {
+    /// Applies [`smart_punctuation`] to the given fields of every balloon and returns the changed lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::balloon::Balloon;
+    /// use rsff::cjk::Field;
+    /// use rsff::typography::Language;
+    ///
+    /// let mut d = Document::default();
+    /// let mut b = Balloon::default();
+    /// b.tl_content.push("\"¡Vámonos!\"".to_string());
+    /// b.comments.push("\"straight\" on purpose".to_string());
+    /// d.balloons.push(b);
+    ///
+    /// let report = d.smart_punctuation(&[Field::TL], Language::Spanish);
+    ///
+    /// assert_eq!(report.len(), 1);
+    /// assert_eq!(d.balloons[0].tl_content[0], "«¡Vámonos!»");
+    /// assert_eq!(d.balloons[0].comments[0], "\"straight\" on purpose");
+    /// ```
+    pub fn smart_punctuation(&mut self, fields: &[Field], profile: Language) -> Vec<Cleaned> {
+        let mut report: Vec<Cleaned> = Vec::new();
+
+        for (i, b) in self.balloons.iter_mut().enumerate() {
+            for field in fields {
+                for (n, line) in field.lines_mut(b).iter_mut().enumerate() {
+                    let new = smart_punctuation(line, profile);
+                    if new != *line {
+                        let old = std::mem::replace(line, new);
+                        report.push(Cleaned { balloon: i, field: *field, line: n, old });
+                    }
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod typography_tests {
+    use super::{Language, smart_punctuation};
+
+    #[test]
+    fn typography_profiles() {
+        let s = "'Don't,' she said - \"<i>now</i>\"";
+        assert_eq!(smart_punctuation(s, Language::English), "‘Don’t,’ she said – “<i>now</i>”");
+        assert_eq!(smart_punctuation("He said 'no'.", Language::Spanish), "He said “no”.");
+
+        // French spacing, times untouched, and nothing left to do the second time
+        let fr = smart_punctuation("Il est 10:30 ! Vraiment ?! Bon; \"Allons-y\"", Language::French);
+        assert_eq!(fr, "Il est 10:30\u{202F}! Vraiment\u{202F}?! Bon\u{202F}; «\u{202F}Allons-y\u{202F}»");
+        assert_eq!(smart_punctuation(&fr, Language::French), fr);
+        assert_eq!(smart_punctuation("`a \"b\"` {'c'}", Language::English), "`a \"b\"` {'c'}");
+    }
+}
@@ -0,0 +1,233 @@
+//! Progress status updates shaped for chat webhooks.
+//!
+//! Only compiled with the `json` feature.
+
+use crate::Document;
+use crate::progress::Progress;
+use crate::qc::QcResults;
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+// Discord embed colors.
+const GREEN: u32 = 0x2ECC71;
+const YELLOW: u32 = 0xF1C40F;
+const RED: u32 = 0xE74C3C;
+
+/// Which service the payload is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookTarget {
+    /// Discord webhook with a single embed.
+    Discord,
+    /// Slack incoming webhook with Block Kit blocks.
+    Slack,
+}
+
+/// How the status update looks.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub target: WebhookTarget,
+    /// Title of the message, e.g. "Ch. 45".
+    pub title: String,
+    /// Name shown as the sender (Discord only).
+    pub username: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn new(target: WebhookTarget, title: &str) -> Self {
+        Self { target, title: title.to_string(), username: None }
+    }
+}
+
+// (name, value) pairs shown in the message.
+fn fields(p: &Progress) -> Vec<(&'static str, String)> {
+    let mut f = vec![
+        ("Translated", format!("{}/{} ({}%)", p.translated, p.balloons, p.translated_percent())),
+        ("Proofread", format!("{}/{} ({}%)", p.proofread, p.balloons, p.proofread_percent())),
+    ];
+
+    if p.conflicts > 0 {
+        f.push(("Conflicts", p.conflicts.to_string()));
+    }
+
+    f
+}
+
+// Issue count per rule, one "rule: count" line each, or "None".
+fn qc_summary(qc: &QcResults) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for issue in qc.values().flatten() {
+        *counts.entry(&issue.rule).or_default() += 1;
+    }
+
+    if counts.is_empty() {
+        return String::from("None");
+    }
+
+    counts
+        .into_iter()
+        .map(|(rule, n)| format!("{rule}: {n}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn discord(cfg: &WebhookConfig, p: &Progress, qc: Option<&QcResults>) -> Value {
+    let has_issues = qc.is_some_and(|qc| qc.values().any(|issues| !issues.is_empty()));
+    let color = if p.conflicts > 0 {
+        RED
+    } else if p.proofread == p.balloons && !has_issues {
+        GREEN
+    } else {
+        YELLOW
+    };
+
+    let mut fields: Vec<Value> = fields(p)
+        .into_iter()
+        .map(|(name, value)| json!({"name": name, "value": value, "inline": true}))
+        .collect();
+    if let Some(qc) = qc {
+        fields.push(json!({"name": "QC issues", "value": qc_summary(qc), "inline": false}));
+    }
+
+    let mut payload = json!({
+        "embeds": [{
+            "title": cfg.title,
+            "color": color,
+            "fields": fields
+        }]
+    });
+
+    if let Some(name) = &cfg.username {
+        payload["username"] = json!(name);
+    }
+
+    payload
+}
+
+fn slack(cfg: &WebhookConfig, p: &Progress, qc: Option<&QcResults>) -> Value {
+    let fields: Vec<Value> = fields(p)
+        .into_iter()
+        .map(|(name, value)| json!({"type": "mrkdwn", "text": format!("*{}*\n{}", name, value)}))
+        .collect();
+
+    let mut blocks = vec![
+        json!({"type": "header", "text": {"type": "plain_text", "text": cfg.title}}),
+        json!({"type": "section", "fields": fields}),
+    ];
+    if let Some(qc) = qc {
+        blocks.push(json!({"type": "section", "text": {"type": "mrkdwn", "text": format!("*QC issues*\n{}", qc_summary(qc))}}));
+    }
+
+    json!({
+        "text": cfg.title,
+        "blocks": blocks
+    })
+}
+
+impl Document {
+    /// JSON payload reporting the document's progress, ready to be posted to a Discord or Slack webhook.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::webhook::{WebhookConfig, WebhookTarget};
+    ///
+    /// let d = Document::default();
+    /// let payload = d.progress_webhook_payload(&WebhookConfig::new(WebhookTarget::Discord, "Ch. 45"));
+    ///
+    /// assert_eq!(payload["embeds"][0]["title"], "Ch. 45");
+    /// ```
+    pub fn progress_webhook_payload(&self, cfg: &WebhookConfig) -> Value {
+        self.webhook_payload(cfg, None)
+    }
+
+    /// Like [`Document::progress_webhook_payload`], with the number of issues per rule of a
+    /// [`Document::run_qc`] run added. A document with issues is never shown as done (green).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::webhook::{WebhookConfig, WebhookTarget};
+    ///
+    /// let d = Document::default();
+    /// let qc = d.run_qc(&[]).unwrap();
+    /// let payload = d.progress_webhook_payload_with_qc(&WebhookConfig::new(WebhookTarget::Discord, "Ch. 45"), &qc);
+    ///
+    /// assert_eq!(payload["embeds"][0]["fields"][2]["value"], "None");
+    /// ```
+    pub fn progress_webhook_payload_with_qc(&self, cfg: &WebhookConfig, qc: &QcResults) -> Value {
+        self.webhook_payload(cfg, Some(qc))
+    }
+
+    fn webhook_payload(&self, cfg: &WebhookConfig, qc: Option<&QcResults>) -> Value {
+        let p = self.progress();
+
+        match cfg.target {
+            WebhookTarget::Discord => discord(cfg, &p, qc),
+            WebhookTarget::Slack => slack(cfg, &p, qc)
+        }
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use std::collections::BTreeSet;
+
+    use super::{WebhookConfig, WebhookTarget};
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::qc::{QcIssue, QcResults};
+
+    fn doc() -> Document {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push("num".to_string());
+        d.balloons.push(b.clone());
+        b.pr_content.push("nam".to_string());
+        d.balloons.push(b);
+        d
+    }
+
+    #[test]
+    fn webhook_discord_payload() {
+        let mut cfg = WebhookConfig::new(WebhookTarget::Discord, "Ch. 45");
+        cfg.username = Some("rsff".to_string());
+
+        let p = doc().progress_webhook_payload(&cfg);
+
+        assert_eq!(p["username"], "rsff");
+        assert_eq!(p["embeds"][0]["fields"][1]["value"], "1/2 (50%)");
+        assert_eq!(p["embeds"][0]["color"], 0xF1C40F);
+    }
+
+    #[test]
+    fn webhook_slack_payload() {
+        let p = doc().progress_webhook_payload(&WebhookConfig::new(WebhookTarget::Slack, "Ch. 45"));
+
+        assert_eq!(p["text"], "Ch. 45");
+        assert_eq!(p["blocks"][1]["fields"][0]["text"], "*Translated*\n2/2 (100%)");
+        assert_eq!(p["blocks"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn webhook_qc_summary() {
+        let mut d = doc();
+        d.balloons[0].pr_content.push("nam".to_string());
+        let issue = |rule: &str, message: &str| QcIssue { rule: rule.to_string(), message: message.to_string() };
+        let mut qc = QcResults::new();
+        qc.insert(0, BTreeSet::from([issue("Speaker", "a"), issue("Budget", "b")]));
+        qc.insert(1, BTreeSet::from([issue("Budget", "c")]));
+
+        let p = d.progress_webhook_payload_with_qc(&WebhookConfig::new(WebhookTarget::Discord, "Ch. 45"), &qc);
+        assert_eq!(p["embeds"][0]["fields"][2]["name"], "QC issues");
+        assert_eq!(p["embeds"][0]["fields"][2]["value"], "Budget: 2\nSpeaker: 1");
+        // Proofread, but not done while QC has issues
+        assert_eq!(p["embeds"][0]["color"], 0xF1C40F);
+
+        let p = d.progress_webhook_payload_with_qc(&WebhookConfig::new(WebhookTarget::Slack, "Ch. 45"), &qc);
+        assert_eq!(p["blocks"][2]["text"]["text"], "*QC issues*\nBudget: 2\nSpeaker: 1");
+    }
+}
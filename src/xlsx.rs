@@ -0,0 +1,192 @@
+//! Excel export for teams delivering scripts to typesetters as spreadsheets.
+//!
+//! One row per balloon with its number, type, tl, pr and comment lines, line breaks kept
+//! inside the cells. Text columns are wide and wrapped, and the header row stays frozen
+//! while scrolling. The tlc column is only there if some balloon has tlc content.
+//!
+//! Written by hand as the minimal set of SpreadsheetML parts, so only compiled with the
+//! `xlsx` feature, which brings the zip dependency.
+
+use crate::{Document, XMLConvertResult};
+use crate::balloon::escape_text;
+
+use std::io::{Cursor, Write};
+
+use zip::ZipWriter;
+use zip::write::FileOptions;
+
+const CONTENT_TYPES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+<Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+<Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+<Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>\
+<Override PartName=\"/xl/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>\
+</Types>";
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>\
+</Relationships>";
+
+const WORKBOOK: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">\
+<sheets><sheet name=\"Script\" sheetId=\"1\" r:id=\"rId1\"/></sheets>\
+</workbook>";
+
+const WORKBOOK_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet1.xml\"/>\
+<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>\
+</Relationships>";
+
+// Cell formats: 0 default, 1 bold header, 2 wrapped text aligned to the top.
+const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<styleSheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+<fonts count=\"2\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font><font><b/><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill><fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"3\">\
+<xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"1\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyFont=\"1\"/>\
+<xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyAlignment=\"1\"><alignment wrapText=\"1\" vertical=\"top\"/></xf>\
+</cellXfs>\
+</styleSheet>";
+
+const HEADER_STYLE: u8 = 1;
+const WRAP_STYLE: u8 = 2;
+
+// Column width in characters: number and type are short, text columns wide.
+const NARROW: u8 = 10;
+const WIDE: u8 = 50;
+
+// Letter of a column, there are never more than 26.
+fn column(i: usize) -> char {
+    (b'A' + i as u8) as char
+}
+
+fn text_cell(reference: &str, text: &str, style: u8) -> String {
+    format!(
+        "<c r=\"{reference}\" t=\"inlineStr\" s=\"{style}\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+        escape_text(text)
+    )
+}
+
+impl Document {
+    // The worksheet part.
+    fn xlsx_sheet(&self) -> String {
+        let tlc = self.balloons.iter().any(|b| !b.tlc_content.is_empty());
+        let mut header = vec!["Number", "Type", "TL"];
+        if tlc {
+            header.push("TLC");
+        }
+        header.extend(["PR", "Comment"]);
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+            <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+            <sheetViews><sheetView workbookViewId=\"0\">\
+            <pane ySplit=\"1\" topLeftCell=\"A2\" activePane=\"bottomLeft\" state=\"frozen\"/>\
+            </sheetView></sheetViews><cols>"
+        );
+
+        for i in 0..header.len() {
+            let width = if i < 2 { NARROW } else { WIDE };
+            xml.push_str(format!("<col min=\"{0}\" max=\"{0}\" width=\"{width}\" customWidth=\"1\"/>", i + 1).as_str());
+        }
+        xml.push_str("</cols><sheetData><row r=\"1\">");
+
+        for (i, h) in header.iter().enumerate() {
+            xml.push_str(text_cell(&format!("{}1", column(i)), h, HEADER_STYLE).as_str());
+        }
+        xml.push_str("</row>");
+
+        for (n, b) in self.balloons.iter().enumerate() {
+            let r = n + 2;
+            let mut cells = vec![b.btype.xml_name().to_string(), b.tl_content.join("\n")];
+            if tlc {
+                cells.push(b.tlc_content.join("\n"));
+            }
+            cells.push(b.pr_content.join("\n"));
+            cells.push(b.comments.join("\n"));
+
+            xml.push_str(format!("<row r=\"{r}\"><c r=\"A{r}\" s=\"{WRAP_STYLE}\"><v>{}</v></c>", n + 1).as_str());
+            for (i, cell) in cells.iter().enumerate() {
+                xml.push_str(text_cell(&format!("{}{r}", column(i + 1)), cell, WRAP_STYLE).as_str());
+            }
+            xml.push_str("</row>");
+        }
+
+        xml.push_str("</sheetData></worksheet>");
+
+        xml
+    }
+
+    /// Generates an xlsx workbook of the balloons, see [`crate::xlsx`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rsff::Document;
+    /// use rsff::consts::Format;
+    ///
+    /// let d = Document::from_file("test.sffx").unwrap();
+    /// let bytes = d.to_xlsx().unwrap();
+    ///
+    /// // Or straight to a file
+    /// d.save(Format::XLSX, "xlsx_example").unwrap();
+    /// # std::fs::remove_file("xlsx_example.xlsx").unwrap();
+    /// ```
+    pub fn to_xlsx(&self) -> XMLConvertResult<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default();
+
+        let parts = [
+            ("[Content_Types].xml", CONTENT_TYPES.to_string()),
+            ("_rels/.rels", ROOT_RELS.to_string()),
+            ("xl/workbook.xml", WORKBOOK.to_string()),
+            ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.to_string()),
+            ("xl/styles.xml", STYLES.to_string()),
+            ("xl/worksheets/sheet1.xml", self.xlsx_sheet()),
+        ];
+
+        for (name, content) in parts {
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod xlsx_tests {
+    use crate::Document;
+    use crate::balloon::Balloon;
+    use crate::consts::{TYPES, Track};
+
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn xlsx_sheet_content() {
+        let mut d = Document::default();
+        d.balloons.push(Balloon::new(TYPES::SFX).with_line(Track::TL, "a & b").with_line(Track::TL, "c"));
+        d.balloons.push(Balloon::default().with_line(Track::PR, "d").with_comment("e"));
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(d.to_xlsx().unwrap())).unwrap();
+        assert_eq!(zip.len(), 6);
+
+        let mut sheet = String::new();
+        zip.by_name("xl/worksheets/sheet1.xml").unwrap().read_to_string(&mut sheet).unwrap();
+
+        // Well-formed, frozen header, no tlc column
+        let tree = roxmltree::Document::parse(&sheet).unwrap();
+        assert_eq!(tree.descendants().filter(|n| n.has_tag_name("row")).count(), 3);
+        assert!(sheet.contains("state=\"frozen\""));
+        assert!(sheet.contains("<c r=\"C2\" t=\"inlineStr\" s=\"2\"><is><t xml:space=\"preserve\">a &amp; b\nc</t></is></c>"));
+        assert!(sheet.contains("<c r=\"E3\" t=\"inlineStr\" s=\"2\"><is><t xml:space=\"preserve\">e</t></is></c>"));
+        assert!(!sheet.contains(">TLC<"));
+    }
+}